@@ -0,0 +1,63 @@
+//! Times decoding a byte-level message of a few MB by walking the boxed
+//! HuffTree against walking the HuffArena that decode_with_tree uses.
+//!
+//! Run with `cargo run --release --example arena_timing`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::random_numbers;
+use std::time::Instant;
+use ue5::{decode_with_tree, encode, Bits, HuffArena, HuffTree};
+
+/// Walk the boxed nodes from the root to a leaf for every symbol.
+fn decode_boxed(huff: &HuffTree, bits: &Bits) -> String {
+    let mut decoded = String::new();
+    let mut bits = bits.iter();
+    loop {
+        let mut node = huff;
+        while let HuffTree::Node { left, right } = node {
+            node = match bits.next() {
+                Some(false) => left,
+                Some(true) => right,
+                None => return decoded,
+            };
+        }
+        if let HuffTree::Leaf { chr, .. } = node {
+            decoded.push(*chr);
+        }
+    }
+}
+
+fn main() {
+    // All 256 byte values as symbols, skewed towards small ones.
+    let message = random_numbers(42)
+        .take(4_000_000)
+        .map(|x| char::from((x as u8).min((x >> 8) as u8)))
+        .collect::<String>();
+    let encoded = encode(&message);
+    let tree = HuffTree::from_codebook(&encoded.codebook).unwrap();
+    println!(
+        "{} symbols, {} distinct",
+        message.chars().count(),
+        encoded.codebook.len()
+    );
+
+    let start = Instant::now();
+    let boxed = decode_boxed(&tree, &encoded.bits);
+    println!(
+        "boxed tree:  {:>8.1} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    assert_eq!(boxed, message);
+
+    let start = Instant::now();
+    let arena = HuffArena::from(&tree);
+    let decoded = arena.decode(&encoded.bits).unwrap();
+    println!(
+        "arena:       {:>8.1} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    assert_eq!(decoded, message);
+    assert_eq!(decode_with_tree(&tree, &encoded.bits).unwrap(), message);
+}
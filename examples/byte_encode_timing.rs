@@ -0,0 +1,39 @@
+//! Times encoding ~5 MB of random bytes with a map lookup per byte against
+//! the table lookup of DenseCodebook.
+//!
+//! Run with `cargo run --release --example byte_encode_timing`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::random_numbers;
+use std::convert::TryFrom;
+use std::time::Instant;
+use ue5::{encode_bytes, Bits, DenseCodebook};
+
+fn main() {
+    // Skewed bytes from a linear congruential generator.
+    let bytes = random_numbers(42)
+        .take(5_000_000)
+        .map(|x| (x as u8).min((x >> 8) as u8))
+        .collect::<Vec<_>>();
+    let (codebook, expected) = encode_bytes(&bytes);
+
+    let start = Instant::now();
+    let mut bits = Bits::new();
+    for byte in &bytes {
+        bits.extend_from_code(&codebook[byte]);
+    }
+    println!(
+        "map:   {:>8.1} ms for {} bytes",
+        start.elapsed().as_secs_f64() * 1000.0,
+        bytes.len()
+    );
+    assert_eq!(bits, expected);
+
+    let start = Instant::now();
+    let dense = DenseCodebook::try_from(&codebook).unwrap();
+    let bits = dense.encode(&bytes).unwrap();
+    println!("dense: {:>8.1} ms", start.elapsed().as_secs_f64() * 1000.0);
+    assert_eq!(bits, expected);
+}
@@ -0,0 +1,54 @@
+//! Times encoding a generated ~5 MB message, comparing appending codes bit by
+//! bit with Bits::extend_from_code.
+//!
+//! Run with `cargo run --release --example encode_timing`.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::random_numbers;
+use std::time::Instant;
+use ue5::{encode, Bits};
+
+fn main() {
+    // A skewed alphabet from a linear congruential generator.
+    let alphabet = "eeeeeeettttaaaooinnsshhrdlu cmfwypvbgkjqxz.,ÄÖÜ€";
+    let alphabet = alphabet.chars().collect::<Vec<_>>();
+    let message = random_numbers(42)
+        .take(5_000_000)
+        .map(|x| alphabet[x % alphabet.len()])
+        .collect::<String>();
+
+    let start = Instant::now();
+    let encoded = encode(&message);
+    let elapsed = start.elapsed();
+    println!(
+        "encode:           {:>8.1} ms for {} bytes",
+        elapsed.as_secs_f64() * 1000.0,
+        message.len()
+    );
+
+    let start = Instant::now();
+    let mut bits = Bits::new();
+    for chr in message.chars() {
+        bits.extend(encoded.codebook[&chr].iter());
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "bit by bit:       {:>8.1} ms",
+        elapsed.as_secs_f64() * 1000.0
+    );
+    assert_eq!(bits, encoded.bits);
+
+    let start = Instant::now();
+    let mut bits = Bits::with_capacity(encoded.bits.len());
+    for chr in message.chars() {
+        bits.extend_from_code(&encoded.codebook[&chr]);
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "extend_from_code: {:>8.1} ms",
+        elapsed.as_secs_f64() * 1000.0
+    );
+    assert_eq!(bits, encoded.bits);
+}
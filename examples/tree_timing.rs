@@ -0,0 +1,77 @@
+//! Times building a Huffman tree over a 10k symbol alphabet, like the tokens
+//! of a word-level model, comparing a heap of boxed trees that calls
+//! lettercount in every comparison with huffman, whose heap entries carry
+//! their weight.
+//!
+//! Run with `cargo run --release --example tree_timing`.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap};
+use std::time::Instant;
+use ue5::{canonical_codebook, huffman, HuffTree};
+
+/// A tree ordered by its letter count and smallest symbol, recomputed on
+/// every comparison.
+struct ByCount(HuffTree);
+
+impl Ord for ByCount {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.0.lettercount(), self.0.min_symbol())
+            .cmp(&(other.0.lettercount(), other.0.min_symbol()))
+    }
+}
+impl PartialOrd for ByCount {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Eq for ByCount {}
+impl PartialEq for ByCount {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+fn huffman_by_lettercount(frequency: &BTreeMap<char, u64>) -> HuffTree {
+    let mut heap = frequency
+        .iter()
+        .map(|(&chr, &occ)| Reverse(ByCount(HuffTree::new(chr, occ))))
+        .collect::<BinaryHeap<_>>();
+    while heap.len() > 1 {
+        let Reverse(ByCount(first)) = heap.pop().unwrap();
+        let Reverse(ByCount(second)) = heap.pop().unwrap();
+        heap.push(Reverse(ByCount(first.merge(second))));
+    }
+    heap.pop().unwrap().0 .0
+}
+
+fn main() {
+    // Zipf distributed counts, as for the words of natural language.
+    let frequency = (0..10_000u32)
+        .map(|rank| {
+            (
+                char::from_u32(0x4e00 + rank).unwrap(),
+                1_000_000 / u64::from(rank + 1),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+    println!("{} symbols", frequency.len());
+
+    let start = Instant::now();
+    let by_lettercount = huffman_by_lettercount(&frequency);
+    println!(
+        "heap calling lettercount: {:>8.1} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    let start = Instant::now();
+    let tree = huffman(frequency).unwrap();
+    println!(
+        "huffman:                  {:>8.1} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+    assert_eq!(
+        canonical_codebook(&by_lettercount),
+        canonical_codebook(&tree)
+    );
+}
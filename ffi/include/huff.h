@@ -0,0 +1,51 @@
+/* The C bindings of ue5, see src/ffi.rs. Link with -lhuff. */
+#ifndef HUFF_H
+#define HUFF_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define HUFF_OK 0
+/* A pointer that must not be null is null. */
+#define HUFF_NULL_POINTER (-1)
+/* The message to compress is not valid UTF-8. */
+#define HUFF_INVALID_UTF8 (-2)
+/* The data is no container, e.g. it does not start with the magic or its
+   header is corrupt. */
+#define HUFF_INVALID_CONTAINER (-3)
+/* The decoded message does not have the checksum of the container. */
+#define HUFF_CHECKSUM_MISMATCH (-4)
+#define HUFF_EMPTY_INPUT (-5)
+#define HUFF_UNKNOWN_SYMBOL (-6)
+#define HUFF_INVALID_PREFIX (-7)
+#define HUFF_TRUNCATED_INPUT (-8)
+#define HUFF_INVALID_CODEBOOK (-9)
+#define HUFF_LIMIT_EXCEEDED (-10)
+#define HUFF_IO (-11)
+/* The library panicked, which is a bug. */
+#define HUFF_PANIC (-12)
+#define HUFF_LENGTH_OVERFLOW (-13)
+
+/* Compress the len bytes of UTF-8 at data into a container. Release *out with
+   huff_free. */
+int32_t huff_compress(const uint8_t *data, size_t len, uint8_t **out, size_t *out_len);
+
+/* Decompress a container into UTF-8, which is not NUL-terminated. Release
+   *out with huff_free. */
+int32_t huff_decompress(const uint8_t *data, size_t len, uint8_t **out, size_t *out_len);
+
+/* Release a buffer returned by huff_compress or huff_decompress. */
+void huff_free(uint8_t *buf, size_t len);
+
+/* The message of the last error on the calling thread, or NULL. */
+const char *huff_last_error_message(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,3 @@
+//! The shared library of the C bindings in ue5::ffi, see include/huff.h.
+
+pub use ue5::ffi::*;
@@ -0,0 +1,44 @@
+//! Builds tests/smoke.c against the shared library with the C compiler `cc`
+//! and runs it.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn c_smoke_test() {
+    // given: the library is next to the deps directory of this test
+    let exe = std::env::current_exe().unwrap();
+    let lib_dir = exe.parent().unwrap().parent().unwrap();
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let smoke = lib_dir.join("huff_smoke");
+    let build = Command::new("cc")
+        .arg(manifest_dir.join("tests/smoke.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg("-lhuff")
+        .arg("-o")
+        .arg(&smoke)
+        .status()
+        .unwrap();
+    assert!(build.success());
+
+    // when:
+    let actual = Command::new(&smoke)
+        .env("LD_LIBRARY_PATH", lib_dir)
+        .env("DYLD_LIBRARY_PATH", lib_dir)
+        .output()
+        .unwrap();
+
+    // then:
+    assert!(
+        actual.status.success(),
+        "{}",
+        String::from_utf8_lossy(&actual.stderr)
+    );
+    assert_eq!(
+        String::from_utf8(actual.stdout).unwrap(),
+        "cannot decompress: the data does not start with \"HUF1\"\n"
+    );
+}
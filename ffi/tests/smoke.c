@@ -0,0 +1,35 @@
+/* Round-trips a message through the shared library like a C caller would. */
+#include <stdio.h>
+#include <string.h>
+
+#include "huff.h"
+
+int main(void) {
+    const char *message = "Gr\xc3\xbc\xc3\x9f" "e aus M\xc3\xbc" "nchen";
+    uint8_t *compressed, *decompressed;
+    size_t compressed_len, decompressed_len;
+
+    if (huff_compress((const uint8_t *)message, strlen(message), &compressed,
+                      &compressed_len) != HUFF_OK) {
+        fprintf(stderr, "compress: %s\n", huff_last_error_message());
+        return 1;
+    }
+    if (huff_decompress(compressed, compressed_len, &decompressed, &decompressed_len) != HUFF_OK) {
+        fprintf(stderr, "decompress: %s\n", huff_last_error_message());
+        return 1;
+    }
+    if (decompressed_len != strlen(message) || memcmp(decompressed, message, decompressed_len)) {
+        fprintf(stderr, "the round trip changed the message\n");
+        return 1;
+    }
+    huff_free(compressed, compressed_len);
+    huff_free(decompressed, decompressed_len);
+
+    if (huff_decompress((const uint8_t *)"HUF0", 4, &decompressed, &decompressed_len) !=
+        HUFF_INVALID_CONTAINER) {
+        fprintf(stderr, "HUF0 is no container\n");
+        return 1;
+    }
+    printf("%s\n", huff_last_error_message());
+    return 0;
+}
@@ -0,0 +1,416 @@
+//! One-pass adaptive Huffman coding with the FGK algorithm.
+//!
+//! Encoder and decoder start with a tree that only contains the NYT (not yet
+//! transmitted) leaf and update their trees identically after every symbol,
+//! so no codebook has to be transmitted. The first occurrence of a symbol is
+//! sent as the code of the NYT leaf followed by the symbol as a 21 bit number.
+
+use crate::bits::Bits;
+use crate::error::HuffmanError;
+use crate::tree::HuffTree;
+use alloc::collections::BTreeMap;
+use alloc::{string::String, vec, vec::Vec};
+use core::convert::TryFrom;
+
+/// The number of bits of a symbol sent after the NYT code.
+const SYMBOL_BITS: usize = 21;
+
+#[derive(Debug, Clone)]
+struct Node {
+    weight: u32,
+    parent: Option<usize>,
+    children: Option<(usize, usize)>,
+    /// None for the NYT leaf and internal nodes.
+    chr: Option<char>,
+}
+
+/// The tree shared by encoder and decoder. The nodes live in an arena and are
+/// additionally ordered by increasing weight, which FGK keeps intact by
+/// swapping subtrees (the sibling property).
+#[derive(Debug, Clone)]
+struct AdaptiveModel {
+    nodes: Vec<Node>,
+    /// Node indices by increasing order, the root is last.
+    by_order: Vec<usize>,
+    /// The position of every node in by_order.
+    order: Vec<usize>,
+    leaves: BTreeMap<char, usize>,
+    nyt: usize,
+}
+
+impl AdaptiveModel {
+    fn new() -> AdaptiveModel {
+        AdaptiveModel {
+            nodes: vec![Node {
+                weight: 0,
+                parent: None,
+                children: None,
+                chr: None,
+            }],
+            by_order: vec![0],
+            order: vec![0],
+            leaves: BTreeMap::new(),
+            nyt: 0,
+        }
+    }
+
+    fn root(&self) -> usize {
+        *self.by_order.last().unwrap()
+    }
+
+    /// The code of a node, read from the node up to the root.
+    fn code(&self, mut node: usize) -> Bits {
+        let mut reversed = Vec::new();
+        while let Some(parent) = self.nodes[node].parent {
+            let (_, right) = self.nodes[parent].children.unwrap();
+            reversed.push(node == right);
+            node = parent;
+        }
+        reversed.into_iter().rev().collect()
+    }
+
+    /// Count one more occurrence of the symbol.
+    fn update(&mut self, chr: char) {
+        let mut node = match self.leaves.get(&chr) {
+            Some(&leaf) => leaf,
+            None => self.split_nyt(chr),
+        };
+        loop {
+            let leader = self.leader(node);
+            if leader != node && Some(leader) != self.nodes[node].parent {
+                self.swap(node, leader);
+            }
+            self.nodes[node].weight += 1;
+            match self.nodes[node].parent {
+                Some(parent) => node = parent,
+                None => break,
+            }
+        }
+    }
+
+    /// Replace the NYT leaf by a node with a new NYT leaf on the left and a
+    /// leaf for the symbol on the right. Returns the new leaf.
+    fn split_nyt(&mut self, chr: char) -> usize {
+        let old_nyt = self.nyt;
+        let nyt = self.nodes.len();
+        let leaf = nyt + 1;
+        for (index, chr) in [(nyt, None), (leaf, Some(chr))].iter() {
+            self.nodes.push(Node {
+                weight: 0,
+                parent: Some(old_nyt),
+                children: None,
+                chr: *chr,
+            });
+            self.order.push(0);
+            self.by_order.insert(0, *index);
+        }
+        self.by_order.swap(0, 1);
+        for (position, &node) in self.by_order.iter().enumerate() {
+            self.order[node] = position;
+        }
+        self.nodes[old_nyt].children = Some((nyt, leaf));
+        self.nyt = nyt;
+        self.leaves.insert(chr, leaf);
+        leaf
+    }
+
+    /// The highest ordered node with the same weight as the node.
+    fn leader(&self, node: usize) -> usize {
+        let weight = self.nodes[node].weight;
+        let mut position = self.order[node];
+        while position + 1 < self.by_order.len()
+            && self.nodes[self.by_order[position + 1]].weight == weight
+        {
+            position += 1;
+        }
+        self.by_order[position]
+    }
+
+    /// Swap the subtrees rooted at the two nodes, which are not ancestors of
+    /// each other.
+    fn swap(&mut self, a: usize, b: usize) {
+        let parent_a = self.nodes[a].parent.unwrap();
+        let parent_b = self.nodes[b].parent.unwrap();
+        let replace = |children: &mut Option<(usize, usize)>, old: usize, new: usize| {
+            if let Some((left, right)) = children {
+                if *left == old {
+                    *left = new;
+                } else if *right == old {
+                    *right = new;
+                }
+            }
+        };
+        if parent_a == parent_b {
+            if let Some((left, right)) = &mut self.nodes[parent_a].children {
+                core::mem::swap(left, right);
+            }
+        } else {
+            replace(&mut self.nodes[parent_a].children, a, b);
+            replace(&mut self.nodes[parent_b].children, b, a);
+            self.nodes[a].parent = Some(parent_b);
+            self.nodes[b].parent = Some(parent_a);
+        }
+        let (order_a, order_b) = (self.order[a], self.order[b]);
+        self.by_order.swap(order_a, order_b);
+        self.order[a] = order_b;
+        self.order[b] = order_a;
+    }
+
+    fn tree(&self, node: usize) -> HuffTree<Option<char>> {
+        match self.nodes[node].children {
+            Some((left, right)) => self.tree(left).merge(self.tree(right)),
+            None => HuffTree::new(self.nodes[node].chr, u64::from(self.nodes[node].weight)),
+        }
+    }
+}
+
+/// Encodes symbols one at a time while adapting the codes to the symbols seen
+/// so far.
+#[derive(Debug, Clone)]
+pub struct AdaptiveEncoder {
+    model: AdaptiveModel,
+    bits: Bits,
+}
+
+impl Default for AdaptiveEncoder {
+    fn default() -> AdaptiveEncoder {
+        AdaptiveEncoder::new()
+    }
+}
+
+impl AdaptiveEncoder {
+    pub fn new() -> AdaptiveEncoder {
+        AdaptiveEncoder {
+            model: AdaptiveModel::new(),
+            bits: Bits::new(),
+        }
+    }
+    pub fn push_symbol(&mut self, chr: char) {
+        match self.model.leaves.get(&chr) {
+            Some(&leaf) => self.bits.extend_from_code(&self.model.code(leaf)),
+            None => {
+                self.bits.extend_from_code(&self.model.code(self.model.nyt));
+                let value = u32::from(chr);
+                self.bits
+                    .extend((0..SYMBOL_BITS).rev().map(|i| value & (1 << i) != 0));
+            }
+        }
+        self.model.update(chr);
+    }
+    /// The bits of all symbols pushed so far.
+    pub fn bits(&self) -> &Bits {
+        &self.bits
+    }
+    pub fn into_bits(self) -> Bits {
+        self.bits
+    }
+    /// A snapshot of the current tree, None is the NYT leaf.
+    pub fn tree(&self) -> HuffTree<Option<char>> {
+        self.model.tree(self.model.root())
+    }
+}
+
+/// Decodes the bits of an AdaptiveEncoder one symbol at a time.
+#[derive(Debug, Clone)]
+pub struct AdaptiveDecoder<'a> {
+    model: AdaptiveModel,
+    bits: &'a Bits,
+    bit_offset: usize,
+    failed: bool,
+}
+
+impl<'a> AdaptiveDecoder<'a> {
+    pub fn new(bits: &'a Bits) -> AdaptiveDecoder<'a> {
+        AdaptiveDecoder {
+            model: AdaptiveModel::new(),
+            bits,
+            bit_offset: 0,
+            failed: false,
+        }
+    }
+
+    /// Decode the next symbol, None at the end of the bits. After an error
+    /// no further symbols are decoded.
+    pub fn next_symbol(&mut self) -> Option<Result<char, HuffmanError>> {
+        if self.failed || self.bit_offset == self.bits.len() {
+            return None;
+        }
+        let result = self.decode_symbol();
+        self.failed = result.is_err();
+        Some(result)
+    }
+
+    fn decode_symbol(&mut self) -> Result<char, HuffmanError> {
+        let start = self.bit_offset;
+        let mut node = self.model.root();
+        while let Some((left, right)) = self.model.nodes[node].children {
+            node = if self.next_bit(start)? { right } else { left };
+        }
+        let chr = match self.model.nodes[node].chr {
+            Some(chr) => chr,
+            None => {
+                let symbol_offset = self.bit_offset;
+                let mut value = 0;
+                for _ in 0..SYMBOL_BITS {
+                    value = value << 1 | self.next_bit(start)? as u32;
+                }
+                char::try_from(value).map_err(|_| HuffmanError::InvalidPrefix {
+                    bit_offset: symbol_offset,
+                })?
+            }
+        };
+        self.model.update(chr);
+        Ok(chr)
+    }
+
+    fn next_bit(&mut self, start: usize) -> Result<bool, HuffmanError> {
+        if self.bit_offset == self.bits.len() {
+            return Err(HuffmanError::TruncatedInput { bit_offset: start });
+        }
+        self.bit_offset += 1;
+        Ok(self.bits.get(self.bit_offset - 1))
+    }
+
+    /// A snapshot of the current tree, None is the NYT leaf.
+    pub fn tree(&self) -> HuffTree<Option<char>> {
+        self.model.tree(self.model.root())
+    }
+}
+
+impl Iterator for AdaptiveDecoder<'_> {
+    type Item = Result<char, HuffmanError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_symbol()
+    }
+}
+
+/// Encode the message in one pass with adaptive codes.
+pub fn encode_adaptive(message: &str) -> Bits {
+    let mut encoder = AdaptiveEncoder::new();
+    for chr in message.chars() {
+        encoder.push_symbol(chr);
+    }
+    encoder.into_bits()
+}
+
+/// Decode the output of encode_adaptive.
+pub fn decode_adaptive(bits: &Bits) -> Result<String, HuffmanError> {
+    AdaptiveDecoder::new(bits).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::codebook;
+    use crate::test_support::random_numbers;
+
+    /// Check the sibling property: ordered by increasing weight and siblings
+    /// are adjacent.
+    fn assert_sibling_property(model: &AdaptiveModel) {
+        let weights = model
+            .by_order
+            .iter()
+            .map(|&node| model.nodes[node].weight)
+            .collect::<Vec<_>>();
+        assert!(weights.windows(2).all(|w| w[0] <= w[1]), "{:?}", weights);
+        for node in &model.nodes {
+            if let Some((left, right)) = node.children {
+                assert_eq!(
+                    node.weight,
+                    model.nodes[left].weight + model.nodes[right].weight
+                );
+                assert_eq!(model.order[left] + 1, model.order[right]);
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_round_trip_random() {
+        // given:
+        let alphabet = ('a'..='z').chain("äöü€ 🦀\n".chars()).collect::<Vec<_>>();
+        let message = random_numbers(42)
+            .take(5000)
+            .map(|x| alphabet[x % alphabet.len()])
+            .collect::<String>();
+
+        // when:
+        let bits = encode_adaptive(&message);
+        let actual = decode_adaptive(&bits);
+
+        // then:
+        assert_eq!(actual, Ok(message));
+    }
+
+    #[test]
+    fn adaptive_decoder_stays_in_sync_after_rare_symbols() {
+        // given:
+        let message = (0..300)
+            .map(|i| match i % 50 {
+                0 => 'z',
+                7 => 'ß',
+                _ if i % 3 == 0 => 'b',
+                _ => 'a',
+            })
+            .chain("zzzzxyz".chars())
+            .collect::<String>();
+        let mut encoder = AdaptiveEncoder::new();
+        for chr in message.chars() {
+            encoder.push_symbol(chr);
+        }
+        let bits = encoder.bits().clone();
+        let mut decoder = AdaptiveDecoder::new(&bits);
+        let mut encoder = AdaptiveEncoder::new();
+
+        for expected in message.chars() {
+            // when:
+            encoder.push_symbol(expected);
+            let actual = decoder.next_symbol();
+
+            // then:
+            assert_eq!(actual, Some(Ok(expected)));
+            assert_eq!(codebook(&decoder.tree()), codebook(&encoder.tree()));
+            assert_sibling_property(&decoder.model);
+        }
+        assert_eq!(decoder.next_symbol(), None);
+    }
+
+    #[test]
+    fn adaptive_codes_adapt() {
+        // given:
+        let mut encoder = AdaptiveEncoder::new();
+        for chr in "ab".chars() {
+            encoder.push_symbol(chr);
+        }
+        let len = encoder.bits().len();
+
+        // when:
+        for _ in 0..10 {
+            encoder.push_symbol('a');
+        }
+
+        // then: 'a' got the shortest code
+        assert_eq!(encoder.bits().len() - len, 10);
+    }
+
+    #[test]
+    fn adaptive_empty_message() {
+        // when:
+        let actual = decode_adaptive(&encode_adaptive(""));
+
+        // then:
+        assert_eq!(actual, Ok(String::new()));
+    }
+
+    #[test]
+    fn adaptive_truncated() {
+        // given: 21 bits for a, 1 + 21 for b and 2 + 21 for c
+        let mut bits = encode_adaptive("abc");
+        bits.pop();
+
+        // when:
+        let actual = decode_adaptive(&bits);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::TruncatedInput { bit_offset: 43 }));
+    }
+}
@@ -0,0 +1,482 @@
+//! An archive of several compressed texts, e.g. the files of a directory.
+//!
+//! The format is the magic `HUFA`, a flag byte, the number of entries as a
+//! big-endian u32, the shared codebook unless the flag byte says per-file,
+//! the file table and the packed bits of each entry, each starting at a byte
+//! boundary. A codebook is a header mode flag byte followed by the header of
+//! that mode as in the container format. Each entry of the file table is the
+//! length of its name as a big-endian u16, the name as UTF-8, the original
+//! size in bytes, the number of encoded bits and the byte offset of the bits
+//! relative to the end of the file table as big-endian u64s, the CRC-32 of
+//! the text as a big-endian u32 and in per-file mode its own codebook.
+
+use crate::bits::Bits;
+use crate::code::{canonical_codebook, decode_into_limited, Codebook};
+use crate::container::{read_codebook, smallest_header, ContainerError};
+use crate::crc::crc32;
+use crate::error::HuffmanError;
+use crate::tree::{frequency, huffman, try_frequency};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::{string::String, vec::Vec};
+use core::convert::{TryFrom, TryInto};
+use core::fmt::{self, Display, Formatter};
+use core::str;
+
+pub const ARCHIVE_MAGIC: &[u8; 4] = b"HUFA";
+
+/// Set in the flag byte if every entry has its own codebook.
+const PER_FILE: u8 = 1;
+
+/// Where the codebooks of the entries of an archive come from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CodebookMode {
+    /// One codebook built from all entries together, which pays off for
+    /// many small and similar texts.
+    #[default]
+    Shared,
+    /// A codebook for each entry built from just that entry.
+    PerFile,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArchiveError {
+    /// The data does not start with ARCHIVE_MAGIC.
+    BadMagic,
+    /// Two entries have the same name.
+    DuplicateName(String),
+    /// The names of entries may be at most u16::MAX bytes long.
+    NameTooLong(String),
+    /// The symbol counts of the texts of a codebook add up to more than
+    /// u64::MAX.
+    FrequencyOverflow,
+    /// The data ends inside the file table.
+    Truncated,
+    /// The name of the entry at this position in the file table is not UTF-8
+    /// or its bits lie outside of the data.
+    InvalidEntry(usize),
+    /// A codebook is malformed.
+    Codebook(ContainerError),
+    /// The bits of the entry do not decode with its codebook.
+    Decode { name: String, error: HuffmanError },
+    /// The decoded text of the entry does not have the original size or
+    /// CRC-32 from the file table.
+    Corrupt(String),
+}
+
+impl Display for ArchiveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveError::BadMagic => write!(f, "the data does not start with {:?}", "HUFA"),
+            ArchiveError::DuplicateName(name) => write!(f, "duplicate entry name {:?}", name),
+            ArchiveError::NameTooLong(name) => write!(f, "the entry name {:?} is too long", name),
+            ArchiveError::FrequencyOverflow => {
+                write!(f, "the symbol counts add up to more than {}", u64::MAX)
+            }
+            ArchiveError::Truncated => write!(f, "the file table is truncated"),
+            ArchiveError::InvalidEntry(index) => write!(f, "invalid entry {}", index),
+            ArchiveError::Codebook(err) => write!(f, "invalid codebook: {}", err),
+            ArchiveError::Decode { name, error } => write!(f, "cannot decode {}: {}", name, error),
+            ArchiveError::Corrupt(name) => write!(f, "{} is corrupt", name),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ArchiveError {}
+
+/// A parsed archive whose entries are decoded one at a time on demand.
+#[derive(Debug)]
+pub struct Archive<'a> {
+    codebooks: Vec<Codebook>,
+    entries: Vec<EntryInfo<'a>>,
+}
+
+#[derive(Debug)]
+struct EntryInfo<'a> {
+    name: String,
+    original_size: usize,
+    bit_len: usize,
+    checksum: u32,
+    /// The index of its codebook in Archive::codebooks.
+    codebook: usize,
+    bytes: &'a [u8],
+}
+
+/// An entry of an Archive.
+#[derive(Clone, Copy, Debug)]
+pub struct Entry<'r, 'a> {
+    archive: &'r Archive<'a>,
+    info: &'r EntryInfo<'a>,
+}
+
+impl<'r, 'a> Entry<'r, 'a> {
+    pub fn name(&self) -> &'r str {
+        &self.info.name
+    }
+    /// The size of the text in bytes of UTF-8.
+    pub fn original_size(&self) -> usize {
+        self.info.original_size
+    }
+    pub fn bit_len(&self) -> usize {
+        self.info.bit_len
+    }
+    /// Decode the text of the entry and verify it against the file table.
+    pub fn decode(&self) -> Result<String, ArchiveError> {
+        let info = self.info;
+        let codebook = &self.archive.codebooks[info.codebook];
+        let bits = Bits::from_bytes(info.bit_len, info.bytes).expect("checked by read");
+        let mut text = String::new();
+        // More than the original size is corrupt anyway.
+        decode_into_limited(codebook, &bits, &mut text, usize::MAX, info.original_size).map_err(
+            |error| ArchiveError::Decode {
+                name: info.name.clone(),
+                error,
+            },
+        )?;
+        if text.len() != info.original_size || crc32(text.as_bytes()) != info.checksum {
+            return Err(ArchiveError::Corrupt(info.name.clone()));
+        }
+        Ok(text)
+    }
+}
+
+impl<'a> Archive<'a> {
+    /// Archive the texts under their names with a shared codebook.
+    pub fn create<I: IntoIterator<Item = (String, String)>>(
+        entries: I,
+    ) -> Result<Vec<u8>, ArchiveError> {
+        Archive::create_with(entries, CodebookMode::Shared)
+    }
+
+    /// Archive the texts under their names with the codebook mode.
+    pub fn create_with<I: IntoIterator<Item = (String, String)>>(
+        entries: I,
+        mode: CodebookMode,
+    ) -> Result<Vec<u8>, ArchiveError> {
+        let entries = entries.into_iter().collect::<Vec<_>>();
+        let mut names = BTreeSet::new();
+        for (name, _) in &entries {
+            if !names.insert(name.as_str()) {
+                return Err(ArchiveError::DuplicateName(name.clone()));
+            }
+            if name.len() > usize::from(u16::MAX) {
+                return Err(ArchiveError::NameTooLong(name.clone()));
+            }
+        }
+        let mut data = ARCHIVE_MAGIC.to_vec();
+        data.push(match mode {
+            CodebookMode::Shared => 0,
+            CodebookMode::PerFile => PER_FILE,
+        });
+        let count = u32::try_from(entries.len()).expect("fewer than 2^32 entries");
+        data.extend(count.to_be_bytes());
+        let shared = match mode {
+            CodebookMode::Shared => {
+                let total = try_frequency(
+                    entries
+                        .iter()
+                        .flat_map(|(_, text)| frequency(&mut text.chars())),
+                )
+                .map_err(|_| ArchiveError::FrequencyOverflow)?;
+                let codebook = write_codebook(&total, &mut data)?;
+                Some(codebook)
+            }
+            CodebookMode::PerFile => None,
+        };
+        let mut payload = Vec::<u8>::new();
+        for (name, text) in &entries {
+            data.extend((name.len() as u16).to_be_bytes());
+            data.extend(name.bytes());
+            let mut table_entry = Vec::new();
+            let own;
+            let codebook = match &shared {
+                Some(codebook) => codebook,
+                None => {
+                    own = write_codebook(&frequency(&mut text.chars()), &mut table_entry)?;
+                    &own
+                }
+            };
+            let mut bits = Bits::new();
+            for chr in text.chars() {
+                bits.extend_from_code(&codebook[&chr]);
+            }
+            data.extend((text.len() as u64).to_be_bytes());
+            data.extend((bits.len() as u64).to_be_bytes());
+            data.extend((payload.len() as u64).to_be_bytes());
+            data.extend(crc32(text.as_bytes()).to_be_bytes());
+            data.extend(table_entry);
+            payload.extend(bits.as_bytes());
+        }
+        data.extend(payload);
+        Ok(data)
+    }
+
+    /// Parse the codebooks and the file table of an archive. The entries are
+    /// only decoded by Entry::decode.
+    pub fn read(data: &'a [u8]) -> Result<Archive<'a>, ArchiveError> {
+        let data = data
+            .strip_prefix(&ARCHIVE_MAGIC[..])
+            .ok_or(ArchiveError::BadMagic)?;
+        let mut reader = Reader { data };
+        let flag = reader.take(1)?[0];
+        let count = u32::from_be_bytes(reader.take(4)?.try_into().unwrap());
+        let mut codebooks = Vec::new();
+        if flag & PER_FILE == 0 {
+            codebooks.push(reader.codebook()?);
+        }
+        let mut table = Vec::new();
+        for index in 0..count as usize {
+            let name_len = u16::from_be_bytes(reader.take(2)?.try_into().unwrap());
+            let name = str::from_utf8(reader.take(usize::from(name_len))?)
+                .map_err(|_| ArchiveError::InvalidEntry(index))?;
+            let original_size = reader.u64()?;
+            let bit_len = reader.u64()?;
+            let offset = reader.u64()?;
+            let checksum = u32::from_be_bytes(reader.take(4)?.try_into().unwrap());
+            let codebook = match flag & PER_FILE {
+                0 => 0,
+                _ => {
+                    codebooks.push(reader.codebook()?);
+                    codebooks.len() - 1
+                }
+            };
+            table.push((name, original_size, bit_len, offset, checksum, codebook));
+        }
+        let payload = reader.data;
+        let mut names = BTreeSet::new();
+        let mut entries = Vec::with_capacity(table.len());
+        for (index, (name, original_size, bit_len, offset, checksum, codebook)) in
+            table.into_iter().enumerate()
+        {
+            if !names.insert(name) {
+                return Err(ArchiveError::DuplicateName(name.into()));
+            }
+            let invalid = || ArchiveError::InvalidEntry(index);
+            let original_size = usize::try_from(original_size).map_err(|_| invalid())?;
+            let bit_len = usize::try_from(bit_len).map_err(|_| invalid())?;
+            let start = usize::try_from(offset).map_err(|_| invalid())?;
+            let bytes = start
+                .checked_add(bit_len.div_ceil(8))
+                .and_then(|end| payload.get(start..end))
+                .ok_or_else(invalid)?;
+            entries.push(EntryInfo {
+                name: name.into(),
+                original_size,
+                bit_len,
+                checksum,
+                codebook,
+                bytes,
+            });
+        }
+        Ok(Archive { codebooks, entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries in the order they were archived.
+    pub fn entries(&self) -> impl Iterator<Item = Entry<'_, 'a>> {
+        self.entries.iter().map(move |info| Entry {
+            archive: self,
+            info,
+        })
+    }
+
+    /// The entry with the name.
+    pub fn get(&self, name: &str) -> Option<Entry<'_, 'a>> {
+        self.entries().find(|entry| entry.name() == name)
+    }
+}
+
+/// Append the header mode flag and the smallest header of the canonical
+/// codebook of the frequencies to data and return the codebook.
+fn write_codebook(
+    frequency: &BTreeMap<char, u64>,
+    data: &mut Vec<u8>,
+) -> Result<Codebook, ArchiveError> {
+    frequency
+        .values()
+        .try_fold(0u64, |total, &occ| total.checked_add(occ))
+        .ok_or(ArchiveError::FrequencyOverflow)?;
+    let codebook = huffman(frequency.clone())
+        .map(|huff| canonical_codebook(&huff))
+        .unwrap_or_default();
+    let mode = smallest_header(frequency, &codebook);
+    data.push(mode.flag());
+    data.extend(mode.serialize(frequency, &codebook));
+    Ok(codebook)
+}
+
+/// Reads the file table from the front of the data.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ArchiveError> {
+        if self.data.len() < len {
+            return Err(ArchiveError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, ArchiveError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn codebook(&mut self) -> Result<Codebook, ArchiveError> {
+        let mode = self.take(1)?[0];
+        let (codebook, len) = read_codebook(mode, self.data).map_err(ArchiveError::Codebook)?;
+        self.data = &self.data[len..];
+        Ok(codebook)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{format, string::ToString};
+
+    fn files() -> Vec<(String, String)> {
+        [
+            ("a.txt", "the quick brown fox jumps over the lazy dog"),
+            ("b.txt", "the lazy dog sleeps"),
+            ("empty.txt", ""),
+            ("c.txt", "Grüße aus München 🦀"),
+        ]
+        .iter()
+        .map(|&(name, text)| (name.to_string(), text.to_string()))
+        .collect()
+    }
+
+    fn extract_all(data: &[u8]) -> Vec<(String, String)> {
+        let archive = Archive::read(data).unwrap();
+        archive
+            .entries()
+            .map(|entry| (entry.name().to_string(), entry.decode().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn shared_and_per_file_extract_the_same() {
+        // when:
+        let shared = Archive::create(files()).unwrap();
+        let per_file = Archive::create_with(files(), CodebookMode::PerFile).unwrap();
+
+        // then:
+        assert_eq!(extract_all(&shared), files());
+        assert_eq!(extract_all(&per_file), files());
+        assert_eq!(shared[4], 0);
+        assert_eq!(per_file[4], PER_FILE);
+    }
+
+    #[test]
+    fn shared_codebook_is_smaller_for_similar_files() {
+        // given:
+        let files = (0..20)
+            .map(|i| {
+                (
+                    format!("{}.txt", i),
+                    format!("line {} of many similar lines", i),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // when:
+        let shared = Archive::create(files.clone()).unwrap();
+        let per_file = Archive::create_with(files.clone(), CodebookMode::PerFile).unwrap();
+
+        // then:
+        assert!(
+            shared.len() < per_file.len(),
+            "{} {}",
+            shared.len(),
+            per_file.len()
+        );
+        assert_eq!(extract_all(&shared), files);
+    }
+
+    #[test]
+    fn duplicate_names() {
+        // given:
+        let mut files = files();
+        files.push(("b.txt".to_string(), "again".to_string()));
+
+        // when:
+        let actual = Archive::create(files);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(ArchiveError::DuplicateName("b.txt".to_string()))
+        );
+    }
+
+    #[test]
+    fn get_decodes_one_entry() {
+        // given:
+        let data = Archive::create(files()).unwrap();
+        let archive = Archive::read(&data).unwrap();
+
+        // when:
+        let actual = archive.get("b.txt").map(|entry| entry.decode());
+
+        // then:
+        assert_eq!(archive.len(), 4);
+        assert_eq!(actual, Some(Ok("the lazy dog sleeps".to_string())));
+        assert!(archive.get("d.txt").is_none());
+    }
+
+    #[test]
+    fn empty_archive() {
+        // when:
+        let data = Archive::create(Vec::new()).unwrap();
+
+        // then:
+        assert!(Archive::read(&data).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated_archive() {
+        // given:
+        let data = Archive::create_with(files(), CodebookMode::PerFile).unwrap();
+
+        for len in 0..data.len() {
+            // when:
+            let actual = Archive::read(&data[..len]).map(|archive| {
+                archive
+                    .entries()
+                    .map(|entry| entry.decode())
+                    .collect::<Result<Vec<_>, _>>()
+            });
+
+            // then:
+            assert!(!matches!(actual, Ok(Ok(_))), "{}", len);
+        }
+    }
+
+    #[test]
+    fn corrupt_entry() {
+        // given:
+        let mut data = Archive::create(files()).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0x80;
+
+        // when:
+        let archive = Archive::read(&data).unwrap();
+        let actual = archive.get("c.txt").unwrap().decode();
+
+        // then:
+        assert!(actual.is_err());
+        assert_eq!(
+            archive.get("a.txt").unwrap().decode(),
+            Ok(files()[0].1.clone())
+        );
+    }
+}
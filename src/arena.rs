@@ -0,0 +1,410 @@
+//! A Huffman tree stored in one Vec with index-based children instead of a
+//! Box per node, used to build trees and to decode by walking them.
+
+use crate::bits::Bits;
+use crate::error::HuffmanError;
+use crate::tree::{HuffTree, HuffWeight, Subtree};
+use alloc::collections::{BTreeMap, BinaryHeap};
+use alloc::{string::String, vec, vec::Vec};
+use core::cmp::Ordering;
+
+/// A node of a HuffArena. The children of a node are at smaller indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArenaNode<T = char, W = u64> {
+    Leaf { occ: W, chr: T },
+    Node { left: usize, right: usize },
+}
+
+/// A Huffman tree whose nodes are stored in children first order, so the root
+/// is the last node. It is never empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffArena<T = char, W = u64> {
+    nodes: Vec<ArenaNode<T, W>>,
+}
+
+impl<T, W> HuffArena<T, W> {
+    pub fn nodes(&self) -> &[ArenaNode<T, W>] {
+        &self.nodes
+    }
+    /// The index of the root node.
+    pub fn root(&self) -> usize {
+        self.nodes.len() - 1
+    }
+}
+
+/// A tree in the heap of HuffArena::build, ordered like the trees of huffman
+/// by its weight with ties broken by its smallest symbol, both reversed so
+/// that a BinaryHeap pops the smallest tree. Weights are never NaN.
+///
+/// The leaves are numbered in the order of their symbols, so the smallest
+/// symbol is represented by the smallest leaf number.
+#[derive(Clone, Copy)]
+struct HeapEntry<W> {
+    weight: W,
+    min_leaf: usize,
+    index: usize,
+}
+
+impl<W: HuffWeight> Ord for HeapEntry<W> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.weight, self.min_leaf)
+            .partial_cmp(&(other.weight, other.min_leaf))
+            .expect("weights are not NaN")
+            .reverse()
+    }
+}
+impl<W: HuffWeight> PartialOrd for HeapEntry<W> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<W: HuffWeight> Eq for HeapEntry<W> {}
+impl<W: HuffWeight> PartialEq for HeapEntry<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+/// A tree of the forest while huffman_with_observer merges them.
+#[derive(Clone, Copy)]
+pub struct ForestTree<'a, T, W> {
+    nodes: &'a [ArenaNode<T, W>],
+    entry: HeapEntry<W>,
+}
+
+impl<'a, T, W: Copy> ForestTree<'a, T, W> {
+    /// The sum of the weights of the leaves.
+    pub fn weight(&self) -> W {
+        self.entry.weight
+    }
+    /// The symbols of the leaves from left to right.
+    pub fn symbols(&self) -> Vec<&'a T> {
+        let mut symbols = Vec::new();
+        let mut stack = vec![self.entry.index];
+        while let Some(index) = stack.pop() {
+            match &self.nodes[index] {
+                ArenaNode::Leaf { chr, .. } => symbols.push(chr),
+                ArenaNode::Node { left, right } => {
+                    stack.push(*right);
+                    stack.push(*left);
+                }
+            }
+        }
+        symbols
+    }
+}
+
+impl<W: Copy> ForestTree<'_, char, W> {
+    /// The symbols like HuffTree::chars.
+    pub fn chars(&self) -> String {
+        self.symbols().into_iter().collect()
+    }
+}
+
+/// The merge of two trees of the forest, passed to the observer of
+/// huffman_with_observer.
+pub struct MergeEvent<'a, T, W> {
+    step: usize,
+    nodes: &'a [ArenaNode<T, W>],
+    heap: &'a BinaryHeap<HeapEntry<W>>,
+    left: HeapEntry<W>,
+    right: HeapEntry<W>,
+    merged: HeapEntry<W>,
+}
+
+impl<'a, T, W: HuffWeight> MergeEvent<'a, T, W> {
+    /// The number of the merge, starting at 1.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+    /// The tree taken first, which becomes the left child.
+    pub fn left(&self) -> ForestTree<'a, T, W> {
+        self.tree(self.left)
+    }
+    /// The tree taken second, which becomes the right child.
+    pub fn right(&self) -> ForestTree<'a, T, W> {
+        self.tree(self.right)
+    }
+    /// The new tree of both.
+    pub fn merged(&self) -> ForestTree<'a, T, W> {
+        self.tree(self.merged)
+    }
+    /// The trees after the merge, including the new one, in the order they
+    /// will be taken.
+    pub fn forest(&self) -> Vec<ForestTree<'a, T, W>> {
+        let mut entries = self.heap.iter().copied().collect::<Vec<_>>();
+        // The heap order is reversed, so the first tree taken is the greatest.
+        entries.sort_by(|a, b| b.cmp(a));
+        entries.into_iter().map(|entry| self.tree(entry)).collect()
+    }
+    fn tree(&self, entry: HeapEntry<W>) -> ForestTree<'a, T, W> {
+        ForestTree {
+            nodes: self.nodes,
+            entry,
+        }
+    }
+}
+
+impl<T: Ord, W: HuffWeight> HuffArena<T, W> {
+    /// Build the same tree as huffman, or None if there are no weights. The
+    /// weights must be valid, which u64 counts always are and huffman_weighted
+    /// checks for others.
+    pub(crate) fn build(weights: BTreeMap<T, W>) -> Option<HuffArena<T, W>> {
+        HuffArena::build_with_observer(weights, |_| {})
+    }
+
+    /// Build the tree like build and call the observer after each merge of
+    /// two trees.
+    pub(crate) fn build_with_observer<F>(
+        weights: BTreeMap<T, W>,
+        mut observer: F,
+    ) -> Option<HuffArena<T, W>>
+    where
+        F: FnMut(MergeEvent<'_, T, W>),
+    {
+        let leaves = weights.len();
+        let mut nodes = Vec::with_capacity((2 * weights.len()).saturating_sub(1));
+        let mut heap = BinaryHeap::with_capacity(weights.len());
+        for (index, (chr, occ)) in weights.into_iter().enumerate() {
+            nodes.push(ArenaNode::Leaf { occ, chr });
+            heap.push(HeapEntry {
+                weight: occ,
+                min_leaf: index,
+                index,
+            });
+        }
+        while let (Some(first), Some(second)) = (heap.pop(), heap.pop()) {
+            nodes.push(ArenaNode::Node {
+                left: first.index,
+                right: second.index,
+            });
+            let merged = HeapEntry {
+                weight: first.weight.combine(second.weight),
+                min_leaf: first.min_leaf.min(second.min_leaf),
+                index: nodes.len() - 1,
+            };
+            heap.push(merged);
+            observer(MergeEvent {
+                step: nodes.len() - leaves,
+                nodes: &nodes,
+                heap: &heap,
+                left: first,
+                right: second,
+                merged,
+            });
+        }
+        match nodes.is_empty() {
+            true => None,
+            false => Some(HuffArena { nodes }),
+        }
+    }
+}
+
+impl<T: Clone, W: Copy> From<&HuffTree<T, W>> for HuffArena<T, W> {
+    fn from(tree: &HuffTree<T, W>) -> HuffArena<T, W> {
+        // A node is added once both of its children are, which are the last
+        // two entries of done at that point.
+        let mut nodes = Vec::new();
+        let mut done = Vec::new();
+        let mut pending = vec![(tree, false)];
+        while let Some((tree, children_done)) = pending.pop() {
+            match tree {
+                HuffTree::Leaf { occ, chr } => {
+                    nodes.push(ArenaNode::Leaf {
+                        occ: *occ,
+                        chr: chr.clone(),
+                    });
+                    done.push(nodes.len() - 1);
+                }
+                HuffTree::Node { .. } if children_done => {
+                    let right = done.pop().expect("the right child is done");
+                    let left = done.pop().expect("the left child is done");
+                    nodes.push(ArenaNode::Node { left, right });
+                    done.push(nodes.len() - 1);
+                }
+                HuffTree::Node { left, right } => {
+                    pending.push((tree, true));
+                    pending.push((right, false));
+                    pending.push((left, false));
+                }
+            }
+        }
+        HuffArena { nodes }
+    }
+}
+
+impl<T, W> From<HuffArena<T, W>> for HuffTree<T, W> {
+    fn from(arena: HuffArena<T, W>) -> HuffTree<T, W> {
+        // Children come before their parent, so each node can take them.
+        let mut trees: Vec<Option<HuffTree<T, W>>> = Vec::with_capacity(arena.nodes.len());
+        for node in arena.nodes {
+            let tree = match node {
+                ArenaNode::Leaf { occ, chr } => HuffTree::Leaf { occ, chr },
+                ArenaNode::Node { left, right } => HuffTree::Node {
+                    left: Subtree::new(trees[left].take().expect("a child has one parent")),
+                    right: Subtree::new(trees[right].take().expect("a child has one parent")),
+                },
+            };
+            trees.push(Some(tree));
+        }
+        trees
+            .pop()
+            .flatten()
+            .expect("an arena has at least one node")
+    }
+}
+
+impl<W> HuffArena<char, W> {
+    /// Decode the bits by walking from the root to a leaf for every symbol,
+    /// like decode_with_tree.
+    pub fn decode(&self, bits: &Bits) -> Result<String, HuffmanError> {
+        let root = self.root();
+        let mut decoded = String::new();
+        let mut bit_offset = 0;
+        while bit_offset < bits.len() {
+            let mut index = root;
+            let mut offset = bit_offset;
+            if let ArenaNode::Leaf { .. } = self.nodes[root] {
+                // A single leaf has the code 0.
+                if bits.get(offset) {
+                    return Err(HuffmanError::InvalidPrefix { bit_offset: offset });
+                }
+                offset += 1;
+            }
+            loop {
+                match self.nodes[index] {
+                    ArenaNode::Leaf { chr, .. } => {
+                        decoded.push(chr);
+                        break;
+                    }
+                    ArenaNode::Node { left, right } => {
+                        if offset == bits.len() {
+                            return Err(HuffmanError::TruncatedInput { bit_offset });
+                        }
+                        index = if bits.get(offset) { right } else { left };
+                        offset += 1;
+                    }
+                }
+            }
+            bit_offset = offset;
+        }
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{codebook, decode_with_tree, encode};
+    use crate::test_support::random_numbers;
+    use crate::tree::{frequency, huffman};
+
+    /// The tree huffman built with boxed nodes before it used HuffArena.
+    fn boxed_huffman(frequency: BTreeMap<char, u64>) -> Option<HuffTree> {
+        let mut trees = frequency
+            .into_iter()
+            .map(|(chr, occ)| HuffTree::new(chr, occ))
+            .collect::<Vec<_>>();
+        while trees.len() > 1 {
+            let mut take_min = || {
+                let (index, _) = trees
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, tree)| (tree.lettercount(), *tree.min_symbol()))
+                    .unwrap();
+                trees.remove(index)
+            };
+            let first = take_min();
+            let second = take_min();
+            trees.push(first.merge(second));
+        }
+        trees.pop()
+    }
+
+    #[test]
+    fn arena_builds_same_codebook_as_boxed_tree() {
+        for seed in 0..20 {
+            // given:
+            let symbols = 1 + seed as usize * 13;
+            let frequency = random_numbers(seed)
+                .take(symbols)
+                .enumerate()
+                .map(|(i, x)| {
+                    (
+                        core::char::from_u32(0x100 + i as u32).unwrap(),
+                        1 + x as u64 % 50,
+                    )
+                })
+                .collect::<BTreeMap<_, _>>();
+
+            // when:
+            let actual = HuffArena::build(frequency.clone()).map(HuffTree::from);
+
+            // then:
+            let expected = boxed_huffman(frequency).unwrap();
+            assert_eq!(actual.as_ref().map(codebook), Some(codebook(&expected)));
+            assert_eq!(actual, Some(expected));
+        }
+    }
+
+    #[test]
+    fn arena_tree_round_trip() {
+        // given:
+        let tree = huffman(frequency(&mut "Grüße aus München 🦀".chars())).unwrap();
+
+        // when:
+        let arena = HuffArena::from(&tree);
+        let actual = HuffTree::from(arena.clone());
+
+        // then:
+        assert_eq!(actual, tree);
+        assert_eq!(arena.nodes().len(), 2 * tree.leaf_count() - 1);
+        assert!(matches!(
+            arena.nodes()[arena.root()],
+            ArenaNode::Node { .. }
+        ));
+    }
+
+    #[test]
+    fn arena_single_leaf() {
+        // given:
+        let frequency = frequency(&mut "aaa".chars());
+
+        // when:
+        let arena = HuffArena::build(frequency).unwrap();
+
+        // then:
+        assert_eq!(arena.nodes(), [ArenaNode::Leaf { occ: 3, chr: 'a' }]);
+        assert_eq!(
+            arena.decode(&"000".chars().map(|c| c == '1').collect()),
+            Ok("aaa".into())
+        );
+    }
+
+    #[test]
+    fn arena_build_empty() {
+        // when:
+        let actual = HuffArena::<char, u32>::build(BTreeMap::new());
+
+        // then:
+        assert_eq!(actual, None);
+    }
+
+    #[test]
+    fn arena_decode_matches_decode_with_tree() {
+        // given:
+        let message = random_numbers(7)
+            .take(5000)
+            .map(|x| char::from(x as u8))
+            .collect::<String>();
+        let encoded = encode(&message);
+        let tree = HuffTree::from_codebook(&encoded.codebook).unwrap();
+
+        // when:
+        let actual = HuffArena::from(&tree).decode(&encoded.bits);
+
+        // then:
+        assert_eq!(actual, Ok(message));
+        assert_eq!(actual, decode_with_tree(&tree, &encoded.bits));
+    }
+}
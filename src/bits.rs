@@ -0,0 +1,565 @@
+//! A packed buffer of bits.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::iter::FromIterator;
+use core::ops::Index;
+use core::str::FromStr;
+
+/// The order in which bits are packed into a byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitOrder {
+    /// The first bit goes into the most significant bit of a byte.
+    #[default]
+    MsbFirst,
+    /// The first bit goes into the least significant bit of a byte, like in
+    /// DEFLATE.
+    LsbFirst,
+}
+
+impl BitOrder {
+    /// The mask of bit `i` of a byte, counted in this order.
+    #[cfg(feature = "std")]
+    pub(crate) fn mask(self, i: usize) -> u8 {
+        match self {
+            BitOrder::MsbFirst => 0x80 >> i,
+            BitOrder::LsbFirst => 1 << i,
+        }
+    }
+}
+
+/// A sequence of bits packed into bytes, most significant bit first.
+///
+/// The bit length is authoritative: only the first `len()` bits are part of
+/// the sequence and the padding bits of the last byte are always 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bits {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl Bits {
+    pub fn new() -> Bits {
+        Bits::default()
+    }
+    /// An empty sequence with room for `capacity` bits.
+    pub fn with_capacity(capacity: usize) -> Bits {
+        Bits {
+            bytes: Vec::with_capacity(capacity.div_ceil(8)),
+            len: 0,
+        }
+    }
+    /// The first `len` bits of `bytes`, or None if there are fewer bits.
+    pub fn from_bytes(len: usize, bytes: &[u8]) -> Option<Bits> {
+        if len > bytes.len().checked_mul(8)? {
+            return None;
+        }
+        let mut bytes = bytes[..len.div_ceil(8)].to_vec();
+        if !len.is_multiple_of(8) {
+            if let Some(last) = bytes.last_mut() {
+                *last &= 0xff << (8 - len % 8);
+            }
+        }
+        Some(Bits { bytes, len })
+    }
+    /// Like from_bytes for bytes packed in the bit order.
+    pub fn from_bytes_with(len: usize, bytes: &[u8], bit_order: BitOrder) -> Option<Bits> {
+        match bit_order {
+            BitOrder::MsbFirst => Bits::from_bytes(len, bytes),
+            BitOrder::LsbFirst => Bits::from_bytes(
+                len,
+                &bytes.iter().map(|b| b.reverse_bits()).collect::<Vec<_>>(),
+            ),
+        }
+    }
+    /// The packed bits, the last byte padded with 0 bits.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    /// Like as_bytes, but packed in the bit order.
+    pub fn to_bytes_with(&self, bit_order: BitOrder) -> Vec<u8> {
+        match bit_order {
+            BitOrder::MsbFirst => self.bytes.clone(),
+            BitOrder::LsbFirst => self.bytes.iter().map(|b| b.reverse_bits()).collect(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// The number of bits the sequence can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.bytes.capacity() * 8
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn push(&mut self, bit: bool) {
+        if self.len.is_multiple_of(8) {
+            self.bytes.push(0);
+        }
+        if bit {
+            self.bytes[self.len / 8] |= 0x80 >> (self.len % 8);
+        }
+        self.len += 1;
+    }
+    pub fn pop(&mut self) -> Option<bool> {
+        if self.len == 0 {
+            return None;
+        }
+        let bit = self.get(self.len - 1);
+        self.len -= 1;
+        if self.len.is_multiple_of(8) {
+            self.bytes.pop();
+        } else {
+            self.bytes[self.len / 8] &= !(0x80 >> (self.len % 8));
+        }
+        Some(bit)
+    }
+    /// Remove all bits, keeping the allocated capacity.
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.len = 0;
+    }
+    /// Shorten the sequence to its first `len` bits, clearing the bits beyond
+    /// in the last byte. Does nothing if `len` is not less than `len()`.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        self.len = len;
+        self.bytes.truncate(len.div_ceil(8));
+        if !len.is_multiple_of(8) {
+            if let Some(last) = self.bytes.last_mut() {
+                *last &= 0xff << (8 - len % 8);
+            }
+        }
+    }
+    /// Remove the first `count` whole bytes, i.e. the first `8 * count` bits,
+    /// in place.
+    ///
+    /// # Panics
+    ///
+    /// If the sequence has fewer than `8 * count` bits.
+    pub fn remove_leading_bytes(&mut self, count: usize) {
+        assert!(
+            count * 8 <= self.len,
+            "cannot remove {} bytes from {} bits",
+            count,
+            self.len
+        );
+        self.bytes.drain(..count);
+        self.len -= count * 8;
+    }
+    /// The bit at `index`.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `len()`.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(
+            index < self.len,
+            "bit index {} out of range for length {}",
+            index,
+            self.len
+        );
+        self.bytes[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+    /// Append all bits of a code, a byte at a time. Only grows the bytes to
+    /// the ones the new bits need, so a sequence from with_capacity with the
+    /// final length is never reallocated.
+    pub fn extend_from_code(&mut self, code: &Bits) {
+        let shift = self.len % 8;
+        self.len += code.len;
+        if shift == 0 {
+            self.bytes.extend_from_slice(&code.bytes);
+        } else {
+            for &byte in &code.bytes {
+                *self.bytes.last_mut().expect("shift > 0 implies a byte") |= byte >> shift;
+                // The last byte may spill only padding into the next one.
+                if self.bytes.len() < self.len.div_ceil(8) {
+                    self.bytes.push(byte << (8 - shift));
+                }
+            }
+        }
+    }
+    /// Append the low `len` bits of `value`, most significant first.
+    ///
+    /// # Panics
+    ///
+    /// If `len` is greater than 64.
+    pub fn extend_from_u64(&mut self, value: u64, len: usize) {
+        assert!(len <= 64, "a u64 has no {} bits", len);
+        let mut remaining = len;
+        while remaining > 0 {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let free = 8 - self.len % 8;
+            let take = free.min(remaining);
+            let chunk = (value >> (remaining - take)) as u8 & (0xff >> (8 - take));
+            *self.bytes.last_mut().expect("a byte was pushed") |= chunk << (free - take);
+            self.len += take;
+            remaining -= take;
+        }
+    }
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            bits: self,
+            index: 0,
+        }
+    }
+}
+
+/// Iterator over the bits of a Bits.
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    bits: &'a Bits,
+    index: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = bool;
+    fn next(&mut self) -> Option<bool> {
+        if self.index < self.bits.len() {
+            self.index += 1;
+            Some(self.bits.get(self.index - 1))
+        } else {
+            None
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.bits.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+impl<'a> IntoIterator for &'a Bits {
+    type Item = bool;
+    type IntoIter = Iter<'a>;
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl FromIterator<bool> for Bits {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Bits {
+        let mut bits = Bits::new();
+        bits.extend(iter);
+        bits
+    }
+}
+
+impl Extend<bool> for Bits {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, iter: I) {
+        for bit in iter {
+            self.push(bit);
+        }
+    }
+}
+
+impl From<Vec<bool>> for Bits {
+    fn from(bits: Vec<bool>) -> Bits {
+        bits.into_iter().collect()
+    }
+}
+
+impl Index<usize> for Bits {
+    type Output = bool;
+    /// Like get.
+    fn index(&self, index: usize) -> &bool {
+        if self.get(index) {
+            &true
+        } else {
+            &false
+        }
+    }
+}
+
+/// Writes the bits as `0` and `1` characters.
+impl Display for Bits {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for bit in self {
+            write!(f, "{}", if bit { "1" } else { "0" })?;
+        }
+        Ok(())
+    }
+}
+
+/// The string has a character other than `0`, `1`, `_` and whitespace at
+/// the byte `index`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseBitsError {
+    pub chr: char,
+    pub index: usize,
+}
+
+impl Display for ParseBitsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid bit {:?} at index {}, expected 0 or 1",
+            self.chr, self.index
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseBitsError {}
+
+/// Parses `0` and `1` characters like Display writes them, ignoring `_` and
+/// whitespace between them, e.g. `"0101_1100"`.
+impl FromStr for Bits {
+    type Err = ParseBitsError;
+    fn from_str(s: &str) -> Result<Bits, ParseBitsError> {
+        let mut bits = Bits::with_capacity(s.len());
+        for (index, chr) in s.char_indices() {
+            match chr {
+                '0' => bits.push(false),
+                '1' => bits.push(true),
+                '_' => {}
+                chr if chr.is_whitespace() => {}
+                chr => return Err(ParseBitsError { chr, index }),
+            }
+        }
+        Ok(bits)
+    }
+}
+
+/// Serializes the bits as a string of `0` and `1` like Display.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Bits {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes a string of only `0` and `1`, unlike FromStr without `_` or
+/// whitespace.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Bits {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Bits, D::Error> {
+        let code = alloc::string::String::deserialize(deserializer)?;
+        match code.chars().all(|chr| chr == '0' || chr == '1') {
+            true => Ok(code.parse().expect("0 and 1 are bits")),
+            false => Err(serde::de::Error::custom(alloc::format!(
+                "code {:?} may only contain 0 and 1",
+                code
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bits_of_various_lengths() {
+        for len in [0, 1, 7, 8, 9, 65] {
+            // given:
+            let pattern = (0..len).map(|i| i % 3 == 0).collect::<Vec<_>>();
+
+            // when:
+            let actual = pattern.iter().copied().collect::<Bits>();
+
+            // then:
+            assert_eq!(actual.len(), len);
+            assert_eq!(actual.is_empty(), len == 0);
+            assert_eq!(actual.as_bytes().len(), len.div_ceil(8));
+            assert_eq!(actual.iter().collect::<Vec<_>>(), pattern);
+            assert_eq!((0..len).map(|i| actual.get(i)).collect::<Vec<_>>(), pattern);
+            assert_eq!(
+                actual.to_string(),
+                pattern
+                    .iter()
+                    .map(|&bit| if bit { '1' } else { '0' })
+                    .collect::<String>()
+            );
+            assert_eq!(Bits::from_bytes(len, actual.as_bytes()), Some(actual));
+        }
+    }
+
+    #[test]
+    fn from_bytes_ignores_padding() {
+        // when:
+        let actual = Bits::from_bytes(9, &[0b1010_1010, 0b1111_1111]).unwrap();
+
+        // then:
+        assert_eq!(actual.to_string(), "101010101");
+        assert_eq!(actual.as_bytes(), [0b1010_1010, 0b1000_0000]);
+    }
+
+    #[test]
+    fn from_bytes_too_short() {
+        // when:
+        let actual = Bits::from_bytes(17, &[0, 0]);
+
+        // then:
+        assert_eq!(actual, None);
+    }
+
+    #[test]
+    fn pop_clears_padding() {
+        // given:
+        let mut bits = "111111111".chars().map(|c| c == '1').collect::<Bits>();
+
+        // when:
+        let actual = (bits.pop(), bits.pop());
+
+        // then:
+        assert_eq!(actual, (Some(true), Some(true)));
+        assert_eq!(bits.as_bytes(), [0b1111_1110]);
+        assert_eq!(bits, "1111111".chars().map(|c| c == '1').collect());
+    }
+
+    #[test]
+    fn remove_leading_bytes_keeps_the_rest() {
+        // given:
+        let mut bits = "1111000011".parse::<Bits>().unwrap();
+
+        // when:
+        bits.remove_leading_bytes(1);
+
+        // then:
+        assert_eq!(bits.to_string(), "11");
+        assert_eq!(bits.as_bytes(), [0b1100_0000]);
+    }
+
+    #[test]
+    fn extend_from_code_matches_push() {
+        for (prefix, code) in [(0, 3), (0, 8), (3, 5), (3, 13), (7, 1), (7, 17), (9, 0)].iter() {
+            // given:
+            let prefix = (0..*prefix).map(|i| i % 2 == 0).collect::<Bits>();
+            let code = (0..*code).map(|i| i % 3 != 1).collect::<Bits>();
+
+            // when:
+            let mut actual = prefix.clone();
+            actual.extend_from_code(&code);
+
+            // then:
+            let expected = prefix.iter().chain(code.iter()).collect::<Bits>();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn extend_from_u64_matches_push() {
+        for (prefix, len) in [(0, 0), (0, 8), (3, 5), (3, 13), (7, 1), (5, 64), (9, 40)].iter() {
+            // given:
+            let value = 0xa5c3_0f96_5a3c_f069u64;
+            let prefix = (0..*prefix).map(|i| i % 2 == 0).collect::<Bits>();
+
+            // when:
+            let mut actual = prefix.clone();
+            actual.extend_from_u64(value, *len);
+
+            // then:
+            let code = (0..*len).rev().map(|i| value >> i & 1 == 1);
+            let expected = prefix.iter().chain(code).collect::<Bits>();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn truncate_clears_padding() {
+        // given:
+        let mut bits = Bits::from_bytes(16, &[0xff, 0xff]).unwrap();
+
+        // when:
+        bits.truncate(20);
+        let unchanged = bits.clone();
+        bits.truncate(3);
+
+        // then:
+        assert_eq!(unchanged.len(), 16);
+        assert_eq!(bits.len(), 3);
+        assert_eq!(bits.as_bytes(), [0b1110_0000]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn get_out_of_range() {
+        Bits::from_bytes(7, &[0xff]).unwrap().get(7);
+    }
+
+    #[test]
+    fn bytes_in_both_bit_orders() {
+        // given:
+        let bits = "101100111000".chars().map(|c| c == '1').collect::<Bits>();
+
+        for (bit_order, expected) in [
+            (BitOrder::MsbFirst, [0b1011_0011, 0b1000_0000]),
+            (BitOrder::LsbFirst, [0b1100_1101, 0b0000_0001]),
+        ] {
+            // when:
+            let actual = bits.to_bytes_with(bit_order);
+
+            // then:
+            assert_eq!(actual, expected, "{:?}", bit_order);
+            assert_eq!(
+                Bits::from_bytes_with(12, &actual, bit_order),
+                Some(bits.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn parse_bits() {
+        for (input, expected) in [
+            ("", ""),
+            ("0101", "0101"),
+            ("0101_1100 1", "010111001"),
+            (" 1\t0\n", "10"),
+        ] {
+            // when:
+            let actual = input.parse::<Bits>();
+
+            // then:
+            assert_eq!(
+                actual.map(|bits| bits.to_string()),
+                Ok(expected.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn parse_bits_invalid_character() {
+        for (input, chr, index) in [("0102", '2', 3), ("01-10", '-', 2), ("1ü0x", 'ü', 1)] {
+            // when:
+            let actual = input.parse::<Bits>();
+
+            // then:
+            assert_eq!(actual, Err(ParseBitsError { chr, index }));
+        }
+        assert_eq!(
+            "0x".parse::<Bits>().unwrap_err().to_string(),
+            "invalid bit 'x' at index 1, expected 0 or 1"
+        );
+    }
+
+    #[test]
+    fn display_parse_round_trip() {
+        for len in [0, 1, 7, 8, 9, 65] {
+            // given:
+            let bits = (0..len).map(|i| i % 3 == 1).collect::<Bits>();
+
+            // when:
+            let actual = bits.to_string().parse::<Bits>();
+
+            // then:
+            assert_eq!(actual, Ok(bits));
+        }
+    }
+
+    #[test]
+    fn index_and_from_vec() {
+        // when:
+        let actual = Bits::from(vec![true, false, true]);
+
+        // then:
+        assert_eq!(actual.to_string(), "101");
+        assert!(actual[0] && !actual[1] && actual[2]);
+    }
+}
@@ -0,0 +1,1527 @@
+//! Codebooks derived from a Huffman tree and encoding/decoding with them.
+
+use crate::arena::HuffArena;
+use crate::bits::Bits;
+use crate::dense::DenseCodebook;
+use crate::error::HuffmanError;
+use crate::fast::FastDecoder;
+use crate::tree::{frequency, huffman, HuffTree};
+use alloc::collections::BTreeMap;
+use alloc::{
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use core::fmt::{self, Display, Formatter};
+
+/// Maps every symbol, `char`s by default, to its code.
+pub type Codebook<T = char> = BTreeMap<T, Bits>;
+
+/// A codebook that serializes as a map from each symbol to its code as a
+/// string of `0` and `1`, e.g. `{"a": "0101"}`. Deserializing fails unless
+/// validate_codebook accepts the codebook.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(transparent)]
+pub struct SerdeCodebook(pub Codebook);
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SerdeCodebook {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let codebook = Codebook::deserialize(deserializer)?;
+        validate_codebook(&codebook).map_err(serde::de::Error::custom)?;
+        Ok(SerdeCodebook(codebook))
+    }
+}
+
+/// Get a mapping from character to bit vector from the Huffman tree
+///
+/// A tree that consists of a single leaf would give its symbol an empty code,
+/// so it gets the one-bit code `0` instead. Otherwise a message of that symbol
+/// would encode to no bits at all and could not be decoded again.
+pub fn codebook<T: Ord + Clone, W>(huff: &HuffTree<T, W>) -> Codebook<T> {
+    let mut btm = BTreeMap::new();
+    if let HuffTree::Leaf { chr, .. } = huff {
+        btm.insert(chr.clone(), Some(false).into_iter().collect());
+        return btm;
+    }
+    // Each entry holds a node, the length of its code and its last bit.
+    // The nodes are visited depth first, so the code of a node is the code of
+    // the node visited before up to its parent, followed by its last bit.
+    let mut code = Bits::new();
+    let mut stack = vec![(huff, 0, false)];
+    while let Some((node, len, bit)) = stack.pop() {
+        if len > 0 {
+            code.truncate(len - 1);
+            code.push(bit);
+        }
+        match node {
+            HuffTree::Leaf { chr, .. } => {
+                btm.insert(chr.clone(), code.clone());
+            }
+            HuffTree::Node { left, right } => {
+                stack.push((right, len + 1, true));
+                stack.push((left, len + 1, false));
+            }
+        }
+    }
+    btm
+}
+
+impl<T: Clone> HuffTree<T> {
+    /// Rebuild the decoding tree from a codebook by inserting every code path.
+    /// The occurrence counts of the leaves are 0. A codebook with a single
+    /// symbol becomes a single leaf, like the tree it was created from.
+    pub fn from_codebook(codebook: &Codebook<T>) -> Result<HuffTree<T>, CodebookError> {
+        /// Build the tree of the sorted entries. Each task is a range of
+        /// entries whose codes share a prefix of the given length, None merges
+        /// the last two built subtrees.
+        fn build<T: Clone>(entries: &[(&T, &Bits)]) -> Result<HuffTree<T>, CodebookError> {
+            let mut tasks = vec![Some((0, entries.len(), 0))];
+            let mut built: Vec<HuffTree<T>> = Vec::new();
+            while let Some(task) = tasks.pop() {
+                let (start, end, depth) = match task {
+                    Some(range) => range,
+                    None => {
+                        let right = built.pop().expect("the right subtree is built");
+                        let left = built.pop().expect("the left subtree is built");
+                        built.push(left.merge(right));
+                        continue;
+                    }
+                };
+                let range = &entries[start..end];
+                if let Some((_, code)) = range.iter().find(|(_, code)| code.len() == depth) {
+                    match range {
+                        [(chr, _)] => {
+                            built.push(HuffTree::new((*chr).clone(), 0));
+                            continue;
+                        }
+                        _ => {
+                            return Err(CodebookError::NotPrefixFree {
+                                code: (*code).clone(),
+                            })
+                        }
+                    }
+                }
+                let split = start + range.partition_point(|(_, code)| !code.get(depth));
+                if split == start || split == end {
+                    return Err(CodebookError::MissingChild {
+                        node: range[0].1.iter().take(depth).collect(),
+                    });
+                }
+                tasks.push(None);
+                tasks.push(Some((split, end, depth + 1)));
+                tasks.push(Some((start, split, depth + 1)));
+            }
+            Ok(built.pop().expect("the tree is built"))
+        }
+        let mut entries = codebook.iter().collect::<Vec<_>>();
+        match entries[..] {
+            [] => Err(CodebookError::Empty),
+            [(chr, code)] if code.len() <= 1 => Ok(HuffTree::new(chr.clone(), 0)),
+            _ => {
+                entries.sort_by(|(_, a), (_, b)| a.iter().cmp(b.iter()));
+                build(&entries)
+            }
+        }
+    }
+}
+
+impl<T: Ord + Clone> HuffTree<T> {
+    /// Rebuild the tree of the canonical codes with the given lengths, see
+    /// codebook_from_lengths. The lengths have to satisfy the Kraft inequality
+    /// with equality, because a Huffman tree is always complete: lengths that
+    /// leave codes unused are rejected just like ones that need too many. A
+    /// single symbol with length 0 or 1 becomes a single leaf.
+    pub fn from_code_lengths(lengths: &BTreeMap<T, u8>) -> Result<HuffTree<T>, CodebookError> {
+        match lengths.iter().collect::<Vec<_>>()[..] {
+            [] => return Err(CodebookError::Empty),
+            [(symbol, &len)] if len <= 1 => return Ok(HuffTree::new(symbol.clone(), 0)),
+            _ => {}
+        }
+        let mut count_per_len = [0usize; 256];
+        for &len in lengths.values() {
+            count_per_len[usize::from(len)] += 1;
+        }
+        // The codes still free at each length after assigning the shorter
+        // ones. Once there are more than symbols left, some stay unused.
+        let mut remaining = lengths.len();
+        let mut free = 1usize;
+        for &count in count_per_len.iter() {
+            if count > free {
+                return Err(CodebookError::OverSubscribed);
+            }
+            free -= count;
+            remaining -= count;
+            if free > remaining {
+                return Err(CodebookError::UnderSubscribed);
+            }
+            free *= 2;
+        }
+        HuffTree::from_codebook(&codebook_from_lengths(lengths))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CodebookError {
+    /// The codebook does not contain any symbol.
+    Empty,
+    /// The code is a prefix of another code.
+    NotPrefixFree { code: Bits },
+    /// Codes continue with only one of the two bits after the node's path.
+    MissingChild { node: Bits },
+    /// The symbol has an empty code although it is not the only symbol.
+    EmptyCode(char),
+    /// Both symbols have the same code.
+    DuplicateCode { first: char, second: char },
+    /// The code of `prefix` is a prefix of the code of `symbol`.
+    PrefixConflict { prefix: char, symbol: char },
+    /// The code lengths violate the Kraft inequality, there are not enough
+    /// codes of these lengths.
+    OverSubscribed,
+    /// The code lengths leave codes unused, so they cannot come from a
+    /// Huffman tree.
+    UnderSubscribed,
+    /// The symbol is given more than one code length.
+    DuplicateSymbol(char),
+}
+
+impl Display for CodebookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CodebookError::Empty => write!(f, "the codebook does not contain any symbol"),
+            CodebookError::NotPrefixFree { code } => {
+                write!(f, "code {} is a prefix of another code", code)
+            }
+            CodebookError::MissingChild { node } => {
+                write!(f, "node {:?} has only one child", node.to_string())
+            }
+            CodebookError::EmptyCode(chr) => write!(f, "symbol {:?} has an empty code", chr),
+            CodebookError::DuplicateCode { first, second } => {
+                write!(f, "symbols {:?} and {:?} have the same code", first, second)
+            }
+            CodebookError::PrefixConflict { prefix, symbol } => write!(
+                f,
+                "the code of {:?} is a prefix of the code of {:?}",
+                prefix, symbol
+            ),
+            CodebookError::OverSubscribed => {
+                write!(f, "there are not enough codes of the given lengths")
+            }
+            CodebookError::UnderSubscribed => {
+                write!(f, "the code lengths leave codes unused")
+            }
+            CodebookError::DuplicateSymbol(chr) => {
+                write!(f, "symbol {:?} has more than one code length", chr)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CodebookError {}
+
+/// The result of encode: the bits together with the canonical codebook and the
+/// tree of that codebook, whose leaves hold the symbol counts. The empty
+/// message has no tree, an empty codebook and no bits.
+pub struct Encoded<T = char> {
+    pub tree: Option<HuffTree<T>>,
+    pub codebook: Codebook<T>,
+    pub bits: Bits,
+}
+
+/// Given a message m, encode returns the Huffman encoded message using
+/// canonical codes. The empty message is encoded as no bits with an empty
+/// codebook, which decode turns back into the empty message. Encoding cannot
+/// fail, because the codebook is built from the message itself.
+pub fn encode(message: &str) -> Encoded {
+    encode_iter(message.chars())
+}
+
+/// Encode the message with an existing codebook, e.g. one trained on a corpus.
+pub fn encode_with(codebook: &Codebook, message: &str) -> Result<Bits, HuffmanError> {
+    let mut bits = Bits::new();
+    for chr in message.chars() {
+        let code = codebook.get(&chr).ok_or(HuffmanError::UnknownSymbol(chr))?;
+        bits.extend_from_code(code);
+    }
+    Ok(bits)
+}
+
+/// The number of bits encoding a message with the symbol frequencies with the
+/// codebook takes, computed without encoding it. A counted symbol without a
+/// code is an UnknownSymbol error, a length beyond u64::MAX a LengthOverflow.
+pub fn encoded_len_bits(
+    frequency: &BTreeMap<char, u64>,
+    codebook: &Codebook,
+) -> Result<u64, HuffmanError> {
+    bit_len(frequency, codebook)
+        .map_err(HuffmanError::UnknownSymbol)?
+        .ok_or(HuffmanError::LengthOverflow)
+}
+
+/// Like encoded_len_bits, but returns the first symbol without a code and
+/// None for a length beyond u64::MAX.
+fn bit_len<T: Ord + Clone>(
+    frequency: &BTreeMap<T, u64>,
+    codebook: &Codebook<T>,
+) -> Result<Option<u64>, T> {
+    frequency
+        .iter()
+        .try_fold(Some(0), |len: Option<u64>, (symbol, &occ)| {
+            let code = codebook.get(symbol).ok_or_else(|| symbol.clone())?;
+            Ok(len.and_then(|len| len.checked_add(occ.checked_mul(code.len() as u64)?)))
+        })
+}
+
+/// A codebook for arbitrary binary data with bytes as symbols.
+pub type ByteCodebook = Codebook<u8>;
+
+/// Encode arbitrary bytes, which need not be valid UTF-8. The bytes are
+/// encoded with a DenseCodebook, unless a code is too long for it. No bytes
+/// have an empty codebook like the empty message of encode.
+pub fn encode_bytes(bytes: &[u8]) -> (ByteCodebook, Bits) {
+    let frequency = frequency(&mut bytes.iter().copied());
+    let codebook = match huffman(frequency) {
+        Some(huff) => canonical_codebook(&huff),
+        None => return (ByteCodebook::new(), Bits::new()),
+    };
+    let bits = match DenseCodebook::try_from(&codebook) {
+        Ok(dense) => dense
+            .encode(bytes)
+            .expect("the codebook is built from the bytes"),
+        Err(_) => {
+            let mut bits = Bits::new();
+            for byte in bytes {
+                bits.extend_from_code(&codebook[byte]);
+            }
+            bits
+        }
+    };
+    (codebook, bits)
+}
+
+/// Decode the bits into exactly the bytes that were passed to encode_bytes.
+pub fn decode_bytes(codebook: &ByteCodebook, bits: &Bits) -> Result<Vec<u8>, HuffmanError> {
+    decode_symbols(codebook, bits)
+}
+
+/// Encode a sequence of arbitrary symbols, e.g. bytes or word tokens. No
+/// symbols have an empty codebook like the empty message of encode.
+pub fn encode_symbols<T: Ord + Clone>(symbols: &[T]) -> (Codebook<T>, Bits) {
+    let Encoded { codebook, bits, .. } = encode_iter(symbols.iter().cloned());
+    (codebook, bits)
+}
+
+/// Encode the symbols of any iterator like encode, e.g. of a one-shot source
+/// like a channel. The symbols are buffered to encode them after counting,
+/// see encode_two_pass to avoid that.
+pub fn encode_from_iter<I: IntoIterator<Item = char>>(symbols: I) -> Encoded {
+    let symbols = symbols.into_iter();
+    let mut buffer = Vec::with_capacity(symbols.size_hint().0);
+    buffer.extend(symbols);
+    encode_iter(buffer.iter().copied())
+}
+
+/// Encode like encode, but count the symbols of one iterator of the source
+/// and encode those of a second one without buffering them, e.g. for a file
+/// that is cheap to read again. Both iterators must yield the same symbols, a
+/// symbol only the second one yields is an UnknownSymbol error.
+pub fn encode_two_pass<I, F>(source: F) -> Result<Encoded, HuffmanError>
+where
+    I: IntoIterator<Item = char>,
+    F: Fn() -> I,
+{
+    encode_passes(|| source().into_iter()).map_err(HuffmanError::UnknownSymbol)
+}
+
+pub(crate) fn encode_iter<T: Ord + Clone, I: Iterator<Item = T> + Clone>(symbols: I) -> Encoded<T> {
+    match encode_passes(|| symbols.clone()) {
+        Ok(encoded) => encoded,
+        Err(_) => unreachable!("a clone yields the same symbols"),
+    }
+}
+
+/// Count the symbols of one iterator of symbols and encode those of a second
+/// one. Fails with a symbol only the second one yields.
+fn encode_passes<T: Ord + Clone, I: Iterator<Item = T>, F: FnMut() -> I>(
+    mut symbols: F,
+) -> Result<Encoded<T>, T> {
+    let frequency = frequency(&mut symbols());
+    let codebook = match huffman(frequency.clone()) {
+        Some(huff) => canonical_codebook(&huff),
+        // Nothing was counted, so any symbol of the second pass is unknown.
+        None => {
+            return match symbols().next() {
+                Some(symbol) => Err(symbol),
+                None => Ok(Encoded {
+                    tree: None,
+                    codebook: Codebook::new(),
+                    bits: Bits::new(),
+                }),
+            }
+        }
+    };
+    let tree = HuffTree::from_codebook(&codebook)
+        .expect("canonical codes of a Huffman tree form a tree")
+        .with_counts(&frequency);
+    let bit_len = bit_len(&frequency, &codebook)
+        .unwrap_or_else(|_| unreachable!("the codebook has a code for each counted symbol"))
+        .expect("the bits of counted symbols fit into memory");
+    let mut bits = Bits::with_capacity(bit_len as usize);
+    for symbol in symbols() {
+        match codebook.get(&symbol) {
+            Some(code) => bits.extend_from_code(code),
+            None => return Err(symbol),
+        }
+    }
+    Ok(Encoded {
+        tree: Some(tree),
+        codebook,
+        bits,
+    })
+}
+
+/// Check that the codebook can be decoded unambiguously: no code is empty,
+/// unless it belongs to the only symbol, and no code is equal to or a prefix of
+/// another one. An empty codebook is valid, it decodes only empty input.
+pub fn validate_codebook(codebook: &Codebook) -> Result<(), CodebookError> {
+    match prefix_conflict(codebook) {
+        None => Ok(()),
+        Some([(&chr, code), _]) if code.is_empty() => Err(CodebookError::EmptyCode(chr)),
+        Some([(&first, a), (&second, b)]) if a == b => {
+            Err(CodebookError::DuplicateCode { first, second })
+        }
+        Some([(&prefix, _), (&symbol, _)]) => Err(CodebookError::PrefixConflict { prefix, symbol }),
+    }
+}
+
+/// Like validate_codebook for arbitrary symbols, reporting any conflict as
+/// NotPrefixFree with the code that is a prefix of another one.
+fn validate_symbol_codebook<T>(codebook: &Codebook<T>) -> Result<(), CodebookError> {
+    match prefix_conflict(codebook) {
+        None => Ok(()),
+        Some([(_, code), _]) => Err(CodebookError::NotPrefixFree { code: code.clone() }),
+    }
+}
+
+/// Two codes of which the first is equal to or a prefix of the second, if
+/// there are any. An empty code is a prefix of every other one.
+fn prefix_conflict<T>(codebook: &Codebook<T>) -> Option<[(&T, &Bits); 2]> {
+    // In lexicographic order a code that is a prefix of another one is
+    // directly followed by a code it is a prefix of. The sort is stable, so
+    // of several empty codes the one of the smallest symbol comes first.
+    let mut entries = codebook.iter().collect::<Vec<_>>();
+    entries.sort_by(|(_, a), (_, b)| a.iter().cmp(b.iter()));
+    entries
+        .windows(2)
+        .map(|pair| [pair[0], pair[1]])
+        .find(|[(_, a), (_, b)]| a.len() <= b.len() && a.iter().zip(b.iter()).all(|(x, y)| x == y))
+}
+
+/// The Kraft sum of the code lengths, sum(2^-len). It is at most 1 for a
+/// prefix-free codebook and exactly 1 if the codebook is complete.
+pub fn kraft_sum<T>(codebook: &Codebook<T>) -> f64 {
+    codebook
+        .values()
+        .map(|code| (0..code.len()).fold(1.0, |weight: f64, _| weight / 2.0))
+        .sum()
+}
+
+/// A codebook is complete if every possible bit pattern eventually maps to a
+/// symbol, i.e. the Kraft sum of its code lengths is exactly 1.
+pub fn is_complete<T>(codebook: &Codebook<T>) -> bool {
+    // Count the codes per length and carry pairs of codes up to their parent
+    // level, which is an exact way of checking sum(2^-len) == 1.
+    let max_len = match codebook.values().map(Bits::len).max() {
+        Some(max_len) => max_len,
+        None => return false,
+    };
+    let mut count_per_len = vec![0usize; max_len + 1];
+    for code in codebook.values() {
+        count_per_len[code.len()] += 1;
+    }
+    for len in (1..=max_len).rev() {
+        if !count_per_len[len].is_multiple_of(2) {
+            return false;
+        }
+        count_per_len[len - 1] += count_per_len[len] / 2;
+    }
+    count_per_len[0] == 1
+}
+
+/// Decode the bits with the codebook, failing if the codebook is invalid or
+/// the bits do not form a sequence of codes.
+pub fn decode(codebook: &Codebook, bits: &Bits) -> Result<String, HuffmanError> {
+    let mut decoded = String::new();
+    decode_into(codebook, bits, &mut decoded)?;
+    Ok(decoded)
+}
+
+/// Decode the bits like decode, but append the symbols to out and return how
+/// many were appended. On error the symbols decoded before the failing code
+/// remain in out; the bit_offset of the error is where that code starts.
+pub fn decode_into(
+    codebook: &Codebook,
+    bits: &Bits,
+    out: &mut String,
+) -> Result<usize, HuffmanError> {
+    decode_into_limited(codebook, bits, out, usize::MAX, usize::MAX)
+}
+
+/// Decode the bits like decode, but fail with LimitExceeded instead of
+/// producing more than max_chars symbols, e.g. for untrusted input where a
+/// few bytes of short codes would decode to a huge string.
+pub fn decode_with_limit(
+    codebook: &Codebook,
+    bits: &Bits,
+    max_chars: usize,
+) -> Result<String, HuffmanError> {
+    let mut decoded = String::new();
+    decode_into_limited(codebook, bits, &mut decoded, max_chars, usize::MAX)?;
+    Ok(decoded)
+}
+
+/// Decode like decode_into, but stop before appending more than max_chars
+/// symbols or letting out grow beyond max_bytes.
+pub(crate) fn decode_into_limited(
+    codebook: &Codebook,
+    bits: &Bits,
+    out: &mut String,
+    max_chars: usize,
+    max_bytes: usize,
+) -> Result<usize, HuffmanError> {
+    validate_codebook(codebook).map_err(HuffmanError::InvalidCodebook)?;
+    let decoder = FastDecoder::new(codebook);
+    let mut count = 0;
+    let mut bit_offset = 0;
+    while bit_offset < bits.len() {
+        let (&chr, code_len) = decoder.decode_symbol(bits, bit_offset, bits.len())?;
+        if count == max_chars || out.len() + chr.len_utf8() > max_bytes {
+            return Err(HuffmanError::LimitExceeded {
+                produced: count,
+                bit_offset,
+            });
+        }
+        out.push(chr);
+        count += 1;
+        bit_offset += code_len;
+    }
+    Ok(count)
+}
+
+/// Decode the bits by walking the tree from the root to a leaf for every
+/// symbol, which does not depend on the size of the alphabet. Build the tree
+/// with HuffTree::from_codebook to decode bits from encode.
+pub fn decode_with_tree(huff: &HuffTree, bits: &Bits) -> Result<String, HuffmanError> {
+    HuffArena::from(huff).decode(bits)
+}
+
+/// Decode only the first bit_len bits, ignoring any padding beyond.
+pub fn decode_len(
+    codebook: &Codebook,
+    bits: &Bits,
+    bit_len: usize,
+) -> Result<String, HuffmanError> {
+    validate_codebook(codebook).map_err(HuffmanError::InvalidCodebook)?;
+    if bit_len > bits.len() {
+        return Err(HuffmanError::TruncatedInput {
+            bit_offset: bits.len(),
+        });
+    }
+    let decoder = FastDecoder::new(codebook);
+    let mut decoded = String::new();
+    let mut bit_offset = 0;
+    while bit_offset < bit_len {
+        let (&chr, code_len) = decoder.decode_symbol(bits, bit_offset, bit_len)?;
+        decoded.push(chr);
+        bit_offset += code_len;
+    }
+    Ok(decoded)
+}
+
+/// Decode the bits into a sequence of arbitrary symbols, failing like decode
+/// if the codebook is not prefix-free.
+pub fn decode_symbols<T: Ord + Clone>(
+    codebook: &Codebook<T>,
+    bits: &Bits,
+) -> Result<Vec<T>, HuffmanError> {
+    validate_symbol_codebook(codebook).map_err(HuffmanError::InvalidCodebook)?;
+    let decoder = FastDecoder::new(codebook);
+    let mut decoded = Vec::new();
+    let mut bit_offset = 0;
+    while bit_offset < bits.len() {
+        let (symbol, code_len) = decoder.decode_symbol(bits, bit_offset, bits.len())?;
+        decoded.push(symbol.clone());
+        bit_offset += code_len;
+    }
+    Ok(decoded)
+}
+
+/// Decode the symbol starting at bit_offset, returning it with its code length.
+/// Bits from bit_len on are not considered. This scans the whole codebook, so
+/// decoders use a FastDecoder instead.
+pub(crate) fn decode_symbol<'a, T>(
+    codebook: &'a Codebook<T>,
+    bits: &Bits,
+    bit_offset: usize,
+    bit_len: usize,
+) -> Result<(&'a T, usize), HuffmanError> {
+    let remaining = bit_len - bit_offset;
+    let matches =
+        |code: &Bits, len: usize| (0..len).all(|i| code.get(i) == bits.get(bit_offset + i));
+    match codebook
+        .iter()
+        .find(|(_, code)| !code.is_empty() && code.len() <= remaining && matches(code, code.len()))
+    {
+        Some((symbol, code)) => Ok((symbol, code.len())),
+        None if codebook
+            .values()
+            .any(|code| code.len() > remaining && matches(code, remaining)) =>
+        {
+            Err(HuffmanError::TruncatedInput { bit_offset })
+        }
+        None => Err(HuffmanError::InvalidPrefix { bit_offset }),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The model does not contain any symbol.
+    EmptyModel,
+    /// The model has more symbols than codes of at most `length_limit` bits.
+    LengthLimitTooSmall { symbols: usize, length_limit: u8 },
+    /// The minimum code length exceeds the length limit.
+    MinLengthAboveLimit { min_length: u8, length_limit: u8 },
+    /// The message contains a symbol that has no code.
+    UnknownSymbol(char),
+    /// The message contains the reserved EOF_SYMBOL.
+    ReservedSymbol(char),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::EmptyModel => write!(f, "the model does not contain any symbol"),
+            EncodeError::LengthLimitTooSmall {
+                symbols,
+                length_limit,
+            } => write!(
+                f,
+                "{} symbols do not fit into codes of at most {} bits",
+                symbols, length_limit
+            ),
+            EncodeError::MinLengthAboveLimit {
+                min_length,
+                length_limit,
+            } => write!(
+                f,
+                "minimum code length {} exceeds the length limit {}",
+                min_length, length_limit
+            ),
+            EncodeError::UnknownSymbol(chr) => write!(f, "no code for symbol {:?}", chr),
+            EncodeError::ReservedSymbol(chr) => write!(f, "symbol {:?} is reserved", chr),
+        }
+    }
+}
+
+/// Get the code length of every symbol in the Huffman tree.
+pub(crate) fn code_lengths<T: Ord + Clone>(huff: &HuffTree<T>) -> BTreeMap<T, usize> {
+    codebook(huff)
+        .into_iter()
+        .map(|(symbol, code)| (symbol, code.len()))
+        .collect()
+}
+
+/// Get the canonical codebook for the Huffman tree. It assigns every symbol a
+/// code of the same length as codebook, but the codes only depend on these
+/// lengths and not on the shape of the tree.
+pub fn canonical_codebook<T: Ord + Clone>(huff: &HuffTree<T>) -> Codebook<T> {
+    canonical_codes(&code_lengths(huff))
+}
+
+/// Get the canonical codebook for the given code lengths, so a codebook can
+/// be transmitted as just the length of each code.
+pub fn codebook_from_lengths<T: Ord + Clone>(lengths: &BTreeMap<T, u8>) -> Codebook<T> {
+    canonical_codes(
+        &lengths
+            .iter()
+            .map(|(symbol, &len)| (symbol.clone(), usize::from(len)))
+            .collect(),
+    )
+}
+
+/// Assign canonical codes to the given code lengths: sorted by length and then
+/// by symbol, each code is the previous one plus one, shifted left to its
+/// length.
+pub(crate) fn canonical_codes<T: Ord + Clone>(lengths: &BTreeMap<T, usize>) -> Codebook<T> {
+    let mut sorted = lengths.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|&(symbol, len)| (len, symbol));
+    let mut codebook = Codebook::new();
+    let mut code = Bits::new();
+    for (i, (symbol, &len)) in sorted.into_iter().enumerate() {
+        if i > 0 {
+            // increment
+            while code.pop() == Some(true) {}
+            code.push(true);
+        }
+        code.extend((code.len()..len).map(|_| false));
+        codebook.insert(symbol.clone(), code.clone());
+    }
+    codebook
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(s: &str) -> Bits {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn single_character_message_round_trip() {
+        // given:
+        let message = "a";
+
+        // when:
+        let Encoded { codebook, bits, .. } = encode(message);
+        let actual = decode(&codebook, &bits).unwrap();
+
+        // then:
+        assert_eq!(codebook[&'a'].to_string(), "0");
+        assert_eq!(actual, message);
+    }
+
+    #[test]
+    fn repeated_single_character_message_round_trip() {
+        // given:
+        let message = "aaaa";
+
+        // when:
+        let Encoded { codebook, bits, .. } = encode(message);
+        let actual = decode(&codebook, &bits).unwrap();
+
+        // then:
+        assert_eq!(bits.to_string(), "0000");
+        assert_eq!(actual, message);
+    }
+
+    #[test]
+    fn two_character_message_round_trip() {
+        // given:
+        let message = "abbab";
+
+        // when:
+        let Encoded { codebook, bits, .. } = encode(message);
+        let actual = decode(&codebook, &bits).unwrap();
+
+        // then:
+        assert_eq!(bits.len(), 5);
+        assert_eq!(actual, message);
+    }
+
+    #[test]
+    fn is_complete_huffman_codebook() {
+        // given:
+        let codebook = encode("aardvarks ate apples around aachen").codebook;
+
+        // when:
+        let actual = is_complete(&codebook);
+
+        // then:
+        assert!(actual);
+    }
+
+    #[test]
+    fn is_complete_prefix_free_but_incomplete() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', bits("0"));
+        codebook.insert('b', bits("10"));
+
+        // when:
+        let actual = is_complete(&codebook);
+
+        // then:
+        assert!(!actual);
+    }
+
+    #[test]
+    fn decode_len_ignores_padding() {
+        // given:
+        let message = "BACADAEAFABBAAAGAH";
+        let Encoded {
+            codebook, mut bits, ..
+        } = encode(message);
+        let bit_len = bits.len();
+        bits.extend(codebook[&'A'].iter().chain(codebook[&'B'].iter()));
+
+        // when:
+        let actual = decode_len(&codebook, &bits, bit_len);
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+    }
+
+    #[test]
+    fn decode_len_beyond_bits() {
+        // given:
+        let Encoded { codebook, bits, .. } = encode("BACADAEAFABBAAAGAH");
+
+        // when:
+        let actual = decode_len(&codebook, &bits, bits.len() + 1);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: bits.len()
+            })
+        );
+    }
+
+    #[test]
+    fn decode_len_and_symbols_reject_codebook_that_is_not_prefix_free() {
+        // given:
+        let codebook = codebook_of(&[('a', "0"), ('b', "01")]);
+        let symbols = codebook
+            .iter()
+            .map(|(&chr, code)| (chr as u8, code.clone()))
+            .collect::<Codebook<u8>>();
+        let input = bits("01");
+
+        // when:
+        let actual = (
+            decode_len(&codebook, &input, input.len()),
+            decode_symbols(&symbols, &input),
+        );
+
+        // then:
+        assert_eq!(
+            actual,
+            (
+                Err(HuffmanError::InvalidCodebook(
+                    CodebookError::PrefixConflict {
+                        prefix: 'a',
+                        symbol: 'b'
+                    }
+                )),
+                Err(HuffmanError::InvalidCodebook(
+                    CodebookError::NotPrefixFree { code: bits("0") }
+                ))
+            )
+        );
+    }
+
+    #[test]
+    fn decode_truncated_code() {
+        // given:
+        let Encoded {
+            codebook, mut bits, ..
+        } = encode("BACADAEAFABBAAAGAH");
+        let bit_len = bits.len();
+        bits.extend(codebook[&'H'].iter().take(codebook[&'H'].len() - 1));
+
+        // when:
+        let actual = decode(&codebook, &bits);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: bit_len
+            })
+        );
+    }
+
+    #[test]
+    fn decode_unknown_prefix() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', bits("0"));
+        codebook.insert('b', bits("10"));
+        let bits = bits("010110");
+
+        // when:
+        let actual = decode(&codebook, &bits);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::InvalidPrefix { bit_offset: 3 }));
+    }
+
+    #[test]
+    fn decode_with_empty_codebook() {
+        // when:
+        let actual = decode(&Codebook::new(), &bits("1"));
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::InvalidPrefix { bit_offset: 0 }));
+    }
+
+    #[test]
+    fn encode_symbols_bytes() {
+        // given:
+        let bytes = b"\x00\xff\x00abc\x00";
+
+        // when:
+        let (codebook, bits) = encode_symbols(bytes);
+        let actual = decode_symbols(&codebook, &bits).unwrap();
+
+        // then:
+        assert_eq!(actual, bytes);
+    }
+
+    #[test]
+    fn encode_symbols_word_tokens() {
+        // given:
+        let words = "the cat and the hat and the bat"
+            .split(' ')
+            .collect::<Vec<_>>();
+
+        // when:
+        let (codebook, bits) = encode_symbols(&words);
+        let actual = decode_symbols(&codebook, &bits).unwrap();
+
+        // then:
+        let shortest = codebook.values().map(Bits::len).min().unwrap();
+        assert_eq!(codebook[&"the"].len(), shortest);
+        assert_eq!(actual, words);
+    }
+
+    #[test]
+    fn canonical_codebook_ignores_tree_shape() {
+        // given:
+        let leaf = |chr, occ| HuffTree::new(chr, occ);
+        let right_leaning =
+            leaf('a', 8).merge(leaf('b', 4).merge(leaf('c', 2).merge(leaf('d', 1))));
+        let left_leaning = leaf('d', 1)
+            .merge(leaf('c', 2))
+            .merge(leaf('b', 4))
+            .merge(leaf('a', 8));
+
+        // when:
+        let actual = canonical_codebook(&right_leaning);
+
+        // then:
+        assert_ne!(codebook(&right_leaning), codebook(&left_leaning));
+        assert_eq!(actual, canonical_codebook(&left_leaning));
+        assert_eq!(actual[&'a'], bits("0"));
+        assert_eq!(actual[&'b'], bits("10"));
+        assert_eq!(actual[&'c'], bits("110"));
+        assert_eq!(actual[&'d'], bits("111"));
+    }
+
+    #[test]
+    fn codebook_from_lengths_matches_canonical_codebook() {
+        // given:
+        let hufftree =
+            huffman(frequency(&mut "aardvarks ate apples around aachen".chars())).unwrap();
+        let lengths = codebook(&hufftree)
+            .into_iter()
+            .map(|(chr, code)| (chr, code.len() as u8))
+            .collect();
+
+        // when:
+        let actual = codebook_from_lengths(&lengths);
+
+        // then:
+        assert_eq!(actual, canonical_codebook(&hufftree));
+        assert!(is_complete(&actual));
+    }
+
+    #[test]
+    fn encode_uses_canonical_codes() {
+        // given:
+        let message = "aardvarks ate apples around aachen";
+
+        // when:
+        let Encoded { codebook, bits, .. } = encode(message);
+        let actual = decode(&codebook, &bits).unwrap();
+
+        // then:
+        let hufftree = huffman(frequency(&mut message.chars())).unwrap();
+        assert_eq!(codebook, canonical_codebook(&hufftree));
+        assert_eq!(actual, message);
+    }
+
+    #[test]
+    fn from_codebook_round_trip() {
+        for message in ["a", "ab", "BACADAEAFABBAAAGAH", "Grüße aus München für 5 €"] {
+            // given:
+            let expected = codebook(&huffman(frequency(&mut message.chars())).unwrap());
+
+            // when:
+            let actual = codebook(&HuffTree::from_codebook(&expected).unwrap());
+
+            // then:
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn from_codebook_canonical_round_trip() {
+        // given:
+        let expected = encode("aardvarks ate apples around aachen").codebook;
+
+        // when:
+        let actual = codebook(&HuffTree::from_codebook(&expected).unwrap());
+
+        // then:
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn from_codebook_not_prefix_free() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', bits("0"));
+        codebook.insert('b', bits("01"));
+        codebook.insert('c', bits("1"));
+
+        // when:
+        let actual = HuffTree::from_codebook(&codebook);
+
+        // then:
+        assert_eq!(
+            actual.err(),
+            Some(CodebookError::NotPrefixFree { code: bits("0") })
+        );
+    }
+
+    #[test]
+    fn from_codebook_missing_child() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', bits("0"));
+        codebook.insert('b', bits("10"));
+
+        // when:
+        let actual = HuffTree::from_codebook(&codebook);
+
+        // then:
+        assert_eq!(
+            actual.err(),
+            Some(CodebookError::MissingChild { node: bits("1") })
+        );
+    }
+
+    #[test]
+    fn from_codebook_empty() {
+        // when:
+        let actual = HuffTree::from_codebook(&Codebook::<char>::new());
+
+        // then:
+        assert_eq!(actual.err(), Some(CodebookError::Empty));
+    }
+
+    #[test]
+    fn from_codebook_deep_tree() {
+        // given: codes of up to 1000 bits
+        let depth = 1000;
+        let tree = (1..=depth).fold(
+            HuffTree::new(char::from_u32(0x100).unwrap(), 0),
+            |tree, i| HuffTree::new(char::from_u32(0x100 + i).unwrap(), 0).merge(tree),
+        );
+        let expected = codebook(&tree);
+
+        // when: on a thread whose stack is too small for recursing that deep
+        let actual = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(128 * 1024)
+                .spawn_scoped(scope, || {
+                    HuffTree::from_codebook(&expected).map(|huff| codebook(&huff))
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        });
+
+        // then:
+        assert_eq!(actual, Ok(expected));
+    }
+
+    #[test]
+    fn from_code_lengths_round_trip() {
+        for message in ["a", "ab", "BACADAEAFABBAAAGAH", "Grüße aus München für 5 €"] {
+            // given:
+            let huff = huffman(frequency(&mut message.chars())).unwrap();
+            let lengths = code_lengths(&huff)
+                .into_iter()
+                .map(|(chr, len)| (chr, len as u8))
+                .collect();
+
+            // when:
+            let actual = HuffTree::from_code_lengths(&lengths).unwrap();
+
+            // then:
+            let codebook = codebook(&actual);
+            assert_eq!(codebook, canonical_codebook(&huff));
+            let bits = encode_with(&codebook, message).unwrap();
+            assert_eq!(decode(&codebook, &bits).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn from_code_lengths_rejects_invalid_lengths() {
+        for (lengths, expected) in [
+            (
+                &[('a', 1), ('b', 1), ('c', 1)][..],
+                CodebookError::OverSubscribed,
+            ),
+            (&[('a', 0), ('b', 1)][..], CodebookError::OverSubscribed),
+            (&[('a', 1), ('b', 2)][..], CodebookError::UnderSubscribed),
+            (
+                &[('a', 2), ('b', 2), ('c', 2)][..],
+                CodebookError::UnderSubscribed,
+            ),
+            (
+                &[('a', 255), ('b', 255)][..],
+                CodebookError::UnderSubscribed,
+            ),
+            (&[][..], CodebookError::Empty),
+        ] {
+            // when:
+            let actual = HuffTree::from_code_lengths(&lengths.iter().copied().collect());
+
+            // then:
+            assert_eq!(actual.err(), Some(expected), "{:?}", lengths);
+        }
+    }
+
+    #[test]
+    fn from_code_lengths_single_symbol() {
+        for len in [0, 1] {
+            // when:
+            let actual = HuffTree::from_code_lengths(&[('a', len)].iter().copied().collect());
+
+            // then:
+            assert_eq!(actual, Ok(HuffTree::new('a', 0)));
+        }
+    }
+
+    #[test]
+    fn decode_with_tree_matches_decode() {
+        // given:
+        let alphabet = ('0'..='9')
+            .chain('A'..='Z')
+            .chain('a'..='z')
+            .collect::<Vec<_>>();
+        let message = (0..alphabet.len())
+            .flat_map(|i| alphabet[i..].iter().copied())
+            .chain("äöü€".chars())
+            .collect::<String>();
+        let Encoded { codebook, bits, .. } = encode(&message);
+        let tree = HuffTree::from_codebook(&codebook).unwrap();
+
+        // when:
+        let actual = decode_with_tree(&tree, &bits);
+
+        // then:
+        assert!(codebook.len() > 60);
+        assert_eq!(actual, decode(&codebook, &bits));
+        assert_eq!(actual, Ok(message));
+    }
+
+    #[test]
+    fn decode_with_tree_single_leaf() {
+        // given:
+        let tree = HuffTree::new('a', 3);
+
+        // when:
+        let actual = (
+            decode_with_tree(&tree, &bits("000")),
+            decode_with_tree(&tree, &bits("01")),
+        );
+
+        // then:
+        assert_eq!(actual.0, Ok("aaa".to_string()));
+        assert_eq!(actual.1, Err(HuffmanError::InvalidPrefix { bit_offset: 1 }));
+    }
+
+    #[test]
+    fn decode_with_tree_truncated() {
+        // given:
+        let Encoded {
+            codebook, mut bits, ..
+        } = encode("cccbba");
+        let tree = HuffTree::from_codebook(&codebook).unwrap();
+        let bit_offset = bits.len() - codebook[&'a'].len();
+        bits.pop();
+
+        // when:
+        let actual = decode_with_tree(&tree, &bits);
+
+        // then:
+        assert_eq!(actual, decode(&codebook, &bits));
+        assert_eq!(actual, Err(HuffmanError::TruncatedInput { bit_offset }));
+    }
+
+    #[test]
+    fn encode_returns_tree_of_codebook() {
+        // given:
+        let message = "aardvarks ate apples around aachen";
+
+        // when:
+        let actual = encode(message);
+
+        // then:
+        let tree = actual.tree.unwrap();
+        assert_eq!(codebook(&tree), actual.codebook);
+        assert_eq!(tree.lettercount() as usize, message.len());
+        assert_eq!(
+            decode_with_tree(&tree, &actual.bits),
+            Ok(message.to_string())
+        );
+    }
+
+    #[test]
+    fn encode_from_iter_and_two_pass_match_encode() {
+        for message in ["", "a", "abracadabra", "Grüße aus München für 5 € 🦀"] {
+            // given:
+            let expected = encode(message);
+            let mut chars = message.chars();
+            let one_shot = core::iter::from_fn(|| chars.next());
+
+            // when:
+            let actual = [
+                encode_from_iter(one_shot),
+                encode_two_pass(|| message.chars()).unwrap(),
+            ];
+
+            // then:
+            for actual in actual.iter() {
+                assert_eq!(actual.codebook, expected.codebook, "{}", message);
+                assert_eq!(actual.bits, expected.bits, "{}", message);
+                assert_eq!(actual.tree, expected.tree, "{}", message);
+            }
+        }
+    }
+
+    #[test]
+    fn encode_two_pass_different_second_pass() {
+        for (first, second, symbol) in [("abc", "abcd", 'd'), ("", "x", 'x')] {
+            // given:
+            let pass = core::cell::Cell::new(0);
+            let source = || {
+                pass.set(pass.get() + 1);
+                match pass.get() {
+                    1 => first.chars(),
+                    _ => second.chars(),
+                }
+            };
+
+            // when:
+            let actual = encode_two_pass(source);
+
+            // then:
+            assert_eq!(actual.err(), Some(HuffmanError::UnknownSymbol(symbol)));
+        }
+    }
+
+    #[test]
+    fn encoded_len_bits_predicts_encode() {
+        for message in [
+            "",
+            "a",
+            "abracadabra",
+            "Grüße aus München für 5 € 🦀",
+            &"the quick brown fox jumps over the lazy dog ".repeat(37),
+        ] {
+            // given:
+            let frequency = frequency(&mut message.chars());
+            let encoded = encode(message);
+
+            // when:
+            let actual = encoded_len_bits(&frequency, &encoded.codebook);
+
+            // then:
+            assert_eq!(actual, Ok(encoded.bits.len() as u64), "{}", message);
+        }
+    }
+
+    #[test]
+    fn encoded_len_bits_unknown_symbol() {
+        // given:
+        let codebook = encode("abc").codebook;
+
+        // when:
+        let actual = encoded_len_bits(&frequency(&mut "abcd".chars()), &codebook);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::UnknownSymbol('d')));
+    }
+
+    #[test]
+    fn encoded_len_bits_overflow() {
+        // given:
+        let codebook = encode("abc").codebook;
+        let frequency = ['a', 'b', 'c'].iter().map(|&chr| (chr, u64::MAX)).collect();
+
+        // when:
+        let actual = encoded_len_bits(&frequency, &codebook);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::LengthOverflow));
+    }
+
+    #[test]
+    fn encode_allocates_the_bits_once() {
+        for message in ["a", "abracadabra", &"Grüße aus München 🦀".repeat(1000)] {
+            // when:
+            let actual = encode(message).bits;
+
+            // then: the capacity grows by doubling, so a reallocation leaves
+            // more than the padding of the last byte
+            assert_eq!(
+                actual.capacity(),
+                actual.len().div_ceil(8) * 8,
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn encode_empty_input() {
+        // when:
+        let actual = encode("");
+
+        // then:
+        assert!(actual.tree.is_none());
+        assert!(actual.codebook.is_empty());
+        assert!(actual.bits.is_empty());
+        assert_eq!(encode_bytes(&[]), (ByteCodebook::new(), Bits::new()));
+        assert_eq!(encode_symbols::<&str>(&[]), (Codebook::new(), Bits::new()));
+    }
+
+    #[test]
+    fn decode_empty_codebook_and_empty_bits() {
+        // given:
+        let empty = encode("");
+        let nonempty = encode("ab");
+
+        // when:
+        let actual = [
+            decode(&empty.codebook, &empty.bits),
+            decode(&nonempty.codebook, &empty.bits),
+            decode(&empty.codebook, &nonempty.bits),
+            decode(&nonempty.codebook, &nonempty.bits),
+        ];
+
+        // then:
+        assert_eq!(
+            actual,
+            [
+                Ok(String::new()),
+                Ok(String::new()),
+                Err(HuffmanError::InvalidPrefix { bit_offset: 0 }),
+                Ok("ab".to_string()),
+            ]
+        );
+    }
+
+    fn codebook_of(entries: &[(char, &str)]) -> Codebook {
+        entries
+            .iter()
+            .map(|&(chr, code)| (chr, code.chars().map(|c| c == '1').collect()))
+            .collect()
+    }
+
+    #[test]
+    fn validate_canonical_codebook() {
+        // given:
+        let codebook = encode("aardvarks ate apples around aachen").codebook;
+
+        // when:
+        let actual = validate_codebook(&codebook);
+
+        // then:
+        assert_eq!(actual, Ok(()));
+        assert_eq!(kraft_sum(&codebook), 1.0);
+    }
+
+    #[test]
+    fn validate_codebook_prefix_conflict() {
+        // given:
+        let codebook = codebook_of(&[('a', "0"), ('b', "10"), ('c', "101"), ('d', "111")]);
+
+        // when:
+        let actual = validate_codebook(&codebook);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(CodebookError::PrefixConflict {
+                prefix: 'b',
+                symbol: 'c'
+            })
+        );
+        assert_eq!(
+            decode(&codebook, &Bits::new()),
+            Err(HuffmanError::InvalidCodebook(
+                CodebookError::PrefixConflict {
+                    prefix: 'b',
+                    symbol: 'c'
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn validate_codebook_duplicate() {
+        // given:
+        let codebook = codebook_of(&[('a', "0"), ('b', "10"), ('c', "10")]);
+
+        // when:
+        let actual = validate_codebook(&codebook);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(CodebookError::DuplicateCode {
+                first: 'b',
+                second: 'c'
+            })
+        );
+    }
+
+    #[test]
+    fn validate_codebook_empty_code() {
+        // when:
+        let actual = (
+            validate_codebook(&codebook_of(&[('a', "")])),
+            validate_codebook(&codebook_of(&[('a', ""), ('b', "1")])),
+            validate_codebook(&Codebook::new()),
+        );
+
+        // then:
+        assert_eq!(actual, (Ok(()), Err(CodebookError::EmptyCode('a')), Ok(())));
+    }
+
+    #[test]
+    fn kraft_sum_of_incomplete_codebook() {
+        // given:
+        let codebook = codebook_of(&[('a', "0"), ('b', "10")]);
+
+        // when:
+        let actual = kraft_sum(&codebook);
+
+        // then:
+        assert_eq!(actual, 0.75);
+        assert!(!is_complete(&codebook));
+    }
+
+    #[test]
+    fn decode_into_appends() {
+        // given:
+        let Encoded { codebook, bits, .. } = encode("abracadabra");
+        let mut out = "> ".to_string();
+
+        // when:
+        let actual = decode_into(&codebook, &bits, &mut out);
+
+        // then:
+        assert_eq!(actual, Ok(11));
+        assert_eq!(out, "> abracadabra");
+    }
+
+    #[test]
+    fn decode_into_keeps_partial_output() {
+        // given:
+        let Encoded {
+            codebook, mut bits, ..
+        } = encode("abracadabra");
+        let bit_len = bits.len();
+        bits.extend(codebook[&'r'].iter().take(1));
+        let mut out = String::new();
+
+        // when:
+        let actual = decode_into(&codebook, &bits, &mut out);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: bit_len
+            })
+        );
+        assert_eq!(out, "abracadabra");
+    }
+
+    #[test]
+    fn decode_with_limit_stops_early() {
+        // given: a million 1 bit codes in 125 KB
+        let codebook = encode("ab").codebook;
+        let bits = Bits::from_bytes(1_000_000, &vec![0; 125_000]).unwrap();
+
+        // when:
+        let actual = decode_with_limit(&codebook, &bits, 10);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::LimitExceeded {
+                produced: 10,
+                bit_offset: 10
+            })
+        );
+    }
+
+    #[test]
+    fn decode_with_limit_allows_exact_length() {
+        // given:
+        let Encoded { codebook, bits, .. } = encode("abracadabra");
+
+        // when:
+        let actual = decode_with_limit(&codebook, &bits, 11);
+
+        // then:
+        assert_eq!(actual, Ok("abracadabra".to_string()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_codebook_json_round_trip() {
+        // given:
+        let codebook = SerdeCodebook(encode("say \"hi\" to München €").codebook);
+
+        // when:
+        let json = serde_json::to_string(&codebook).unwrap();
+        let actual = serde_json::from_str::<SerdeCodebook>(&json).unwrap();
+
+        // then:
+        assert_eq!(actual, codebook);
+        assert_eq!(
+            serde_json::to_string(&SerdeCodebook(encode("aab").codebook)).unwrap(),
+            r#"{"a":"0","b":"1"}"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_codebook_rejects_invalid_code() {
+        // when:
+        let actual = serde_json::from_str::<SerdeCodebook>(r#"{"a": "012", "b": "1"}"#);
+
+        // then:
+        let err = actual.unwrap_err().to_string();
+        assert!(
+            err.starts_with(r#"code "012" may only contain 0 and 1"#),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_codebook_rejects_prefix() {
+        // when:
+        let actual = serde_json::from_str::<SerdeCodebook>(r#"{"a": "01", "b": "1", "c": "011"}"#);
+
+        // then:
+        let err = actual.unwrap_err().to_string();
+        assert_eq!(err, "the code of 'a' is a prefix of the code of 'c'");
+    }
+}
@@ -0,0 +1,409 @@
+//! A codec that bundles the codebook with its tree.
+
+use crate::bits::Bits;
+use crate::code::{canonical_codebook, Codebook, CodebookError};
+use crate::error::HuffmanError;
+use crate::tree::{frequency, huffman, HuffTree};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+
+/// The symbol whose code is the escape code. It is a Unicode noncharacter, so
+/// it does not occur in regular text; if it does, it is escaped itself. It
+/// differs from io::EOF_SYMBOL, so a codebook with an EOF code does not escape.
+pub const ESCAPE: char = '\u{fffe}';
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CodecOptions {
+    /// Reserve a code for ESCAPE and encode symbols without a code as the
+    /// escape code followed by their UTF-8 bytes instead of failing.
+    pub escape_unknown: bool,
+}
+
+/// Owns a canonical codebook for encoding and the matching tree for decoding.
+///
+/// Encoding and decoding only read the codec, so one codec can be shared by
+/// threads, e.g. in an Arc.
+pub struct HuffmanCodec {
+    codebook: Codebook,
+    tree: HuffTree,
+    options: CodecOptions,
+}
+
+// A codec, its parts and its errors can be shared by and sent to threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<HuffmanCodec>();
+    assert_send_sync::<HuffTree>();
+    assert_send_sync::<Codebook>();
+    assert_send_sync::<Bits>();
+    assert_send_sync::<HuffmanError>();
+};
+
+impl HuffmanCodec {
+    /// The codec for the symbol frequencies of the message.
+    pub fn from_message(message: &str) -> Result<HuffmanCodec, HuffmanError> {
+        HuffmanCodec::from_frequencies(frequency(&mut message.chars()))
+    }
+    pub fn from_frequencies(frequency: BTreeMap<char, u64>) -> Result<HuffmanCodec, HuffmanError> {
+        HuffmanCodec::with_options(frequency, CodecOptions::default())
+    }
+    /// The codec for the symbol frequencies with the given options. With
+    /// escape_unknown the escape code gets the count 1 and an empty frequency
+    /// map is fine, every symbol is escaped then.
+    pub fn with_options(
+        mut frequency: BTreeMap<char, u64>,
+        options: CodecOptions,
+    ) -> Result<HuffmanCodec, HuffmanError> {
+        if options.escape_unknown {
+            frequency.insert(ESCAPE, 1);
+        }
+        let hufftree = huffman(frequency.clone()).ok_or(HuffmanError::EmptyInput)?;
+        let codebook = canonical_codebook(&hufftree);
+        let tree = HuffTree::from_codebook(&codebook)
+            .expect("canonical codes of a Huffman tree form a tree")
+            .with_counts(&frequency);
+        Ok(HuffmanCodec {
+            codebook,
+            tree,
+            options,
+        })
+    }
+    /// The codec for an existing codebook. The counts in its tree are 0.
+    /// Unknown symbols are escaped if the codebook has a code for ESCAPE.
+    pub fn from_codebook(codebook: Codebook) -> Result<HuffmanCodec, CodebookError> {
+        let tree = HuffTree::from_codebook(&codebook)?;
+        let options = CodecOptions {
+            escape_unknown: codebook.contains_key(&ESCAPE),
+        };
+        Ok(HuffmanCodec {
+            codebook,
+            tree,
+            options,
+        })
+    }
+    pub fn codebook(&self) -> &Codebook {
+        &self.codebook
+    }
+    pub fn tree(&self) -> &HuffTree {
+        &self.tree
+    }
+    pub fn options(&self) -> CodecOptions {
+        self.options
+    }
+    pub fn encode(&self, message: &str) -> Result<Bits, HuffmanError> {
+        let mut bits = Bits::new();
+        self.encode_into(message, &mut bits)?;
+        Ok(bits)
+    }
+    /// Encode the message like encode, but append the bits to a buffer of the
+    /// caller, e.g. one per thread sharing the codec. On error the codes of the
+    /// symbols before the unknown one remain in bits.
+    pub fn encode_into(&self, message: &str, bits: &mut Bits) -> Result<(), HuffmanError> {
+        for chr in message.chars() {
+            match self.codebook.get(&chr) {
+                Some(code) if chr != ESCAPE || !self.options.escape_unknown => {
+                    bits.extend_from_code(code)
+                }
+                _ if self.options.escape_unknown => {
+                    bits.extend_from_code(&self.codebook[&ESCAPE]);
+                    for &byte in chr.encode_utf8(&mut [0; 4]).as_bytes() {
+                        bits.extend_from_u64(u64::from(byte), 8);
+                    }
+                }
+                _ => return Err(HuffmanError::UnknownSymbol(chr)),
+            }
+        }
+        Ok(())
+    }
+    pub fn decode(&self, bits: &Bits) -> Result<String, HuffmanError> {
+        let mut decoded = String::new();
+        self.decode_into(bits, &mut decoded)?;
+        Ok(decoded)
+    }
+    /// Decode the bits like decode, but append the symbols to out and return
+    /// how many were appended. On error the symbols decoded before the failing
+    /// code remain in out.
+    pub fn decode_into(&self, bits: &Bits, out: &mut String) -> Result<usize, HuffmanError> {
+        let mut symbols = 0;
+        let mut offset = 0;
+        while offset < bits.len() {
+            let bit_offset = offset;
+            let mut node = &self.tree;
+            if let HuffTree::Leaf { .. } = node {
+                // A single leaf has the code 0.
+                if bits.get(offset) {
+                    return Err(HuffmanError::InvalidPrefix { bit_offset });
+                }
+                offset += 1;
+            }
+            while let HuffTree::Node { left, right } = node {
+                if offset == bits.len() {
+                    return Err(HuffmanError::TruncatedInput { bit_offset });
+                }
+                node = if bits.get(offset) { right } else { left };
+                offset += 1;
+            }
+            match node {
+                HuffTree::Leaf { chr: ESCAPE, .. } if self.options.escape_unknown => {
+                    let (chr, len) = read_literal(bits, bit_offset, offset)?;
+                    out.push(chr);
+                    offset += len;
+                }
+                HuffTree::Leaf { chr, .. } => out.push(*chr),
+                HuffTree::Node { .. } => unreachable!(),
+            }
+            symbols += 1;
+        }
+        Ok(symbols)
+    }
+}
+
+/// Read the UTF-8 bytes of an escaped symbol at offset, returning the symbol
+/// and the number of bits it occupied.
+fn read_literal(
+    bits: &Bits,
+    bit_offset: usize,
+    offset: usize,
+) -> Result<(char, usize), HuffmanError> {
+    let read_byte = |i: usize| {
+        let start = offset + 8 * i;
+        if start + 8 > bits.len() {
+            return Err(HuffmanError::TruncatedInput { bit_offset });
+        }
+        Ok((start..start + 8).fold(0u8, |byte, j| byte << 1 | bits.get(j) as u8))
+    };
+    let first = read_byte(0)?;
+    let len = match first.leading_ones() {
+        0 => 1,
+        len @ 2..=4 => len as usize,
+        _ => return Err(HuffmanError::InvalidPrefix { bit_offset: offset }),
+    };
+    let mut utf8 = [first, 0, 0, 0];
+    for (i, byte) in utf8.iter_mut().enumerate().take(len).skip(1) {
+        *byte = read_byte(i)?;
+    }
+    core::str::from_utf8(&utf8[..len])
+        .ok()
+        .and_then(|s| s.chars().next())
+        .map(|chr| (chr, 8 * len))
+        .ok_or(HuffmanError::InvalidPrefix { bit_offset: offset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::encode;
+
+    #[test]
+    fn codec_constructors_agree() {
+        // given:
+        let message = "aardvarks ate apples around aachen";
+        let codecs = [
+            HuffmanCodec::from_message(message).unwrap(),
+            HuffmanCodec::from_frequencies(frequency(&mut message.chars())).unwrap(),
+            HuffmanCodec::from_codebook(encode(message).codebook).unwrap(),
+        ];
+
+        for codec in codecs.iter() {
+            // when:
+            let bits = codec.encode(message).unwrap();
+            let actual = codec.decode(&bits);
+
+            // then:
+            assert_eq!(actual, Ok(message.to_string()));
+            assert_eq!(bits, encode(message).bits);
+            assert_eq!(codec.codebook(), codecs[0].codebook());
+        }
+    }
+
+    #[test]
+    fn codec_tree_has_counts() {
+        // when:
+        let actual = HuffmanCodec::from_message("abb").unwrap();
+
+        // then:
+        assert_eq!(actual.tree().lettercount(), 3);
+    }
+
+    #[test]
+    fn codec_errors() {
+        // given:
+        let codec = HuffmanCodec::from_message("ab").unwrap();
+
+        // when:
+        let actual = (
+            HuffmanCodec::from_message("").err(),
+            HuffmanCodec::from_codebook(Codebook::new()).err(),
+            codec.encode("abc").err(),
+        );
+
+        // then:
+        assert_eq!(actual.0, Some(HuffmanError::EmptyInput));
+        assert_eq!(actual.1, Some(CodebookError::Empty));
+        assert_eq!(actual.2, Some(HuffmanError::UnknownSymbol('c')));
+    }
+
+    fn escaping_codec(training: &str) -> HuffmanCodec {
+        let options = CodecOptions {
+            escape_unknown: true,
+        };
+        HuffmanCodec::with_options(frequency(&mut training.chars()), options).unwrap()
+    }
+
+    #[test]
+    fn codec_escapes_unknown_symbols() {
+        // given:
+        let codec = escaping_codec("the quick brown fox jumps over the lazy dog");
+        let message = "the 🦀 jumps over the lazy Hund, größer als 5 €\u{fffe}!";
+
+        // when:
+        let bits = codec.encode(message).unwrap();
+        let actual = codec.decode(&bits);
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+        assert!(codec.codebook().contains_key(&ESCAPE));
+    }
+
+    #[test]
+    fn codec_escape_costs_code_and_literal() {
+        // given:
+        let codec = escaping_codec("aab");
+        let escape_len = codec.codebook()[&ESCAPE].len();
+
+        // when:
+        let actual = codec.encode("a🦀").unwrap();
+
+        // then:
+        assert_eq!(actual.len(), codec.codebook()[&'a'].len() + escape_len + 32);
+    }
+
+    #[test]
+    fn codec_escape_without_training() {
+        // given:
+        let codec = escaping_codec("");
+
+        // when:
+        let bits = codec.encode("hi").unwrap();
+        let actual = codec.decode(&bits);
+
+        // then:
+        assert_eq!(actual, Ok("hi".to_string()));
+    }
+
+    #[test]
+    fn codec_escape_from_codebook() {
+        // given:
+        let codebook = escaping_codec("abc").codebook().clone();
+
+        // when:
+        let actual = HuffmanCodec::from_codebook(codebook).unwrap();
+
+        // then:
+        assert!(actual.options().escape_unknown);
+        let bits = actual.encode("abcd").unwrap();
+        assert_eq!(actual.decode(&bits), Ok("abcd".to_string()));
+    }
+
+    #[test]
+    fn codec_escape_truncated_literal() {
+        // given:
+        let codec = escaping_codec("aab");
+        let mut bits = codec.encode("a€").unwrap();
+        let literal_start = codec.codebook()[&'a'].len();
+        bits.pop();
+
+        // when:
+        let actual = codec.decode(&bits);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: literal_start
+            })
+        );
+    }
+
+    #[test]
+    fn codec_without_escape_rejects_unknown_symbols() {
+        // given:
+        let codec = HuffmanCodec::from_message("the quick brown fox").unwrap();
+
+        // when:
+        let actual = codec.encode("the 🦀");
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::UnknownSymbol('🦀')));
+        assert!(!codec.options().escape_unknown);
+    }
+
+    #[test]
+    fn codec_without_escape_codes_the_escape_symbol() {
+        // given:
+        let message = "a\u{fffe}b\u{fffe}";
+        let options = CodecOptions::default();
+        let codec = HuffmanCodec::with_options(frequency(&mut message.chars()), options).unwrap();
+
+        // when:
+        let bits = codec.encode(message).unwrap();
+        let actual = codec.decode(&bits);
+
+        // then:
+        assert_eq!(actual.as_deref(), Ok(message));
+        assert_eq!(bits, encode(message).bits);
+    }
+
+    #[test]
+    fn codec_into_appends_to_the_buffers() {
+        // given:
+        let codec = escaping_codec("abc");
+        let mut bits = codec.encode("ab").unwrap();
+        let mut out = "xy".to_string();
+
+        // when:
+        codec.encode_into("c🦀", &mut bits).unwrap();
+        let actual = codec.decode_into(&bits, &mut out);
+
+        // then:
+        assert_eq!(actual, Ok(4));
+        assert_eq!(out, "xyabc🦀");
+    }
+
+    #[test]
+    fn codec_shared_by_threads() {
+        // given:
+        let corpus = "the quick brown fox jumps over the lazy dog";
+        let codec = std::sync::Arc::new(escaping_codec(corpus));
+
+        // when:
+        let handles = (0..8)
+            .map(|thread| {
+                let codec = std::sync::Arc::clone(&codec);
+                std::thread::spawn(move || {
+                    let mut bits = Bits::new();
+                    let mut out = String::new();
+                    let mut round_trips = Vec::new();
+                    for round in 0..200 {
+                        let message = alloc::format!("{} {} 🦀 #{}", corpus, thread, round);
+                        bits.clear();
+                        out.clear();
+                        codec.encode_into(&message, &mut bits).unwrap();
+                        codec.decode_into(&bits, &mut out).unwrap();
+                        round_trips.push((message, out.clone()));
+                    }
+                    round_trips
+                })
+            })
+            .collect::<Vec<_>>();
+
+        // then:
+        for handle in handles {
+            let round_trips = handle.join().unwrap();
+            assert_eq!(round_trips.len(), 200);
+            for (message, decoded) in round_trips {
+                assert_eq!(decoded, message);
+            }
+        }
+    }
+}
@@ -0,0 +1,2202 @@
+//! A self-contained compressed format holding the codebook and the bits.
+//!
+//! The format is the magic `HUF1`, a flag byte selecting the header mode, the
+//! CRC-32 of the UTF-8 message as a big-endian u32, the header, the number of
+//! encoded bits as a big-endian u64 and the packed bits, most significant bit
+//! first unless the flag byte says LSB first. In the block format of
+//! compress_blocks the bits are split into byte-aligned blocks followed by an
+//! index of them. With CompressOptions::block_size every block has its own
+//! header instead, see compress_with_progress. With CompressOptions::mtf the
+//! header is followed by the alphabet of serialize_alphabet and the Huffman
+//! coded symbols are the output of mtf_encode_text; with CompressOptions::rle
+//! they are the output of rle_encode, of the output of mtf_encode_text if both
+//! are set. The CRC-32 is always the one of the message. The output of
+//! compress_with_codebook only identifies its codebook by a checksum.
+
+use crate::bits::{BitOrder, Bits};
+use crate::code::{canonical_codebook, decode, encode_with, encoded_len_bits, Codebook};
+use crate::codec::{HuffmanCodec, ESCAPE};
+use crate::crc::crc32;
+use crate::error::HuffmanError;
+use crate::fast::FastDecoder;
+use crate::header::{
+    deserialize_alphabet, deserialize_frequencies, deserialize_lengths, serialize_alphabet,
+    serialize_frequencies, serialize_lengths, CodebookHeader, HeaderError,
+};
+use crate::mtf::{mtf_decode_text_with_limit, mtf_encode_text, MtfError};
+use crate::progress::{Progress, Reporter, DEFAULT_PROGRESS_INTERVAL};
+use crate::rle::{rle_decode_with_limit, rle_encode, RleError};
+use crate::tree::{frequency, huffman};
+use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::convert::{TryFrom, TryInto};
+use core::fmt::{self, Display, Formatter};
+use core::ops::Range;
+
+pub const MAGIC: &[u8; 4] = b"HUF1";
+
+/// What the header after the flag byte stores.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderMode {
+    /// The codebook as a code tree, flag byte 0.
+    #[default]
+    Codebook,
+    /// The symbol frequencies the codebook is rebuilt from, flag byte 1.
+    Frequencies,
+    /// The lengths of the canonical codes, flag byte 2.
+    Lengths,
+}
+
+impl HeaderMode {
+    /// All modes, in the order compress prefers them if their headers have
+    /// the same size.
+    pub const ALL: [HeaderMode; 3] = [
+        HeaderMode::Codebook,
+        HeaderMode::Frequencies,
+        HeaderMode::Lengths,
+    ];
+
+    pub(crate) fn flag(self) -> u8 {
+        match self {
+            HeaderMode::Codebook => 0,
+            HeaderMode::Frequencies => 1,
+            HeaderMode::Lengths => 2,
+        }
+    }
+
+    /// The header of a message with the frequencies, encoded with the
+    /// canonical codebook built from them.
+    pub(crate) fn serialize(self, frequency: &BTreeMap<char, u64>, codebook: &Codebook) -> Vec<u8> {
+        match self {
+            HeaderMode::Codebook => codebook.serialize(),
+            HeaderMode::Frequencies => serialize_frequencies(frequency),
+            HeaderMode::Lengths => serialize_lengths(codebook),
+        }
+    }
+}
+
+/// The size in bytes of the header of each mode for a message with the
+/// frequencies, encoded with the canonical codebook built from them.
+pub fn header_sizes(
+    frequency: &BTreeMap<char, u64>,
+    codebook: &Codebook,
+) -> Vec<(HeaderMode, usize)> {
+    HeaderMode::ALL
+        .iter()
+        .map(|&mode| (mode, mode.serialize(frequency, codebook).len()))
+        .collect()
+}
+
+/// The mode of header_sizes with the smallest header, which compress uses.
+pub fn smallest_header(frequency: &BTreeMap<char, u64>, codebook: &Codebook) -> HeaderMode {
+    header_sizes(frequency, codebook)
+        .into_iter()
+        .min_by_key(|&(_, size)| size)
+        .map(|(mode, _)| mode)
+        .expect("there are header modes")
+}
+
+/// The exact length in bytes of the output of compress for a message with the
+/// symbol frequencies: the header of smallest_header, the encoded bits padded
+/// to whole bytes and the fixed fields, computed without encoding. Fails with
+/// LengthOverflow if the bits do not fit into u64.
+pub fn estimated_container_len(frequency: &BTreeMap<char, u64>) -> Result<u64, HuffmanError> {
+    let codebook = huffman(frequency.clone())
+        .map(|huff| canonical_codebook(&huff))
+        .unwrap_or_default();
+    let header = smallest_header(frequency, &codebook).serialize(frequency, &codebook);
+    let bit_len = encoded_len_bits(frequency, &codebook)?;
+    // The magic, the flag byte, the checksum and the number of bits.
+    Ok((MAGIC.len() + 1 + 4 + header.len() + 8) as u64 + bit_len.div_ceil(8))
+}
+
+/// How compress_with_progress encodes the message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompressOptions {
+    /// The header mode, by default the one of the smallest header.
+    pub header_mode: Option<HeaderMode>,
+    pub bit_order: BitOrder,
+    /// The number of symbols between two progress reports.
+    pub progress_interval: usize,
+    /// Split messages longer than this many symbols into blocks, each with
+    /// its own codebook, for messages whose distribution of symbols changes.
+    /// By default the whole message shares one codebook.
+    pub block_size: Option<usize>,
+    /// Replace runs of equal symbols with rle_encode before Huffman coding,
+    /// for messages with long runs like indentation or simple bitmaps.
+    pub rle: bool,
+    /// Replace the symbols with their move-to-front indices of
+    /// mtf_encode_text before Huffman coding, and before rle, for messages
+    /// that use few symbols at a time.
+    pub mtf: bool,
+    /// Decompress the output again and compare it with the message before
+    /// returning it, see try_compress_with_progress.
+    pub verify: bool,
+}
+
+impl Default for CompressOptions {
+    fn default() -> CompressOptions {
+        CompressOptions {
+            header_mode: None,
+            bit_order: BitOrder::default(),
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            block_size: None,
+            rle: false,
+            mtf: false,
+            verify: false,
+        }
+    }
+}
+
+/// How decompress_with decodes the data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecompressOptions {
+    /// Whether to check the decoded message against the CRC-32 in the header.
+    pub verify: bool,
+    /// The most bytes of UTF-8 the decoded message may have, to not decode
+    /// untrusted data into a huge string. Unlimited by default.
+    pub max_output_bytes: Option<usize>,
+    /// The number of symbols between two progress reports of
+    /// decompress_with_progress.
+    pub progress_interval: usize,
+}
+
+impl Default for DecompressOptions {
+    fn default() -> DecompressOptions {
+        DecompressOptions {
+            verify: true,
+            max_output_bytes: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ContainerError {
+    /// The data does not start with MAGIC.
+    BadMagic,
+    /// The flag byte is not a known HeaderMode.
+    UnknownHeaderMode(u8),
+    /// The codebook header is malformed.
+    Header(HeaderError),
+    /// The counts of the frequency header add up to more than u64::MAX, so no
+    /// tree can be built from them.
+    FrequencyOverflow,
+    /// The data ends inside the number of encoded bits.
+    TruncatedLength,
+    /// The payload does not have the number of bytes the bit count requires.
+    PayloadLength { expected: u64, actual: usize },
+    /// The payload does not decode with the codebook.
+    Decode(HuffmanError),
+    /// The block index is truncated or its offsets do not match the blocks.
+    InvalidIndex,
+    /// The symbol range is reversed or extends beyond the `len` symbols of
+    /// the message.
+    RangeOutOfBounds {
+        start: usize,
+        end: usize,
+        len: usize,
+    },
+    /// The decoded message does not have the CRC-32 stored in the header.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// The data was compressed with an external codebook, see
+    /// decompress_with_codebook.
+    MissingCodebook,
+    /// The data was compressed with another external codebook than the given
+    /// one, whose CRC-32 is actual.
+    CodebookMismatch { expected: u32, actual: u32 },
+    /// The block with the index failed to decode, see decompress_parallel.
+    Block {
+        index: usize,
+        error: Box<ContainerError>,
+    },
+    /// The decoded symbols of a message compressed with CompressOptions::rle
+    /// are no output of rle_encode.
+    Rle(RleError),
+    /// The decoded symbols of a message compressed with CompressOptions::mtf
+    /// are no output of mtf_encode_text.
+    Mtf(MtfError),
+    /// The output of compress with CompressOptions::verify does not
+    /// reproduce the message from the symbol with this index on, which is 0
+    /// if it does not decompress at all.
+    SelfCheckFailed { index: usize },
+}
+
+impl Display for ContainerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::BadMagic => write!(f, "the data does not start with {:?}", "HUF1"),
+            ContainerError::UnknownHeaderMode(flag) => write!(f, "unknown header mode {}", flag),
+            ContainerError::Header(err) => write!(f, "invalid codebook header: {}", err),
+            ContainerError::FrequencyOverflow => {
+                write!(f, "the frequencies add up to more than {}", u64::MAX)
+            }
+            ContainerError::TruncatedLength => write!(f, "the bit count is truncated"),
+            ContainerError::PayloadLength { expected, actual } => write!(
+                f,
+                "the payload has {} bytes, but {} are required",
+                actual, expected
+            ),
+            ContainerError::Decode(err) => write!(f, "invalid payload: {}", err),
+            ContainerError::InvalidIndex => write!(f, "invalid block index"),
+            ContainerError::RangeOutOfBounds { start, end, len } => write!(
+                f,
+                "symbol range {}..{} is out of bounds for {} symbols",
+                start, end, len
+            ),
+            ContainerError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "checksum mismatch: expected {:08x}, but the decoded message has {:08x}",
+                expected, actual
+            ),
+            ContainerError::MissingCodebook => {
+                write!(f, "the data was compressed with an external codebook")
+            }
+            ContainerError::CodebookMismatch { expected, actual } => write!(
+                f,
+                "the data was compressed with the codebook {:08x}, not with {:08x}",
+                expected, actual
+            ),
+            ContainerError::Block { index, error } => write!(f, "block {}: {}", index, error),
+            ContainerError::Rle(err) => write!(f, "invalid runs: {}", err),
+            ContainerError::Mtf(err) => write!(f, "invalid move-to-front indices: {}", err),
+            ContainerError::SelfCheckFailed { index } => write!(
+                f,
+                "self-check failed: the output does not reproduce symbol {}",
+                index
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ContainerError {}
+
+/// Compress the message into a self-describing byte buffer with the header
+/// mode of the smallest header.
+pub fn compress(message: &str) -> Vec<u8> {
+    compress_with_progress(message, CompressOptions::default(), |_| {})
+}
+
+/// Compress the message into a self-describing byte buffer with the header
+/// mode, which decompress detects by itself.
+pub fn compress_with(message: &str, mode: HeaderMode) -> Vec<u8> {
+    let options = CompressOptions {
+        header_mode: Some(mode),
+        ..CompressOptions::default()
+    };
+    compress_with_progress(message, options, |_| {})
+}
+
+/// Compress like compress, but pack the bits in the bit order, which
+/// decompress detects by itself.
+pub fn compress_with_bit_order(message: &str, bit_order: BitOrder) -> Vec<u8> {
+    let options = CompressOptions {
+        bit_order,
+        ..CompressOptions::default()
+    };
+    compress_with_progress(message, options, |_| {})
+}
+
+/// Compress with the options and report the progress of encoding the
+/// symbols every progress_interval symbols and once more when done.
+///
+/// With a block_size the header is followed by the number of blocks as a
+/// big-endian u64 and each block by the flag byte of its header mode, its
+/// header, its number of bits as a big-endian u64 and its packed bits.
+///
+/// # Panics
+///
+/// If block_size is 0, or if verify is set and the self-check fails, see
+/// try_compress_with_progress.
+pub fn compress_with_progress<F: FnMut(Progress)>(
+    message: &str,
+    options: CompressOptions,
+    progress: F,
+) -> Vec<u8> {
+    try_compress_with_progress(message, options, progress).expect("compress decodes its own output")
+}
+
+/// Compress like compress_with_progress, but if verify is set and the output
+/// does not decompress to the message, fail with SelfCheckFailed instead of
+/// panicking. Unless a transform is set, the output is decoded and compared
+/// one symbol at a time without a second copy of the message.
+///
+/// # Panics
+///
+/// If block_size is 0.
+pub fn try_compress_with_progress<F: FnMut(Progress)>(
+    message: &str,
+    options: CompressOptions,
+    progress: F,
+) -> Result<Vec<u8>, ContainerError> {
+    compress_and_check(message, options, progress, |_| {})
+}
+
+/// try_compress_with_progress with a hook that changes each codebook after
+/// its header is written, for tests of the self-check.
+fn compress_and_check<F: FnMut(Progress), T: Fn(&mut Codebook)>(
+    message: &str,
+    options: CompressOptions,
+    mut progress: F,
+    tamper: T,
+) -> Result<Vec<u8>, ContainerError> {
+    assert!(options.block_size != Some(0), "blocks must not be empty");
+    let original = message;
+    let checksum = crc32(message.as_bytes());
+    let mut transformed = None;
+    let mut alphabet = Vec::new();
+    if options.mtf {
+        let (mtf_alphabet, indices) = mtf_encode_text(message);
+        alphabet = serialize_alphabet(&mtf_alphabet);
+        transformed = Some(indices);
+    }
+    if options.rle {
+        transformed = Some(rle_encode(transformed.as_deref().unwrap_or(message)));
+    }
+    let message = transformed.as_deref().unwrap_or(message);
+    let total_symbols = message.chars().count();
+    let mut reporter = Reporter::new(
+        &mut progress,
+        options.progress_interval,
+        Some(total_symbols),
+    );
+    let mut flags = match options.bit_order {
+        BitOrder::MsbFirst => 0,
+        BitOrder::LsbFirst => LSB_FIRST,
+    };
+    if options.rle {
+        flags |= RLE;
+    }
+    if options.mtf {
+        flags |= MTF;
+    }
+    let mut data;
+    match options.block_size {
+        Some(block_size) if total_symbols > block_size => {
+            data = MAGIC.to_vec();
+            data.push(flags | BLOCK_CODEBOOKS);
+            data.extend(checksum.to_be_bytes());
+            data.extend(&alphabet);
+            data.extend((total_symbols.div_ceil(block_size) as u64).to_be_bytes());
+            let mut rest = message;
+            while !rest.is_empty() {
+                let end = rest
+                    .char_indices()
+                    .nth(block_size)
+                    .map_or(rest.len(), |(end, _)| end);
+                let (block, tail) = rest.split_at(end);
+                let (frequency, mut codebook, mode) = build_codebook(block, options.header_mode);
+                data.push(mode.flag());
+                data.extend(mode.serialize(&frequency, &codebook));
+                tamper(&mut codebook);
+                write_payload(
+                    block,
+                    &codebook,
+                    options.bit_order,
+                    &mut data,
+                    &mut reporter,
+                );
+                rest = tail;
+            }
+        }
+        _ => {
+            let (frequency, mut codebook, mode) = build_codebook(message, options.header_mode);
+            data = write_header(checksum, &frequency, &codebook, mode, flags);
+            data.extend(&alphabet);
+            tamper(&mut codebook);
+            write_payload(
+                message,
+                &codebook,
+                options.bit_order,
+                &mut data,
+                &mut reporter,
+            );
+        }
+    }
+    reporter.finish(data.len());
+    if options.verify {
+        self_check(&data, original)?;
+    }
+    Ok(data)
+}
+
+/// Decompress the output of compress and compare it with the message. Without
+/// transforms the symbols are compared while they are decoded.
+fn self_check(data: &[u8], message: &str) -> Result<(), ContainerError> {
+    let failed = |index| ContainerError::SelfCheckFailed { index };
+    let header = read_header(data).map_err(|_| failed(0))?;
+    if header.rle || header.alphabet.is_some() || header.layout == Layout::Blocks {
+        let decoded = decompress_unverified(data).map_err(|_| failed(0))?;
+        return match decoded
+            .chars()
+            .zip(message.chars())
+            .position(|(a, b)| a != b)
+        {
+            Some(index) => Err(failed(index)),
+            None if decoded.len() != message.len() => {
+                Err(failed(decoded.chars().count().min(message.chars().count())))
+            }
+            None => Ok(()),
+        };
+    }
+    let mut expected = message.chars();
+    let mut index = 0;
+    match header.layout {
+        Layout::BlockCodebooks => {
+            if header.data.len() < 8 {
+                return Err(failed(0));
+            }
+            let (count, mut rest) = header.data.split_at(8);
+            for _ in 0..u64::from_be_bytes(count.try_into().unwrap()) {
+                let (codebook, bits, tail) =
+                    read_block(rest, header.bit_order).map_err(|_| failed(index))?;
+                compare_symbols(&codebook, &bits, &mut expected, &mut index)?;
+                rest = tail;
+            }
+        }
+        _ => {
+            let (bits, _) = read_payload(header.data, header.bit_order).map_err(|_| failed(0))?;
+            compare_symbols(&header.codebook, &bits, &mut expected, &mut index)?;
+        }
+    }
+    match expected.next() {
+        Some(_) => Err(failed(index)),
+        None => Ok(()),
+    }
+}
+
+/// Decode the bits and compare each symbol with the next expected one,
+/// counting them in index.
+fn compare_symbols(
+    codebook: &Codebook,
+    bits: &Bits,
+    expected: &mut core::str::Chars<'_>,
+    index: &mut usize,
+) -> Result<(), ContainerError> {
+    let decoder = FastDecoder::new(codebook);
+    let mut bit_offset = 0;
+    while bit_offset < bits.len() {
+        match decoder.decode_symbol(bits, bit_offset, bits.len()) {
+            Ok((&chr, len)) if expected.next() == Some(chr) => {
+                bit_offset += len;
+                *index += 1;
+            }
+            _ => return Err(ContainerError::SelfCheckFailed { index: *index }),
+        }
+    }
+    Ok(())
+}
+
+/// The frequencies, the canonical codebook built from them and the given or
+/// else the smallest header mode for the text.
+fn build_codebook(
+    text: &str,
+    mode: Option<HeaderMode>,
+) -> (BTreeMap<char, u64>, Codebook, HeaderMode) {
+    let frequency = frequency(&mut text.chars());
+    let codebook = huffman(frequency.clone())
+        .map(|huff| canonical_codebook(&huff))
+        .unwrap_or_default();
+    let mode = mode.unwrap_or_else(|| smallest_header(&frequency, &codebook));
+    (frequency, codebook, mode)
+}
+
+/// Append the number of bits of the encoded text as a big-endian u64 and the
+/// packed bits to data, counting each symbol with the reporter.
+fn write_payload(
+    text: &str,
+    codebook: &Codebook,
+    bit_order: BitOrder,
+    data: &mut Vec<u8>,
+    reporter: &mut Reporter<'_>,
+) {
+    let bit_len = text.chars().map(|chr| codebook[&chr].len()).sum::<usize>();
+    data.extend((bit_len as u64).to_be_bytes());
+    let mut bits = Bits::with_capacity(bit_len);
+    for chr in text.chars() {
+        bits.extend_from_code(&codebook[&chr]);
+        reporter.symbol(data.len() + bits.len() / 8);
+    }
+    data.extend(bits.to_bytes_with(bit_order));
+}
+
+/// Compress the message in blocks of block_len symbols, so decompress_range
+/// can decode a part of it without decoding everything before.
+///
+/// After the header each block is byte-aligned, followed by an index with the
+/// byte offset of each block relative to the first one and its number of
+/// symbols as big-endian u64 pairs and finally the number of blocks as a
+/// big-endian u64.
+///
+/// # Panics
+///
+/// If block_len is 0.
+pub fn compress_blocks(message: &str, mode: HeaderMode, block_len: usize) -> Vec<u8> {
+    assert!(block_len > 0, "blocks must not be empty");
+    let codebook = huffman(frequency(&mut message.chars()))
+        .map(|huff| canonical_codebook(&huff))
+        .unwrap_or_default();
+    let mut data = write_header(
+        crc32(message.as_bytes()),
+        &frequency(&mut message.chars()),
+        &codebook,
+        mode,
+        BLOCKS,
+    );
+    let blocks_start = data.len();
+    let mut index = Vec::new();
+    let chars = message.chars().collect::<Vec<_>>();
+    for block in chars.chunks(block_len) {
+        let mut bits = Bits::new();
+        for chr in block {
+            bits.extend_from_code(&codebook[chr]);
+        }
+        index.push(((data.len() - blocks_start) as u64, block.len() as u64));
+        data.extend(bits.as_bytes());
+    }
+    for (offset, symbols) in &index {
+        data.extend(offset.to_be_bytes());
+        data.extend(symbols.to_be_bytes());
+    }
+    data.extend((index.len() as u64).to_be_bytes());
+    data
+}
+
+/// Compress the message with an external codebook, e.g. one trained on a
+/// corpus, which is not stored but identified by the CRC-32 of its serialized
+/// form in place of the header. Symbols without a code are escaped like
+/// HuffmanCodec does, if escape_unknown is set and the codebook has a code
+/// for ESCAPE, otherwise they are an UnknownSymbol error.
+pub fn compress_with_codebook(
+    message: &str,
+    codebook: &Codebook,
+    escape_unknown: bool,
+) -> Result<Vec<u8>, HuffmanError> {
+    let bits = match escape_unknown && codebook.contains_key(&ESCAPE) {
+        true => HuffmanCodec::from_codebook(codebook.clone())
+            .map_err(HuffmanError::InvalidCodebook)?
+            .encode(message)?,
+        false => encode_with(codebook, message)?,
+    };
+    let mut data = MAGIC.to_vec();
+    data.push(EXTERNAL_CODEBOOK);
+    data.extend(crc32(message.as_bytes()).to_be_bytes());
+    data.extend(crc32(&codebook.serialize()).to_be_bytes());
+    data.extend((bits.len() as u64).to_be_bytes());
+    data.extend(bits.as_bytes());
+    Ok(data)
+}
+
+/// Set in the flag byte for the block format of compress_blocks.
+const BLOCKS: u8 = 0x80;
+/// Set in the flag byte if the bits are packed least significant bit first.
+const LSB_FIRST: u8 = 0x40;
+/// Set in the flag byte for blocks with their own codebooks, see
+/// CompressOptions::block_size.
+const BLOCK_CODEBOOKS: u8 = 0x20;
+/// Set in the flag byte if the symbols are the output of rle_encode, see
+/// CompressOptions::rle.
+const RLE: u8 = 0x10;
+/// Set in the flag byte if the symbols are the output of mtf_encode_text, see
+/// CompressOptions::mtf.
+const MTF: u8 = 0x08;
+/// The header mode in the flag byte of compress_with_codebook.
+pub(crate) const EXTERNAL_CODEBOOK: u8 = 3;
+
+fn write_header(
+    checksum: u32,
+    frequency: &BTreeMap<char, u64>,
+    codebook: &Codebook,
+    mode: HeaderMode,
+    flags: u8,
+) -> Vec<u8> {
+    let mut data = MAGIC.to_vec();
+    data.push(flags | mode.flag());
+    data.extend(checksum.to_be_bytes());
+    data.extend(mode.serialize(frequency, codebook));
+    data
+}
+
+/// Decompress the output of compress, compress_with or compress_blocks and
+/// verify its checksum.
+pub fn decompress(data: &[u8]) -> Result<String, ContainerError> {
+    decompress_with(data, DecompressOptions::default())
+}
+
+/// Decompress like decompress, but without verifying the checksum, e.g. to
+/// recover what is left of a damaged file.
+pub fn decompress_unverified(data: &[u8]) -> Result<String, ContainerError> {
+    let options = DecompressOptions {
+        verify: false,
+        ..DecompressOptions::default()
+    };
+    decompress_with(data, options)
+}
+
+/// Decompress like decompress with the options. Exceeding max_output_bytes is
+/// a Decode error with LimitExceeded.
+pub fn decompress_with(data: &[u8], options: DecompressOptions) -> Result<String, ContainerError> {
+    decompress_with_progress(data, options, |_| {})
+}
+
+/// Decompress like decompress_with and report the progress of decoding the
+/// symbols every progress_interval symbols and once more when done. Only the
+/// block format knows the total number of symbols in advance.
+pub fn decompress_with_progress<F: FnMut(Progress)>(
+    data: &[u8],
+    options: DecompressOptions,
+    mut progress: F,
+) -> Result<String, ContainerError> {
+    let Header {
+        codebook,
+        layout,
+        bit_order,
+        rle,
+        alphabet,
+        checksum,
+        data,
+    } = read_header(data)?;
+    let max_bytes = options.max_output_bytes.unwrap_or(usize::MAX);
+    let mut message = String::new();
+    let mut reporter = Reporter::new(&mut progress, options.progress_interval, None);
+    match layout {
+        Layout::Blocks => {
+            let (index, data) = read_index(data)?;
+            reporter.set_total_symbols(index.iter().map(|block| block.symbols).sum());
+            for block in &index {
+                decode_block(
+                    &codebook,
+                    data,
+                    block,
+                    bit_order,
+                    &mut message,
+                    max_bytes,
+                    &mut reporter,
+                )?;
+            }
+        }
+        Layout::BlockCodebooks => {
+            if data.len() < 8 {
+                return Err(ContainerError::TruncatedLength);
+            }
+            let (count, mut rest) = data.split_at(8);
+            for _ in 0..u64::from_be_bytes(count.try_into().unwrap()) {
+                let (codebook, bits, tail) = read_block(rest, bit_order)?;
+                decode_bits(
+                    &codebook,
+                    &bits,
+                    None,
+                    &mut message,
+                    max_bytes,
+                    &mut reporter,
+                )
+                .map_err(ContainerError::Decode)?;
+                rest = tail;
+            }
+            if !rest.is_empty() {
+                return Err(ContainerError::PayloadLength {
+                    expected: 0,
+                    actual: rest.len(),
+                });
+            }
+        }
+        Layout::Single => {
+            let (bits, rest) = read_payload(data, bit_order)?;
+            if !rest.is_empty() {
+                return Err(ContainerError::PayloadLength {
+                    expected: bits.len().div_ceil(8) as u64,
+                    actual: data.len() - 8,
+                });
+            }
+            decode_bits(
+                &codebook,
+                &bits,
+                None,
+                &mut message,
+                max_bytes,
+                &mut reporter,
+            )
+            .map_err(ContainerError::Decode)?;
+        }
+    }
+    let message = reverse_transforms(message, rle, alphabet.as_deref(), max_bytes)?;
+    reporter.finish(message.len());
+    let actual = crc32(message.as_bytes());
+    if options.verify && actual != checksum {
+        return Err(ContainerError::ChecksumMismatch {
+            expected: checksum,
+            actual,
+        });
+    }
+    Ok(message)
+}
+
+/// Reverse the transforms of CompressOptions::rle and mtf on the decoded
+/// symbols, producing at most max_bytes bytes of UTF-8.
+pub(crate) fn reverse_transforms(
+    mut message: String,
+    rle: bool,
+    alphabet: Option<&[char]>,
+    max_bytes: usize,
+) -> Result<String, ContainerError> {
+    if rle {
+        message = rle_decode_with_limit(&message, max_bytes).map_err(ContainerError::Rle)?;
+    }
+    if let Some(alphabet) = alphabet {
+        message = mtf_decode_text_with_limit(alphabet, &message, max_bytes)
+            .map_err(ContainerError::Mtf)?;
+    }
+    Ok(message)
+}
+
+/// Decompress only the symbols in symbol_range. For the output of
+/// compress_blocks only the blocks overlapping the range are decoded and the
+/// checksum is not verified, other formats are decoded completely.
+pub fn decompress_range(data: &[u8], symbol_range: Range<usize>) -> Result<String, ContainerError> {
+    let Header {
+        codebook,
+        layout,
+        bit_order,
+        rle,
+        alphabet,
+        data: blocks_data,
+        ..
+    } = read_header(data)?;
+    let out_of_bounds = |len| ContainerError::RangeOutOfBounds {
+        start: symbol_range.start,
+        end: symbol_range.end,
+        len,
+    };
+    if layout != Layout::Blocks || rle || alphabet.is_some() {
+        let message = decompress(data)?;
+        let len = message.chars().count();
+        if symbol_range.start > symbol_range.end || symbol_range.end > len {
+            return Err(out_of_bounds(len));
+        }
+        return Ok(message
+            .chars()
+            .skip(symbol_range.start)
+            .take(symbol_range.len())
+            .collect());
+    }
+    let (index, blocks_data) = read_index(blocks_data)?;
+    let len = index.iter().map(|block| block.symbols).sum();
+    if symbol_range.start > symbol_range.end || symbol_range.end > len {
+        return Err(out_of_bounds(len));
+    }
+    let mut decoded = String::new();
+    let mut block_start = 0;
+    for block in &index {
+        let block_end = block_start + block.symbols;
+        if block_start < symbol_range.end && symbol_range.start < block_end {
+            let skip = symbol_range.start.saturating_sub(block_start);
+            let take = symbol_range.end.min(block_end) - block_start - skip;
+            let mut symbols = String::new();
+            decode_block(
+                &codebook,
+                blocks_data,
+                block,
+                bit_order,
+                &mut symbols,
+                usize::MAX,
+                &mut Reporter::new(&mut |_| {}, usize::MAX, None),
+            )?;
+            decoded.extend(symbols.chars().skip(skip).take(take));
+        }
+        block_start = block_end;
+    }
+    Ok(decoded)
+}
+
+/// Decompress the output of compress_with_codebook with the same codebook and
+/// verify its checksum. Other data is decompressed like decompress.
+pub fn decompress_with_codebook(
+    data: &[u8],
+    codebook: &Codebook,
+) -> Result<String, ContainerError> {
+    let rest = data
+        .strip_prefix(&MAGIC[..])
+        .ok_or(ContainerError::BadMagic)?;
+    if rest.first() != Some(&EXTERNAL_CODEBOOK) {
+        return decompress(data);
+    }
+    if rest.len() < 9 {
+        return Err(ContainerError::Header(HeaderError::Truncated));
+    }
+    let checksum = u32::from_be_bytes(rest[1..5].try_into().unwrap());
+    let expected = u32::from_be_bytes(rest[5..9].try_into().unwrap());
+    let actual = crc32(&codebook.serialize());
+    if actual != expected {
+        return Err(ContainerError::CodebookMismatch { expected, actual });
+    }
+    let (bits, tail) = read_payload(&rest[9..], BitOrder::MsbFirst)?;
+    if !tail.is_empty() {
+        return Err(ContainerError::PayloadLength {
+            expected: bits.len().div_ceil(8) as u64,
+            actual: rest.len() - 17,
+        });
+    }
+    let message = match codebook.contains_key(&ESCAPE) {
+        true => HuffmanCodec::from_codebook(codebook.clone())
+            .map_err(HuffmanError::InvalidCodebook)
+            .and_then(|codec| codec.decode(&bits)),
+        false => decode(codebook, &bits),
+    }
+    .map_err(ContainerError::Decode)?;
+    let actual = crc32(message.as_bytes());
+    if actual != checksum {
+        return Err(ContainerError::ChecksumMismatch {
+            expected: checksum,
+            actual,
+        });
+    }
+    Ok(message)
+}
+
+/// How the data after the header is laid out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Layout {
+    /// The number of bits and the packed bits.
+    Single,
+    /// The block format of compress_blocks.
+    Blocks,
+    /// Blocks with their own codebooks, the codebook of the header is empty.
+    BlockCodebooks,
+}
+
+pub(crate) struct Header<'a> {
+    pub(crate) codebook: Codebook,
+    pub(crate) layout: Layout,
+    pub(crate) bit_order: BitOrder,
+    /// Whether the decoded symbols are the output of rle_encode.
+    pub(crate) rle: bool,
+    /// The alphabet if the decoded symbols are the output of mtf_encode_text.
+    pub(crate) alphabet: Option<Vec<char>>,
+    pub(crate) checksum: u32,
+    /// The data after the header.
+    pub(crate) data: &'a [u8],
+}
+
+pub(crate) fn read_header(data: &[u8]) -> Result<Header<'_>, ContainerError> {
+    let data = data
+        .strip_prefix(&MAGIC[..])
+        .ok_or(ContainerError::BadMagic)?;
+    if data.len() < 5 {
+        return Err(ContainerError::Header(HeaderError::Truncated));
+    }
+    let flag = &data[0];
+    let checksum = u32::from_be_bytes(data[1..5].try_into().unwrap());
+    let data = &data[5..];
+    let mode = flag & !(BLOCKS | LSB_FIRST | BLOCK_CODEBOOKS | RLE | MTF);
+    if *flag == EXTERNAL_CODEBOOK {
+        return Err(ContainerError::MissingCodebook);
+    }
+    let (layout, (codebook, header_len)) = match flag & (BLOCKS | BLOCK_CODEBOOKS) {
+        0 => (Layout::Single, read_codebook(mode, data)?),
+        BLOCKS => (Layout::Blocks, read_codebook(mode, data)?),
+        BLOCK_CODEBOOKS if mode == 0 => (Layout::BlockCodebooks, (Codebook::new(), 0)),
+        _ => return Err(ContainerError::UnknownHeaderMode(*flag)),
+    };
+    let mut data = &data[header_len..];
+    let mut alphabet = None;
+    if flag & MTF != 0 {
+        let (mtf_alphabet, alphabet_len) =
+            deserialize_alphabet(data).map_err(ContainerError::Header)?;
+        alphabet = Some(mtf_alphabet);
+        data = &data[alphabet_len..];
+    }
+    Ok(Header {
+        codebook,
+        layout,
+        bit_order: match flag & LSB_FIRST {
+            0 => BitOrder::MsbFirst,
+            _ => BitOrder::LsbFirst,
+        },
+        rle: flag & RLE != 0,
+        alphabet,
+        checksum,
+        data,
+    })
+}
+
+/// Read the header of the mode with the flag from the start of data. Returns
+/// the codebook and the length of the header in bytes.
+pub(crate) fn read_codebook(mode: u8, data: &[u8]) -> Result<(Codebook, usize), ContainerError> {
+    Ok(match mode {
+        0 => Codebook::deserialize(data).map_err(ContainerError::Header)?,
+        1 => {
+            let (frequency, header_len) =
+                deserialize_frequencies(data).map_err(ContainerError::Header)?;
+            frequency
+                .values()
+                .try_fold(0u64, |total, &occ| total.checked_add(occ))
+                .ok_or(ContainerError::FrequencyOverflow)?;
+            // The same canonical codebook that compress encoded with.
+            let codebook = huffman(frequency)
+                .map(|huff| canonical_codebook(&huff))
+                .unwrap_or_default();
+            (codebook, header_len)
+        }
+        2 => deserialize_lengths(data).map_err(ContainerError::Header)?,
+        _ => return Err(ContainerError::UnknownHeaderMode(mode)),
+    })
+}
+
+/// Read the number of bits as a big-endian u64 and the packed bits from the
+/// start of data. Returns them and the data after them.
+fn read_payload(data: &[u8], bit_order: BitOrder) -> Result<(Bits, &[u8]), ContainerError> {
+    if data.len() < 8 {
+        return Err(ContainerError::TruncatedLength);
+    }
+    let (bit_len, payload) = data.split_at(8);
+    let bit_len = u64::from_be_bytes(bit_len.try_into().unwrap());
+    let byte_len = usize::try_from(bit_len.div_ceil(8))
+        .ok()
+        .filter(|&len| len <= payload.len())
+        .ok_or(ContainerError::PayloadLength {
+            expected: bit_len.div_ceil(8),
+            actual: payload.len(),
+        })?;
+    let (payload, rest) = payload.split_at(byte_len);
+    let bits = Bits::from_bytes_with(bit_len as usize, payload, bit_order)
+        .expect("the length does not exceed the bytes");
+    Ok((bits, rest))
+}
+
+/// Read a block with its own codebook from the start of data: the flag byte
+/// of its header mode, its header and its payload. Returns its codebook, its
+/// bits and the data after it.
+pub(crate) fn read_block(
+    data: &[u8],
+    bit_order: BitOrder,
+) -> Result<(Codebook, Bits, &[u8]), ContainerError> {
+    let (&mode, data) = data
+        .split_first()
+        .ok_or(ContainerError::Header(HeaderError::Truncated))?;
+    let (codebook, header_len) = read_codebook(mode, data)?;
+    let (bits, rest) = read_payload(&data[header_len..], bit_order)?;
+    Ok((codebook, bits, rest))
+}
+
+/// A block of the block format: the byte range of its bits relative to the
+/// first block and its number of symbols.
+pub(crate) struct Block {
+    bytes: Range<usize>,
+    pub(crate) symbols: usize,
+}
+
+/// The blocks of the index at the end of data and the data before the index.
+pub(crate) fn read_index(data: &[u8]) -> Result<(Vec<Block>, &[u8]), ContainerError> {
+    let read_u64 = |bytes: &[u8]| {
+        usize::try_from(u64::from_be_bytes(bytes.try_into().unwrap()))
+            .map_err(|_| ContainerError::InvalidIndex)
+    };
+    if data.len() < 8 {
+        return Err(ContainerError::InvalidIndex);
+    }
+    let (data, count) = data.split_at(data.len() - 8);
+    let index_len = read_u64(count)?
+        .checked_mul(16)
+        .filter(|&len| len <= data.len())
+        .ok_or(ContainerError::InvalidIndex)?;
+    let (data, index) = data.split_at(data.len() - index_len);
+    let entries = index
+        .chunks_exact(16)
+        .map(|entry| Ok((read_u64(&entry[..8])?, read_u64(&entry[8..])?)))
+        .collect::<Result<Vec<_>, _>>()?;
+    // The blocks are contiguous, so each one ends where the next one starts.
+    if entries
+        .first()
+        .map_or(!data.is_empty(), |&(start, _)| start != 0)
+    {
+        return Err(ContainerError::InvalidIndex);
+    }
+    let mut blocks = Vec::with_capacity(entries.len());
+    for (i, &(start, symbols)) in entries.iter().enumerate() {
+        let end = entries.get(i + 1).map_or(data.len(), |&(end, _)| end);
+        // Every code has at least 1 bit.
+        if end < start || end > data.len() || symbols > (end - start).saturating_mul(8) {
+            return Err(ContainerError::InvalidIndex);
+        }
+        blocks.push(Block {
+            bytes: start..end,
+            symbols,
+        });
+    }
+    Ok((blocks, data))
+}
+
+/// Append exactly the symbols of the block to out, ignoring the padding of its
+/// last byte, but not beyond max_bytes. Bit offsets in errors are relative to
+/// the start of the block.
+pub(crate) fn decode_block(
+    codebook: &Codebook,
+    data: &[u8],
+    block: &Block,
+    bit_order: BitOrder,
+    out: &mut String,
+    max_bytes: usize,
+    reporter: &mut Reporter<'_>,
+) -> Result<(), ContainerError> {
+    let bytes = &data[block.bytes.clone()];
+    let bits =
+        Bits::from_bytes_with(8 * bytes.len(), bytes, bit_order).expect("all bits of the bytes");
+    decode_bits(
+        codebook,
+        &bits,
+        Some(block.symbols),
+        out,
+        max_bytes,
+        reporter,
+    )
+    .map_err(ContainerError::Decode)
+}
+
+/// Append the given number of symbols of the bits or else all of them to out,
+/// but not beyond max_bytes, and count each one with the reporter.
+pub(crate) fn decode_bits(
+    codebook: &Codebook,
+    bits: &Bits,
+    symbols: Option<usize>,
+    out: &mut String,
+    max_bytes: usize,
+    reporter: &mut Reporter<'_>,
+) -> Result<(), HuffmanError> {
+    let decoder = FastDecoder::new(codebook);
+    let mut bit_offset = 0;
+    let mut produced = 0;
+    while symbols.map_or(bit_offset < bits.len(), |symbols| produced < symbols) {
+        let (&chr, len) = decoder.decode_symbol(bits, bit_offset, bits.len())?;
+        if out.len() + chr.len_utf8() > max_bytes {
+            return Err(HuffmanError::LimitExceeded {
+                produced,
+                bit_offset,
+            });
+        }
+        out.push(chr);
+        bit_offset += len;
+        produced += 1;
+        reporter.symbol(out.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn container_round_trip() {
+        for message in [
+            "",
+            "a",
+            "aaaa",
+            "Grüße aus München für 5 € 🦀",
+            "BACADAEAFABBAAAGAH",
+        ] {
+            // when:
+            let actual = decompress(&compress(message));
+
+            // then:
+            assert_eq!(actual, Ok(message.to_string()));
+        }
+    }
+
+    #[test]
+    fn container_empty_message() {
+        // given:
+        let mut containers = HeaderMode::ALL
+            .iter()
+            .flat_map(|&mode| [compress_with("", mode), compress_blocks("", mode, 4)])
+            .collect::<Vec<_>>();
+        containers.push(compress_with_bit_order("", BitOrder::LsbFirst));
+        let options = CompressOptions {
+            block_size: Some(4),
+            ..CompressOptions::default()
+        };
+        containers.push(compress_with_progress("", options, |_| {}));
+
+        for data in containers {
+            // when:
+            let actual = decompress(&data);
+
+            // then:
+            assert_eq!(actual.as_deref(), Ok(""), "{:?}", data);
+            assert_eq!(&data[..4], MAGIC);
+        }
+        let data = compress_with_codebook("", &Codebook::new(), false).unwrap();
+        assert_eq!(
+            decompress_with_codebook(&data, &Codebook::new()).as_deref(),
+            Ok("")
+        );
+    }
+
+    #[test]
+    fn container_layout() {
+        // when:
+        let actual = compress("aaa");
+
+        // then: header 0 10 01100001 11, 3 bits 000
+        assert_eq!(actual[..4], *b"HUF1");
+        assert_eq!(actual[4], 0);
+        assert_eq!(actual[5..9], crc32(b"aaa").to_be_bytes());
+        assert_eq!(actual[9..11], [0b0100_1100, 0b0011_1000]);
+        assert_eq!(actual[11..19], 3u64.to_be_bytes());
+        assert_eq!(actual[19..], [0]);
+    }
+
+    #[test]
+    fn container_header_modes_round_trip() {
+        for message in [
+            "",
+            "a",
+            "Grüße aus München für 5 € 🦀",
+            "BACADAEAFABBAAAGAH",
+        ]
+        .iter()
+        {
+            for &mode in HeaderMode::ALL.iter() {
+                // when:
+                let actual = decompress(&compress_with(message, mode));
+
+                // then:
+                assert_eq!(actual, Ok(message.to_string()), "{:?}", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn container_picks_codebook_for_few_symbols() {
+        // given:
+        let message = "abbccc";
+
+        // when:
+        let actual = compress(message);
+
+        // then:
+        let freq = frequency(&mut message.chars());
+        let codebook = canonical_codebook(&huffman(freq.clone()).unwrap());
+        assert_eq!(smallest_header(&freq, &codebook), HeaderMode::Codebook);
+        assert_eq!(actual[4], HeaderMode::Codebook.flag());
+        assert_eq!(decompress(&actual), Ok(message.to_string()));
+    }
+
+    #[test]
+    fn container_picks_lengths_for_many_symbols_with_huge_counts() {
+        // given: 100 consecutive symbols, each a few thousand times.
+        let message = (0..100u32)
+            .flat_map(|i| {
+                let chr = core::char::from_u32(0x21 + i).unwrap();
+                core::iter::repeat_n(chr, 1000 + 100 * i as usize)
+            })
+            .collect::<String>();
+
+        // when:
+        let actual = compress(&message);
+
+        // then:
+        let freq = frequency(&mut message.chars());
+        let codebook = canonical_codebook(&huffman(freq.clone()).unwrap());
+        let sizes = header_sizes(&freq, &codebook);
+        assert!(
+            sizes[2].1 < sizes[0].1 && sizes[2].1 < sizes[1].1,
+            "{:?}",
+            sizes
+        );
+        assert_eq!(smallest_header(&freq, &codebook), HeaderMode::Lengths);
+        assert_eq!(actual[4], HeaderMode::Lengths.flag());
+        assert_eq!(decompress(&actual), Ok(message));
+    }
+
+    #[test]
+    fn container_header_choice_is_deterministic() {
+        // given: every mode is within a byte here, so ties are likely.
+        let message = "abcd";
+
+        // when:
+        let actual = (0..10).map(|_| compress(message)).collect::<Vec<_>>();
+
+        // then:
+        assert!(actual.iter().all(|data| *data == actual[0]));
+        let freq = frequency(&mut message.chars());
+        let sizes = header_sizes(&freq, &canonical_codebook(&huffman(freq.clone()).unwrap()));
+        let min = sizes.iter().map(|&(_, size)| size).min().unwrap();
+        let first = sizes.iter().find(|&&(_, size)| size == min).unwrap().0;
+        assert_eq!(actual[0][4], first.flag());
+    }
+
+    #[test]
+    fn container_header_sizes() {
+        // given: a leaf of the code tree costs 2 bits besides the symbol, a
+        // frequency entry at least 8 bits for the count.
+        let message = "the quick brown fox jumps over the lazy dog";
+
+        // when:
+        let actual = (
+            compress_with(message, HeaderMode::Codebook).len(),
+            compress_with(message, HeaderMode::Frequencies).len(),
+        );
+
+        // then:
+        assert!(actual.0 < actual.1, "{:?}", actual);
+    }
+
+    #[test]
+    fn container_bit_orders() {
+        // given: a is coded 0, b 10 and c 11.
+        let message = "abacabac";
+
+        for (bit_order, expected) in [
+            (BitOrder::MsbFirst, [0b0100_1101, 0b0011_0000]),
+            (BitOrder::LsbFirst, [0b1011_0010, 0b0000_1100]),
+        ] {
+            // when:
+            let actual = compress_with_bit_order(message, bit_order);
+
+            // then:
+            assert_eq!(actual[4] & LSB_FIRST != 0, bit_order == BitOrder::LsbFirst);
+            assert_eq!(
+                actual[actual.len() - 10..actual.len() - 2],
+                12u64.to_be_bytes()
+            );
+            assert_eq!(actual[actual.len() - 2..], expected, "{:?}", bit_order);
+            assert_eq!(decompress(&actual), Ok(message.to_string()));
+        }
+    }
+
+    #[test]
+    fn container_wrong_bit_order_detected() {
+        // given: the flag claims the other bit order.
+        let mut data = compress_with_bit_order("abacabac", BitOrder::LsbFirst);
+        data[4] ^= LSB_FIRST;
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert!(
+            matches!(actual, Err(ContainerError::ChecksumMismatch { .. })),
+            "{:?}",
+            actual
+        );
+    }
+
+    fn assert_monotonic(events: &[Progress]) {
+        for pair in events.windows(2) {
+            assert!(pair[0].symbols <= pair[1].symbols, "{:?}", pair);
+            assert!(pair[0].bytes_written <= pair[1].bytes_written, "{:?}", pair);
+        }
+    }
+
+    #[test]
+    fn compress_progress() {
+        // given:
+        let message = "abracadabra".repeat(100);
+        let options = CompressOptions {
+            progress_interval: 100,
+            ..CompressOptions::default()
+        };
+        let mut events = Vec::new();
+
+        // when:
+        let actual = compress_with_progress(&message, options, |progress| events.push(progress));
+
+        // then: every 100 of the 1100 symbols and once when done.
+        assert_eq!(events.len(), 12);
+        assert_monotonic(&events);
+        assert!(events.iter().all(|event| event.total_symbols == Some(1100)));
+        assert_eq!(events[0].symbols, 100);
+        assert_eq!(
+            events.last(),
+            Some(&Progress {
+                symbols: 1100,
+                total_symbols: Some(1100),
+                bytes_written: actual.len(),
+            })
+        );
+        assert_eq!(actual, compress(&message));
+    }
+
+    #[test]
+    fn decompress_progress() {
+        // given:
+        let message = "abracadabra".repeat(100);
+        let options = DecompressOptions {
+            progress_interval: 100,
+            ..DecompressOptions::default()
+        };
+
+        for (data, total_symbols) in [
+            (compress(&message), None),
+            (
+                compress_blocks(&message, HeaderMode::Codebook, 300),
+                Some(1100),
+            ),
+        ] {
+            let mut events = Vec::new();
+
+            // when:
+            let actual = decompress_with_progress(&data, options, |progress| events.push(progress));
+
+            // then:
+            assert_eq!(actual, Ok(message.clone()));
+            assert_eq!(events.len(), 12);
+            assert_monotonic(&events);
+            assert!(events[..11]
+                .iter()
+                .all(|event| event.total_symbols == total_symbols));
+            assert_eq!(
+                events[11],
+                Progress {
+                    symbols: 1100,
+                    total_symbols: Some(1100),
+                    bytes_written: 1100,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn progress_of_empty_message() {
+        // given:
+        let mut events = Vec::new();
+
+        // when:
+        let data = compress_with_progress("", CompressOptions::default(), |progress| {
+            events.push(progress)
+        });
+
+        // then:
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].fraction(), Some(1.0));
+        assert_eq!(decompress(&data), Ok(String::new()));
+    }
+
+    /// The payload bits of every block of the output of compress_with_progress.
+    fn payload_bits(data: &[u8]) -> usize {
+        let Header { layout, data, .. } = read_header(data).unwrap();
+        let mut rest = match layout {
+            Layout::Single => return read_payload(data, BitOrder::MsbFirst).unwrap().0.len(),
+            _ => &data[8..],
+        };
+        let mut bit_len = 0;
+        while !rest.is_empty() {
+            let (_, header_len) = read_codebook(rest[0], &rest[1..]).unwrap();
+            let (bits, tail) = read_payload(&rest[1 + header_len..], BitOrder::MsbFirst).unwrap();
+            bit_len += bits.len();
+            rest = tail;
+        }
+        bit_len
+    }
+
+    #[test]
+    fn block_codebooks_for_shifting_distribution() {
+        // given: 16 lowercase letters and then 16 other symbols.
+        let ascii = (0..4000).map(|i| (b'a' + (i * 7 % 16) as u8) as char);
+        let other = (0..4000).map(|i| "0123456789+/=ÄÖÜ".chars().nth(i * 5 % 16).unwrap());
+        let message = ascii.chain(other).collect::<String>();
+        let options = CompressOptions {
+            block_size: Some(1000),
+            ..CompressOptions::default()
+        };
+
+        // when:
+        let actual = compress_with_progress(&message, options, |_| {});
+
+        // then:
+        let global = compress(&message);
+        assert_eq!(actual[4], BLOCK_CODEBOOKS);
+        assert_eq!(actual[9..17], 8u64.to_be_bytes());
+        assert_eq!(payload_bits(&actual), 8000 * 4);
+        assert_eq!(payload_bits(&global), 8000 * 5);
+        assert!(actual.len() < global.len());
+        assert_eq!(decompress(&actual), Ok(message));
+    }
+
+    #[test]
+    fn block_codebooks_round_trip_at_block_boundaries() {
+        // given:
+        let message = "Grüße aus München für 5 € 🦀🦀";
+        let len = message.chars().count();
+
+        for block_size in 1..=len + 1 {
+            for bit_order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+                let options = CompressOptions {
+                    block_size: Some(block_size),
+                    bit_order,
+                    ..CompressOptions::default()
+                };
+
+                // when:
+                let data = compress_with_progress(message, options, |_| {});
+
+                // then:
+                assert_eq!(data[4] & BLOCK_CODEBOOKS != 0, block_size < len);
+                assert_eq!(decompress(&data), Ok(message.to_string()));
+                assert_eq!(decompress_range(&data, 6..11), Ok("aus M".to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn block_codebooks_fall_back_to_one_block() {
+        // given:
+        let options = CompressOptions {
+            block_size: Some(1000),
+            ..CompressOptions::default()
+        };
+
+        // when:
+        let actual = compress_with_progress("abracadabra", options, |_| {});
+
+        // then:
+        assert_eq!(actual, compress("abracadabra"));
+    }
+
+    #[test]
+    fn block_codebooks_truncated() {
+        // given:
+        let options = CompressOptions {
+            block_size: Some(4),
+            ..CompressOptions::default()
+        };
+        let data = compress_with_progress("abracadabra", options, |_| {});
+
+        for len in 0..data.len() {
+            // when:
+            let actual = decompress(&data[..len]);
+
+            // then:
+            assert!(actual.is_err(), "{}", len);
+        }
+        let mut longer = data.clone();
+        longer.push(0);
+        assert_eq!(
+            decompress(&longer),
+            Err(ContainerError::PayloadLength {
+                expected: 0,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn estimated_container_len_is_exact() {
+        for message in [
+            "",
+            "a",
+            "BACADAEAFABBAAAGAH",
+            "Grüße aus München für 5 € 🦀",
+            &"the quick brown fox jumps over the lazy dog ".repeat(37),
+        ] {
+            // given:
+            let frequency = frequency(&mut message.chars());
+
+            // when:
+            let actual = estimated_container_len(&frequency);
+
+            // then:
+            assert_eq!(actual, Ok(compress(message).len() as u64), "{}", message);
+        }
+    }
+
+    #[test]
+    fn estimated_container_len_overflow() {
+        // given:
+        let frequency = [('a', u64::MAX), ('b', u64::MAX), ('c', 1)]
+            .iter()
+            .copied()
+            .collect();
+
+        // when:
+        let actual = estimated_container_len(&frequency);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::LengthOverflow));
+    }
+
+    #[test]
+    fn container_unknown_header_mode() {
+        // given:
+        let mut data = compress("aaa");
+        data[4] = 4;
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(actual, Err(ContainerError::UnknownHeaderMode(4)));
+    }
+
+    #[test]
+    fn container_corrupted_magic() {
+        // given:
+        let mut data = compress("aaa");
+        data[3] = b'2';
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(actual, Err(ContainerError::BadMagic));
+    }
+
+    #[test]
+    fn container_corrupted_header() {
+        // given:
+        let mut data = compress("aaa");
+        data[9] = 0b0101_1111;
+        data[10] = 0b1111_1111;
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(ContainerError::Header(HeaderError::InvalidSymbol {
+                bit_offset: 3
+            }))
+        );
+    }
+
+    #[test]
+    fn container_corrupted_length() {
+        // given:
+        let mut data = compress("aaa");
+        data[18] = 9;
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(ContainerError::PayloadLength {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn container_corrupted_payload() {
+        // given:
+        let mut data = compress("aaa");
+        data[19] = 0b0100_0000;
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(ContainerError::Decode(HuffmanError::InvalidPrefix {
+                bit_offset: 1
+            }))
+        );
+    }
+
+    #[test]
+    fn container_flipped_payload_bit() {
+        // given: a is coded 0 and b 1, so every flipped bit still decodes.
+        let message = "abab";
+        let mut data = compress(message);
+        *data.last_mut().unwrap() ^= 0b0010_0000;
+
+        // when:
+        let actual = (decompress(&data), decompress_unverified(&data));
+
+        // then:
+        assert_eq!(
+            actual.0,
+            Err(ContainerError::ChecksumMismatch {
+                expected: crc32(b"abab"),
+                actual: crc32(b"abbb")
+            })
+        );
+        assert_eq!(actual.1, Ok("abbb".to_string()));
+    }
+
+    #[test]
+    fn container_blocks_flipped_payload_bit() {
+        // given: two blocks of one byte each before the index of 2 * 16 + 8
+        // bytes.
+        let mut data = compress_blocks("abababab", HeaderMode::Codebook, 4);
+        let first_block = data.len() - 42;
+        data[first_block] ^= 0b1000_0000;
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(ContainerError::ChecksumMismatch {
+                expected: crc32(b"abababab"),
+                actual: crc32(b"bbababab")
+            })
+        );
+    }
+
+    #[test]
+    fn container_output_limit() {
+        // given: 80000 symbols of 1 bit in 10 KB
+        let message = "ab".repeat(40_000);
+        let options = DecompressOptions {
+            max_output_bytes: Some(1000),
+            ..DecompressOptions::default()
+        };
+
+        for data in [
+            compress(&message),
+            compress_blocks(&message, HeaderMode::Codebook, 300),
+        ]
+        .iter()
+        {
+            // when:
+            let actual = decompress_with(data, options);
+
+            // then: in blocks the count and offset are relative to the
+            // fourth block, which starts at symbol 900.
+            let produced = if data[4] & BLOCKS == 0 { 1000 } else { 100 };
+            assert_eq!(
+                actual,
+                Err(ContainerError::Decode(HuffmanError::LimitExceeded {
+                    produced,
+                    bit_offset: produced
+                }))
+            );
+        }
+    }
+
+    #[test]
+    fn container_output_limit_allows_exact_length() {
+        // given:
+        let message = "Grüße";
+        let options = DecompressOptions {
+            max_output_bytes: Some(message.len()),
+            ..DecompressOptions::default()
+        };
+
+        // when:
+        let actual = decompress_with(&compress(message), options);
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+    }
+
+    #[test]
+    fn container_truncated() {
+        // given:
+        let data = compress("Grüße");
+        let header_len = Codebook::deserialize(&data[9..]).unwrap().1;
+
+        for len in 0..data.len() {
+            // when:
+            let actual = decompress(&data[..len]);
+
+            // then:
+            let expected = match len {
+                0..=3 => ContainerError::BadMagic,
+                _ if len < 9 + header_len => ContainerError::Header(HeaderError::Truncated),
+                _ if len < 17 + header_len => ContainerError::TruncatedLength,
+                _ => ContainerError::PayloadLength {
+                    expected: (data.len() - 17 - header_len) as u64,
+                    actual: len - 17 - header_len,
+                },
+            };
+            assert_eq!(actual, Err(expected), "truncated to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn container_regression_block_symbols_overflow() {
+        // given: "ab" in blocks of 1 symbol, the last one claiming 0x44 << 56
+        // symbols, which overflowed the sum of the symbols.
+        let data = [
+            72, 85, 70, 49, 128, 158, 131, 72, 109, 76, 51, 16, 0, 128, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 68, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0,
+            0, 2,
+        ];
+
+        // when:
+        let actual = (decompress(&data), decompress_range(&data, 0..1));
+
+        // then:
+        assert_eq!(actual.0, Err(ContainerError::InvalidIndex));
+        assert_eq!(actual.1, Err(ContainerError::InvalidIndex));
+    }
+
+    #[test]
+    fn container_regression_frequencies_overflow() {
+        // given: a frequency header with the counts u64::MAX for a and b,
+        // whose sum overflows when building the tree.
+        let data = [
+            72, 85, 70, 49, 1, 158, 131, 72, 109, 2, 97, 255, 255, 255, 255, 255, 255, 255, 255,
+            255, 1, 98, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1, 0, 0, 0, 0, 0, 0, 0, 2, 64,
+        ];
+
+        // when:
+        let actual = decompress(&data);
+
+        // then:
+        assert_eq!(actual, Err(ContainerError::FrequencyOverflow));
+    }
+
+    #[test]
+    fn container_garbage() {
+        for data in [
+            &b""[..],
+            b"HUF1",
+            b"HUF1\x00",
+            b"HUF1\xff\xff\xff",
+            &[0x48; 100],
+        ] {
+            // when:
+            let actual = decompress(data);
+
+            // then:
+            assert!(actual.is_err());
+        }
+    }
+
+    fn block_message() -> String {
+        (0..1000)
+            .map(|i| ['a', 'b', 'ä', '€', '🦀', ' '][i * i % 6])
+            .collect()
+    }
+
+    #[test]
+    fn container_blocks_round_trip() {
+        for message in ["", "a", "Grüße aus München", &block_message()].iter() {
+            for &mode in [HeaderMode::Codebook, HeaderMode::Frequencies].iter() {
+                // when:
+                let actual = decompress(&compress_blocks(message, mode, 64));
+
+                // then:
+                assert_eq!(actual, Ok(message.to_string()));
+            }
+        }
+    }
+
+    #[test]
+    fn container_blocks_range() {
+        // given:
+        let message = block_message();
+        let chars = message.chars().collect::<Vec<_>>();
+        let data = compress_blocks(&message, HeaderMode::Codebook, 64);
+
+        for range in [
+            10..20,
+            60..70,
+            100..300,
+            64..128,
+            0..1000,
+            999..1000,
+            500..500,
+        ]
+        .iter()
+        {
+            // when:
+            let actual = decompress_range(&data, range.clone());
+
+            // then:
+            let expected = chars[range.clone()].iter().collect::<String>();
+            assert_eq!(actual, Ok(expected), "{:?}", range);
+        }
+    }
+
+    #[test]
+    fn container_range_without_blocks() {
+        // when:
+        let actual = decompress_range(&compress("Grüße aus München"), 3..8);
+
+        // then:
+        assert_eq!(actual, Ok("ße au".to_string()));
+    }
+
+    #[test]
+    fn container_range_out_of_bounds() {
+        // given:
+        let data = compress_blocks(&block_message(), HeaderMode::Codebook, 64);
+
+        for range in [990..1001, 1001..1002].iter() {
+            // when:
+            let actual = decompress_range(&data, range.clone());
+
+            // then:
+            assert_eq!(
+                actual,
+                Err(ContainerError::RangeOutOfBounds {
+                    start: range.start,
+                    end: range.end,
+                    len: 1000
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn container_blocks_corrupted_index() {
+        // given:
+        let data = compress_blocks(&block_message(), HeaderMode::Codebook, 64);
+
+        let corruptions: [fn(&mut Vec<u8>); 3] = [
+            |data| *data.last_mut().unwrap() += 1,
+            |data| {
+                data.pop();
+            },
+            |data| {
+                let offset_of_last_block = data.len() - 24;
+                data[offset_of_last_block] = 0xff;
+            },
+        ];
+
+        for corrupt in corruptions.iter() {
+            let mut data = data.clone();
+            corrupt(&mut data);
+
+            // when:
+            let actual = decompress_range(&data, 0..1);
+
+            // then:
+            assert_eq!(actual, Err(ContainerError::InvalidIndex));
+        }
+    }
+
+    fn trained_codebook(corpus: &str) -> Codebook {
+        let mut frequency = frequency(&mut corpus.chars());
+        frequency.insert(crate::codec::ESCAPE, 1);
+        canonical_codebook(&huffman(frequency).unwrap())
+    }
+
+    #[test]
+    fn external_codebook_round_trip() {
+        // given: symbols of the message missing in the corpus are escaped
+        let codebook = trained_codebook("the quick brown fox jumps over the lazy dog");
+        let message = "Grüße, the lazy dog jumps over the quick brown fox!";
+
+        // when:
+        let data = compress_with_codebook(message, &codebook, true).unwrap();
+        let actual = decompress_with_codebook(&data, &codebook);
+
+        // then:
+        assert_eq!(actual.as_deref(), Ok(message));
+        assert!(data.len() < compress(message).len());
+    }
+
+    #[test]
+    fn external_codebook_unknown_symbol_without_escape() {
+        // given:
+        let codebook = trained_codebook("abc");
+
+        // when:
+        let actual = compress_with_codebook("abcd", &codebook, false);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::UnknownSymbol('d')));
+        assert!(compress_with_codebook("cab", &codebook, false).is_ok());
+    }
+
+    #[test]
+    fn external_codebook_required() {
+        // given:
+        let codebook = trained_codebook("abc");
+        let other = trained_codebook("abcd");
+        let data = compress_with_codebook("cab", &codebook, true).unwrap();
+
+        // when:
+        let without = decompress(&data);
+        let with_other = decompress_with_codebook(&data, &other);
+
+        // then:
+        assert_eq!(without, Err(ContainerError::MissingCodebook));
+        assert_eq!(
+            with_other,
+            Err(ContainerError::CodebookMismatch {
+                expected: crc32(&codebook.serialize()),
+                actual: crc32(&other.serialize()),
+            })
+        );
+        assert_eq!(
+            decompress_with_codebook(&compress("cab"), &other).as_deref(),
+            Ok("cab")
+        );
+    }
+
+    fn compress_rle(message: &str, block_size: Option<usize>) -> Vec<u8> {
+        let options = CompressOptions {
+            rle: true,
+            block_size,
+            ..CompressOptions::default()
+        };
+        compress_with_progress(message, options, |_| {})
+    }
+
+    #[test]
+    fn rle_round_trip() {
+        // given: runs longer than a length symbol and runs of the marker
+        let run = crate::rle::RUN.to_string();
+        let messages = [
+            String::new(),
+            "abc".to_string(),
+            format!("x{}y{}z", "ö".repeat(1000), " ".repeat(256)),
+            format!("{}a{}", run.repeat(700), run),
+            format!("{}{}", run, "\u{f0003}".repeat(20)),
+        ];
+
+        for message in &messages {
+            for block_size in [None, Some(3)] {
+                // when:
+                let data = compress_rle(message, block_size);
+
+                // then:
+                assert_eq!(data[4] & RLE, RLE);
+                assert_eq!(decompress(&data).as_ref(), Ok(message));
+            }
+        }
+    }
+
+    #[test]
+    fn rle_shrinks_runs() {
+        // given: indented lines
+        let message = (0..200)
+            .map(|line| format!("{}line {}\n", " ".repeat(4 * (line % 8)), line))
+            .collect::<String>();
+
+        // when:
+        let plain = compress(&message);
+        let rle = compress_rle(&message, None);
+
+        // then:
+        assert!(rle.len() < plain.len(), "{} >= {}", rle.len(), plain.len());
+        assert_eq!(decompress(&rle), Ok(message));
+    }
+
+    #[test]
+    fn rle_output_limit_applies_to_the_runs() {
+        // given:
+        let message = "a".repeat(10_000);
+        let data = compress_rle(&message, None);
+        let options = |max| DecompressOptions {
+            max_output_bytes: Some(max),
+            ..DecompressOptions::default()
+        };
+
+        // when:
+        let limited = decompress_with(&data, options(9_999));
+        let exact = decompress_with(&data, options(10_000));
+
+        // then:
+        assert_eq!(
+            limited,
+            Err(ContainerError::Rle(RleError::LimitExceeded {
+                max_bytes: 9_999
+            }))
+        );
+        assert_eq!(exact, Ok(message));
+    }
+
+    #[test]
+    fn mtf_round_trip() {
+        // given:
+        let messages = [
+            String::new(),
+            "a".to_string(),
+            "Grüße aus München für 5 € 🦀🦀🦀".to_string(),
+            format!(
+                "{}{}{}",
+                "a".repeat(300),
+                "\u{fffe}".repeat(5),
+                "b".repeat(300)
+            ),
+        ];
+
+        for message in &messages {
+            for (rle, block_size) in [
+                (false, None),
+                (true, None),
+                (false, Some(3)),
+                (true, Some(3)),
+            ] {
+                let options = CompressOptions {
+                    mtf: true,
+                    rle,
+                    block_size,
+                    ..CompressOptions::default()
+                };
+
+                // when:
+                let data = compress_with_progress(message, options, |_| {});
+
+                // then:
+                assert_eq!(data[4] & MTF, MTF);
+                assert_eq!(decompress(&data).as_ref(), Ok(message), "{:?}", options);
+                let first = message.chars().take(1).collect::<String>();
+                assert_eq!(decompress_range(&data, 0..first.len().min(1)), Ok(first));
+            }
+        }
+    }
+
+    #[test]
+    fn mtf_shrinks_clustered_symbols() {
+        // given: every letter used in one cluster
+        let message = ('a'..='z')
+            .map(|letter| letter.to_string().repeat(1000))
+            .collect::<String>();
+        let compress_with_options = |mtf, rle| {
+            let options = CompressOptions {
+                mtf,
+                rle,
+                ..CompressOptions::default()
+            };
+            compress_with_progress(&message, options, |_| {})
+        };
+
+        // when:
+        let plain = compress_with_options(false, false);
+        let mtf = compress_with_options(true, false);
+        let mtf_rle = compress_with_options(true, true);
+
+        // then:
+        assert!(
+            mtf.len() * 3 < plain.len(),
+            "{} vs {}",
+            mtf.len(),
+            plain.len()
+        );
+        assert!(mtf_rle.len() < mtf.len());
+        assert_eq!(decompress(&mtf_rle), Ok(message));
+    }
+
+    #[test]
+    fn mtf_index_out_of_range() {
+        // given: the alphabet of "ab" with the indices of "abc"
+        let options = CompressOptions {
+            mtf: true,
+            header_mode: Some(HeaderMode::Codebook),
+            ..CompressOptions::default()
+        };
+        let data = compress_with_progress("abc", options, |_| {});
+        let alphabet = serialize_alphabet(&['a', 'b', 'c']);
+        let position = data
+            .windows(alphabet.len())
+            .position(|window| window == &alphabet[..])
+            .unwrap();
+        let mut corrupted = data[..position].to_vec();
+        corrupted.extend(serialize_alphabet(&['a', 'b']));
+        corrupted.extend(&data[position + alphabet.len()..]);
+
+        // when:
+        let actual = decompress(&corrupted);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(ContainerError::Mtf(MtfError::IndexOutOfRange {
+                position: 2,
+                index: 2,
+                len: 2
+            }))
+        );
+    }
+
+    /// Swap the codes of 'a' and 'b', so the payload decodes to a different
+    /// message than the header promises.
+    fn swap_a_and_b(codebook: &mut Codebook) {
+        let a = codebook.remove(&'a').unwrap();
+        let b = codebook.insert('b', a).unwrap();
+        codebook.insert('a', b);
+    }
+
+    #[test]
+    fn container_verify_round_trip() {
+        let message = "Grüße aus München für 5 € 🦀🦀🦀🦀🦀 aaaaaaaa";
+        for options in [
+            CompressOptions::default(),
+            CompressOptions {
+                block_size: Some(4),
+                ..CompressOptions::default()
+            },
+            CompressOptions {
+                bit_order: BitOrder::LsbFirst,
+                ..CompressOptions::default()
+            },
+            CompressOptions {
+                mtf: true,
+                rle: true,
+                ..CompressOptions::default()
+            },
+        ] {
+            // given:
+            let options = CompressOptions {
+                verify: true,
+                ..options
+            };
+
+            // when:
+            let actual = try_compress_with_progress(message, options, |_| {});
+
+            // then:
+            assert_eq!(
+                actual.as_deref(),
+                Ok(&compress_with_progress(
+                    message,
+                    CompressOptions {
+                        verify: false,
+                        ..options
+                    },
+                    |_| {}
+                )[..])
+            );
+        }
+    }
+
+    #[test]
+    fn container_verify_catches_a_bad_round_trip() {
+        // given: the first 'a' or 'b' is at symbol 3, each block has both
+        let message = "xyzabbaab";
+        for block_size in [None, Some(5)] {
+            let options = CompressOptions {
+                block_size,
+                verify: true,
+                ..CompressOptions::default()
+            };
+
+            // when:
+            let actual = compress_and_check(message, options, |_| {}, swap_a_and_b);
+
+            // then:
+            assert_eq!(actual, Err(ContainerError::SelfCheckFailed { index: 3 }));
+        }
+    }
+
+    #[test]
+    fn container_verify_catches_a_bad_round_trip_after_transforms() {
+        // given: rle keeps the symbols before the first run
+        let message = "xyzbaaaaaa";
+        let options = CompressOptions {
+            rle: true,
+            verify: true,
+            ..CompressOptions::default()
+        };
+
+        // when:
+        let actual = compress_and_check(message, options, |_| {}, swap_a_and_b);
+        let unverified = compress_and_check(
+            message,
+            CompressOptions {
+                verify: false,
+                ..options
+            },
+            |_| {},
+            swap_a_and_b,
+        );
+
+        // then:
+        assert_eq!(actual, Err(ContainerError::SelfCheckFailed { index: 3 }));
+        assert!(unverified.is_ok());
+    }
+}
@@ -0,0 +1,73 @@
+//! The CRC-32 checksum of zlib, PNG and Ethernet.
+
+/// The reversed polynomial 0x04C11DB7.
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+const TABLE: [u32; 256] = table();
+
+/// The CRC of each byte value, so the checksum takes one lookup per byte.
+const fn table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut crc = byte as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[byte] = crc;
+        byte += 1;
+    }
+    table
+}
+
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32_update(0, bytes)
+}
+
+/// Continue the CRC-32 of some bytes with the bytes after them, so that the
+/// checksum of a stream can be computed piece by piece starting with 0.
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, &byte| {
+        TABLE[usize::from(crc as u8 ^ byte)] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_known_answers() {
+        for (bytes, expected) in [
+            (&b""[..], 0),
+            (b"123456789", 0xCBF4_3926),
+            (b"The quick brown fox jumps over the lazy dog", 0x414F_A339),
+        ]
+        .iter()
+        {
+            // when:
+            let actual = crc32(bytes);
+
+            // then:
+            assert_eq!(actual, *expected, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn crc32_update_in_pieces() {
+        // given:
+        let bytes = b"The quick brown fox jumps over the lazy dog";
+
+        // when:
+        let actual = bytes.chunks(5).fold(0, crc32_update);
+
+        // then:
+        assert_eq!(actual, crc32(bytes));
+    }
+}
@@ -0,0 +1,177 @@
+//! A codebook for byte symbols as a table indexed by the byte, for encoding
+//! without a map lookup per byte.
+
+use crate::bits::Bits;
+use crate::code::Codebook;
+use crate::error::HuffmanError;
+use core::convert::TryFrom;
+use core::fmt::{self, Display, Formatter};
+
+/// A code of at most 64 bits, stored in the low `len` bits of `bits`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Code {
+    bits: u64,
+    len: u8,
+}
+
+impl Code {
+    pub fn bits(self) -> u64 {
+        self.bits
+    }
+    pub fn len(self) -> usize {
+        usize::from(self.len)
+    }
+    pub fn is_empty(self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The code of each byte value, or None if it has none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenseCodebook {
+    codes: [Option<Code>; 256],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DenseCodebookError {
+    /// The codebook contains a symbol with this code point above 255.
+    NonByteSymbol(u32),
+    /// The code of the byte is longer than 64 bits.
+    CodeTooLong(u8),
+}
+
+impl Display for DenseCodebookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DenseCodebookError::NonByteSymbol(symbol) => {
+                write!(f, "symbol {} is not a byte", symbol)
+            }
+            DenseCodebookError::CodeTooLong(byte) => {
+                write!(f, "the code of byte {} is longer than 64 bits", byte)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DenseCodebookError {}
+
+impl DenseCodebook {
+    pub fn get(&self, byte: u8) -> Option<Code> {
+        self.codes[usize::from(byte)]
+    }
+    /// Encode the bytes like encode_with, failing with UnknownSymbol for a
+    /// byte without code.
+    pub fn encode(&self, bytes: &[u8]) -> Result<Bits, HuffmanError> {
+        let mut bits = Bits::new();
+        for &byte in bytes {
+            let code = self
+                .get(byte)
+                .ok_or(HuffmanError::UnknownSymbol(char::from(byte)))?;
+            bits.extend_from_u64(code.bits, code.len());
+        }
+        Ok(bits)
+    }
+}
+
+/// Convert a codebook of bytes, or of other symbols such as chars as long as
+/// all of them are below 256.
+impl<T: Copy + Into<u32>> TryFrom<&Codebook<T>> for DenseCodebook {
+    type Error = DenseCodebookError;
+    fn try_from(codebook: &Codebook<T>) -> Result<DenseCodebook, DenseCodebookError> {
+        let mut codes = [None; 256];
+        for (&symbol, code) in codebook {
+            let byte = u8::try_from(symbol.into())
+                .map_err(|_| DenseCodebookError::NonByteSymbol(symbol.into()))?;
+            if code.len() > 64 {
+                return Err(DenseCodebookError::CodeTooLong(byte));
+            }
+            codes[usize::from(byte)] = Some(Code {
+                bits: code.iter().fold(0, |bits, bit| bits << 1 | u64::from(bit)),
+                len: code.len() as u8,
+            });
+        }
+        Ok(DenseCodebook { codes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{encode_bytes, encode_symbols, encode_with};
+    use crate::test_support::random_numbers;
+
+    fn bits(s: &str) -> Bits {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn dense_encode_matches_map_based_encode() {
+        for seed in 0..10 {
+            // given: random binary data, skewed more for larger seeds
+            let bytes = random_numbers(seed)
+                .take(10_000)
+                .map(|x| (x as u8) >> (seed % 8) as u8)
+                .collect::<Vec<_>>();
+            let (codebook, expected) = encode_symbols(&bytes);
+
+            // when:
+            let dense = DenseCodebook::try_from(&codebook).unwrap().encode(&bytes);
+            let actual = encode_bytes(&bytes);
+
+            // then:
+            assert_eq!(dense, Ok(expected.clone()));
+            assert_eq!(actual, (codebook, expected));
+        }
+    }
+
+    #[test]
+    fn dense_codebook_from_chars() {
+        // given:
+        let codebook = [('a', bits("0")), ('ÿ', bits("10")), ('\0', bits("11"))]
+            .iter()
+            .cloned()
+            .collect::<Codebook>();
+
+        // when:
+        let actual = DenseCodebook::try_from(&codebook).unwrap();
+
+        // then:
+        assert_eq!(actual.get(b'a'), Some(Code { bits: 0, len: 1 }));
+        assert_eq!(actual.get(0xff), Some(Code { bits: 2, len: 2 }));
+        assert_eq!(actual.get(0), Some(Code { bits: 3, len: 2 }));
+        assert_eq!(actual.get(b'b'), None);
+        assert_eq!(actual.encode(b"a\xff\0"), encode_with(&codebook, "aÿ\0"));
+        assert_eq!(actual.encode(b"ab"), Err(HuffmanError::UnknownSymbol('b')));
+    }
+
+    #[test]
+    fn dense_codebook_rejects_non_byte_symbols() {
+        // given:
+        let codebook = [('a', bits("0")), ('€', bits("1"))]
+            .iter()
+            .cloned()
+            .collect::<Codebook>();
+
+        // when:
+        let actual = DenseCodebook::try_from(&codebook);
+
+        // then:
+        assert_eq!(actual, Err(DenseCodebookError::NonByteSymbol(0x20ac)));
+    }
+
+    #[test]
+    fn dense_codebook_rejects_long_codes() {
+        // given:
+        let codebook = [(7u8, (0..65).map(|_| true).collect::<Bits>())]
+            .iter()
+            .cloned()
+            .collect::<Codebook<u8>>();
+
+        // when:
+        let actual = DenseCodebook::try_from(&codebook);
+
+        // then:
+        assert_eq!(actual, Err(DenseCodebookError::CodeTooLong(7)));
+    }
+}
@@ -0,0 +1,156 @@
+//! Encoding many messages with one codebook without allocating per message.
+
+use crate::bits::Bits;
+use crate::code::Codebook;
+use crate::error::HuffmanError;
+use core::mem;
+
+/// Encodes messages with a borrowed codebook into a buffer that is reused for
+/// every message, so after the longest message nothing is allocated anymore.
+#[derive(Debug, Clone)]
+pub struct Encoder<'a> {
+    codebook: &'a Codebook,
+    bits: Bits,
+}
+
+impl<'a> Encoder<'a> {
+    pub fn new(codebook: &'a Codebook) -> Encoder<'a> {
+        Encoder {
+            codebook,
+            bits: Bits::new(),
+        }
+    }
+
+    /// Replace the buffer with the message encoded like encode_with. On an
+    /// unknown symbol the partial output is cleared, leaving the buffer empty.
+    pub fn encode_into(&mut self, message: &str) -> Result<&Bits, HuffmanError> {
+        self.bits.clear();
+        for chr in message.chars() {
+            if let Err(err) = self.encode_char(chr) {
+                self.bits.clear();
+                return Err(err);
+            }
+        }
+        Ok(&self.bits)
+    }
+
+    /// Append the code of the symbol to the buffer. On an unknown symbol the
+    /// buffer is left unchanged.
+    pub fn encode_char(&mut self, chr: char) -> Result<(), HuffmanError> {
+        let code = self
+            .codebook
+            .get(&chr)
+            .ok_or(HuffmanError::UnknownSymbol(chr))?;
+        self.bits.extend_from_code(code);
+        Ok(())
+    }
+
+    /// The bits encoded since the last encode_into, take_bits or clear.
+    pub fn bits(&self) -> &Bits {
+        &self.bits
+    }
+
+    /// Empty the buffer, keeping its capacity.
+    pub fn clear(&mut self) {
+        self.bits.clear();
+    }
+
+    /// Move the encoded bits out, leaving an empty buffer without capacity.
+    pub fn take_bits(&mut self) -> Bits {
+        mem::take(&mut self.bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{encode, encode_with};
+    use alloc::{format, vec::Vec};
+
+    #[test]
+    fn encoder_matches_encode_with() {
+        // given:
+        let codebook = encode("0123456789 GET POST /index.html ok error ms äöü 🦀").codebook;
+        let messages = (0..10_000)
+            .map(|i| match i % 3 {
+                0 => format!("GET /index.html {} ok {} ms", i, i % 97),
+                1 => format!("POST / {} error", i * 7),
+                _ => format!("{} äöü 🦀", i % 10),
+            })
+            .collect::<Vec<_>>();
+        let mut encoder = Encoder::new(&codebook);
+
+        for (i, message) in messages.iter().enumerate() {
+            // when:
+            let actual = encoder.encode_into(message).unwrap();
+
+            // then:
+            if i % 97 == 0 {
+                assert_eq!(
+                    actual,
+                    &encode_with(&codebook, message).unwrap(),
+                    "{}",
+                    message
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encoder_reuses_buffer() {
+        // given:
+        let codebook = encode("abc").codebook;
+        let mut encoder = Encoder::new(&codebook);
+        let buffer = encoder
+            .encode_into(&"abc".repeat(100))
+            .unwrap()
+            .as_bytes()
+            .as_ptr();
+
+        for message in ["a", "cab", "", "bca"] {
+            // when:
+            let actual = encoder.encode_into(message).unwrap();
+
+            // then:
+            assert_eq!(actual.as_bytes().as_ptr(), buffer);
+        }
+    }
+
+    #[test]
+    fn encode_into_unknown_symbol_clears_buffer() {
+        // given:
+        let codebook = encode("abc").codebook;
+        let mut encoder = Encoder::new(&codebook);
+        encoder.encode_into("abc").unwrap();
+
+        // when:
+        let actual = encoder.encode_into("abxc");
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::UnknownSymbol('x')));
+        assert!(encoder.bits().is_empty());
+        assert_eq!(
+            encoder.encode_into("cab"),
+            Ok(&encode_with(&codebook, "cab").unwrap())
+        );
+    }
+
+    #[test]
+    fn encode_char_and_take_bits() {
+        // given:
+        let codebook = encode("abc").codebook;
+        let mut encoder = Encoder::new(&codebook);
+
+        // when:
+        let results = "abxc"
+            .chars()
+            .map(|chr| encoder.encode_char(chr))
+            .collect::<Vec<_>>();
+        let actual = encoder.take_bits();
+
+        // then:
+        assert_eq!(results[2], Err(HuffmanError::UnknownSymbol('x')));
+        assert_eq!(actual, encode_with(&codebook, "abc").unwrap());
+        assert!(encoder.bits().is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+//! The error type shared by encoding and decoding.
+
+use crate::code::CodebookError;
+use core::fmt::{self, Display, Formatter};
+#[cfg(feature = "std")]
+use std::io;
+
+#[derive(Debug)]
+pub enum HuffmanError {
+    /// There is nothing to build a code from.
+    EmptyInput,
+    /// The message contains a symbol that has no code.
+    UnknownSymbol(char),
+    /// The bits at `bit_offset` do not start with any code of the codebook.
+    InvalidPrefix { bit_offset: usize },
+    /// The input ends at `bit_offset` in the middle of a code.
+    TruncatedInput { bit_offset: usize },
+    /// The codebook cannot be decoded unambiguously.
+    InvalidCodebook(CodebookError),
+    /// Decoding stopped at `bit_offset` after `produced` symbols because the
+    /// next one would exceed the output limit.
+    LimitExceeded { produced: usize, bit_offset: usize },
+    /// The length of the encoded message in bits exceeds u64::MAX.
+    LengthOverflow,
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl Display for HuffmanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HuffmanError::EmptyInput => write!(f, "the input is empty"),
+            HuffmanError::UnknownSymbol(chr) => write!(f, "no code for symbol {:?}", chr),
+            HuffmanError::InvalidPrefix { bit_offset } => {
+                write!(f, "no entry in code at bit {}", bit_offset)
+            }
+            HuffmanError::TruncatedInput { bit_offset } => {
+                write!(
+                    f,
+                    "input ends in the middle of a code at bit {}",
+                    bit_offset
+                )
+            }
+            HuffmanError::InvalidCodebook(err) => write!(f, "invalid codebook: {}", err),
+            HuffmanError::LimitExceeded {
+                produced,
+                bit_offset,
+            } => write!(
+                f,
+                "output limit exceeded after {} symbols at bit {}",
+                produced, bit_offset
+            ),
+            HuffmanError::LengthOverflow => {
+                write!(f, "the encoded message is longer than {} bits", u64::MAX)
+            }
+            #[cfg(feature = "std")]
+            HuffmanError::Io(err) => write!(f, "I/O error: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for HuffmanError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HuffmanError::InvalidCodebook(err) => Some(err),
+            HuffmanError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// I/O errors are equal if they are of the same kind, so results can be
+/// compared in tests.
+impl PartialEq for HuffmanError {
+    fn eq(&self, other: &HuffmanError) -> bool {
+        use HuffmanError::*;
+        match (self, other) {
+            (EmptyInput, EmptyInput) => true,
+            (UnknownSymbol(a), UnknownSymbol(b)) => a == b,
+            (InvalidPrefix { bit_offset: a }, InvalidPrefix { bit_offset: b }) => a == b,
+            (TruncatedInput { bit_offset: a }, TruncatedInput { bit_offset: b }) => a == b,
+            (InvalidCodebook(a), InvalidCodebook(b)) => a == b,
+            (
+                LimitExceeded {
+                    produced: a,
+                    bit_offset: c,
+                },
+                LimitExceeded {
+                    produced: b,
+                    bit_offset: d,
+                },
+            ) => a == b && c == d,
+            (LengthOverflow, LengthOverflow) => true,
+            #[cfg(feature = "std")]
+            (Io(a), Io(b)) => a.kind() == b.kind(),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for HuffmanError {}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for HuffmanError {
+    fn from(err: io::Error) -> HuffmanError {
+        HuffmanError::Io(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<HuffmanError> for io::Error {
+    fn from(err: HuffmanError) -> io::Error {
+        match err {
+            HuffmanError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn io_error_round_trip() {
+        // given:
+        let err = io::Error::new(io::ErrorKind::UnexpectedEof, "eof");
+
+        // when:
+        let actual = HuffmanError::from(err);
+
+        // then:
+        assert!(
+            matches!(&actual, HuffmanError::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof)
+        );
+        assert!(actual.source().is_some());
+        assert_eq!(io::Error::from(actual).kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn decode_errors_become_invalid_data() {
+        // when:
+        let actual = io::Error::from(HuffmanError::InvalidPrefix { bit_offset: 3 });
+
+        // then:
+        assert_eq!(actual.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(actual.to_string(), "no entry in code at bit 3");
+    }
+}
@@ -0,0 +1,283 @@
+//! A table-driven decoder that decodes a whole code per lookup.
+
+use crate::bits::Bits;
+use crate::code::{decode_symbol, Codebook};
+use crate::error::HuffmanError;
+use alloc::{string::String, vec, vec::Vec};
+
+/// The maximum number of bits used to index the lookup table.
+pub const MAX_TABLE_BITS: usize = 12;
+
+/// Decodes by looking up the next bits in a table that stores the decoded
+/// symbol and its code length. Codes longer than the table index fall back to
+/// walking a binary trie of the codes, so no step depends on the size of the
+/// alphabet. Codebooks that are not prefix-free are decoded by scanning the
+/// codebook like decode.
+#[derive(Debug, Clone)]
+pub struct FastDecoder<T = char> {
+    codebook: Codebook<T>,
+    symbols: Vec<T>,
+    prefix_free: bool,
+    table_bits: usize,
+    /// The index of the symbol and the length of its code.
+    table: Vec<Option<(usize, usize)>>,
+    /// The children of each node of the trie, the root is node 0.
+    trie: Vec<[Link; 2]>,
+}
+
+/// A branch of a node of the trie of a FastDecoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Link {
+    /// No code continues with this bit.
+    None,
+    Node(usize),
+    /// The index of the symbol whose code ends with this bit.
+    Symbol(usize),
+}
+
+impl<T: Ord + Clone> FastDecoder<T> {
+    pub fn new(codebook: &Codebook<T>) -> FastDecoder<T> {
+        let max_len = codebook.values().map(Bits::len).max().unwrap_or(0);
+        let table_bits = max_len.min(MAX_TABLE_BITS);
+        let mut table = vec![None; 1 << table_bits];
+        let mut trie = vec![[Link::None; 2]];
+        // Like validate_codebook, only the code of the only symbol may be
+        // empty.
+        let mut prefix_free = codebook.len() == 1 || codebook.values().all(|code| !code.is_empty());
+        for (index, code) in codebook.values().enumerate() {
+            if code.is_empty() {
+                continue;
+            }
+            if code.len() <= table_bits {
+                let first = code.iter().fold(0, |first, bit| first << 1 | bit as usize);
+                let first = first << (table_bits - code.len());
+                for entry in &mut table[first..first + (1 << (table_bits - code.len()))] {
+                    entry.get_or_insert((index, code.len()));
+                }
+            }
+            let mut node = 0;
+            for (i, bit) in code.iter().enumerate() {
+                let bit = bit as usize;
+                match trie[node][bit] {
+                    Link::None if i + 1 == code.len() => trie[node][bit] = Link::Symbol(index),
+                    Link::None => {
+                        trie.push([Link::None; 2]);
+                        trie[node][bit] = Link::Node(trie.len() - 1);
+                        node = trie.len() - 1;
+                    }
+                    Link::Node(child) if i + 1 < code.len() => node = child,
+                    // The code is equal to or a prefix of another code or
+                    // the other way round.
+                    _ => {
+                        prefix_free = false;
+                        break;
+                    }
+                }
+            }
+        }
+        FastDecoder {
+            codebook: codebook.clone(),
+            symbols: codebook.keys().cloned().collect(),
+            prefix_free,
+            table_bits,
+            table,
+            trie,
+        }
+    }
+
+    /// Decode the symbol starting at bit_offset, returning it with its code
+    /// length, like decode does. Bits from bit_len on are not considered.
+    pub fn decode_symbol(
+        &self,
+        bits: &Bits,
+        bit_offset: usize,
+        bit_len: usize,
+    ) -> Result<(&T, usize), HuffmanError> {
+        if !self.prefix_free {
+            return decode_symbol(&self.codebook, bits, bit_offset, bit_len);
+        }
+        match self.table[self.peek(bits, bit_offset)] {
+            Some((index, code_len)) if code_len <= bit_len - bit_offset => {
+                Ok((&self.symbols[index], code_len))
+            }
+            _ => self.walk(bits, bit_offset, bit_len),
+        }
+    }
+
+    /// Decode the symbol at bit_offset by walking the trie one bit at a time.
+    fn walk(
+        &self,
+        bits: &Bits,
+        bit_offset: usize,
+        bit_len: usize,
+    ) -> Result<(&T, usize), HuffmanError> {
+        let mut node = 0;
+        for offset in bit_offset..bit_len {
+            match self.trie[node][bits.get(offset) as usize] {
+                Link::None => return Err(HuffmanError::InvalidPrefix { bit_offset }),
+                Link::Node(child) => node = child,
+                Link::Symbol(index) => return Ok((&self.symbols[index], offset + 1 - bit_offset)),
+            }
+        }
+        Err(HuffmanError::TruncatedInput { bit_offset })
+    }
+
+    /// The next table_bits bits at bit_offset, 0 beyond the end of the bits.
+    fn peek(&self, bits: &Bits, bit_offset: usize) -> usize {
+        let bytes = bits.as_bytes();
+        let window = (0..3).fold(0, |window, i| {
+            window << 8 | *bytes.get(bit_offset / 8 + i).unwrap_or(&0) as usize
+        });
+        window >> (24 - bit_offset % 8 - self.table_bits) & ((1 << self.table_bits) - 1)
+    }
+}
+
+impl FastDecoder {
+    /// Decode the bits, with the same result as decode.
+    pub fn decode(&self, bits: &Bits) -> Result<String, HuffmanError> {
+        let mut decoded = String::new();
+        let mut bit_offset = 0;
+        while bit_offset < bits.len() {
+            let (&chr, code_len) = self.decode_symbol(bits, bit_offset, bits.len())?;
+            decoded.push(chr);
+            bit_offset += code_len;
+        }
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{codebook_from_lengths, decode, encode, Encoded};
+    use crate::test_support::random_numbers;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn fast_decoder_matches_decode_on_random_messages() {
+        let alphabet = ('a'..='z').chain("äöü€ 🦀".chars()).collect::<Vec<_>>();
+        for seed in 0..50 {
+            // given:
+            let mut numbers = random_numbers(seed);
+            let len = 1 + numbers.next().unwrap() % 500;
+            let symbols = 1 + numbers.next().unwrap() % alphabet.len();
+            let message = numbers
+                .take(len)
+                .map(|x| alphabet[x % symbols])
+                .collect::<String>();
+            let Encoded { codebook, bits, .. } = encode(&message);
+
+            // when:
+            let actual = FastDecoder::new(&codebook).decode(&bits);
+
+            // then:
+            assert_eq!(actual, Ok(message));
+        }
+    }
+
+    #[test]
+    fn fast_decoder_long_codes() {
+        // given:
+        let lengths = ('a'..='t')
+            .zip(1..)
+            .chain(Some(('u', 20)))
+            .collect::<BTreeMap<_, _>>();
+        let codebook = codebook_from_lengths(&lengths);
+        let message = "abcdefghijklmnopqrstuutsrqponmlkjihgfedcba";
+        let bits = message
+            .chars()
+            .flat_map(|chr| codebook[&chr].iter())
+            .collect::<Bits>();
+
+        // when:
+        let actual = FastDecoder::new(&codebook).decode(&bits);
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+    }
+
+    #[test]
+    fn fast_decoder_matches_decode_at_end_of_stream() {
+        // given:
+        let lengths = ('a'..='t')
+            .zip(1..)
+            .chain(Some(('u', 20)))
+            .collect::<BTreeMap<_, _>>();
+        let codebook = codebook_from_lengths(&lengths);
+        let decoder = FastDecoder::new(&codebook);
+        let bits = "abcu"
+            .chars()
+            .flat_map(|chr| codebook[&chr].iter())
+            .collect::<Bits>();
+
+        for len in 0..=bits.len() {
+            // when:
+            let truncated = Bits::from_bytes(len, bits.as_bytes()).unwrap();
+            let actual = decoder.decode(&truncated);
+
+            // then:
+            assert_eq!(actual, decode(&codebook, &truncated));
+        }
+    }
+
+    #[test]
+    fn fast_decoder_unknown_prefix() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', "0".chars().map(|c| c == '1').collect());
+        let bits = "001".chars().map(|c| c == '1').collect::<Bits>();
+
+        // when:
+        let actual = FastDecoder::new(&codebook).decode(&bits);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::InvalidPrefix { bit_offset: 2 }));
+    }
+
+    #[test]
+    fn fast_decoder_first_matching_code_without_prefix_free_codebook() {
+        // given:
+        let code = |code: &str| code.chars().map(|c| c == '1').collect::<Bits>();
+        let mut codebook = Codebook::new();
+        codebook.insert('a', code("01"));
+        codebook.insert('b', code("0"));
+        codebook.insert('c', code("0110"));
+        codebook.insert('d', code("1"));
+        let bits = code("0110100110");
+
+        // when:
+        let actual = FastDecoder::new(&codebook).decode(&bits);
+
+        // then:
+        assert_eq!(actual, Ok("adabadb".to_string()));
+    }
+
+    #[test]
+    fn fast_decoder_of_string_symbols() {
+        // given:
+        let code = |code: &str| code.chars().map(|c| c == '1').collect::<Bits>();
+        let mut codebook = Codebook::new();
+        codebook.insert("the".to_string(), code("0"));
+        codebook.insert("quick".to_string(), code("10"));
+        codebook.insert("fox".to_string(), code("11"));
+        let decoder = FastDecoder::new(&codebook);
+        let bits = code("1101");
+
+        // when:
+        let actual = (
+            decoder.decode_symbol(&bits, 0, bits.len()),
+            decoder.decode_symbol(&bits, 2, bits.len()),
+            decoder.decode_symbol(&bits, 3, bits.len()),
+        );
+
+        // then:
+        assert_eq!(
+            actual,
+            (
+                Ok((&"fox".to_string(), 2)),
+                Ok((&"the".to_string(), 1)),
+                Err(HuffmanError::TruncatedInput { bit_offset: 3 })
+            )
+        );
+    }
+}
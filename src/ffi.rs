@@ -0,0 +1,323 @@
+//! C bindings of compress and decompress. The ue5-ffi package in ffi/ builds
+//! them as the shared library libhuff with `cargo build --release -p ue5-ffi`
+//! and declares them in ffi/include/huff.h:
+//!
+//! ```c
+//! int32_t huff_compress(const uint8_t *data, size_t len, uint8_t **out, size_t *out_len);
+//! int32_t huff_decompress(const uint8_t *data, size_t len, uint8_t **out, size_t *out_len);
+//! void huff_free(uint8_t *buf, size_t len);
+//! const char *huff_last_error_message(void);
+//! ```
+//!
+//! The functions return HUFF_OK or one of the negative error codes below.
+//! Buffers returned in out must be released with huff_free, an empty result
+//! is a null pointer with the length 0.
+
+use crate::container::{compress, decompress, ContainerError};
+use crate::error::HuffmanError;
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::{ptr, slice, str};
+
+pub const HUFF_OK: i32 = 0;
+/// A pointer that must not be null is null.
+pub const HUFF_NULL_POINTER: i32 = -1;
+/// The message to compress is not valid UTF-8.
+pub const HUFF_INVALID_UTF8: i32 = -2;
+/// The data is no container, e.g. it does not start with the magic or its
+/// header is corrupt.
+pub const HUFF_INVALID_CONTAINER: i32 = -3;
+/// The decoded message does not have the checksum of the container.
+pub const HUFF_CHECKSUM_MISMATCH: i32 = -4;
+/// HuffmanError::EmptyInput.
+pub const HUFF_EMPTY_INPUT: i32 = -5;
+/// HuffmanError::UnknownSymbol.
+pub const HUFF_UNKNOWN_SYMBOL: i32 = -6;
+/// HuffmanError::InvalidPrefix.
+pub const HUFF_INVALID_PREFIX: i32 = -7;
+/// HuffmanError::TruncatedInput.
+pub const HUFF_TRUNCATED_INPUT: i32 = -8;
+/// HuffmanError::InvalidCodebook.
+pub const HUFF_INVALID_CODEBOOK: i32 = -9;
+/// HuffmanError::LimitExceeded.
+pub const HUFF_LIMIT_EXCEEDED: i32 = -10;
+/// HuffmanError::Io.
+pub const HUFF_IO: i32 = -11;
+/// The library panicked, which is a bug.
+pub const HUFF_PANIC: i32 = -12;
+/// HuffmanError::LengthOverflow.
+pub const HUFF_LENGTH_OVERFLOW: i32 = -13;
+
+thread_local! {
+    /// The message of the last error on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// The error code of the HuffmanError variant.
+fn huffman_error_code(err: &HuffmanError) -> i32 {
+    match err {
+        HuffmanError::EmptyInput => HUFF_EMPTY_INPUT,
+        HuffmanError::UnknownSymbol(_) => HUFF_UNKNOWN_SYMBOL,
+        HuffmanError::InvalidPrefix { .. } => HUFF_INVALID_PREFIX,
+        HuffmanError::TruncatedInput { .. } => HUFF_TRUNCATED_INPUT,
+        HuffmanError::InvalidCodebook(_) => HUFF_INVALID_CODEBOOK,
+        HuffmanError::LimitExceeded { .. } => HUFF_LIMIT_EXCEEDED,
+        HuffmanError::Io(_) => HUFF_IO,
+        HuffmanError::LengthOverflow => HUFF_LENGTH_OVERFLOW,
+    }
+}
+
+/// The error code of the ContainerError, the one of the HuffmanError for
+/// payloads that do not decode.
+fn container_error_code(err: &ContainerError) -> i32 {
+    match err {
+        ContainerError::Decode(err) => huffman_error_code(err),
+        ContainerError::Block { error, .. } => container_error_code(error),
+        ContainerError::ChecksumMismatch { .. } => HUFF_CHECKSUM_MISMATCH,
+        _ => HUFF_INVALID_CONTAINER,
+    }
+}
+
+/// Remember the message for huff_last_error_message and return the code.
+fn fail(code: i32, message: String) -> i32 {
+    // A message with a NUL byte is cut there.
+    let message = CString::new(message).unwrap_or_else(|err| {
+        let nul = err.nul_position();
+        CString::new(&err.into_vec()[..nul]).expect("there is no NUL before the first")
+    });
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+/// Run f on the input as a slice and hand its output to the caller, catching
+/// panics so they never unwind into C.
+///
+/// # Safety
+///
+/// See huff_compress.
+unsafe fn call<F>(data: *const u8, len: usize, out: *mut *mut u8, out_len: *mut usize, f: F) -> i32
+where
+    F: FnOnce(&[u8]) -> Result<Vec<u8>, (i32, String)>,
+{
+    if out.is_null() || out_len.is_null() {
+        return fail(HUFF_NULL_POINTER, "out and out_len must not be null".into());
+    }
+    *out = ptr::null_mut();
+    *out_len = 0;
+    let input = match (data.is_null(), len) {
+        (_, 0) => &[][..],
+        (true, _) => return fail(HUFF_NULL_POINTER, "data is null".into()),
+        (false, _) => slice::from_raw_parts(data, len),
+    };
+    let result = catch_unwind(AssertUnwindSafe(|| f(input))).unwrap_or_else(|panic| {
+        let reason = match panic.downcast_ref::<&str>() {
+            Some(reason) => reason.to_string(),
+            None => panic.downcast_ref::<String>().cloned().unwrap_or_default(),
+        };
+        Err((HUFF_PANIC, format!("panic: {}", reason)))
+    });
+    match result {
+        Ok(output) if output.is_empty() => HUFF_OK,
+        Ok(output) => {
+            *out_len = output.len();
+            *out = Box::into_raw(output.into_boxed_slice()) as *mut u8;
+            HUFF_OK
+        }
+        Err((code, message)) => fail(code, message),
+    }
+}
+
+/// Compress the len bytes of UTF-8 at data like compress and store the
+/// output and its length in out and out_len.
+///
+/// # Safety
+///
+/// data must point to len readable bytes unless len is 0, out and out_len
+/// must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn huff_compress(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    call(data, len, out, out_len, |input| {
+        let message = str::from_utf8(input)
+            .map_err(|err| (HUFF_INVALID_UTF8, format!("invalid UTF-8: {}", err)))?;
+        Ok(compress(message))
+    })
+}
+
+/// Decompress the len bytes at data like decompress and store the UTF-8
+/// message, which is not NUL-terminated, and its length in out and out_len.
+///
+/// # Safety
+///
+/// See huff_compress.
+#[no_mangle]
+pub unsafe extern "C" fn huff_decompress(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut u8,
+    out_len: *mut usize,
+) -> i32 {
+    call(data, len, out, out_len, |input| {
+        decompress(input).map(String::into_bytes).map_err(|err| {
+            (
+                container_error_code(&err),
+                format!("cannot decompress: {}", err),
+            )
+        })
+    })
+}
+
+/// Release a buffer returned by huff_compress or huff_decompress. Does
+/// nothing for a null pointer.
+///
+/// # Safety
+///
+/// buf and len must be a buffer and its length returned by this library that
+/// was not released before.
+#[no_mangle]
+pub unsafe extern "C" fn huff_free(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buf, len)));
+    }
+}
+
+/// The message of the last error on the calling thread, or null if there was
+/// none. It is valid until the next call of a function of this library on the
+/// same thread.
+#[no_mangle]
+pub extern "C" fn huff_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    /// Call the function like C would and copy the output.
+    fn call_c(
+        f: unsafe extern "C" fn(*const u8, usize, *mut *mut u8, *mut usize) -> i32,
+        data: *const u8,
+        len: usize,
+    ) -> (i32, Vec<u8>) {
+        let mut out = ptr::null_mut();
+        let mut out_len = 0;
+        unsafe {
+            let code = f(data, len, &mut out, &mut out_len);
+            let output = match out.is_null() {
+                true => Vec::new(),
+                false => slice::from_raw_parts(out, out_len).to_vec(),
+            };
+            huff_free(out, out_len);
+            (code, output)
+        }
+    }
+
+    fn last_error() -> String {
+        let message = huff_last_error_message();
+        assert!(!message.is_null());
+        unsafe { CStr::from_ptr(message) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn ffi_round_trip() {
+        for message in ["", "a", "Grüße aus München 🦀"] {
+            // when:
+            let (code, compressed) = call_c(huff_compress, message.as_ptr(), message.len());
+            let (decode_code, decompressed) =
+                call_c(huff_decompress, compressed.as_ptr(), compressed.len());
+
+            // then:
+            assert_eq!((code, decode_code), (HUFF_OK, HUFF_OK));
+            assert_eq!(compressed, compress(message));
+            assert_eq!(decompressed, message.as_bytes());
+        }
+    }
+
+    #[test]
+    fn ffi_corrupt_input() {
+        // given:
+        let mut data = compress("aardvarks ate apples around aachen");
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+
+        // when:
+        let corrupt = call_c(huff_decompress, data.as_ptr(), data.len());
+        let magic = call_c(huff_decompress, b"HUF0".as_ptr(), 4);
+
+        // then:
+        assert_eq!(corrupt, (HUFF_CHECKSUM_MISMATCH, Vec::new()));
+        assert_eq!(magic, (HUFF_INVALID_CONTAINER, Vec::new()));
+        assert_eq!(
+            last_error(),
+            "cannot decompress: the data does not start with \"HUF1\""
+        );
+    }
+
+    #[test]
+    fn ffi_invalid_utf8() {
+        // when:
+        let actual = call_c(huff_compress, b"a\xffb".as_ptr(), 3);
+
+        // then:
+        assert_eq!(actual, (HUFF_INVALID_UTF8, Vec::new()));
+        assert!(last_error().starts_with("invalid UTF-8"));
+    }
+
+    #[test]
+    fn ffi_null_pointers() {
+        // when:
+        let empty = call_c(huff_compress, ptr::null(), 0);
+        let null_data = call_c(huff_decompress, ptr::null(), 3);
+        let null_out = unsafe { huff_compress(b"a".as_ptr(), 1, ptr::null_mut(), ptr::null_mut()) };
+
+        // then:
+        assert_eq!(empty, (HUFF_OK, compress("")));
+        assert_eq!(null_data, (HUFF_NULL_POINTER, Vec::new()));
+        assert_eq!(null_out, HUFF_NULL_POINTER);
+        unsafe { huff_free(ptr::null_mut(), 0) };
+    }
+
+    #[test]
+    fn ffi_error_codes() {
+        assert_eq!(
+            container_error_code(&ContainerError::Block {
+                index: 1,
+                error: Box::new(ContainerError::Decode(HuffmanError::TruncatedInput {
+                    bit_offset: 3
+                })),
+            }),
+            HUFF_TRUNCATED_INPUT
+        );
+        assert_eq!(
+            container_error_code(&ContainerError::BadMagic),
+            HUFF_INVALID_CONTAINER
+        );
+    }
+
+    #[test]
+    fn ffi_catches_panics() {
+        // given:
+        let mut out = ptr::null_mut();
+        let mut out_len = 0;
+
+        // when:
+        let actual = unsafe { call(b"a".as_ptr(), 1, &mut out, &mut out_len, |_| panic!("boom")) };
+
+        // then:
+        assert_eq!(actual, HUFF_PANIC);
+        assert!(out.is_null());
+        assert_eq!(last_error(), "panic: boom");
+    }
+}
@@ -0,0 +1,716 @@
+//! A compact serialized form of a codebook for storing it next to the bits.
+//!
+//! The codebook is written as a preorder traversal of its code tree: an
+//! internal node is the bit `0`, a leaf is `10` followed by the UTF-8 bytes of
+//! its symbol and an unused branch (which only occurs in incomplete codebooks)
+//! is `11`. The bits are packed most significant bit first and the last byte
+//! is padded with 0 bits.
+//!
+//! Alternatively the frequencies the codebook was built from can be stored:
+//! the number of symbols and then each symbol as UTF-8 followed by its count,
+//! both as LEB128 varints. The decoder rebuilds the codebook with huffman,
+//! which breaks ties deterministically.
+//!
+//! Or just the code lengths of a canonical codebook: the sorted symbols as
+//! runs of consecutive code points, i.e. the number of runs and for each run
+//! its first symbol as UTF-8 and its number of symbols, followed by the code
+//! length of each symbol, all numbers as varints.
+
+use crate::bits::Bits;
+use crate::code::{canonical_codes, validate_codebook, Codebook};
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::{string::ToString, vec, vec::Vec};
+use core::convert::TryFrom;
+use core::fmt::{self, Display, Formatter};
+
+/// Codes longer than this are rejected when deserializing, which bounds the
+/// memory a malicious header can claim.
+pub const MAX_CODE_LEN: usize = 256;
+
+/// The number of chars, which no list of distinct symbols exceeds.
+const MAX_SYMBOLS: usize = 0x110000 - 0x800;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HeaderError {
+    /// The header ends before the tree is complete.
+    Truncated,
+    /// The bytes of a symbol at `bit_offset` are not valid UTF-8.
+    InvalidSymbol { bit_offset: usize },
+    /// The symbol occurs in more than one leaf.
+    DuplicateSymbol(char),
+    /// The node at `bit_offset` has two unused branches, so no code leads
+    /// through it.
+    EmptyNode { bit_offset: usize },
+    /// The code of the node at `bit_offset` is longer than MAX_CODE_LEN.
+    CodeTooLong { bit_offset: usize },
+    /// The varint at `bit_offset` is 0 where a count is expected or does not
+    /// fit into a u32, or a u64 for symbol counts.
+    InvalidCount { bit_offset: usize },
+    /// The code lengths do not form a prefix-free canonical codebook.
+    InvalidLengths,
+}
+
+impl Display for HeaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HeaderError::Truncated => write!(f, "the codebook header is truncated"),
+            HeaderError::InvalidSymbol { bit_offset } => {
+                write!(f, "invalid UTF-8 symbol at bit {}", bit_offset)
+            }
+            HeaderError::DuplicateSymbol(chr) => {
+                write!(f, "symbol {:?} occurs more than once", chr)
+            }
+            HeaderError::EmptyNode { bit_offset } => {
+                write!(f, "no code leads through the node at bit {}", bit_offset)
+            }
+            HeaderError::CodeTooLong { bit_offset } => write!(
+                f,
+                "the code at bit {} is longer than {} bits",
+                bit_offset, MAX_CODE_LEN
+            ),
+            HeaderError::InvalidCount { bit_offset } => {
+                write!(f, "invalid count at bit {}", bit_offset)
+            }
+            HeaderError::InvalidLengths => {
+                write!(f, "the code lengths do not form a prefix-free code")
+            }
+        }
+    }
+}
+
+/// Serialization of a codebook into the compact header format.
+pub trait CodebookHeader: Sized {
+    /// Serialize the codebook.
+    ///
+    /// # Panics
+    ///
+    /// If the codebook is not prefix-free, which never happens for codebooks
+    /// built from a Huffman tree.
+    fn serialize(&self) -> Vec<u8>;
+    /// Deserialize a codebook from the start of `bytes`. Returns the codebook
+    /// and the number of bytes it occupied, which is where the data after the
+    /// header starts.
+    fn deserialize(bytes: &[u8]) -> Result<(Self, usize), HeaderError>;
+}
+
+impl CodebookHeader for Codebook {
+    fn serialize(&self) -> Vec<u8> {
+        fn write_node(entries: &[(char, &Bits)], depth: usize, out: &mut Bits) {
+            match entries {
+                [] => out.extend([true, true]),
+                [(chr, code)] if code.len() == depth => {
+                    out.extend([true, false]);
+                    for byte in chr.to_string().bytes() {
+                        out.extend((0..8).map(|i| byte & (0x80 >> i) != 0));
+                    }
+                }
+                _ => {
+                    assert!(
+                        entries.iter().all(|(_, code)| code.len() > depth),
+                        "the codebook is not prefix-free"
+                    );
+                    out.push(false);
+                    let split = entries.partition_point(|(_, code)| !code.get(depth));
+                    write_node(&entries[..split], depth + 1, out);
+                    write_node(&entries[split..], depth + 1, out);
+                }
+            }
+        }
+        let mut entries = self
+            .iter()
+            .map(|(&chr, code)| (chr, code))
+            .collect::<Vec<_>>();
+        entries.sort_by(|(_, a), (_, b)| a.iter().cmp(b.iter()));
+        let mut out = Bits::new();
+        write_node(&entries, 0, &mut out);
+        out.as_bytes().to_vec()
+    }
+
+    fn deserialize(bytes: &[u8]) -> Result<(Codebook, usize), HeaderError> {
+        let mut reader = BitReader { bytes, offset: 0 };
+        let mut codebook = Codebook::new();
+        let mut symbols = BTreeSet::new();
+        // The path to the current node, and for each node that is still to be
+        // read the length of its parent's path and its branch, so the stack
+        // only stores small entries.
+        let mut path = Bits::new();
+        let mut pending = vec![(0, None)];
+        // The path of the previous node, if it was an unused branch.
+        let mut previous_unused: Option<Bits> = None;
+        while let Some((parent_len, branch)) = pending.pop() {
+            let bit_offset = reader.offset;
+            while path.len() > parent_len {
+                path.pop();
+            }
+            path.extend(branch);
+            if path.len() > MAX_CODE_LEN {
+                return Err(HeaderError::CodeTooLong { bit_offset });
+            }
+            if !reader.read_bit()? {
+                pending.push((path.len(), Some(true)));
+                pending.push((path.len(), Some(false)));
+                previous_unused = None;
+            } else if !reader.read_bit()? {
+                let chr = reader.read_char()?;
+                if !symbols.insert(chr) {
+                    return Err(HeaderError::DuplicateSymbol(chr));
+                }
+                codebook.insert(chr, path.clone());
+                previous_unused = None;
+            } else {
+                // Both branches of a node unused means the left sibling was
+                // the node read right before this one.
+                if branch == Some(true) {
+                    let mut sibling = path.clone();
+                    sibling.pop();
+                    sibling.push(false);
+                    if previous_unused.as_ref() == Some(&sibling) {
+                        return Err(HeaderError::EmptyNode { bit_offset });
+                    }
+                }
+                previous_unused = Some(path.clone());
+            }
+        }
+        Ok((codebook, reader.offset.div_ceil(8)))
+    }
+}
+
+/// Serialize the frequencies the codebook is built from.
+pub fn serialize_frequencies(frequency: &BTreeMap<char, u64>) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_varint(frequency.len() as u32, &mut out);
+    for (chr, &occ) in frequency {
+        out.extend(chr.to_string().bytes());
+        write_varint(occ, &mut out);
+    }
+    out
+}
+
+/// Deserialize frequencies from the start of `bytes`. Returns them and the
+/// number of bytes they occupied.
+pub fn deserialize_frequencies(bytes: &[u8]) -> Result<(BTreeMap<char, u64>, usize), HeaderError> {
+    let mut reader = BitReader { bytes, offset: 0 };
+    let symbols = reader.read_varint()?;
+    let mut frequency = BTreeMap::new();
+    for _ in 0..symbols {
+        let chr = reader.read_char()?;
+        let bit_offset = reader.offset;
+        let occ = reader.read_varint64()?;
+        if occ == 0 {
+            return Err(HeaderError::InvalidCount { bit_offset });
+        }
+        if frequency.insert(chr, occ).is_some() {
+            return Err(HeaderError::DuplicateSymbol(chr));
+        }
+    }
+    Ok((frequency, reader.offset / 8))
+}
+
+/// Serialize the code lengths of a canonical codebook.
+pub fn serialize_lengths(codebook: &Codebook) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_runs(codebook.keys().copied(), &mut out);
+    for code in codebook.values() {
+        write_varint(code.len() as u32, &mut out);
+    }
+    out
+}
+
+/// Deserialize code lengths from the start of `bytes` into their canonical
+/// codebook. Returns it and the number of bytes the lengths occupied.
+pub fn deserialize_lengths(bytes: &[u8]) -> Result<(Codebook, usize), HeaderError> {
+    let mut reader = BitReader { bytes, offset: 0 };
+    let mut lengths = BTreeMap::new();
+    for chr in reader.read_runs()? {
+        let bit_offset = reader.offset;
+        let len = reader.read_varint()? as usize;
+        if len == 0 {
+            return Err(HeaderError::InvalidLengths);
+        }
+        if len > MAX_CODE_LEN {
+            return Err(HeaderError::CodeTooLong { bit_offset });
+        }
+        if lengths.insert(chr, len).is_some() {
+            return Err(HeaderError::DuplicateSymbol(chr));
+        }
+    }
+    let codebook = canonical_codes(&lengths);
+    validate_codebook(&codebook).map_err(|_| HeaderError::InvalidLengths)?;
+    Ok((codebook, reader.offset / 8))
+}
+
+/// Serialize sorted distinct symbols like the symbols of serialize_lengths.
+pub fn serialize_alphabet(alphabet: &[char]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_runs(alphabet.iter().copied(), &mut out);
+    out
+}
+
+/// Deserialize symbols from the start of `bytes`. Returns them sorted and the
+/// number of bytes they occupied.
+pub fn deserialize_alphabet(bytes: &[u8]) -> Result<(Vec<char>, usize), HeaderError> {
+    let mut reader = BitReader { bytes, offset: 0 };
+    let mut alphabet = BTreeSet::new();
+    for chr in reader.read_runs()? {
+        if !alphabet.insert(chr) {
+            return Err(HeaderError::DuplicateSymbol(chr));
+        }
+    }
+    Ok((alphabet.into_iter().collect(), reader.offset / 8))
+}
+
+/// Write the sorted symbols as the number of runs of consecutive code points
+/// and the first symbol and the length of each run.
+fn write_runs<I: Iterator<Item = char>>(symbols: I, out: &mut Vec<u8>) {
+    let mut runs: Vec<(char, u32)> = Vec::new();
+    for chr in symbols {
+        match runs.last_mut() {
+            Some((first, len)) if *first as u32 + *len == chr as u32 => *len += 1,
+            _ => runs.push((chr, 1)),
+        }
+    }
+    write_varint(runs.len() as u32, out);
+    for (first, len) in runs {
+        out.extend(first.to_string().bytes());
+        write_varint(len, out);
+    }
+}
+
+fn write_varint<V: Into<u64>>(value: V, out: &mut Vec<u8>) {
+    let mut value = value.into();
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl BitReader<'_> {
+    fn read_bit(&mut self) -> Result<bool, HeaderError> {
+        let byte = self
+            .bytes
+            .get(self.offset / 8)
+            .ok_or(HeaderError::Truncated)?;
+        let bit = byte & (0x80 >> (self.offset % 8)) != 0;
+        self.offset += 1;
+        Ok(bit)
+    }
+    fn read_byte(&mut self) -> Result<u8, HeaderError> {
+        (0..8).try_fold(0, |byte, _| Ok(byte << 1 | self.read_bit()? as u8))
+    }
+    fn read_varint(&mut self) -> Result<u32, HeaderError> {
+        let bit_offset = self.offset;
+        let value = self.read_varint64()?;
+        u32::try_from(value).map_err(|_| HeaderError::InvalidCount { bit_offset })
+    }
+    fn read_varint64(&mut self) -> Result<u64, HeaderError> {
+        let bit_offset = self.offset;
+        let mut value = 0u64;
+        for shift in (0..70).step_by(7) {
+            let byte = self.read_byte()?;
+            let bits = u64::from(byte & 0x7f);
+            if bits.checked_shl(shift).map(|v| v >> shift) != Some(bits) {
+                return Err(HeaderError::InvalidCount { bit_offset });
+            }
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(HeaderError::InvalidCount { bit_offset })
+    }
+    /// Read the runs of write_runs and return their symbols.
+    fn read_runs(&mut self) -> Result<Vec<char>, HeaderError> {
+        let mut runs = Vec::new();
+        let mut total = 0;
+        for _ in 0..self.read_varint()? {
+            let bit_offset = self.offset;
+            let first = self.read_char()?;
+            let len_offset = self.offset;
+            let len = self.read_varint()?;
+            // The symbols are distinct, so longer runs can only be garbage
+            // that would claim a lot of memory.
+            total += len as usize;
+            if total > MAX_SYMBOLS {
+                return Err(HeaderError::InvalidCount {
+                    bit_offset: len_offset,
+                });
+            }
+            runs.push((first, len, bit_offset));
+        }
+        let mut symbols = Vec::with_capacity(total);
+        for (first, len, bit_offset) in runs {
+            for i in 0..len {
+                let chr = (first as u32)
+                    .checked_add(i)
+                    .and_then(core::char::from_u32)
+                    .ok_or(HeaderError::InvalidSymbol { bit_offset })?;
+                symbols.push(chr);
+            }
+        }
+        Ok(symbols)
+    }
+    fn read_char(&mut self) -> Result<char, HeaderError> {
+        let bit_offset = self.offset;
+        let first = self.read_byte()?;
+        let len = match first.leading_ones() {
+            0 => 1,
+            len @ 2..=4 => len as usize,
+            _ => return Err(HeaderError::InvalidSymbol { bit_offset }),
+        };
+        let mut utf8 = vec![first];
+        for _ in 1..len {
+            utf8.push(self.read_byte()?);
+        }
+        core::str::from_utf8(&utf8)
+            .ok()
+            .and_then(|s| s.chars().next())
+            .ok_or(HeaderError::InvalidSymbol { bit_offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{canonical_codebook, codebook, encode, Encoded};
+    use crate::tree::{frequency, huffman};
+
+    fn bits(s: &str) -> Bits {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    #[test]
+    fn header_round_trip() {
+        // given:
+        let codebook = encode("Grüße aus München für 5 € 🦀").codebook;
+
+        // when:
+        let serialized = codebook.serialize();
+        let actual = Codebook::deserialize(&serialized);
+
+        // then:
+        assert_eq!(actual, Ok((codebook, serialized.len())));
+    }
+
+    #[test]
+    fn header_round_trip_non_canonical() {
+        // given:
+        let codebook = codebook(&huffman(frequency(&mut "BACADAEAFABBAAAGAH".chars())).unwrap());
+
+        // when:
+        let actual = Codebook::deserialize(&codebook.serialize()).unwrap().0;
+
+        // then:
+        assert_eq!(actual, codebook);
+    }
+
+    #[test]
+    fn header_round_trip_incomplete() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', bits("0"));
+        codebook.insert('ä', bits("101"));
+
+        // when:
+        let actual = Codebook::deserialize(&codebook.serialize()).unwrap().0;
+
+        // then:
+        assert_eq!(actual, codebook);
+    }
+
+    #[test]
+    fn header_round_trip_empty() {
+        // when:
+        let actual = Codebook::deserialize(&Codebook::new().serialize());
+
+        // then:
+        assert_eq!(actual, Ok((Codebook::new(), 1)));
+    }
+
+    #[test]
+    fn header_is_compact() {
+        // given:
+        let mut codebook = Codebook::new();
+        codebook.insert('a', bits("0"));
+        codebook.insert('b', bits("1"));
+
+        // when:
+        let actual = codebook.serialize();
+
+        // then: 0 10 01100001 10 01100010
+        assert_eq!(actual, [0b0100_1100, 0b0011_0011, 0b0001_0000]);
+    }
+
+    #[test]
+    fn header_deserialize_reports_consumed_bytes() {
+        // given:
+        let Encoded { codebook, bits, .. } = encode("abracadabra");
+        let mut data = codebook.serialize();
+        let header_len = data.len();
+        data.extend(bits.as_bytes());
+
+        // when:
+        let actual = Codebook::deserialize(&data);
+
+        // then:
+        assert_eq!(actual, Ok((codebook, header_len)));
+    }
+
+    #[test]
+    fn header_truncated() {
+        // given:
+        let codebook = encode("Grüße").codebook;
+        let serialized = codebook.serialize();
+
+        for len in 0..serialized.len() {
+            // when:
+            let actual = Codebook::deserialize(&serialized[..len]);
+
+            // then:
+            assert_eq!(actual, Err(HeaderError::Truncated));
+        }
+    }
+
+    #[test]
+    fn header_invalid_symbol() {
+        // when: 10 11111111
+        let actual = Codebook::deserialize(&[0b1011_1111, 0b1100_0000]);
+
+        // then:
+        assert_eq!(actual, Err(HeaderError::InvalidSymbol { bit_offset: 2 }));
+    }
+
+    #[test]
+    fn header_duplicate_symbol() {
+        // when: 0 10 01100001 10 01100001
+        let actual = Codebook::deserialize(&[0b0100_1100, 0b0011_0011, 0b0000_1000]);
+
+        // then:
+        assert_eq!(actual, Err(HeaderError::DuplicateSymbol('a')));
+    }
+
+    #[test]
+    fn header_empty_node() {
+        // when: 0 0 11 11 10 01100001
+        let actual = Codebook::deserialize(&[0b0011_1110, 0b0110_0001]);
+
+        // then:
+        assert_eq!(actual, Err(HeaderError::EmptyNode { bit_offset: 4 }));
+    }
+
+    #[test]
+    fn header_code_too_long() {
+        // when:
+        let actual = Codebook::deserialize(&[0; 64]);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HeaderError::CodeTooLong {
+                bit_offset: MAX_CODE_LEN + 1
+            })
+        );
+    }
+
+    #[test]
+    fn frequencies_round_trip() {
+        // given:
+        let freq = frequency(&mut "Grüße aus München für 5 € 🦀".chars());
+        let mut freq_with_large_counts = freq.clone();
+        freq_with_large_counts.insert('x', 300);
+        freq_with_large_counts.insert('y', u64::MAX);
+
+        for freq in [freq, freq_with_large_counts, BTreeMap::new()].iter() {
+            // when:
+            let serialized = serialize_frequencies(freq);
+            let actual = deserialize_frequencies(&serialized);
+
+            // then:
+            assert_eq!(actual, Ok((freq.clone(), serialized.len())));
+        }
+    }
+
+    #[test]
+    fn frequencies_layout() {
+        // given:
+        let freq = frequency(&mut "ab".chars())
+            .into_iter()
+            .chain(Some(('c', 200)))
+            .collect();
+
+        // when:
+        let actual = serialize_frequencies(&freq);
+
+        // then:
+        assert_eq!(actual, [3, b'a', 1, b'b', 1, b'c', 0xc8, 0x01]);
+    }
+
+    #[test]
+    fn frequencies_truncated() {
+        // given:
+        let serialized = serialize_frequencies(&frequency(&mut "Grüße".chars()));
+
+        for len in 0..serialized.len() {
+            // when:
+            let actual = deserialize_frequencies(&serialized[..len]);
+
+            // then:
+            assert_eq!(actual, Err(HeaderError::Truncated));
+        }
+    }
+
+    #[test]
+    fn frequencies_invalid_count() {
+        // given: a count of 0 and one of 2^64
+        let too_large = [
+            1, b'a', 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02,
+        ];
+
+        for bytes in [&[1, b'a', 0][..], &too_large].iter() {
+            // when:
+            let actual = deserialize_frequencies(bytes);
+
+            // then:
+            assert_eq!(actual, Err(HeaderError::InvalidCount { bit_offset: 16 }));
+        }
+    }
+
+    #[test]
+    fn frequencies_duplicate_symbol() {
+        // when:
+        let actual = deserialize_frequencies(&[2, b'a', 1, b'a', 2]);
+
+        // then:
+        assert_eq!(actual, Err(HeaderError::DuplicateSymbol('a')));
+    }
+
+    #[test]
+    fn lengths_round_trip() {
+        for message in [
+            "a",
+            "abbccc",
+            "Grüße aus München für 5 € 🦀",
+            "BACADAEAFABBAAAGAH",
+        ]
+        .iter()
+        {
+            // given:
+            let codebook = canonical_codebook(&huffman(frequency(&mut message.chars())).unwrap());
+            let mut serialized = serialize_lengths(&codebook);
+            serialized.push(0xff);
+
+            // when:
+            let actual = deserialize_lengths(&serialized);
+
+            // then:
+            assert_eq!(actual, Ok((codebook, serialized.len() - 1)));
+        }
+    }
+
+    #[test]
+    fn lengths_layout() {
+        // given:
+        let codebook = canonical_codebook(&huffman(frequency(&mut "abbccc".chars())).unwrap());
+
+        // when:
+        let actual = serialize_lengths(&codebook);
+
+        // then: one run of 3 symbols from a, then their lengths.
+        assert_eq!(actual, [1, b'a', 3, 2, 2, 1]);
+    }
+
+    #[test]
+    fn lengths_runs() {
+        // given:
+        let codebook = canonical_codebook(&huffman(frequency(&mut "abdd".chars())).unwrap());
+
+        // when:
+        let actual = serialize_lengths(&codebook);
+
+        // then:
+        assert_eq!(actual, [2, b'a', 2, b'd', 1, 2, 2, 1]);
+    }
+
+    #[test]
+    fn lengths_truncated() {
+        // given:
+        let codebook = canonical_codebook(&huffman(frequency(&mut "Grüße".chars())).unwrap());
+        let serialized = serialize_lengths(&codebook);
+
+        for len in 0..serialized.len() {
+            // when:
+            let actual = deserialize_lengths(&serialized[..len]);
+
+            // then:
+            assert_eq!(actual, Err(HeaderError::Truncated));
+        }
+    }
+
+    #[test]
+    fn lengths_invalid() {
+        for (bytes, expected) in [
+            (&[1, b'a', 2, 1, 0][..], HeaderError::InvalidLengths),
+            (&[1, b'a', 3, 1, 1, 1][..], HeaderError::InvalidLengths),
+            (
+                &[2, b'a', 1, b'a', 1, 1, 1][..],
+                HeaderError::DuplicateSymbol('a'),
+            ),
+            (
+                &[1, b'a', 1, 0xac, 0x02][..],
+                HeaderError::CodeTooLong { bit_offset: 24 },
+            ),
+            (
+                &[1, 0xed, 0x9f, 0xbf, 2, 1, 1][..],
+                HeaderError::InvalidSymbol { bit_offset: 8 },
+            ),
+        ] {
+            // when:
+            let actual = deserialize_lengths(bytes);
+
+            // then:
+            assert_eq!(actual, Err(expected), "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn alphabet_round_trip() {
+        // given:
+        let alphabet = "abcdxyzäöü€🦀".chars().collect::<BTreeSet<_>>();
+        let alphabet = alphabet.into_iter().collect::<Vec<_>>();
+
+        // when:
+        let serialized = serialize_alphabet(&alphabet);
+        let actual = deserialize_alphabet(&serialized);
+
+        // then:
+        assert_eq!(actual, Ok((alphabet, serialized.len())));
+        assert_eq!(serialized[..5], [7, b'a', 4, b'x', 3]);
+    }
+
+    #[test]
+    fn alphabet_invalid() {
+        for (bytes, expected) in [
+            (
+                &[2, b'a', 2, b'b', 1][..],
+                HeaderError::DuplicateSymbol('b'),
+            ),
+            // A run of more symbols than there are chars
+            (
+                &[1, 1, 0xff, 0xff, 0x43][..],
+                HeaderError::InvalidCount { bit_offset: 16 },
+            ),
+            (&[1, b'a'][..], HeaderError::Truncated),
+        ] {
+            // when:
+            let actual = deserialize_alphabet(bytes);
+
+            // then:
+            assert_eq!(actual, Err(expected), "{:?}", bytes);
+        }
+    }
+}
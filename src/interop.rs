@@ -0,0 +1,173 @@
+//! Decoding canonical Huffman data written by other tools.
+//!
+//! Many implementations only store the code length of each symbol and assign
+//! the canonical codes from them: the symbols are sorted by code length and
+//! then by code point, the first gets the code of all 0 bits and every
+//! further code is the previous one plus 1, shifted left to its length.
+
+use crate::bits::{BitOrder, Bits};
+use crate::code::{decode_with_tree, CodebookError};
+use crate::error::HuffmanError;
+use crate::tree::HuffTree;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::convert::TryFrom;
+
+/// Decode the first bit_len bits of packed, which are packed most significant
+/// bit first, with the canonical codes of the (symbol, code length) pairs.
+///
+/// The lengths must describe a complete code like the ones of a Huffman
+/// tree, a single symbol may have the length 0 or 1. Invalid lengths are an
+/// InvalidCodebook error, fewer than bit_len bits in packed a TruncatedInput
+/// error at the end of packed.
+pub fn import_canonical(
+    lengths: &[(char, u8)],
+    packed: &[u8],
+    bit_len: u64,
+) -> Result<String, HuffmanError> {
+    import_canonical_with(lengths, packed, bit_len, BitOrder::MsbFirst)
+}
+
+/// Like import_canonical for bits packed in the bit order.
+pub fn import_canonical_with(
+    lengths: &[(char, u8)],
+    packed: &[u8],
+    bit_len: u64,
+    bit_order: BitOrder,
+) -> Result<String, HuffmanError> {
+    let mut by_symbol = BTreeMap::new();
+    for &(chr, len) in lengths {
+        if by_symbol.insert(chr, len).is_some() {
+            return Err(HuffmanError::InvalidCodebook(
+                CodebookError::DuplicateSymbol(chr),
+            ));
+        }
+    }
+    let tree = HuffTree::from_code_lengths(&by_symbol).map_err(HuffmanError::InvalidCodebook)?;
+    let available = packed.len().saturating_mul(8);
+    let bits = usize::try_from(bit_len)
+        .ok()
+        .and_then(|len| Bits::from_bytes_with(len, packed, bit_order))
+        .ok_or(HuffmanError::TruncatedInput {
+            bit_offset: available,
+        })?;
+    decode_with_tree(&tree, &bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{codebook_from_lengths, encode_with};
+    use alloc::vec::Vec;
+
+    /// The code lengths of the textbook example BACADAEAFABBAAAGAH.
+    const LENGTHS: [(char, u8); 8] = [
+        ('H', 4),
+        ('G', 4),
+        ('F', 4),
+        ('E', 4),
+        ('D', 4),
+        ('C', 4),
+        ('B', 3),
+        ('A', 1),
+    ];
+
+    #[test]
+    fn import_canonical_known_answer() {
+        // given: the codes A 0, B 100, C 1010 to H 1111 packed MSB-first
+        let packed = [0x8a, 0x5b, 0x1a, 0x90, 0x73, 0xc0];
+
+        // when:
+        let actual = import_canonical(&LENGTHS, &packed, 42);
+
+        // then:
+        assert_eq!(actual.unwrap(), "BACADAEAFABBAAAGAH");
+    }
+
+    #[test]
+    fn import_canonical_lsb_first() {
+        // given: the same bits packed LSB-first
+        let packed = [0x51, 0xda, 0x58, 0x09, 0xce, 0x03];
+
+        // when:
+        let actual = import_canonical_with(&LENGTHS, &packed, 42, BitOrder::LsbFirst);
+
+        // then:
+        assert_eq!(actual.unwrap(), "BACADAEAFABBAAAGAH");
+    }
+
+    #[test]
+    fn import_canonical_matches_codebook_from_lengths() {
+        // given:
+        let message = "the quick brown fox jumps over the lazy dog";
+        let lengths = LENGTHS
+            .iter()
+            .map(|&(chr, len)| (chr.to_ascii_lowercase(), len));
+        let lengths = lengths.collect::<Vec<_>>();
+        let codebook = codebook_from_lengths(&lengths.iter().copied().collect());
+        let text = message
+            .chars()
+            .filter(|chr| codebook.contains_key(chr))
+            .collect::<String>();
+        let bits = encode_with(&codebook, &text).unwrap();
+
+        // when:
+        let actual = import_canonical(&lengths, bits.as_bytes(), bits.len() as u64);
+
+        // then:
+        assert_eq!(actual.unwrap(), text);
+    }
+
+    #[test]
+    fn import_canonical_ignores_padding() {
+        // given: the last byte has trailing 1 bits beyond bit_len
+        let packed = [0x8a, 0x5b, 0x1a, 0x90, 0x73, 0xff];
+
+        // when:
+        let actual = import_canonical(&LENGTHS, &packed, 42);
+
+        // then:
+        assert_eq!(actual.unwrap(), "BACADAEAFABBAAAGAH");
+    }
+
+    #[test]
+    fn import_canonical_invalid_input() {
+        // given: the first 16 bits of the example, B A C A D A and part of E
+        let packed = [0x8a, 0x5b];
+        assert!(matches!(
+            import_canonical(&LENGTHS, &packed, 17),
+            Err(HuffmanError::TruncatedInput { bit_offset: 16 })
+        ));
+        assert!(matches!(
+            import_canonical(&LENGTHS, &packed, 10),
+            Err(HuffmanError::TruncatedInput { bit_offset: 9 })
+        ));
+        assert!(matches!(
+            import_canonical(&[('a', 1), ('b', 2)], &packed, 1),
+            Err(HuffmanError::InvalidCodebook(
+                CodebookError::UnderSubscribed
+            ))
+        ));
+        assert!(matches!(
+            import_canonical(&[('a', 1), ('b', 1), ('a', 1)], &packed, 1),
+            Err(HuffmanError::InvalidCodebook(
+                CodebookError::DuplicateSymbol('a')
+            ))
+        ));
+        assert!(matches!(
+            import_canonical(&[], &packed, 0),
+            Err(HuffmanError::InvalidCodebook(CodebookError::Empty))
+        ));
+    }
+
+    #[test]
+    fn import_canonical_single_symbol() {
+        for len in [0, 1] {
+            // when:
+            let actual = import_canonical(&[('x', len)], &[0], 3);
+
+            // then:
+            assert_eq!(actual.unwrap(), "xxx");
+        }
+    }
+}
@@ -0,0 +1,1497 @@
+//! Packing encoded bits into bytes, configurable byte level encoders and
+//! std::io adapters.
+
+pub use crate::bits::BitOrder;
+use crate::bits::Bits;
+use crate::code::{decode_len, validate_codebook, Codebook, EncodeError};
+use crate::codec::ESCAPE;
+use crate::container::{
+    decompress, decompress_with_codebook, read_header, ContainerError, Layout, EXTERNAL_CODEBOOK,
+    MAGIC,
+};
+use crate::crc::{crc32, crc32_update};
+use crate::error::HuffmanError;
+use crate::fast::FastDecoder;
+use crate::header::{CodebookHeader, HeaderError};
+use crate::limited::{huffman_bounded, LengthLimitError};
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, Read, Write};
+
+/// Pack bits into bytes. Returns the bytes together with the number of valid
+/// bits in the last byte.
+pub fn pack_bits(bits: &Bits, bit_order: BitOrder) -> (Vec<u8>, u8) {
+    let bytes = bits.to_bytes_with(bit_order);
+    let valid_bits_in_last = match bits.len() % 8 {
+        0 if bits.is_empty() => 0,
+        0 => 8,
+        rest => rest as u8,
+    };
+    (bytes, valid_bits_in_last)
+}
+
+/// Inverse of pack_bits: the padding after the valid bits of the last byte is
+/// dropped.
+pub fn unpack_bits(bytes: &[u8], valid_bits_in_last: u8, bit_order: BitOrder) -> Bits {
+    let len = match bytes.len() {
+        0 => 0,
+        n => (n - 1) * 8 + usize::from(valid_bits_in_last.min(8)),
+    };
+    Bits::from_bytes_with(len, bytes, bit_order).expect("the length does not exceed the bytes")
+}
+
+/// Decode the first bit_len bits of the packed bytes, so the padding of the
+/// last byte is never decoded as symbols. A bit_len beyond the bytes or in the
+/// middle of a code is reported as TruncatedInput.
+pub fn decode_packed(
+    codebook: &Codebook,
+    bytes: &[u8],
+    bit_len: usize,
+) -> Result<String, HuffmanError> {
+    let mut bits = Bits::from_bytes(8 * bytes.len(), bytes).expect("all bits of the bytes");
+    if bit_len > bits.len() {
+        return Err(HuffmanError::TruncatedInput {
+            bit_offset: bits.len(),
+        });
+    }
+    bits.truncate(bit_len);
+    decode_len(codebook, &bits, bit_len)
+}
+
+/// Decode the bits into the writer as UTF-8, buffering only a few KiB of output
+/// at a time. Returns the number of decoded symbols. On error everything
+/// decoded before the failing code has been written; the bit_offset of the
+/// error is where that code starts.
+pub fn decode_to_writer<W: Write>(
+    codebook: &Codebook,
+    bits: &Bits,
+    w: &mut W,
+) -> Result<usize, HuffmanError> {
+    const BUFFER_LEN: usize = 4096;
+    validate_codebook(codebook).map_err(HuffmanError::InvalidCodebook)?;
+    let decoder = FastDecoder::new(codebook);
+    let mut buffer = String::with_capacity(BUFFER_LEN + 4);
+    let mut count = 0;
+    let mut bit_offset = 0;
+    while bit_offset < bits.len() {
+        let (&chr, code_len) = match decoder.decode_symbol(bits, bit_offset, bits.len()) {
+            Ok(symbol) => symbol,
+            Err(err) => {
+                w.write_all(buffer.as_bytes())?;
+                return Err(err);
+            }
+        };
+        buffer.push(chr);
+        count += 1;
+        bit_offset += code_len;
+        if buffer.len() >= BUFFER_LEN {
+            w.write_all(buffer.as_bytes())?;
+            buffer.clear();
+        }
+    }
+    w.write_all(buffer.as_bytes())?;
+    Ok(count)
+}
+
+/// Decode byte packed bits, recovering from errors by skipping to the next byte
+/// boundary. Returns the recovered text and the byte offsets where decoding was
+/// resumed after an error.
+pub fn decode_resync_bytes(
+    codebook: &Codebook,
+    bytes: &[u8],
+    valid_bits_in_last: u8,
+) -> (String, Vec<usize>) {
+    let bits = unpack_bits(bytes, valid_bits_in_last, BitOrder::MsbFirst);
+    let decoder = FastDecoder::new(codebook);
+    let mut decoded = String::new();
+    let mut resyncs = Vec::new();
+    let mut bit_offset = 0;
+    while bit_offset < bits.len() {
+        match decoder.decode_symbol(&bits, bit_offset, bits.len()) {
+            Ok((&chr, code_len)) => {
+                decoded.push(chr);
+                bit_offset += code_len;
+            }
+            Err(_) => {
+                let next_byte = bit_offset / 8 + 1;
+                bit_offset = next_byte * 8;
+                if bit_offset < bits.len() {
+                    resyncs.push(next_byte);
+                }
+            }
+        }
+    }
+    (decoded, resyncs)
+}
+
+/// Reserved symbol that terminates a message encoded with an EOF code. It is a
+/// Unicode noncharacter, so it never appears in interchanged text.
+pub const EOF_SYMBOL: char = '\u{ffff}';
+
+/// Configures an Encoder, which packs the Huffman code of a message into bytes.
+#[derive(Debug, Clone, Default)]
+pub struct EncoderBuilder {
+    length_limit: Option<u8>,
+    min_length: u8,
+    bit_order: BitOrder,
+    with_eof: bool,
+    model: BTreeMap<char, u64>,
+}
+
+impl EncoderBuilder {
+    pub fn new() -> EncoderBuilder {
+        EncoderBuilder::default()
+    }
+    /// No code will be longer than max_len bits. The codes are the optimal
+    /// ones within the limits, computed with package-merge.
+    pub fn length_limit(mut self, max_len: u8) -> EncoderBuilder {
+        self.length_limit = Some(max_len);
+        self
+    }
+    /// No code will be shorter than min_len bits.
+    pub fn min_length(mut self, min_len: u8) -> EncoderBuilder {
+        self.min_length = min_len;
+        self
+    }
+    pub fn bit_order(mut self, bit_order: BitOrder) -> EncoderBuilder {
+        self.bit_order = bit_order;
+        self
+    }
+    /// Terminate each message with the code of EOF_SYMBOL instead of storing
+    /// the number of valid bits in a trailing byte.
+    pub fn with_eof(mut self, with_eof: bool) -> EncoderBuilder {
+        self.with_eof = with_eof;
+        self
+    }
+    /// The symbol frequencies the codes are built from.
+    pub fn model(mut self, model: &BTreeMap<char, u64>) -> EncoderBuilder {
+        self.model = model.clone();
+        self
+    }
+    pub fn build(self) -> Result<Encoder, EncodeError> {
+        Ok(Encoder {
+            codebook: model_codebook(
+                self.model,
+                self.with_eof,
+                self.min_length,
+                self.length_limit,
+            )?,
+            bit_order: self.bit_order,
+            with_eof: self.with_eof,
+        })
+    }
+}
+
+/// Build the canonical codebook for a model as configured in an EncoderBuilder.
+fn model_codebook(
+    mut model: BTreeMap<char, u64>,
+    with_eof: bool,
+    min_length: u8,
+    length_limit: Option<u8>,
+) -> Result<Codebook, EncodeError> {
+    if with_eof {
+        model.entry(EOF_SYMBOL).or_insert(1);
+    }
+    let length_limit = length_limit.unwrap_or(u8::MAX);
+    huffman_bounded(model, min_length, length_limit).map_err(|err| match err {
+        LengthLimitError::Empty => EncodeError::EmptyModel,
+        LengthLimitError::AlphabetTooLarge { symbols, max_len } => {
+            EncodeError::LengthLimitTooSmall {
+                symbols,
+                length_limit: max_len,
+            }
+        }
+        LengthLimitError::MinLenAboveMaxLen { min_len, max_len } => {
+            EncodeError::MinLengthAboveLimit {
+                min_length: min_len,
+                length_limit: max_len,
+            }
+        }
+    })
+}
+
+/// Encodes messages into bytes as configured by an EncoderBuilder.
+#[derive(Debug)]
+pub struct Encoder {
+    codebook: Codebook,
+    bit_order: BitOrder,
+    with_eof: bool,
+}
+
+impl Encoder {
+    pub fn codebook(&self) -> &Codebook {
+        &self.codebook
+    }
+    /// Encode the message into packed bytes. Without EOF code the last byte
+    /// holds the number of valid bits in the byte before it.
+    pub fn encode(&self, message: &str) -> Result<Vec<u8>, EncodeError> {
+        let mut bits = Bits::new();
+        for chr in message.chars() {
+            if self.with_eof && chr == EOF_SYMBOL {
+                return Err(EncodeError::ReservedSymbol(chr));
+            }
+            let code = self
+                .codebook
+                .get(&chr)
+                .ok_or(EncodeError::UnknownSymbol(chr))?;
+            bits.extend_from_code(code);
+        }
+        if self.with_eof {
+            bits.extend_from_code(&self.codebook[&EOF_SYMBOL]);
+        }
+        let (mut bytes, valid_bits_in_last) = pack_bits(&bits, self.bit_order);
+        if !self.with_eof {
+            bytes.push(valid_bits_in_last);
+        }
+        Ok(bytes)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecoderBuildError {
+    /// Neither a model nor a non-empty codebook was configured.
+    EmptyCodebook,
+    /// EOF mode was configured, but the codebook has no code for EOF_SYMBOL.
+    MissingEofSymbol,
+    /// The codebook could not be built from the model.
+    Model(EncodeError),
+}
+
+impl Display for DecoderBuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecoderBuildError::EmptyCodebook => write!(f, "the codebook is empty"),
+            DecoderBuildError::MissingEofSymbol => {
+                write!(f, "EOF mode requires a code for the EOF symbol")
+            }
+            DecoderBuildError::Model(err) => write!(f, "invalid model: {}", err),
+        }
+    }
+}
+
+/// Where a Decoder gets its codes from.
+#[derive(Debug, Clone)]
+enum CodeSource {
+    Model(BTreeMap<char, u64>),
+    Codebook(Codebook),
+}
+
+/// Configures a Decoder for the output of an Encoder. Every option has to match
+/// the EncoderBuilder configuration, otherwise decoding produces garbage.
+#[derive(Debug, Clone, Default)]
+pub struct DecoderBuilder {
+    length_limit: Option<u8>,
+    min_length: u8,
+    bit_order: BitOrder,
+    with_eof: bool,
+    source: Option<CodeSource>,
+}
+
+impl DecoderBuilder {
+    pub fn new() -> DecoderBuilder {
+        DecoderBuilder::default()
+    }
+    /// Only relevant when the codes are built from a model.
+    pub fn length_limit(mut self, max_len: u8) -> DecoderBuilder {
+        self.length_limit = Some(max_len);
+        self
+    }
+    /// Only relevant when the codes are built from a model.
+    pub fn min_length(mut self, min_len: u8) -> DecoderBuilder {
+        self.min_length = min_len;
+        self
+    }
+    pub fn bit_order(mut self, bit_order: BitOrder) -> DecoderBuilder {
+        self.bit_order = bit_order;
+        self
+    }
+    pub fn with_eof(mut self, with_eof: bool) -> DecoderBuilder {
+        self.with_eof = with_eof;
+        self
+    }
+    /// Build the codes from the same model as the encoder. Replaces a codebook.
+    pub fn model(mut self, model: &BTreeMap<char, u64>) -> DecoderBuilder {
+        self.source = Some(CodeSource::Model(model.clone()));
+        self
+    }
+    /// Use the codebook of the encoder. Replaces a model.
+    pub fn codebook(mut self, codebook: &Codebook) -> DecoderBuilder {
+        self.source = Some(CodeSource::Codebook(codebook.clone()));
+        self
+    }
+    pub fn build(self) -> Result<Decoder, DecoderBuildError> {
+        let codebook = match self.source {
+            Some(CodeSource::Model(model)) => {
+                model_codebook(model, self.with_eof, self.min_length, self.length_limit)
+                    .map_err(DecoderBuildError::Model)?
+            }
+            Some(CodeSource::Codebook(codebook)) => codebook,
+            None => Codebook::new(),
+        };
+        if codebook.is_empty() {
+            return Err(DecoderBuildError::EmptyCodebook);
+        }
+        if self.with_eof && !codebook.contains_key(&EOF_SYMBOL) {
+            return Err(DecoderBuildError::MissingEofSymbol);
+        }
+        Ok(Decoder {
+            decoder: FastDecoder::new(&codebook),
+            codebook,
+            bit_order: self.bit_order,
+            with_eof: self.with_eof,
+        })
+    }
+}
+
+/// Decodes the output of an Encoder as configured by a DecoderBuilder.
+#[derive(Debug)]
+pub struct Decoder {
+    codebook: Codebook,
+    decoder: FastDecoder,
+    bit_order: BitOrder,
+    with_eof: bool,
+}
+
+impl Decoder {
+    pub fn codebook(&self) -> &Codebook {
+        &self.codebook
+    }
+    pub fn decode(&self, bytes: &[u8]) -> Result<String, HuffmanError> {
+        if self.with_eof {
+            let bits = unpack_bits(bytes, 8, self.bit_order);
+            let mut decoded = String::new();
+            let mut bit_offset = 0;
+            loop {
+                if bit_offset == bits.len() {
+                    return Err(HuffmanError::TruncatedInput { bit_offset });
+                }
+                match self.decoder.decode_symbol(&bits, bit_offset, bits.len())? {
+                    (&EOF_SYMBOL, _) => return Ok(decoded),
+                    (&chr, code_len) => {
+                        decoded.push(chr);
+                        bit_offset += code_len;
+                    }
+                }
+            }
+        } else {
+            let (&valid_bits_in_last, bytes) = bytes
+                .split_last()
+                .ok_or(HuffmanError::TruncatedInput { bit_offset: 0 })?;
+            let bits = unpack_bits(bytes, valid_bits_in_last, self.bit_order);
+            self.decoder.decode(&bits)
+        }
+    }
+}
+
+/// Encodes text written to it and writes the packed bytes to the inner writer
+/// in the format of an Encoder without EOF code, so a Decoder or a
+/// HuffmanReader can decode them.
+///
+/// Bytes written through the Write trait must be UTF-8, but a character may
+/// be split across writes. Call finish to write the last byte.
+#[derive(Debug)]
+pub struct HuffmanWriter<W: Write> {
+    codebook: Codebook,
+    bit_order: BitOrder,
+    inner: W,
+    bits: Bits,
+    incomplete_utf8: Vec<u8>,
+    total_bits: usize,
+}
+
+impl<W: Write> HuffmanWriter<W> {
+    pub fn new(codebook: Codebook, inner: W) -> HuffmanWriter<W> {
+        HuffmanWriter {
+            codebook,
+            bit_order: BitOrder::default(),
+            inner,
+            bits: Bits::new(),
+            incomplete_utf8: Vec::new(),
+            total_bits: 0,
+        }
+    }
+
+    /// Pack the bits in the bit order, MsbFirst by default. A HuffmanReader
+    /// has to use the same one.
+    pub fn with_bit_order(mut self, bit_order: BitOrder) -> HuffmanWriter<W> {
+        self.bit_order = bit_order;
+        self
+    }
+
+    /// Encode the text. Nothing is encoded if a character has no code or the
+    /// inner writer fails, so the text can be written again.
+    pub fn write_str(&mut self, text: &str) -> Result<(), HuffmanError> {
+        if let Some(chr) = text.chars().find(|chr| !self.codebook.contains_key(chr)) {
+            return Err(HuffmanError::UnknownSymbol(chr));
+        }
+        let buffered = self.bits.len();
+        for chr in text.chars() {
+            self.bits.extend_from_code(&self.codebook[&chr]);
+        }
+        if let Err(err) = self.write_complete_bytes() {
+            self.bits.truncate(buffered);
+            return Err(err.into());
+        }
+        Ok(())
+    }
+
+    fn write_complete_bytes(&mut self) -> io::Result<()> {
+        let complete = self.bits.len() / 8;
+        if complete > 0 {
+            self.inner
+                .write_all(&self.bits.to_bytes_with(self.bit_order)[..complete])?;
+            self.total_bits += complete * 8;
+            self.bits.remove_leading_bytes(complete);
+        }
+        Ok(())
+    }
+
+    /// Write the last partial byte and the number of its valid bits and
+    /// return the inner writer.
+    pub fn finish(mut self) -> Result<W, HuffmanError> {
+        if !self.incomplete_utf8.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the text ends in the middle of a UTF-8 character",
+            )
+            .into());
+        }
+        self.total_bits += self.bits.len();
+        let valid_bits_in_last = match self.total_bits % 8 {
+            0 if self.total_bits == 0 => 0,
+            0 => 8,
+            rest => rest as u8,
+        };
+        self.inner
+            .write_all(&self.bits.to_bytes_with(self.bit_order))?;
+        self.inner.write_all(&[valid_bits_in_last])?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for HuffmanWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The incomplete character stays buffered if the write fails.
+        let bytes = [&self.incomplete_utf8[..], buf].concat();
+        let (text, rest) = match std::str::from_utf8(&bytes) {
+            Ok(text) => (text, &[][..]),
+            // An error without length is a character cut off at the end.
+            Err(err) if err.error_len().is_none() => {
+                let (valid, rest) = bytes.split_at(err.valid_up_to());
+                (std::str::from_utf8(valid).unwrap(), rest)
+            }
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        self.write_str(text)?;
+        self.incomplete_utf8 = rest.to_vec();
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decodes the bytes of a HuffmanWriter or Encoder without EOF code read from
+/// the inner reader. The decoded text is available through the Read trait as
+/// UTF-8 or through decode_to_string.
+#[derive(Debug)]
+pub struct HuffmanReader<R: Read> {
+    decoder: FastDecoder,
+    bit_order: BitOrder,
+    /// The number of bits if it is known instead of stored after them.
+    bit_len: Option<u64>,
+    inner: R,
+    /// The last two bytes read, which may be the last payload byte and the
+    /// number of its valid bits.
+    held_back: Vec<u8>,
+    bits: Bits,
+    consumed_bits: usize,
+    decoded: Vec<u8>,
+    decoded_offset: usize,
+    at_end: bool,
+}
+
+impl<R: Read> HuffmanReader<R> {
+    pub fn new(codebook: Codebook, inner: R) -> HuffmanReader<R> {
+        HuffmanReader {
+            decoder: FastDecoder::new(&codebook),
+            bit_order: BitOrder::default(),
+            bit_len: None,
+            inner,
+            held_back: Vec::new(),
+            bits: Bits::new(),
+            consumed_bits: 0,
+            decoded: Vec::new(),
+            decoded_offset: 0,
+            at_end: false,
+        }
+    }
+
+    /// Unpack the bits in the bit order, MsbFirst by default.
+    pub fn with_bit_order(mut self, bit_order: BitOrder) -> HuffmanReader<R> {
+        self.bit_order = bit_order;
+        self
+    }
+
+    /// Decode exactly bit_len bits instead of reading the number of valid bits
+    /// of the last byte after it, e.g. for the payload of a container. The
+    /// padding and bytes after them are ignored.
+    pub fn with_bit_len(mut self, bit_len: u64) -> HuffmanReader<R> {
+        self.bit_len = Some(bit_len);
+        self
+    }
+
+    /// The inner reader, e.g. to read what follows the bits.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Decode everything that is left.
+    pub fn decode_to_string(&mut self) -> Result<String, HuffmanError> {
+        let mut decoded = String::new();
+        while self.fill_decoded()? {
+            let text = std::str::from_utf8(&self.decoded[self.decoded_offset..])
+                .expect("decoded text is UTF-8");
+            decoded.push_str(text);
+            self.decoded_offset = self.decoded.len();
+        }
+        Ok(decoded)
+    }
+
+    /// Make sure there is decoded text that was not returned yet. Returns
+    /// false at the end of the message.
+    fn fill_decoded(&mut self) -> Result<bool, HuffmanError> {
+        let mut buf = [0; 4096];
+        while self.decoded_offset == self.decoded.len() {
+            if self.at_end {
+                return Ok(false);
+            }
+            self.decoded.clear();
+            self.decoded_offset = 0;
+            let read = match self.inner.read(&mut buf) {
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            self.held_back.extend_from_slice(&buf[..read]);
+            let bit_order = self.bit_order;
+            let read_bits = (self.consumed_bits + self.bits.len()) as u64;
+            if let Some(bit_len) = self.bit_len {
+                for byte in self.held_back.drain(..) {
+                    self.bits
+                        .extend((0..8).map(|i| byte & bit_order.mask(i) != 0));
+                }
+                let left = bit_len - self.consumed_bits as u64;
+                self.bits
+                    .truncate(usize::try_from(left).unwrap_or(usize::MAX));
+                if read == 0 {
+                    self.at_end = true;
+                    if read_bits < bit_len {
+                        return Err(HuffmanError::TruncatedInput {
+                            bit_offset: read_bits as usize,
+                        });
+                    }
+                }
+            } else if read == 0 {
+                self.at_end = true;
+                let (&valid_bits_in_last, payload) =
+                    self.held_back
+                        .split_last()
+                        .ok_or(HuffmanError::TruncatedInput {
+                            bit_offset: self.consumed_bits + self.bits.len(),
+                        })?;
+                if let Some(&last) = payload.first() {
+                    let valid = usize::from(valid_bits_in_last.min(8));
+                    self.bits
+                        .extend((0..valid).map(|i| last & bit_order.mask(i) != 0));
+                }
+                self.held_back.clear();
+            } else {
+                let complete = self.held_back.len().saturating_sub(2);
+                for byte in self.held_back.drain(..complete) {
+                    self.bits
+                        .extend((0..8).map(|i| byte & bit_order.mask(i) != 0));
+                }
+            }
+            self.decode_bits()?;
+        }
+        Ok(true)
+    }
+
+    /// Decode all complete codes of the bits read so far.
+    fn decode_bits(&mut self) -> Result<(), HuffmanError> {
+        let mut bit_offset = 0;
+        let mut chars = String::new();
+        while bit_offset < self.bits.len() {
+            match self
+                .decoder
+                .decode_symbol(&self.bits, bit_offset, self.bits.len())
+            {
+                Ok((&chr, code_len)) => {
+                    chars.push(chr);
+                    bit_offset += code_len;
+                }
+                Err(HuffmanError::TruncatedInput { .. }) if !self.at_end => break,
+                Err(HuffmanError::TruncatedInput { bit_offset }) => {
+                    return Err(HuffmanError::TruncatedInput {
+                        bit_offset: self.consumed_bits + bit_offset,
+                    })
+                }
+                Err(HuffmanError::InvalidPrefix { bit_offset }) => {
+                    return Err(HuffmanError::InvalidPrefix {
+                        bit_offset: self.consumed_bits + bit_offset,
+                    })
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        self.decoded.extend_from_slice(chars.as_bytes());
+        self.bits = self.bits.iter().skip(bit_offset).collect();
+        self.consumed_bits += bit_offset;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for HuffmanReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.fill_decoded()? {
+            return Ok(0);
+        }
+        let available = &self.decoded[self.decoded_offset..];
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.decoded_offset += len;
+        Ok(len)
+    }
+}
+
+/// Decompresses the output of compress or compress_with_codebook read from
+/// the inner reader. The message is available through the Read trait as
+/// UTF-8. A single payload without transforms, which is what compress writes
+/// by default, is decoded while it is read, so only the header is held in
+/// memory. Other formats are read completely and decompressed in memory.
+///
+/// The checksum of the message is verified when the end is read. Errors of
+/// the container are InvalidData errors.
+pub struct ContainerReader<R: Read> {
+    source: Source<R>,
+}
+
+/// The bytes of a payload, the first of them already read with the header.
+type Payload<R> = io::Take<io::Chain<io::Cursor<Vec<u8>>, R>>;
+
+enum Source<R: Read> {
+    /// The payload of a single block, after which the data has to end.
+    Stream {
+        reader: Box<HuffmanReader<Payload<R>>>,
+        payload_len: u64,
+        checksum: u32,
+        /// The CRC-32 of the message read so far.
+        crc: u32,
+    },
+    /// The message decompressed and verified in memory.
+    Decompressed(io::Cursor<String>),
+}
+
+impl<R: Read> ContainerReader<R> {
+    /// Read the header of the output of compress from the inner reader.
+    pub fn new(inner: R) -> io::Result<ContainerReader<R>> {
+        ContainerReader::with_codebook(inner, None)
+    }
+
+    /// Read the header like new, decompressing the output of
+    /// compress_with_codebook with the codebook.
+    pub fn with_codebook(
+        mut inner: R,
+        codebook: Option<&Codebook>,
+    ) -> io::Result<ContainerReader<R>> {
+        let invalid = |err: ContainerError| io::Error::new(io::ErrorKind::InvalidData, err);
+        let mut data = Vec::new();
+        let mut at_end = false;
+        // Read more until the header and the number of bits are complete.
+        let stream = loop {
+            match stream_header(&data, codebook) {
+                Ok(stream) => break stream,
+                Err(Some(err)) if at_end => return Err(invalid(err)),
+                Err(None) if at_end => break None,
+                Err(_) => {
+                    let mut chunk = vec![0; data.len().max(4096)];
+                    let read = read_full(&mut inner, &mut chunk)?;
+                    at_end = read < chunk.len();
+                    data.extend_from_slice(&chunk[..read]);
+                }
+            }
+        };
+        let source = match stream {
+            Some(StreamHeader {
+                codebook,
+                bit_order,
+                checksum,
+                payload_offset,
+                bit_len,
+            }) => {
+                let payload_len = bit_len.div_ceil(8);
+                let payload = io::Cursor::new(data.split_off(payload_offset))
+                    .chain(inner)
+                    .take(payload_len);
+                let reader = HuffmanReader::new(codebook, payload)
+                    .with_bit_order(bit_order)
+                    .with_bit_len(bit_len);
+                let reader = Box::new(reader);
+                Source::Stream {
+                    reader,
+                    payload_len,
+                    checksum,
+                    crc: 0,
+                }
+            }
+            None => {
+                inner.read_to_end(&mut data)?;
+                let message = match codebook {
+                    Some(codebook) => decompress_with_codebook(&data, codebook),
+                    None => decompress(&data),
+                }
+                .map_err(invalid)?;
+                Source::Decompressed(io::Cursor::new(message))
+            }
+        };
+        Ok(ContainerReader { source })
+    }
+}
+
+impl<R: Read> Read for ContainerReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (reader, payload_len, checksum, crc) = match &mut self.source {
+            Source::Stream {
+                reader,
+                payload_len,
+                checksum,
+                crc,
+            } => (reader, *payload_len, *checksum, crc),
+            Source::Decompressed(message) => return message.read(buf),
+        };
+        let read = reader.read(buf)?;
+        *crc = crc32_update(*crc, &buf[..read]);
+        if read > 0 || buf.is_empty() {
+            return Ok(read);
+        }
+        // The end of the message, after which the data has to end as well.
+        let rest = io::copy(reader.get_mut().get_mut(), &mut io::sink())?;
+        let err = match *crc {
+            _ if rest > 0 => ContainerError::PayloadLength {
+                expected: payload_len,
+                actual: (payload_len + rest) as usize,
+            },
+            actual if actual != checksum => ContainerError::ChecksumMismatch {
+                expected: checksum,
+                actual,
+            },
+            _ => return Ok(0),
+        };
+        Err(io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// What ContainerReader needs to decode a single payload while it is read.
+struct StreamHeader {
+    codebook: Codebook,
+    bit_order: BitOrder,
+    checksum: u32,
+    /// Where the packed bits start in the data.
+    payload_offset: usize,
+    bit_len: u64,
+}
+
+/// Read the header of data that starts with the output of compress or
+/// compress_with_codebook. Ok(None) means that the format cannot be decoded
+/// while it is read, Err(None) that the data ends too early to tell.
+fn stream_header(
+    data: &[u8],
+    codebook: Option<&Codebook>,
+) -> Result<Option<StreamHeader>, Option<ContainerError>> {
+    let truncated = |err| match err {
+        ContainerError::Header(HeaderError::Truncated) | ContainerError::TruncatedLength => None,
+        ContainerError::BadMagic if data.len() < MAGIC.len() => None,
+        err => Some(err),
+    };
+    let bit_len = |data: &[u8]| match data.get(..8) {
+        Some(bit_len) => Ok(u64::from_be_bytes(bit_len.try_into().unwrap())),
+        None => Err(None),
+    };
+    if let (Some(codebook), Some(rest)) = (codebook, data.strip_prefix(&MAGIC[..])) {
+        // The output of compress_with_codebook only stores the checksum of
+        // the codebook, see decompress_with_codebook.
+        match rest.first() {
+            Some(&EXTERNAL_CODEBOOK) if !codebook.contains_key(&ESCAPE) => {
+                let prefix = rest.get(1..9).ok_or(None)?;
+                let expected = u32::from_be_bytes(prefix[4..].try_into().unwrap());
+                let actual = crc32(&codebook.serialize());
+                if actual != expected {
+                    return Err(Some(ContainerError::CodebookMismatch { expected, actual }));
+                }
+                return Ok(Some(StreamHeader {
+                    codebook: codebook.clone(),
+                    bit_order: BitOrder::MsbFirst,
+                    checksum: u32::from_be_bytes(prefix[..4].try_into().unwrap()),
+                    payload_offset: MAGIC.len() + 17,
+                    bit_len: bit_len(&rest[9..])?,
+                }));
+            }
+            Some(&EXTERNAL_CODEBOOK) => return Ok(None),
+            _ => {}
+        }
+    }
+    let header = read_header(data).map_err(truncated)?;
+    if header.layout != Layout::Single || header.rle || header.alphabet.is_some() {
+        return Ok(None);
+    }
+    Ok(Some(StreamHeader {
+        bit_len: bit_len(header.data)?,
+        payload_offset: data.len() - header.data.len() + 8,
+        codebook: header.codebook,
+        bit_order: header.bit_order,
+        checksum: header.checksum,
+    }))
+}
+
+/// Read into buf until it is full or the reader is at its end, retrying
+/// interrupted reads. Returns the number of bytes read.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{codebook, encode, Encoded};
+    use crate::codec::HuffmanCodec;
+    use crate::tree::{frequency, huffman};
+
+    #[test]
+    fn pack_bits_round_trip() {
+        for len in [0usize, 1, 7, 8, 9, 17] {
+            // given:
+            let bits = (0..len).map(|i| i % 3 == 0).collect::<Bits>();
+
+            // when:
+            for bit_order in [BitOrder::MsbFirst, BitOrder::LsbFirst] {
+                let (bytes, valid_bits_in_last) = pack_bits(&bits, bit_order);
+                let actual = unpack_bits(&bytes, valid_bits_in_last, bit_order);
+
+                // then:
+                assert_eq!(bytes.len(), len.div_ceil(8));
+                assert_eq!(actual, bits);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_resync_bytes_recovers_after_corrupt_byte() {
+        // given: a byte aligned codebook that does not cover every byte value
+        let codebook = ('a'..='z')
+            .map(|chr| {
+                (
+                    chr,
+                    (0..8).map(|i| chr as u8 & (1 << (7 - i)) != 0).collect(),
+                )
+            })
+            .collect::<Codebook>();
+        let bits = "hello world"
+            .chars()
+            .filter(|chr| chr.is_ascii_lowercase())
+            .flat_map(|chr| codebook[&chr].iter())
+            .collect::<Bits>();
+        let (mut bytes, valid_bits_in_last) = pack_bits(&bits, BitOrder::MsbFirst);
+        bytes[4] = 0xff;
+
+        // when:
+        let actual = decode_resync_bytes(&codebook, &bytes, valid_bits_in_last);
+
+        // then:
+        assert_eq!(actual, ("hellworld".to_string(), vec![5]));
+    }
+
+    #[test]
+    fn huffman_writer_reader_bit_orders() {
+        // given: 0 10 0 11 0 10 0 11
+        let codebook = [('a', "0"), ('b', "10"), ('c', "11")]
+            .iter()
+            .map(|&(chr, code)| (chr, code.chars().map(|c| c == '1').collect()))
+            .collect::<Codebook>();
+        let message = "abacabac";
+
+        for (bit_order, expected) in [
+            (BitOrder::MsbFirst, [0b0100_1101, 0b0011_0000, 4]),
+            (BitOrder::LsbFirst, [0b1011_0010, 0b0000_1100, 4]),
+        ] {
+            // when:
+            let mut writer =
+                HuffmanWriter::new(codebook.clone(), Vec::new()).with_bit_order(bit_order);
+            writer.write_str(message).unwrap();
+            let actual = writer.finish().unwrap();
+
+            // then:
+            assert_eq!(actual, expected, "{:?}", bit_order);
+            let decoded = HuffmanReader::new(codebook.clone(), &actual[..])
+                .with_bit_order(bit_order)
+                .decode_to_string();
+            assert_eq!(decoded.unwrap(), message);
+        }
+    }
+
+    // Decode the output of an Encoder, mirroring its configuration.
+    fn decode_encoded(encoder: &Encoder, bytes: &[u8]) -> String {
+        let decoder = DecoderBuilder::new()
+            .codebook(encoder.codebook())
+            .bit_order(encoder.bit_order)
+            .with_eof(encoder.with_eof)
+            .build()
+            .unwrap();
+        decoder.decode(bytes).unwrap()
+    }
+
+    #[test]
+    fn encoder_length_limit_lsb_first() {
+        // given:
+        let message = "aaaaaaaaaaaaaaaabbbbbbbbccccddeffgh";
+        let encoder = EncoderBuilder::new()
+            .model(&frequency(&mut message.chars()))
+            .length_limit(3)
+            .bit_order(BitOrder::LsbFirst)
+            .build()
+            .unwrap();
+
+        // when:
+        let actual = encoder.encode(message).unwrap();
+
+        // then:
+        assert!(encoder.codebook().values().all(|code| code.len() <= 3));
+        assert_eq!(decode_encoded(&encoder, &actual), message);
+    }
+
+    #[test]
+    fn encoder_with_eof_and_min_length() {
+        // given:
+        let model = frequency(&mut "aardvarks ate apples around aachen".chars());
+        let encoder = EncoderBuilder::new()
+            .model(&model)
+            .min_length(4)
+            .with_eof(true)
+            .build()
+            .unwrap();
+
+        // when:
+        let actual = encoder.encode("a snake crashed").unwrap();
+
+        // then:
+        assert!(encoder.codebook().contains_key(&EOF_SYMBOL));
+        assert!(encoder.codebook().values().all(|code| code.len() >= 4));
+        assert_eq!(decode_encoded(&encoder, &actual), "a snake crashed");
+    }
+
+    #[test]
+    fn codec_does_not_escape_with_eof_code() {
+        // given:
+        let model = frequency(&mut "abracadabra".chars());
+        let encoder = EncoderBuilder::new()
+            .model(&model)
+            .with_eof(true)
+            .build()
+            .unwrap();
+
+        // when:
+        let codec = HuffmanCodec::from_codebook(encoder.codebook().clone()).unwrap();
+
+        // then:
+        assert!(!codec.options().escape_unknown);
+        assert_eq!(codec.encode("abz"), Err(HuffmanError::UnknownSymbol('z')));
+        let bits = codec.encode("abra\u{ffff}").unwrap();
+        assert_eq!(codec.decode(&bits).as_deref(), Ok("abra\u{ffff}"));
+    }
+
+    #[test]
+    fn encoder_single_symbol_model() {
+        // given:
+        let encoder = EncoderBuilder::new()
+            .model(&frequency(&mut "aaaa".chars()))
+            .build()
+            .unwrap();
+
+        // when:
+        let actual = encoder.encode("aaaa").unwrap();
+
+        // then:
+        assert_eq!(decode_encoded(&encoder, &actual), "aaaa");
+    }
+
+    #[test]
+    fn encoder_unknown_symbol() {
+        // given:
+        let encoder = EncoderBuilder::new()
+            .model(&frequency(&mut "abc".chars()))
+            .build()
+            .unwrap();
+
+        // when:
+        let actual = encoder.encode("abcd");
+
+        // then:
+        assert_eq!(actual, Err(EncodeError::UnknownSymbol('d')));
+    }
+
+    #[test]
+    fn encoder_builder_length_limit_too_small() {
+        // when:
+        let actual = EncoderBuilder::new()
+            .model(&frequency(&mut "abcde".chars()))
+            .length_limit(2)
+            .build();
+
+        // then:
+        assert_eq!(
+            actual.unwrap_err(),
+            EncodeError::LengthLimitTooSmall {
+                symbols: 5,
+                length_limit: 2
+            }
+        );
+    }
+
+    #[test]
+    fn decoder_builder_mirrors_encoder_builder() {
+        // given:
+        let model = frequency(&mut "aardvarks ate apples around aachen".chars());
+        let encoder = EncoderBuilder::new()
+            .model(&model)
+            .length_limit(5)
+            .bit_order(BitOrder::LsbFirst)
+            .with_eof(true)
+            .build()
+            .unwrap();
+        let decoder = DecoderBuilder::new()
+            .model(&model)
+            .length_limit(5)
+            .bit_order(BitOrder::LsbFirst)
+            .with_eof(true)
+            .build()
+            .unwrap();
+        let bytes = encoder.encode("a snake crashed near the dunes").unwrap();
+
+        // when:
+        let actual = decoder.decode(&bytes);
+
+        // then:
+        assert_eq!(decoder.codebook(), encoder.codebook());
+        assert_eq!(actual, Ok("a snake crashed near the dunes".to_string()));
+    }
+
+    #[test]
+    fn decoder_with_mismatched_bit_order() {
+        // given:
+        let message = "aardvarks ate apples around aachen";
+        let model = frequency(&mut message.chars());
+        let encoder = EncoderBuilder::new()
+            .model(&model)
+            .bit_order(BitOrder::MsbFirst)
+            .build()
+            .unwrap();
+        let decoder = DecoderBuilder::new()
+            .model(&model)
+            .bit_order(BitOrder::LsbFirst)
+            .build()
+            .unwrap();
+        let bytes = encoder.encode(message).unwrap();
+
+        // when:
+        let actual = decoder.decode(&bytes);
+
+        // then: the bit order is part of the format and has to match
+        assert_ne!(actual, Ok(message.to_string()));
+    }
+
+    #[test]
+    fn decoder_builder_rejects_eof_mode_without_eof_symbol() {
+        // given:
+        let codebook = codebook(&huffman(frequency(&mut "abc".chars())).unwrap());
+
+        // when:
+        let actual = DecoderBuilder::new()
+            .codebook(&codebook)
+            .with_eof(true)
+            .build();
+
+        // then:
+        assert_eq!(actual.unwrap_err(), DecoderBuildError::MissingEofSymbol);
+    }
+
+    #[test]
+    fn decoder_builder_rejects_missing_codebook() {
+        // when:
+        let actual = DecoderBuilder::new().build();
+
+        // then:
+        assert_eq!(actual.unwrap_err(), DecoderBuildError::EmptyCodebook);
+    }
+
+    /// Reads at most a few bytes at a time to exercise the buffering.
+    struct SlowReader<'a>(&'a [u8]);
+
+    impl Read for SlowReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(self.0.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn writer_reader_round_trip() {
+        // given:
+        let message = "Grüße aus München für 5 €, aardvarks ate apples around aachen";
+        let codebook = encode(message).codebook;
+        let mut writer = HuffmanWriter::new(codebook.clone(), Vec::new());
+
+        // when:
+        for part in message.split_inclusive(' ') {
+            writer.write_all(part.as_bytes()).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        let mut actual = String::new();
+        HuffmanReader::new(codebook.clone(), SlowReader(&bytes))
+            .read_to_string(&mut actual)
+            .unwrap();
+
+        // then:
+        assert_eq!(actual, message);
+        let decoder = DecoderBuilder::new().codebook(&codebook).build().unwrap();
+        assert_eq!(decoder.decode(&bytes), Ok(message.to_string()));
+    }
+
+    #[test]
+    fn writer_accepts_characters_split_across_writes() {
+        // given:
+        let message = "ä€";
+        let codebook = encode(message).codebook;
+        let mut writer = HuffmanWriter::new(codebook.clone(), Vec::new());
+
+        // when:
+        for byte in message.bytes() {
+            writer.write_all(&[byte]).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        // then:
+        let actual = HuffmanReader::new(codebook, &bytes[..]).decode_to_string();
+        assert_eq!(actual.unwrap(), message);
+    }
+
+    #[test]
+    fn writer_keeps_split_character_after_failed_write() {
+        // given: the first byte of 'ä' is buffered
+        let message = "aä";
+        let codebook = encode(message).codebook;
+        let mut writer = HuffmanWriter::new(codebook.clone(), Vec::new());
+        writer.write_all(&message.as_bytes()[..2]).unwrap();
+
+        // when: the rest of 'ä' is followed by an unknown symbol, then by
+        // invalid UTF-8
+        let unknown = writer.write(&[0xa4, b'z']);
+        let invalid = writer.write(&[0xff]);
+        writer.write_all(&message.as_bytes()[2..]).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        // then:
+        assert_eq!(unknown.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(invalid.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let actual = HuffmanReader::new(codebook, &bytes[..]).decode_to_string();
+        assert_eq!(actual.unwrap(), message);
+    }
+
+    /// A writer that fails the first write.
+    struct FailsOnce {
+        failed: bool,
+        written: Vec<u8>,
+    }
+
+    impl Write for FailsOnce {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.failed {
+                self.failed = true;
+                return Err(io::Error::other("failing once"));
+            }
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writer_drops_text_of_failed_write() {
+        // given:
+        let message = "abracadabra";
+        let codebook = encode(message).codebook;
+        let inner = FailsOnce {
+            failed: false,
+            written: Vec::new(),
+        };
+        let mut writer = HuffmanWriter::new(codebook.clone(), inner);
+
+        // when:
+        let failed = writer.write(message.as_bytes());
+        writer.write_all(message.as_bytes()).unwrap();
+        let bytes = writer.finish().unwrap().written;
+
+        // then:
+        assert_eq!(failed.unwrap_err().kind(), io::ErrorKind::Other);
+        let actual = HuffmanReader::new(codebook, &bytes[..]).decode_to_string();
+        assert_eq!(actual.unwrap(), message);
+    }
+
+    #[test]
+    fn writer_unknown_symbol() {
+        // given:
+        let codebook = encode("ab").codebook;
+        let mut writer = HuffmanWriter::new(codebook, Vec::new());
+
+        // when:
+        let actual = writer.write_str("abc");
+
+        // then:
+        assert!(matches!(actual, Err(HuffmanError::UnknownSymbol('c'))));
+    }
+
+    #[test]
+    fn reader_truncated() {
+        // given:
+        let codebook = encode("abbccc").codebook;
+        let mut writer = HuffmanWriter::new(codebook.clone(), Vec::new());
+        writer.write_str("cccbba").unwrap();
+        let mut bytes = writer.finish().unwrap();
+        *bytes.last_mut().unwrap() -= 1;
+
+        // when:
+        let actual = HuffmanReader::new(codebook, &bytes[..]).decode_to_string();
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::TruncatedInput { bit_offset: 7 }));
+    }
+
+    #[test]
+    fn decode_packed_ignores_padding() {
+        // given: 'a' has the code 0, so the zero padding looks like more 'a's
+        let message = "aaabcbc";
+        let Encoded { codebook, bits, .. } = encode(message);
+        assert_eq!(codebook[&'a'].to_string(), "0");
+        assert_ne!(bits.len() % 8, 0);
+        let (bytes, _) = pack_bits(&bits, BitOrder::MsbFirst);
+
+        // when:
+        let actual = decode_packed(&codebook, &bytes, bits.len());
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+        let with_padding = decode_packed(&codebook, &bytes, 8 * bytes.len()).unwrap();
+        assert!(with_padding.starts_with(message) && with_padding.ends_with('a'));
+    }
+
+    #[test]
+    fn decode_packed_length_in_code() {
+        // given:
+        let Encoded { codebook, bits, .. } = encode("aaabcbc");
+        let (bytes, _) = pack_bits(&bits, BitOrder::MsbFirst);
+
+        // when: the last code is 2 bits long
+        let actual = decode_packed(&codebook, &bytes, bits.len() - 1);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: bits.len() - 2
+            })
+        );
+    }
+
+    #[test]
+    fn decode_packed_length_beyond_bytes() {
+        // given:
+        let Encoded { codebook, bits, .. } = encode("aaabcbc");
+        let (bytes, _) = pack_bits(&bits, BitOrder::MsbFirst);
+
+        // when:
+        let actual = decode_packed(&codebook, &bytes, 8 * bytes.len() + 1);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: 8 * bytes.len()
+            })
+        );
+    }
+
+    #[test]
+    fn decode_to_writer_streams_long_output() {
+        // given:
+        let message = "Grüße aus München für 5 € 🦀 ".repeat(500);
+        let Encoded { codebook, bits, .. } = encode(&message);
+        let mut out = Vec::new();
+
+        // when:
+        let actual = decode_to_writer(&codebook, &bits, &mut out);
+
+        // then:
+        assert_eq!(actual, Ok(message.chars().count()));
+        assert_eq!(String::from_utf8(out).unwrap(), message);
+    }
+
+    #[test]
+    fn decode_to_writer_keeps_partial_output() {
+        // given:
+        let Encoded {
+            codebook, mut bits, ..
+        } = encode("aaabcbc");
+        let bit_len = bits.len();
+        bits.push(true);
+        let mut out = Vec::new();
+
+        // when:
+        let actual = decode_to_writer(&codebook, &bits, &mut out);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::TruncatedInput {
+                bit_offset: bit_len
+            })
+        );
+        assert_eq!(out, b"aaabcbc");
+    }
+
+    #[test]
+    fn decode_to_writer_reports_write_errors() {
+        // given:
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let Encoded { codebook, bits, .. } = encode("aaabcbc");
+
+        // when:
+        let actual = decode_to_writer(&codebook, &bits, &mut Broken);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(HuffmanError::Io(io::Error::from(io::ErrorKind::BrokenPipe)))
+        );
+    }
+
+    #[test]
+    fn container_reader_round_trip() {
+        use crate::container::{
+            compress, compress_blocks, compress_with, compress_with_bit_order,
+            compress_with_codebook, HeaderMode,
+        };
+        // given: also a header longer than the first piece read
+        let many_symbols = (0x100..0x1100)
+            .filter_map(char::from_u32)
+            .collect::<String>();
+        for message in ["", "a", "Grüße aus München für 5 €", &many_symbols] {
+            let codebook = encode(message).codebook;
+            let streamed = [
+                compress(message),
+                compress_with(message, HeaderMode::Frequencies),
+                compress_with(message, HeaderMode::Lengths),
+                compress_with_bit_order(message, BitOrder::LsbFirst),
+            ];
+            let in_memory = [compress_blocks(message, HeaderMode::Codebook, 7)];
+            for (data, streams) in streamed
+                .iter()
+                .map(|data| (data, true))
+                .chain(in_memory.iter().map(|data| (data, false)))
+            {
+                // when:
+                let mut reader = ContainerReader::new(SlowReader(data)).unwrap();
+                let mut actual = String::new();
+                reader.read_to_string(&mut actual).unwrap();
+
+                // then:
+                assert_eq!(actual, message);
+                assert_eq!(matches!(reader.source, Source::Stream { .. }), streams);
+            }
+            // when:
+            let data = compress_with_codebook(message, &codebook, false).unwrap();
+            let mut reader =
+                ContainerReader::with_codebook(SlowReader(&data), Some(&codebook)).unwrap();
+            let mut actual = String::new();
+            reader.read_to_string(&mut actual).unwrap();
+
+            // then:
+            assert_eq!(actual, message);
+            assert!(matches!(reader.source, Source::Stream { .. }));
+        }
+    }
+
+    #[test]
+    fn container_reader_reports_corrupt_data() {
+        // given:
+        let data = crate::container::compress("BACADAEAFABBAAAGAH");
+        let mut checksum = data.clone();
+        checksum[5] ^= 1;
+        let mut longer = data.clone();
+        longer.push(0);
+        let shorter = &data[..data.len() - 1];
+
+        for (data, expected) in [
+            (&checksum[..], "checksum mismatch"),
+            (&longer[..], "the payload has 7 bytes, but 6 are required"),
+            (shorter, "in the middle of a code"),
+            (b"HUF", "does not start with"),
+        ] {
+            // when:
+            let actual = ContainerReader::new(data)
+                .and_then(|mut reader| reader.read_to_string(&mut String::new()));
+
+            // then:
+            let err = actual.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert!(err.to_string().contains(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn reader_with_bit_len_ignores_padding() {
+        // given: 'a' has the code 0, so the zero padding looks like more 'a's
+        let Encoded { codebook, bits, .. } = encode("aaabcbc");
+        let (mut bytes, _) = pack_bits(&bits, BitOrder::MsbFirst);
+        bytes.push(0);
+
+        // when:
+        let actual = HuffmanReader::new(codebook, SlowReader(&bytes))
+            .with_bit_len(bits.len() as u64)
+            .decode_to_string();
+
+        // then:
+        assert_eq!(actual, Ok("aaabcbc".to_string()));
+    }
+}
@@ -0,0 +1,224 @@
+//! Lazy decoding of one symbol at a time from any source of bits.
+
+use crate::code::{validate_codebook, Codebook};
+use crate::error::HuffmanError;
+use alloc::{vec, vec::Vec};
+use core::iter::FusedIterator;
+
+/// A node of the prefix tree of the codes. Missing children are bit patterns
+/// no code starts with.
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: [Option<usize>; 2],
+    symbol: Option<char>,
+}
+
+/// Decodes the bits of a source symbol by symbol, see decode_iter.
+#[derive(Debug)]
+pub struct DecodeIter<I> {
+    nodes: Vec<Node>,
+    bits: I,
+    bit_offset: usize,
+    /// The error to return first, if the codebook is invalid.
+    error: Option<HuffmanError>,
+    done: bool,
+}
+
+/// Decode the bits lazily with the codebook: every item pulls just the bits of
+/// one code from the source. Bits not forming a code are an InvalidPrefix and
+/// bits ending in the middle of a code a TruncatedInput error, after which the
+/// iterator only returns None, just like after an invalid codebook.
+///
+/// Like decode, empty codes are ignored.
+pub fn decode_iter<I: IntoIterator<Item = bool>>(
+    codebook: &Codebook,
+    bits: I,
+) -> DecodeIter<I::IntoIter> {
+    let mut nodes = vec![Node::default()];
+    for (&chr, code) in codebook.iter().filter(|(_, code)| !code.is_empty()) {
+        let mut node = 0;
+        for bit in code {
+            node = match nodes[node].children[usize::from(bit)] {
+                Some(child) => child,
+                None => {
+                    nodes.push(Node::default());
+                    nodes[node].children[usize::from(bit)] = Some(nodes.len() - 1);
+                    nodes.len() - 1
+                }
+            };
+        }
+        nodes[node].symbol = Some(chr);
+    }
+    DecodeIter {
+        nodes,
+        bits: bits.into_iter(),
+        bit_offset: 0,
+        error: validate_codebook(codebook)
+            .err()
+            .map(HuffmanError::InvalidCodebook),
+        done: false,
+    }
+}
+
+impl<I: Iterator<Item = bool>> Iterator for DecodeIter<I> {
+    type Item = Result<char, HuffmanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(err) = self.error.take() {
+            self.done = true;
+            return Some(Err(err));
+        }
+        let bit_offset = self.bit_offset;
+        let mut node = 0;
+        loop {
+            let bit = match self.bits.next() {
+                Some(bit) => bit,
+                None => {
+                    self.done = true;
+                    return match node {
+                        0 => None,
+                        _ => Some(Err(HuffmanError::TruncatedInput { bit_offset })),
+                    };
+                }
+            };
+            self.bit_offset += 1;
+            node = match self.nodes[node].children[usize::from(bit)] {
+                Some(child) => child,
+                None => {
+                    self.done = true;
+                    return Some(Err(HuffmanError::InvalidPrefix { bit_offset }));
+                }
+            };
+            if let Some(chr) = self.nodes[node].symbol {
+                return Some(Ok(chr));
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = bool>> FusedIterator for DecodeIter<I> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bits::Bits;
+    use crate::code::{decode, encode, CodebookError, Encoded};
+    use alloc::string::String;
+    use core::cell::Cell;
+
+    fn bits(s: &str) -> Vec<bool> {
+        s.chars().map(|c| c == '1').collect()
+    }
+
+    fn codebook_of(entries: &[(char, &str)]) -> Codebook {
+        entries
+            .iter()
+            .map(|&(chr, code)| (chr, code.chars().map(|c| c == '1').collect()))
+            .collect()
+    }
+
+    #[test]
+    fn decode_iter_matches_decode() {
+        for message in ["a", "abracadabra", "Grüße aus München für 5 € 🦀"] {
+            // given:
+            let Encoded { codebook, bits, .. } = encode(message);
+
+            // when:
+            let actual = decode_iter(&codebook, &bits).collect::<Result<String, _>>();
+
+            // then:
+            assert_eq!(actual, Ok(message.to_string()));
+            assert_eq!(actual, decode(&codebook, &bits));
+        }
+    }
+
+    #[test]
+    fn decode_iter_stops_early() {
+        // given:
+        let message = "the quick brown fox\njumps over the lazy dog";
+        let Encoded { codebook, bits, .. } = encode(message);
+        let pulled = Cell::new(0);
+        let source = bits.iter().inspect(|_| pulled.set(pulled.get() + 1));
+
+        // when:
+        let actual = decode_iter(&codebook, source)
+            .take(10)
+            .collect::<Result<String, _>>();
+
+        // then:
+        assert_eq!(actual, Ok("the quick ".to_string()));
+        let expected = message.chars().take(10).map(|chr| codebook[&chr].len());
+        assert_eq!(pulled.get(), expected.sum::<usize>());
+    }
+
+    #[test]
+    fn decode_iter_find_newline() {
+        // given:
+        let message = "first line\nsecond line\n";
+        let Encoded { codebook, bits, .. } = encode(message);
+
+        // when:
+        let actual = decode_iter(&codebook, bits.iter()).position(|chr| chr == Ok('\n'));
+
+        // then:
+        assert_eq!(actual, Some(10));
+    }
+
+    #[test]
+    fn decode_iter_error_at_corrupted_position() {
+        // given: 11 is no code.
+        let codebook = codebook_of(&[('a', "0"), ('b', "10")]);
+
+        // when:
+        let mut actual = decode_iter(&codebook, bits("0100110"));
+
+        // then:
+        assert_eq!(actual.next(), Some(Ok('a')));
+        assert_eq!(actual.next(), Some(Ok('b')));
+        assert_eq!(actual.next(), Some(Ok('a')));
+        assert_eq!(
+            actual.next(),
+            Some(Err(HuffmanError::InvalidPrefix { bit_offset: 4 }))
+        );
+        assert_eq!(actual.next(), None);
+        assert_eq!(actual.next(), None);
+    }
+
+    #[test]
+    fn decode_iter_truncated() {
+        // given:
+        let codebook = codebook_of(&[('a', "0"), ('b', "10"), ('c', "11")]);
+
+        // when:
+        let actual = decode_iter(&codebook, bits("01")).collect::<Vec<_>>();
+
+        // then:
+        assert_eq!(
+            actual,
+            [Ok('a'), Err(HuffmanError::TruncatedInput { bit_offset: 1 })]
+        );
+    }
+
+    #[test]
+    fn decode_iter_invalid_codebook() {
+        // given:
+        let codebook = codebook_of(&[('a', "0"), ('b', "01")]);
+
+        // when:
+        let actual = decode_iter(&codebook, Bits::new().iter()).collect::<Vec<_>>();
+
+        // then:
+        assert_eq!(
+            actual,
+            [Err(HuffmanError::InvalidCodebook(
+                CodebookError::PrefixConflict {
+                    prefix: 'a',
+                    symbol: 'b'
+                }
+            ))]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! JSON export for external tooling.
+
+use crate::code::Codebook;
+use crate::codec::HuffmanCodec;
+use crate::tree::HuffTree;
+use alloc::collections::BTreeMap;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+/// Dump a frequency map as a pretty JSON object mapping each symbol to its
+/// count, e.g. for feeding it into external analysis scripts.
+pub fn frequencies_to_json(freq: &BTreeMap<char, u64>) -> String {
+    let entries = freq
+        .iter()
+        .map(|(chr, occ)| format!("  {}: {}", json_string(&chr.to_string()), occ))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{\n{}\n}}", entries.join(",\n"))
+    }
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for chr in s.chars() {
+        match chr {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Parse the output of frequencies_to_json back into a frequency map. Returns
+/// None if the JSON is malformed or a key is not exactly one symbol.
+pub fn frequencies_from_json(json: &str) -> Option<BTreeMap<char, u64>> {
+    fn skip_whitespace(chars: &mut core::iter::Peekable<core::str::Chars>) {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+    fn parse_string(chars: &mut core::iter::Peekable<core::str::Chars>) -> Option<String> {
+        if chars.next()? != '"' {
+            return None;
+        }
+        let mut s = String::new();
+        loop {
+            match chars.next()? {
+                '"' => return Some(s),
+                '\\' => match chars.next()? {
+                    '"' => s.push('"'),
+                    '\\' => s.push('\\'),
+                    '/' => s.push('/'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let hex = chars.by_ref().take(4).collect::<String>();
+                        let code = u32::from_str_radix(&hex, 16).ok()?;
+                        s.push(core::char::from_u32(code)?);
+                    }
+                    _ => return None,
+                },
+                c => s.push(c),
+            }
+        }
+    }
+
+    let mut freq = BTreeMap::new();
+    let mut chars = json.chars().peekable();
+    skip_whitespace(&mut chars);
+    if chars.next()? != '{' {
+        return None;
+    }
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&'}') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            let key = parse_string(&mut chars)?;
+            let mut key_chars = key.chars();
+            let chr = key_chars.next()?;
+            if key_chars.next().is_some() {
+                return None;
+            }
+            skip_whitespace(&mut chars);
+            if chars.next()? != ':' {
+                return None;
+            }
+            skip_whitespace(&mut chars);
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            freq.insert(chr, digits.parse().ok()?);
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                '}' => break,
+                _ => return None,
+            }
+        }
+    }
+    skip_whitespace(&mut chars);
+    match chars.next() {
+        None => Some(freq),
+        Some(_) => None,
+    }
+}
+
+/// Dump a codebook as a pretty JSON object mapping each symbol to its code as
+/// a string of 0s and 1s, e.g. `{"a": "0101"}`.
+pub fn codebook_to_json(codebook: &Codebook) -> String {
+    let entries = codebook
+        .iter()
+        .map(|(chr, code)| format!("  {}: \"{}\"", json_string(&chr.to_string()), code))
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{\n{}\n}}", entries.join(",\n"))
+    }
+}
+
+/// Dump a tree as compact nested JSON objects: nodes as
+/// `{"left": …, "right": …}` and leaves as `{"symbol": "a", "count": 12}`.
+pub fn tree_to_json(tree: &HuffTree) -> String {
+    /// What is left to write: a subtree or the text that follows one.
+    enum Part<'a> {
+        Tree(&'a HuffTree),
+        Text(&'static str),
+    }
+    let mut json = String::new();
+    let mut stack = vec![Part::Tree(tree)];
+    while let Some(part) = stack.pop() {
+        match part {
+            Part::Text(text) => json.push_str(text),
+            Part::Tree(HuffTree::Leaf { chr, occ }) => {
+                let symbol = json_string(&chr.to_string());
+                json.push_str(&format!("{{\"symbol\": {}, \"count\": {}}}", symbol, occ));
+            }
+            Part::Tree(HuffTree::Node { left, right }) => {
+                json.push_str("{\"left\": ");
+                stack.push(Part::Text("}"));
+                stack.push(Part::Tree(right));
+                stack.push(Part::Text(", \"right\": "));
+                stack.push(Part::Tree(left));
+            }
+        }
+    }
+    json
+}
+
+/// Dump everything known about a codec as one JSON document with the keys
+/// `frequencies`, `codebook`, `code_lengths` (number of symbols per code
+/// length) and `tree`.
+pub fn inspect_to_json(codec: &HuffmanCodec) -> String {
+    let tree = codec.tree();
+    let frequencies = tree
+        .leaves()
+        .map(|(&chr, occ)| (chr, occ))
+        .collect::<BTreeMap<_, _>>();
+    let code_lengths = tree
+        .code_length_histogram()
+        .iter()
+        .map(|(len, symbols)| format!("\"{}\": {}", len, symbols))
+        .collect::<Vec<_>>();
+    let indent = |json: String| json.replace('\n', "\n  ");
+    format!(
+        "{{\n  \"frequencies\": {},\n  \"codebook\": {},\n  \"code_lengths\": {{{}}},\n  \"tree\": {}\n}}\n",
+        indent(frequencies_to_json(&frequencies)),
+        indent(codebook_to_json(codec.codebook())),
+        code_lengths.join(", "),
+        tree_to_json(tree)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::encode;
+    use crate::tree::frequency;
+    use serde_json::Value;
+
+    fn tree_from_value(value: &Value) -> Option<HuffTree> {
+        match (&value["symbol"], &value["count"]) {
+            (Value::String(symbol), Value::Number(count)) => {
+                Some(HuffTree::new(symbol.parse().ok()?, count.as_u64()?))
+            }
+            _ => Some(tree_from_value(&value["left"])?.merge(tree_from_value(&value["right"])?)),
+        }
+    }
+
+    #[test]
+    fn frequencies_json_round_trip() {
+        // given:
+        let freq = frequency(&mut "say \"hi\"\\\t\n\u{1}äöü€".chars());
+
+        // when:
+        let json = frequencies_to_json(&freq);
+        let actual = frequencies_from_json(&json);
+
+        // then:
+        assert_eq!(actual, Some(freq));
+    }
+
+    #[test]
+    fn frequencies_to_json_is_pretty() {
+        // given:
+        let freq = frequency(&mut "aab\"".chars());
+
+        // when:
+        let actual = frequencies_to_json(&freq);
+
+        // then:
+        assert_eq!(actual, "{\n  \"\\\"\": 1,\n  \"a\": 2,\n  \"b\": 1\n}");
+    }
+
+    #[test]
+    fn frequencies_from_json_rejects_multi_symbol_keys() {
+        // when:
+        let actual = frequencies_from_json("{\"ab\": 1}");
+
+        // then:
+        assert_eq!(actual, None);
+    }
+
+    #[test]
+    fn codebook_to_json_is_readable() {
+        // given:
+        let codebook = encode("aab").codebook;
+
+        // when:
+        let actual = codebook_to_json(&codebook);
+
+        // then:
+        assert_eq!(actual, "{\n  \"a\": \"0\",\n  \"b\": \"1\"\n}");
+    }
+
+    #[test]
+    fn tree_to_json_escapes_symbols() {
+        // given:
+        let tree =
+            HuffTree::new('"', 2).merge(HuffTree::new('\\', 1).merge(HuffTree::new('\u{1}', 1)));
+
+        // when:
+        let actual = tree_to_json(&tree);
+
+        // then:
+        assert_eq!(
+            actual,
+            "{\"left\": {\"symbol\": \"\\\"\", \"count\": 2}, \"right\": {\"left\": {\"symbol\": \"\\\\\", \"count\": 1}, \"right\": {\"symbol\": \"\\u0001\", \"count\": 1}}}"
+        );
+        assert_eq!(
+            serde_json::from_str(&actual)
+                .ok()
+                .as_ref()
+                .and_then(tree_from_value),
+            Some(tree)
+        );
+    }
+
+    #[test]
+    fn tree_to_json_of_deep_tree() {
+        // given: a leaf merged onto the previous tree again and again
+        let depth = 10_000;
+        let tree = (1..=depth).fold(HuffTree::new('a', 1), |tree, _| {
+            HuffTree::new('b', 1).merge(tree)
+        });
+
+        // when: on a thread whose stack is too small for recursing that deep
+        let actual = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(128 * 1024)
+                .spawn_scoped(scope, || tree_to_json(&tree))
+                .unwrap()
+                .join()
+                .unwrap()
+        });
+
+        // then:
+        assert_eq!(actual.matches("\"symbol\"").count(), depth + 1);
+        assert!(actual
+            .starts_with("{\"left\": {\"symbol\": \"b\", \"count\": 1}, \"right\": {\"left\""));
+        assert!(actual.ends_with(&format!(
+            "{{\"symbol\": \"a\", \"count\": 1}}{}",
+            "}".repeat(depth)
+        )));
+    }
+
+    #[test]
+    fn inspect_to_json_matches_codec() {
+        // given:
+        let codec = HuffmanCodec::from_message("say \"hi\"\\\t\n to aachen").unwrap();
+
+        // when:
+        let actual: Value = serde_json::from_str(&inspect_to_json(&codec)).unwrap();
+
+        // then:
+        let codes = actual["codebook"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .map(|(symbol, code)| (symbol.parse().unwrap(), code.as_str().unwrap().to_string()))
+            .collect::<BTreeMap<char, _>>();
+        let expected = codec
+            .codebook()
+            .iter()
+            .map(|(&chr, code)| (chr, code.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(codes, expected);
+        assert_eq!(
+            tree_from_value(&actual["tree"]).as_ref(),
+            Some(codec.tree())
+        );
+        assert_eq!(
+            actual["frequencies"].as_object().unwrap().len(),
+            expected.len()
+        );
+        let symbols = actual["code_lengths"]
+            .as_object()
+            .unwrap()
+            .values()
+            .map(|symbols| symbols.as_u64().unwrap() as usize)
+            .sum::<usize>();
+        assert_eq!(symbols, expected.len());
+    }
+}
@@ -0,0 +1,105 @@
+//! Huffman coding of text.
+//!
+//! Without the default feature `std` the crate is `no_std` and only needs
+//! `alloc`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod adaptive;
+pub mod archive;
+pub mod arena;
+pub mod bits;
+pub mod code;
+pub mod codec;
+pub mod container;
+pub mod crc;
+pub mod dense;
+pub mod encoder;
+pub mod error;
+pub mod fast;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod header;
+pub mod interop;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod iter;
+pub mod json;
+pub mod limited;
+pub mod lossy;
+pub mod mtf;
+#[cfg(feature = "std")]
+pub mod parallel;
+pub mod pretrained;
+pub mod progress;
+pub mod rle;
+#[cfg(feature = "std")]
+pub mod stats;
+pub mod stream;
+pub mod table;
+#[cfg(test)]
+mod test_support;
+pub mod text;
+pub mod tokens;
+pub mod tree;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use adaptive::{decode_adaptive, encode_adaptive, AdaptiveDecoder, AdaptiveEncoder};
+pub use archive::{Archive, ArchiveError, CodebookMode, Entry};
+pub use arena::{ArenaNode, ForestTree, HuffArena, MergeEvent};
+pub use bits::{BitOrder, Bits, ParseBitsError};
+#[cfg(feature = "serde")]
+pub use code::SerdeCodebook;
+pub use code::{
+    canonical_codebook, codebook, codebook_from_lengths, decode, decode_bytes, decode_into,
+    decode_symbols, decode_with_limit, decode_with_tree, encode, encode_bytes, encode_from_iter,
+    encode_symbols, encode_two_pass, encode_with, encoded_len_bits, kraft_sum, validate_codebook,
+    ByteCodebook, Codebook, CodebookError, Encoded,
+};
+pub use codec::{CodecOptions, HuffmanCodec, ESCAPE};
+pub use container::{
+    compress, compress_blocks, compress_with, compress_with_bit_order, compress_with_codebook,
+    compress_with_progress, decompress, decompress_range, decompress_unverified, decompress_with,
+    decompress_with_codebook, decompress_with_progress, estimated_container_len, header_sizes,
+    smallest_header, try_compress_with_progress, CompressOptions, ContainerError,
+    DecompressOptions, HeaderMode,
+};
+pub use crc::{crc32, crc32_update};
+pub use dense::{Code, DenseCodebook, DenseCodebookError};
+pub use encoder::Encoder;
+pub use error::HuffmanError;
+pub use fast::FastDecoder;
+pub use header::{CodebookHeader, HeaderError};
+pub use interop::{import_canonical, import_canonical_with};
+pub use iter::{decode_iter, DecodeIter};
+pub use limited::{huffman_limited, LengthLimitError};
+pub use lossy::{decode_lossy, decode_lossy_with, Gap, LossyOptions, LossyResult, GAP_MARKER};
+pub use mtf::{
+    mtf_decode, mtf_decode_text, mtf_decode_text_with_limit, mtf_encode, mtf_encode_text, MtfError,
+};
+#[cfg(feature = "std")]
+pub use parallel::{decompress_parallel, encode_parallel, frequency_parallel};
+pub use pretrained::Pretrained;
+pub use progress::{Progress, DEFAULT_PROGRESS_INTERVAL};
+pub use rle::{rle_decode, rle_decode_with_limit, rle_encode, RleError, RUN};
+#[cfg(feature = "std")]
+pub use stats::{
+    entropy, expected_code_length, CompressionStats, InputEncoding, SavingsReport, SymbolSavings,
+};
+pub use stream::{HuffmanDecoder, HuffmanEncoder};
+pub use table::{frequency_histogram, CodebookTable, HistogramOrder, SortBy};
+pub use text::{CodebookParseError, CodebookText};
+pub use tokens::{
+    decode_digrams, decode_tokens, encode_digrams, encode_tokens, encode_with_mode,
+    split_graphemes, split_words, Digram, SymbolMode,
+};
+#[cfg(feature = "std")]
+pub use tree::{byte_frequency_from_reader, frequency_from_reader};
+pub use tree::{
+    dump_tree, frequency, huffman, huffman_weighted, huffman_with_observer, try_frequency,
+    CountOverflow, FrequencyTable, HuffTree, HuffWeight, Leaves, Subtree, TreeFormatOptions,
+    WeightError,
+};
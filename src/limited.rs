@@ -0,0 +1,305 @@
+//! Length-limited Huffman codes with the package-merge algorithm.
+
+use crate::code::{canonical_codes, Codebook};
+use alloc::collections::BTreeMap;
+use alloc::{vec, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LengthLimitError {
+    /// The frequency map does not contain any symbol.
+    Empty,
+    /// There are more than 2^max_len symbols.
+    AlphabetTooLarge { symbols: usize, max_len: u8 },
+    /// The minimum code length exceeds the maximum one.
+    MinLenAboveMaxLen { min_len: u8, max_len: u8 },
+}
+
+impl Display for LengthLimitError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LengthLimitError::Empty => write!(f, "the frequency map does not contain any symbol"),
+            LengthLimitError::AlphabetTooLarge { symbols, max_len } => write!(
+                f,
+                "{} symbols do not fit into codes of at most {} bits",
+                symbols, max_len
+            ),
+            LengthLimitError::MinLenAboveMaxLen { min_len, max_len } => write!(
+                f,
+                "minimum code length {} exceeds the maximum {}",
+                min_len, max_len
+            ),
+        }
+    }
+}
+
+/// An item of a package-merge list: a symbol or a package of two items of the
+/// list of the previous level.
+enum Item {
+    Leaf(usize),
+    Package(usize, usize),
+}
+
+/// The optimal canonical codes that are no longer than max_len bits, computed
+/// with package-merge. A single symbol gets a one-bit code.
+pub fn huffman_limited(
+    frequency: BTreeMap<char, u64>,
+    max_len: u8,
+) -> Result<Codebook, LengthLimitError> {
+    huffman_bounded(frequency, 0, max_len)
+}
+
+/// Like huffman_limited, but no code is shorter than min_len bits either. Up
+/// to 2^min_len symbols all get codes of min_len bits.
+pub(crate) fn huffman_bounded(
+    frequency: BTreeMap<char, u64>,
+    min_len: u8,
+    max_len: u8,
+) -> Result<Codebook, LengthLimitError> {
+    let symbols = frequency.len();
+    if symbols == 0 {
+        return Err(LengthLimitError::Empty);
+    }
+    if min_len > max_len {
+        return Err(LengthLimitError::MinLenAboveMaxLen { min_len, max_len });
+    }
+    if max_len == 0 || (max_len < 64 && symbols > 1 << max_len) {
+        return Err(LengthLimitError::AlphabetTooLarge { symbols, max_len });
+    }
+    let mut sorted = frequency.into_iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|&(chr, occ)| (occ, chr));
+    // A single symbol still needs a one-bit code, two or more need at least
+    // one bit anyway.
+    let min_len = usize::from(min_len.max(1));
+    if min_len >= 64 || symbols <= 1 << min_len {
+        let lengths = sorted.iter().map(|&(chr, _)| (chr, min_len)).collect();
+        return Ok(canonical_codes(&lengths));
+    }
+
+    // All items live in one arena, each list holds (weight, arena index).
+    let mut arena = (0..symbols).map(Item::Leaf).collect::<Vec<_>>();
+    let leaves = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &(_, occ))| (occ, i))
+        .collect::<Vec<_>>();
+    let mut list = leaves.clone();
+    // Codes are never more than symbols - 1 bits longer than min_len, more
+    // levels do not help. The levels up to min_len are taken by every symbol,
+    // so the list ends at level min_len + 1.
+    let max_len = usize::from(max_len).min(min_len + symbols - 1);
+    for _ in min_len + 1..max_len {
+        let mut packages = Vec::with_capacity(list.len() / 2);
+        for pair in list.chunks_exact(2) {
+            arena.push(Item::Package(pair[0].1, pair[1].1));
+            packages.push((pair[0].0.saturating_add(pair[1].0), arena.len() - 1));
+        }
+        list = merge(&leaves, &packages);
+    }
+
+    // The code length of a symbol is min_len plus the number of selected
+    // items it is in. The codes of min_len bits leave room for
+    // 2 * (symbols - 2^min_len) items of the next level.
+    let mut lengths = vec![min_len; symbols];
+    let mut stack = list[..2 * (symbols - (1 << min_len))]
+        .iter()
+        .map(|&(_, item)| item)
+        .collect::<Vec<_>>();
+    while let Some(item) = stack.pop() {
+        match arena[item] {
+            Item::Leaf(symbol) => lengths[symbol] += 1,
+            Item::Package(a, b) => stack.extend([a, b].iter()),
+        }
+    }
+    let lengths = sorted
+        .iter()
+        .zip(lengths)
+        .map(|(&(chr, _), len)| (chr, len))
+        .collect();
+    Ok(canonical_codes(&lengths))
+}
+
+/// Merge two lists sorted by weight, taking leaves first on ties.
+fn merge(leaves: &[(u64, usize)], packages: &[(u64, usize)]) -> Vec<(u64, usize)> {
+    let mut merged = Vec::with_capacity(leaves.len() + packages.len());
+    let (mut leaves, mut packages) = (leaves.iter().peekable(), packages.iter().peekable());
+    loop {
+        let next = match (leaves.peek(), packages.peek()) {
+            (Some(leaf), Some(package)) if leaf.0 <= package.0 => leaves.next(),
+            (_, Some(_)) => packages.next(),
+            (Some(_), None) => leaves.next(),
+            (None, None) => break,
+        };
+        merged.extend(next);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{canonical_codebook, decode, encode_with, is_complete};
+    use crate::tree::{frequency, huffman, HuffTree};
+
+    /// 20 symbols with Fibonacci counts, which give a tree of depth 19.
+    fn fibonacci_frequency() -> BTreeMap<char, u64> {
+        let mut fibonacci = (1, 1);
+        ('a'..='t')
+            .map(|chr| {
+                let occ = fibonacci.0;
+                fibonacci = (fibonacci.1, fibonacci.0 + fibonacci.1);
+                (chr, occ)
+            })
+            .collect()
+    }
+
+    fn cost(frequency: &BTreeMap<char, u64>, codebook: &Codebook) -> u64 {
+        frequency
+            .iter()
+            .map(|(chr, &occ)| occ * codebook[chr].len() as u64)
+            .sum()
+    }
+
+    #[test]
+    fn huffman_limited_respects_cap() {
+        // given:
+        let frequency = fibonacci_frequency();
+        let message = frequency
+            .iter()
+            .flat_map(|(&chr, &occ)| std::iter::repeat_n(chr, occ.min(50) as usize))
+            .collect::<String>();
+
+        for max_len in 5..=12 {
+            // when:
+            let actual = huffman_limited(frequency.clone(), max_len).unwrap();
+
+            // then:
+            assert!(actual
+                .values()
+                .all(|code| code.len() <= usize::from(max_len)));
+            assert!(actual
+                .values()
+                .any(|code| code.len() == usize::from(max_len)));
+            assert!(is_complete(&actual));
+            assert!(HuffTree::from_codebook(&actual).is_ok());
+            let bits = encode_with(&actual, &message).unwrap();
+            assert_eq!(decode(&actual, &bits), Ok(message.clone()));
+        }
+    }
+
+    #[test]
+    fn huffman_limited_without_effective_limit_is_optimal() {
+        // given:
+        let frequency = frequency(&mut "aardvarks ate apples around aachen".chars());
+        let unlimited = canonical_codebook(&huffman(frequency.clone()).unwrap());
+
+        // when:
+        let actual = huffman_limited(frequency.clone(), 15).unwrap();
+
+        // then:
+        assert_eq!(cost(&frequency, &actual), cost(&frequency, &unlimited));
+    }
+
+    #[test]
+    fn huffman_limited_cost_grows_with_tighter_cap() {
+        // given:
+        let frequency = fibonacci_frequency();
+
+        // when:
+        let costs = (5..=19)
+            .map(|max_len| {
+                cost(
+                    &frequency,
+                    &huffman_limited(frequency.clone(), max_len).unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // then:
+        assert!(costs.windows(2).all(|w| w[0] >= w[1]));
+        let unlimited = canonical_codebook(&huffman(frequency.clone()).unwrap());
+        assert_eq!(costs.last(), Some(&cost(&frequency, &unlimited)));
+    }
+
+    #[test]
+    fn huffman_limited_alphabet_too_large() {
+        // when:
+        let actual = huffman_limited(frequency(&mut "abcde".chars()), 2);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(LengthLimitError::AlphabetTooLarge {
+                symbols: 5,
+                max_len: 2
+            })
+        );
+    }
+
+    #[test]
+    fn huffman_bounded_respects_min_len() {
+        // given:
+        let frequency = fibonacci_frequency();
+
+        for (min_len, max_len) in [(3, 19), (3, 6), (4, 5)] {
+            // when:
+            let actual = huffman_bounded(frequency.clone(), min_len, max_len).unwrap();
+
+            // then:
+            let range = usize::from(min_len)..=usize::from(max_len);
+            assert!(actual.values().all(|code| range.contains(&code.len())));
+            assert!(is_complete(&actual));
+        }
+    }
+
+    #[test]
+    fn huffman_bounded_min_len_is_optimal() {
+        // given: with codes of at least 2 bits, 'e' takes one of the four and
+        // two of the others have to share one
+        let frequency = [('a', 1), ('b', 1), ('c', 1), ('d', 1), ('e', 100)]
+            .iter()
+            .copied()
+            .collect::<BTreeMap<_, _>>();
+
+        // when:
+        let actual = huffman_bounded(frequency.clone(), 2, 8).unwrap();
+
+        // then:
+        assert_eq!(actual[&'e'].len(), 2);
+        assert_eq!(cost(&frequency, &actual), 210);
+    }
+
+    #[test]
+    fn huffman_bounded_few_symbols_get_min_len() {
+        // when:
+        let actual = (
+            huffman_bounded(frequency(&mut "abcc".chars()), 3, 8).unwrap(),
+            huffman_bounded(frequency(&mut "abc".chars()), 4, 3),
+        );
+
+        // then:
+        assert!(actual.0.values().all(|code| code.len() == 3));
+        assert_eq!(
+            actual.1,
+            Err(LengthLimitError::MinLenAboveMaxLen {
+                min_len: 4,
+                max_len: 3
+            })
+        );
+    }
+
+    #[test]
+    fn huffman_limited_small_inputs() {
+        // when:
+        let actual = (
+            huffman_limited(BTreeMap::new(), 8),
+            huffman_limited(frequency(&mut "aaa".chars()), 1).unwrap(),
+            huffman_limited(frequency(&mut "abcd".chars()), 2).unwrap(),
+        );
+
+        // then:
+        assert_eq!(actual.0, Err(LengthLimitError::Empty));
+        assert_eq!(actual.1[&'a'].len(), 1);
+        assert!(actual.2.values().all(|code| code.len() == 2));
+    }
+}
@@ -0,0 +1,294 @@
+//! Recovering what is left of damaged bits instead of failing like decode.
+//!
+//! Only bits that start no code are recognized as damage. With a complete
+//! code like the ones of encode every bit sequence decodes, so damage turns
+//! into wrong symbols instead; a codebook with unused codes, e.g. one trained
+//! on symbols that were dropped later, leaves room to notice it.
+
+use crate::bits::Bits;
+use crate::code::Codebook;
+use crate::fast::FastDecoder;
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+
+/// The symbol decode_lossy puts where it skipped bits.
+pub const GAP_MARKER: char = char::REPLACEMENT_CHARACTER;
+
+/// How decode_lossy continues after damage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LossyOptions {
+    /// Skip the damaged bits one at a time until this many consecutive
+    /// symbols decode, or the rest of the bits if fewer are left, and decode
+    /// on from there. None gives up at the first damage.
+    pub resync_symbols: Option<usize>,
+}
+
+impl Default for LossyOptions {
+    fn default() -> LossyOptions {
+        LossyOptions {
+            resync_symbols: Some(8),
+        }
+    }
+}
+
+/// Bits that decode_lossy skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Gap {
+    /// The skipped bits.
+    pub bits: Range<usize>,
+    /// The byte index of the GAP_MARKER for them in LossyResult::tail.
+    pub index: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LossyResult {
+    /// The symbols before the first damage, all symbols if there is none.
+    pub prefix: String,
+    /// The bit offset of the first code that does not decode.
+    pub failed_at: Option<usize>,
+    /// What decoded after the first damage, with a GAP_MARKER for each gap.
+    /// Empty unless decoding resynced or ran out of bits.
+    pub tail: String,
+    pub gaps: Vec<Gap>,
+}
+
+/// Decode the bits like decode, but keep the symbols before the first code
+/// that does not decode and try to resync after it as with the default
+/// LossyOptions.
+pub fn decode_lossy(codebook: &Codebook, bits: &Bits) -> LossyResult {
+    decode_lossy_with(codebook, bits, LossyOptions::default())
+}
+
+/// Decode the bits like decode_lossy with the options.
+pub fn decode_lossy_with(codebook: &Codebook, bits: &Bits, options: LossyOptions) -> LossyResult {
+    let decoder = FastDecoder::new(codebook);
+    let mut result = LossyResult::default();
+    let mut offset = decode_until_error(&decoder, bits, 0, &mut result.prefix);
+    if offset == bits.len() {
+        return result;
+    }
+    result.failed_at = Some(offset);
+    let resync_symbols = match options.resync_symbols {
+        Some(resync_symbols) => resync_symbols.max(1),
+        None => return result,
+    };
+    let mut gap_start = offset;
+    let mut candidate = offset + 1;
+    let mut decoded = String::new();
+    while candidate < bits.len() {
+        decoded.clear();
+        match decode_symbols(&decoder, bits, candidate, resync_symbols, &mut decoded) {
+            Some(end) => {
+                result.gaps.push(Gap {
+                    bits: gap_start..candidate,
+                    index: result.tail.len(),
+                });
+                result.tail.push(GAP_MARKER);
+                result.tail.push_str(&decoded);
+                offset = decode_until_error(&decoder, bits, end, &mut result.tail);
+                if offset == bits.len() {
+                    return result;
+                }
+                gap_start = offset;
+                candidate = offset + 1;
+            }
+            None => candidate += 1,
+        }
+    }
+    result.gaps.push(Gap {
+        bits: gap_start..bits.len(),
+        index: result.tail.len(),
+    });
+    result.tail.push(GAP_MARKER);
+    result
+}
+
+/// Append the symbols from the offset to out until a code does not decode or
+/// the bits end. Returns the offset where decoding stopped.
+fn decode_until_error(
+    decoder: &FastDecoder,
+    bits: &Bits,
+    mut offset: usize,
+    out: &mut String,
+) -> usize {
+    while offset < bits.len() {
+        match decoder.decode_symbol(bits, offset, bits.len()) {
+            Ok((&chr, len)) => {
+                out.push(chr);
+                offset += len;
+            }
+            Err(_) => break,
+        }
+    }
+    offset
+}
+
+/// Append count symbols from the offset to out, or all up to the end of the
+/// bits. Returns the offset after them or None if a code does not decode.
+fn decode_symbols(
+    decoder: &FastDecoder,
+    bits: &Bits,
+    mut offset: usize,
+    count: usize,
+    out: &mut String,
+) -> Option<usize> {
+    for _ in 0..count {
+        if offset == bits.len() {
+            break;
+        }
+        let (&chr, len) = decoder.decode_symbol(bits, offset, bits.len()).ok()?;
+        out.push(chr);
+        offset += len;
+    }
+    Some(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{canonical_codebook, decode, encode_with};
+    use crate::tree::{frequency, huffman};
+    use alloc::format;
+    use alloc::string::ToString;
+
+    const LAST_SENTENCE: &str = "The log ends here.";
+
+    fn log() -> String {
+        let mut log = (0..200)
+            .map(|i| format!("line {}: all systems nominal\n", i))
+            .collect::<String>();
+        log.push_str(LAST_SENTENCE);
+        log
+    }
+
+    /// A codebook for the message that also has a code for '#', which is
+    /// removed, so its code is damage.
+    fn codebook_with_unused_code(message: &str) -> (Codebook, Bits) {
+        let mut frequency = frequency(&mut message.chars());
+        frequency.insert('#', 500);
+        let mut codebook = canonical_codebook(&huffman(frequency).unwrap());
+        let unused = codebook.remove(&'#').unwrap();
+        (codebook, unused)
+    }
+
+    #[test]
+    fn decode_lossy_intact_bits() {
+        // given:
+        let message = log();
+        let (codebook, _) = codebook_with_unused_code(&message);
+        let bits = encode_with(&codebook, &message).unwrap();
+
+        // when:
+        let actual = decode_lossy(&codebook, &bits);
+
+        // then:
+        assert_eq!(
+            actual,
+            LossyResult {
+                prefix: message,
+                ..LossyResult::default()
+            }
+        );
+    }
+
+    #[test]
+    fn decode_lossy_resyncs_after_damage() {
+        // given: the bits of 100 symbols from the 3000th on are overwritten
+        let message = log();
+        let (codebook, unused) = codebook_with_unused_code(&message);
+        let damage_start = message
+            .chars()
+            .take(3000)
+            .map(|chr| codebook[&chr].len())
+            .sum::<usize>();
+        let damage_len = message
+            .chars()
+            .skip(3000)
+            .take(100)
+            .map(|chr| codebook[&chr].len())
+            .sum::<usize>();
+        let bits = encode_with(&codebook, &message).unwrap();
+        let mut damaged = Bits::new();
+        for i in 0..damage_start + damage_len {
+            let bit = match i.checked_sub(damage_start) {
+                Some(j) => unused.get(j % unused.len()),
+                None => bits.get(i),
+            };
+            damaged.push(bit);
+        }
+        damaged.extend((damage_start + damage_len..bits.len()).map(|i| bits.get(i)));
+
+        // when:
+        let actual = decode_lossy(&codebook, &damaged);
+
+        // then:
+        assert!(decode(&codebook, &damaged).is_err());
+        assert_eq!(
+            actual.prefix,
+            message.chars().take(3000).collect::<String>()
+        );
+        assert_eq!(actual.failed_at, Some(damage_start));
+        assert!(actual.tail.starts_with(GAP_MARKER));
+        assert!(actual.tail.ends_with(&format!("\n{}", LAST_SENTENCE)));
+        assert!(!actual.gaps.is_empty());
+        assert_eq!(actual.gaps[0].bits.start, damage_start);
+        assert!(actual
+            .gaps
+            .iter()
+            .all(|gap| gap.bits.end <= damage_start + damage_len + 64));
+        for gap in &actual.gaps {
+            assert_eq!(actual.tail[gap.index..].chars().next(), Some(GAP_MARKER));
+        }
+    }
+
+    #[test]
+    fn decode_lossy_without_resync() {
+        // given:
+        let (codebook, unused) = codebook_with_unused_code("abcabc");
+        let mut bits = encode_with(&codebook, "abc").unwrap();
+        let failed_at = bits.len();
+        bits.extend_from_code(&unused);
+        bits.extend_from_code(&encode_with(&codebook, "cab").unwrap());
+        let options = LossyOptions {
+            resync_symbols: None,
+        };
+
+        // when:
+        let actual = decode_lossy_with(&codebook, &bits, options);
+
+        // then:
+        assert_eq!(
+            actual,
+            LossyResult {
+                prefix: "abc".into(),
+                failed_at: Some(failed_at),
+                ..LossyResult::default()
+            }
+        );
+    }
+
+    #[test]
+    fn decode_lossy_truncated_code() {
+        // given: a code is cut off at the end
+        let message = "abracadabra";
+        let (codebook, _) = codebook_with_unused_code(message);
+        let mut bits = encode_with(&codebook, message).unwrap();
+        let last = codebook[&'a'].len();
+        bits.truncate(bits.len() - last + 1);
+
+        // when:
+        let actual = decode_lossy(&codebook, &bits);
+
+        // then:
+        assert_eq!(actual.prefix, "abracadabr");
+        assert_eq!(actual.failed_at, Some(bits.len() - 1));
+        assert_eq!(actual.tail, GAP_MARKER.to_string());
+        assert_eq!(
+            actual.gaps,
+            [Gap {
+                bits: bits.len() - 1..bits.len(),
+                index: 0
+            }]
+        );
+    }
+}
@@ -1,205 +1,901 @@
-use std::clone::Clone;
-use std::cmp::{Ord, Ordering};
-use std::collections::BTreeMap;
-use std::collections::BinaryHeap;
-use std::fmt::{Debug, Display, Formatter, Result};
+use std::fs;
+use std::io::{self, BufReader, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Instant;
+use ue5::io::ContainerReader;
+use ue5::json::inspect_to_json;
+use ue5::{
+    canonical_codebook, compress_with_codebook, decompress_with_codebook, decompress_with_progress,
+    dump_tree, frequency, frequency_histogram, huffman, huffman_with_observer, mtf_encode_text,
+    rle_encode, try_compress_with_progress, Archive, Codebook, CodebookMode, CodebookTable,
+    CodebookText, CodecOptions, CompressOptions, CompressionStats, DecompressOptions, ForestTree,
+    FrequencyTable, HistogramOrder, HuffTree, HuffmanCodec, InputEncoding, Progress, SavingsReport,
+    SortBy,
+};
 
-type BitVec = Vec<bool>;
-fn bitvec_str(bv: &BitVec) -> String {
-    bv.iter().map(|&b| if b { "1" } else { "0" }).collect()
-}
+const USAGE: &str = "usage:
+  huff compress [<input>] [-o <output>] [--mtf] [--rle] [--stats] [--explain]
+                [--verify] [--book <book> [--no-escape]]
+  huff decompress [<input>] [-o <output>] [--no-verify] [--book <book>]
+  huff train <file>... [-o <book>]
+  huff verify <original> <compressed> [--book <book>]
+  huff archive <file>... [-o <output>] [--per-file]
+  huff extract <archive> [<name>] [-d <dir>]
+  huff freq <input> [--sort count|symbol] [--top <n>]
+  huff inspect <input-or-message> [--json]
+  huff demo [--verbose] [--explain] [--steps <message>]
+  huff --dot <message>
+
+<input> and <output> may be - for stdin and stdout, which are also the defaults of
+compress and decompress. Diagnostics always go to stderr.
+compress --rle replaces runs of 4 or more equal symbols before Huffman coding, which
+pays off for inputs like indented source code or simple bitmaps. --mtf replaces each
+symbol by its position in a list of recently used symbols, for inputs that use few
+symbols at a time; with both the runs are replaced after the symbols. --verify
+decompresses the output again and fails if it does not reproduce the input.
+archive stores the files under their file names with a codebook shared by all files
+unless --per-file is given. extract restores the named file or all files into <dir>,
+by default the current directory.
+train writes a codebook for the symbols of all files, to compress many similar
+messages with --book without storing it in each of them. Symbols missing from the
+book are escaped, or rejected with --no-escape.
+verify decompresses the compressed file and compares it with the original, which is
+read piece by piece. It exits with 0 if they are identical, 3 if they differ and 1
+if the compressed file cannot be decompressed.
+inspect treats its argument as the message if there is no file of that name.
+--explain prints the bits each symbol contributes compared to a fixed-length code.
+demo --steps shows how the tree for the message is built, one merge at a time.";
 
-enum HuffTree {
-    Leaf {
-        occ: u32,
-        chr: char,
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Compress {
+        input: String,
+        output: String,
+        stats: bool,
+        explain: bool,
+        book: Option<String>,
+        escape: bool,
+        rle: bool,
+        mtf: bool,
+        verify: bool,
+    },
+    Decompress {
+        input: String,
+        output: String,
+        verify: bool,
+        book: Option<String>,
+    },
+    Train {
+        inputs: Vec<String>,
+        output: String,
+    },
+    Verify {
+        original: String,
+        compressed: String,
+        book: Option<String>,
+    },
+    Archive {
+        inputs: Vec<String>,
+        output: String,
+        per_file: bool,
+    },
+    Extract {
+        input: String,
+        name: Option<String>,
+        dir: String,
+    },
+    Freq {
+        input: String,
+        order: HistogramOrder,
+        top: Option<usize>,
     },
-    Node {
-        left: Box<HuffTree>,
-        right: Box<HuffTree>,
+    Inspect {
+        input: String,
+        json: bool,
+    },
+    Demo {
+        verbose: bool,
+        explain: bool,
+        steps: Option<String>,
+    },
+    Dot {
+        message: String,
     },
 }
 
-impl HuffTree {
-    fn new(chr: char, occ: u32) -> HuffTree {
-        HuffTree::Leaf { occ, chr }
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let command = args.next().ok_or("missing subcommand")?;
+    if command == "--dot" {
+        let message = args.next().ok_or("missing message for --dot")?;
+        return match args.next() {
+            Some(arg) => Err(format!("unexpected argument {:?}", arg)),
+            None => Ok(Command::Dot { message }),
+        };
     }
-    fn merge(self, other: HuffTree) -> HuffTree {
-        HuffTree::Node {
-            left: Box::new(self),
-            right: Box::new(other),
+    if command == "demo" {
+        let mut verbose = false;
+        let mut explain = false;
+        let mut steps = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-v" | "--verbose" => verbose = true,
+                "--explain" => explain = true,
+                "--steps" if steps.is_none() => {
+                    steps = Some(args.next().ok_or("missing message for --steps")?);
+                }
+                "--steps" => return Err("--steps given more than once".to_string()),
+                flag if flag.starts_with('-') => return Err(format!("unknown flag {:?}", flag)),
+                _ => return Err(format!("unexpected argument {:?}", arg)),
+            }
         }
+        return Ok(Command::Demo {
+            verbose,
+            explain,
+            steps,
+        });
     }
-    fn chars(&self) -> String {
-        match self {
-            HuffTree::Node { left, right, .. } => left.chars() + &right.chars(),
-            HuffTree::Leaf { chr, .. } => chr.to_string(),
+    if command == "freq" {
+        return parse_freq_args(args);
+    }
+    if command == "archive" || command == "extract" || command == "train" {
+        return parse_archive_args(&command, args);
+    }
+    if command == "verify" {
+        let mut paths = Vec::new();
+        let mut book = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--book" if book.is_none() => {
+                    book = Some(args.next().ok_or("missing value for --book")?);
+                }
+                "--book" => return Err("--book given more than once".to_string()),
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("unknown flag {:?}", flag));
+                }
+                _ => paths.push(arg),
+            }
+        }
+        let mut paths = paths.into_iter();
+        let original = paths.next().ok_or("missing original")?;
+        let compressed = paths.next().ok_or("missing compressed file")?;
+        if let Some(arg) = paths.next() {
+            return Err(format!("unexpected argument {:?}", arg));
+        }
+        return Ok(Command::Verify {
+            original,
+            compressed,
+            book,
+        });
+    }
+    if command == "inspect" {
+        let mut input = None;
+        let mut json = false;
+        for arg in args {
+            match arg.as_str() {
+                "--json" => json = true,
+                flag if flag.starts_with('-') && flag != "-" => {
+                    return Err(format!("unknown flag {:?}", flag));
+                }
+                _ if input.is_none() => input = Some(arg),
+                _ => return Err(format!("unexpected argument {:?}", arg)),
+            }
         }
+        let input = input.ok_or("missing input")?;
+        return Ok(Command::Inspect { input, json });
     }
-    fn lettercount(&self) -> u32 {
-        match self {
-            HuffTree::Leaf { occ, .. } => *occ,
-            HuffTree::Node { left, right } => left.lettercount() + right.lettercount(),
+    if command != "compress" && command != "decompress" {
+        return Err(format!("unknown subcommand {:?}", command));
+    }
+    let mut input = None;
+    let mut output = None;
+    let mut verify = true;
+    let mut stats = false;
+    let mut explain = false;
+    let mut book = None;
+    let mut escape = true;
+    let mut rle = false;
+    let mut mtf = false;
+    let mut self_check = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--no-verify" if command == "decompress" => verify = false,
+            "--verify" if command == "compress" => self_check = true,
+            "--stats" if command == "compress" => stats = true,
+            "--explain" if command == "compress" => explain = true,
+            "--no-escape" if command == "compress" => escape = false,
+            "--rle" if command == "compress" => rle = true,
+            "--mtf" if command == "compress" => mtf = true,
+            "--book" if book.is_none() => {
+                book = Some(args.next().ok_or("missing value for --book")?);
+            }
+            "--book" => return Err("--book given more than once".to_string()),
+            "-o" | "--output" if output.is_none() => {
+                output = Some(args.next().ok_or("missing value for -o")?);
+            }
+            "-o" | "--output" => return Err("-o given more than once".to_string()),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown flag {:?}", flag));
+            }
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
         }
     }
+    if stats && book.is_some() {
+        return Err("--stats cannot be combined with --book".to_string());
+    }
+    if explain && book.is_some() {
+        return Err("--explain cannot be combined with --book".to_string());
+    }
+    if rle && book.is_some() {
+        return Err("--rle cannot be combined with --book".to_string());
+    }
+    if mtf && book.is_some() {
+        return Err("--mtf cannot be combined with --book".to_string());
+    }
+    if self_check && book.is_some() {
+        return Err("--verify cannot be combined with --book".to_string());
+    }
+    if !escape && book.is_none() {
+        return Err("--no-escape requires --book".to_string());
+    }
+    let input = input.unwrap_or_else(|| "-".to_string());
+    let output = output.unwrap_or_else(|| "-".to_string());
+    Ok(match command.as_str() {
+        "compress" => Command::Compress {
+            input,
+            output,
+            stats,
+            explain,
+            book,
+            escape,
+            rle,
+            mtf,
+            verify: self_check,
+        },
+        _ => Command::Decompress {
+            input,
+            output,
+            verify,
+            book,
+        },
+    })
 }
 
-const INDENT: &str = "  ";
-impl Display for HuffTree {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        fn fmt_prefixed(s: &HuffTree, f: &mut Formatter<'_>, depth: usize) -> Result {
-            match s {
-                HuffTree::Leaf { chr, occ } => {
-                    write!(f, "{}{}: {}", INDENT.repeat(depth), chr, occ)
-                }
-                HuffTree::Node { left, right } => {
-                    write!(f, "{}left:\n", INDENT.repeat(depth))?;
-                    fmt_prefixed(left, f, depth + 1)?;
-                    write!(f, "\n{}right:\n", INDENT.repeat(depth))?;
-                    fmt_prefixed(right, f, depth + 1)
-                }
+fn parse_archive_args<I: Iterator<Item = String>>(
+    command: &str,
+    mut args: I,
+) -> Result<Command, String> {
+    let mut paths = Vec::new();
+    let mut output = None;
+    let mut per_file = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--per-file" if command == "archive" => per_file = true,
+            "-o" | "--output" | "-d" | "--dir" if output.is_some() => {
+                return Err(format!("{} given more than once", arg));
+            }
+            "-o" | "--output" if command == "archive" || command == "train" => {
+                output = Some(args.next().ok_or("missing value for -o")?);
+            }
+            "-d" | "--dir" if command == "extract" => {
+                output = Some(args.next().ok_or("missing value for -d")?);
             }
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown flag {:?}", flag));
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if command == "train" {
+        if paths.is_empty() {
+            return Err("missing files".to_string());
         }
-        fmt_prefixed(self, f, 0)
+        return Ok(Command::Train {
+            inputs: paths,
+            output: output.unwrap_or_else(|| "-".to_string()),
+        });
     }
+    if command == "archive" {
+        if paths.is_empty() {
+            return Err("missing files".to_string());
+        }
+        return Ok(Command::Archive {
+            inputs: paths,
+            output: output.unwrap_or_else(|| "-".to_string()),
+            per_file,
+        });
+    }
+    let mut paths = paths.into_iter();
+    let input = paths.next().ok_or("missing archive")?;
+    let name = paths.next();
+    if let Some(arg) = paths.next() {
+        return Err(format!("unexpected argument {:?}", arg));
+    }
+    Ok(Command::Extract {
+        input,
+        name,
+        dir: output.unwrap_or_else(|| ".".to_string()),
+    })
 }
 
-impl Ord for HuffTree {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.lettercount().cmp(&other.lettercount()).reverse()
+fn parse_freq_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut input = None;
+    let mut order = None;
+    let mut top = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sort" if order.is_none() => {
+                order = Some(match args.next().as_deref() {
+                    Some("count") => HistogramOrder::Count,
+                    Some("symbol") => HistogramOrder::Symbol,
+                    Some(value) => return Err(format!("unknown sort order {:?}", value)),
+                    None => return Err("missing value for --sort".to_string()),
+                });
+            }
+            "--top" if top.is_none() => {
+                let value = args.next().ok_or("missing value for --top")?;
+                top = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid number {:?} for --top", value))?,
+                );
+            }
+            "--sort" | "--top" => return Err(format!("{} given more than once", arg)),
+            flag if flag.starts_with('-') && flag != "-" => {
+                return Err(format!("unknown flag {:?}", flag));
+            }
+            _ if input.is_none() => input = Some(arg),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
     }
+    Ok(Command::Freq {
+        input: input.ok_or("missing input")?,
+        order: order.unwrap_or_default(),
+        top,
+    })
 }
-impl PartialOrd for HuffTree {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
+
+/// Count the symbols of the input without reading it into memory at once.
+fn count_input(path: &str) -> Result<FrequencyTable, String> {
+    let mut table = FrequencyTable::new();
+    let result = match path {
+        "-" => table.count_reader(io::stdin().lock()),
+        _ => fs::File::open(path).and_then(|file| table.count_reader(BufReader::new(file))),
+    };
+    result.map_err(|err| format!("cannot read {}: {}", path, err))?;
+    Ok(table)
 }
-impl Eq for HuffTree {}
-impl PartialEq for HuffTree {
-    fn eq(&self, other: &Self) -> bool {
-        self.lettercount().eq(&other.lettercount())
-    }
+
+fn read_input(path: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let result = match path {
+        "-" => io::stdin().read_to_end(&mut data).map(|_| data),
+        _ => fs::read(path),
+    };
+    result.map_err(|err| format!("cannot read {}: {}", path, err))
 }
 
-// Build a Huffmann tree by iteratively combining two minimal elements.
-fn huffman(frequency: BTreeMap<char, u32>) -> Option<HuffTree> {
-    let mut heap = frequency
-        .into_iter()
-        .map(|(chr, occ)| HuffTree::new(chr, occ))
-        .collect::<BinaryHeap<_>>();
+fn read_text(path: &str) -> Result<String, String> {
+    String::from_utf8(read_input(path)?).map_err(|_| format!("{} is not valid UTF-8 text", path))
+}
 
-    loop {
-        match (heap.pop(), heap.pop()) {
-            (Some(first), Some(second)) => heap.push(first.merge(second)),
-            (first, _) => break first,
+fn read_book(path: &str) -> Result<Codebook, String> {
+    Codebook::from_text(&read_text(path)?)
+        .map_err(|err| format!("{} is not a valid codebook: {}", path, err))
+}
+
+/// Write the bytes as they are, also to stdout, which may be binary.
+fn write_output(path: &str, data: &[u8]) -> Result<(), String> {
+    let result = match path {
+        "-" => {
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(data).and_then(|()| stdout.flush())
         }
+        _ => fs::write(path, data),
+    };
+    result.map_err(|err| format!("cannot write {}: {}", path, err))
+}
+
+fn ratio(original: usize, compressed: usize) -> f64 {
+    match original {
+        0 => 0.0,
+        _ => compressed as f64 / original as f64 * 100.0,
     }
 }
 
-type Codebook = BTreeMap<char, BitVec>;
+fn print_sizes(original: usize, compressed: usize) {
+    eprintln!(
+        "original: {} bytes, compressed: {} bytes, ratio: {:.1}%",
+        original,
+        compressed,
+        ratio(original, compressed)
+    );
+}
+
+/// How the original compares with the decompressed bytes.
+enum Comparison {
+    /// Both have the len bytes.
+    Identical { len: usize },
+    /// The first differing byte and the bytes of both around it.
+    Differs {
+        offset: usize,
+        original: Vec<u8>,
+        decompressed: Vec<u8>,
+    },
+    /// One is a prefix of the other.
+    Length {
+        original: usize,
+        decompressed: usize,
+    },
+}
+
+/// Which side of a comparison could not be read.
+enum CompareError {
+    Original(io::Error),
+    Decompressed(io::Error),
+}
 
-// Get a mapping from character to bit vector from the Huffman tree
-fn codebook(huff: &HuffTree) -> Codebook {
-    fn traverse(huff: &HuffTree, mut bv: BitVec) -> Codebook {
-        match huff {
-            HuffTree::Leaf { chr, .. } => {
-                let mut btm = BTreeMap::new();
-                btm.insert(chr.clone(), bv);
-                btm
+/// The bytes before and after a difference shown by verify.
+const CONTEXT: usize = 8;
+
+/// The number of bytes compare reads from each side at a time.
+const PIECE: u64 = 1 << 16;
+
+/// Replace the contents of buf with the next PIECE bytes of the reader, fewer
+/// only at its end. Interrupted reads are retried.
+fn read_piece<R: Read>(reader: &mut R, buf: &mut Vec<u8>) -> io::Result<()> {
+    buf.clear();
+    reader.take(PIECE).read_to_end(buf).map(drop)
+}
+
+/// The len bytes from skip on of before, then chunk, then what follows in
+/// rest.
+fn window<R: Read>(
+    before: &[u8],
+    chunk: &[u8],
+    rest: &mut R,
+    skip: usize,
+    len: usize,
+) -> io::Result<Vec<u8>> {
+    let mut window = before
+        .iter()
+        .chain(chunk)
+        .skip(skip)
+        .take(len)
+        .copied()
+        .collect::<Vec<_>>();
+    rest.take((len - window.len()) as u64)
+        .read_to_end(&mut window)?;
+    Ok(window)
+}
+
+/// Compare the original with the decompressed bytes, reading both piece by
+/// piece. The decompressed bytes are always read to their end, so that a
+/// corrupt archive is an error rather than a difference.
+fn compare<A: Read, B: Read>(
+    mut original: A,
+    mut decompressed: B,
+) -> Result<Comparison, CompareError> {
+    use CompareError::{Decompressed, Original};
+    let mut original_chunk = Vec::new();
+    let mut decompressed_chunk = Vec::new();
+    let mut offset = 0;
+    // The last CONTEXT bytes before the pieces, which both have in common.
+    let mut before = Vec::new();
+    loop {
+        read_piece(&mut original, &mut original_chunk).map_err(Original)?;
+        read_piece(&mut decompressed, &mut decompressed_chunk).map_err(Decompressed)?;
+        let (original_len, decompressed_len) = (original_chunk.len(), decompressed_chunk.len());
+        let comparison = match original_chunk
+            .iter()
+            .zip(&decompressed_chunk)
+            .position(|(a, b)| a != b)
+        {
+            Some(i) => {
+                let start = (offset + i).saturating_sub(CONTEXT);
+                let skip = start - (offset - before.len());
+                let len = offset + i + CONTEXT + 1 - start;
+                Comparison::Differs {
+                    offset: offset + i,
+                    original: window(&before, &original_chunk, &mut original, skip, len)
+                        .map_err(Original)?,
+                    decompressed: window(
+                        &before,
+                        &decompressed_chunk,
+                        &mut decompressed,
+                        skip,
+                        len,
+                    )
+                    .map_err(Decompressed)?,
+                }
             }
-            HuffTree::Node { left, right, .. } => {
-                let mut br = bv.clone();
-                br.push(true); // bit-vector right
-                bv.push(false); // bit-vector left
-                let mut btm = traverse(left, bv);
-                btm.append(&mut traverse(right, br));
-                btm
+            // Only a piece at the end is shorter than the buffers.
+            None if original_len != decompressed_len => Comparison::Length {
+                original: offset
+                    + original_len
+                    + io::copy(&mut original, &mut io::sink()).map_err(Original)? as usize,
+                decompressed: offset + decompressed_len,
+            },
+            None if original_len == 0 => Comparison::Identical { len: offset },
+            None => {
+                offset += original_len;
+                before.extend(&original_chunk);
+                before.drain(..before.len().saturating_sub(CONTEXT));
+                continue;
             }
-        }
+        };
+        let rest = io::copy(&mut decompressed, &mut io::sink()).map_err(Decompressed)?;
+        return Ok(match comparison {
+            Comparison::Length {
+                original,
+                decompressed,
+            } => Comparison::Length {
+                original,
+                decompressed: decompressed + rest as usize,
+            },
+            comparison => comparison,
+        });
     }
-    traverse(huff, BitVec::new())
 }
 
-// Given a message m, encode returns the Huffman encoded message.
-fn encode(message: &str) -> Option<(Codebook, BitVec)> {
-    let frequency = frequency(&mut message.chars());
-    let hufftree = huffman(frequency)?;
-    println!("{}", hufftree);
-    let codebook = codebook(&hufftree);
-    let bits = message.chars().flat_map(|c| codebook[&c].clone()).collect();
-    Some((codebook, bits))
+fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
-fn decode(codebook: &Codebook, mut bits: &[bool]) -> String {
-    let mut decoded = String::new();
-    while !bits.is_empty() {
-        let mut found = false;
-        for (chr, code) in codebook {
-            if bits.starts_with(code) {
-                decoded.push(*chr);
-                bits = &bits[code.len()..];
-                found = true;
-                break;
-            }
+/// A progress line on stderr that is overwritten by each update, shown only
+/// if stderr is a terminal.
+struct ProgressLine {
+    action: &'static str,
+    start: Instant,
+    enabled: bool,
+}
+
+impl ProgressLine {
+    fn new(action: &'static str) -> ProgressLine {
+        ProgressLine {
+            action,
+            start: Instant::now(),
+            enabled: io::stderr().is_terminal(),
         }
-        if !found {
-            panic!("No entry in code")
+    }
+
+    fn update(&self, progress: Progress) {
+        if !self.enabled {
+            return;
         }
+        let percent = match progress.fraction() {
+            Some(fraction) => format!("{:5.1}%", fraction * 100.0),
+            None => "    ?%".to_string(),
+        };
+        let seconds = self.start.elapsed().as_secs_f64();
+        let rate = match seconds {
+            seconds if seconds > 0.0 => progress.symbols as f64 / seconds / 1e6,
+            _ => 0.0,
+        };
+        eprint!(
+            "\r{} {}  {} symbols, {} bytes, {:.1} M symbols/s",
+            self.action, percent, progress.symbols, progress.bytes_written, rate
+        );
     }
-    decoded
-}
 
-fn frequency<T: Ord, I: Iterator<Item = T>>(iter: &mut I) -> BTreeMap<T, u32> {
-    iter.fold(BTreeMap::new(), |mut map, element| {
-        *map.entry(element).or_default() += 1;
-        map
-    })
+    fn finish(&self) {
+        if self.enabled {
+            eprintln!();
+        }
+    }
 }
 
-fn main() {
-    let examples = vec!["BACADAEAFABBAAAGAH", "aardvarks ate apples around aachen"];
-    for message in examples {
-        if let Some((cb, cs)) = encode(message) {
-            for (chr, bitvec) in &cb {
-                println!("{}: {}", chr, bitvec_str(bitvec));
+fn run(command: Command) -> Result<ExitCode, String> {
+    match command {
+        Command::Compress {
+            input,
+            output,
+            stats,
+            explain,
+            book,
+            escape,
+            rle,
+            mtf,
+            verify,
+        } => {
+            let message = read_text(&input)?;
+            let compressed = match book {
+                Some(book) => compress_with_codebook(&message, &read_book(&book)?, escape)
+                    .map_err(|err| format!("cannot compress with {}: {}", book, err))?,
+                None => {
+                    let options = CompressOptions {
+                        rle,
+                        mtf,
+                        verify,
+                        ..CompressOptions::default()
+                    };
+                    let line = ProgressLine::new("compressing");
+                    let compressed = try_compress_with_progress(&message, options, |progress| {
+                        line.update(progress)
+                    });
+                    line.finish();
+                    compressed.map_err(|err| format!("cannot compress: {}", err))?
+                }
+            };
+            write_output(&output, &compressed)?;
+            print_sizes(message.len(), compressed.len());
+            if stats || explain {
+                // The statistics of the symbols that were Huffman coded.
+                let mut coded = message;
+                if mtf {
+                    coded = mtf_encode_text(&coded).1;
+                }
+                if rle {
+                    coded = rle_encode(&coded);
+                }
+                let frequency = frequency(&mut coded.chars());
+                let codebook = huffman(frequency.clone())
+                    .map(|huff| canonical_codebook(&huff))
+                    .unwrap_or_default();
+                if stats {
+                    let stats =
+                        CompressionStats::new(&frequency, &codebook, InputEncoding::default());
+                    eprintln!("stats: {}", stats);
+                }
+                if explain {
+                    eprintln!("{}", SavingsReport::new(&frequency, &codebook));
+                }
+            }
+        }
+        Command::Decompress {
+            input,
+            output,
+            verify,
+            book,
+        } => {
+            let data = read_input(&input)?;
+            let message = match book {
+                Some(book) => decompress_with_codebook(&data, &read_book(&book)?),
+                None => {
+                    let options = DecompressOptions {
+                        verify,
+                        ..DecompressOptions::default()
+                    };
+                    let line = ProgressLine::new("decompressing");
+                    let message =
+                        decompress_with_progress(&data, options, |progress| line.update(progress));
+                    line.finish();
+                    message
+                }
+            };
+            let message =
+                message.map_err(|err| format!("{} is not a valid archive: {}", input, err))?;
+            write_output(&output, message.as_bytes())?;
+            print_sizes(message.len(), data.len());
+        }
+        Command::Archive {
+            inputs,
+            output,
+            per_file,
+        } => {
+            let mut entries = Vec::new();
+            for input in &inputs {
+                let name = Path::new(input)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or_else(|| format!("{} has no file name", input))?;
+                entries.push((name.to_string(), read_text(input)?));
+            }
+            let original = entries.iter().map(|(_, text)| text.len()).sum();
+            let mode = match per_file {
+                true => CodebookMode::PerFile,
+                false => CodebookMode::Shared,
+            };
+            let archive = Archive::create_with(entries, mode)
+                .map_err(|err| format!("cannot archive: {}", err))?;
+            write_output(&output, &archive)?;
+            print_sizes(original, archive.len());
+        }
+        Command::Train { inputs, output } => {
+            let mut table = FrequencyTable::new();
+            for input in &inputs {
+                table
+                    .try_merge(count_input(input)?)
+                    .map_err(|err| format!("cannot train: {}", err))?;
             }
-            println!("String: {}\n", bitvec_str(&cs));
-            println!("Decoded: {}\n", decode(&cb, &cs));
+            let frequency = table.into_counts();
+            let options = CodecOptions {
+                escape_unknown: true,
+            };
+            let codec = HuffmanCodec::with_options(frequency, options)
+                .map_err(|err| format!("cannot train: {}", err))?;
+            write_output(&output, codec.codebook().to_text().as_bytes())?;
+            eprintln!("codebook: {} symbols", codec.codebook().len());
+        }
+        Command::Verify {
+            original,
+            compressed,
+            book,
+        } => {
+            let open = |path: &str| {
+                fs::File::open(path)
+                    .map(BufReader::new)
+                    .map_err(|err| format!("cannot read {}: {}", path, err))
+            };
+            let compressed_len = fs::metadata(&compressed)
+                .map_err(|err| format!("cannot read {}: {}", compressed, err))?
+                .len();
+            let book = book.map(|book| read_book(&book)).transpose()?;
+            let not_an_archive = |err| format!("{} is not a valid archive: {}", compressed, err);
+            let message = ContainerReader::with_codebook(open(&compressed)?, book.as_ref())
+                .map_err(not_an_archive)?;
+            let comparison = compare(open(&original)?, message).map_err(|err| match err {
+                CompareError::Original(err) => format!("cannot read {}: {}", original, err),
+                CompareError::Decompressed(err) => not_an_archive(err),
+            })?;
+            let report = match comparison {
+                Comparison::Identical { len } => {
+                    let ratio = ratio(len, compressed_len as usize);
+                    println!("OK ({} bytes, ratio {:.1}%)", len, ratio);
+                    return Ok(ExitCode::SUCCESS);
+                }
+                Comparison::Differs {
+                    offset,
+                    original,
+                    decompressed,
+                } => format!(
+                    "content differs at byte {}\n  original:     {}\n  decompressed: {}",
+                    offset,
+                    hex(&original),
+                    hex(&decompressed)
+                ),
+                Comparison::Length {
+                    original,
+                    decompressed,
+                } => format!(
+                    "length differs: the original has {} bytes, the decompressed data {}",
+                    original, decompressed
+                ),
+            };
+            println!("{}", report);
+            return Ok(ExitCode::from(3));
+        }
+        Command::Extract { input, name, dir } => {
+            let data = read_input(&input)?;
+            let archive = Archive::read(&data)
+                .map_err(|err| format!("{} is not a valid archive: {}", input, err))?;
+            let entries = match &name {
+                Some(name) => vec![archive
+                    .get(name)
+                    .ok_or_else(|| format!("{} has no entry {:?}", input, name))?],
+                None => archive.entries().collect(),
+            };
+            fs::create_dir_all(&dir).map_err(|err| format!("cannot create {}: {}", dir, err))?;
+            for entry in entries {
+                // Never write outside of dir, whatever the archive says.
+                let file_name = Path::new(entry.name()).file_name();
+                if file_name.and_then(|name| name.to_str()) != Some(entry.name()) {
+                    return Err(format!("invalid entry name {:?}", entry.name()));
+                }
+                let text = entry.decode().map_err(|err| err.to_string())?;
+                let path = Path::new(&dir).join(entry.name());
+                fs::write(&path, text)
+                    .map_err(|err| format!("cannot write {}: {}", path.display(), err))?;
+            }
+        }
+        Command::Freq { input, order, top } => {
+            let table = count_input(&input)?;
+            let histogram = frequency_histogram(table.counts(), order, top);
+            write_output("-", histogram.as_bytes())?;
+        }
+        Command::Inspect { input, json } => {
+            let message = match input.as_str() {
+                "-" => read_text(&input)?,
+                path if Path::new(path).is_file() => read_text(path)?,
+                _ => input,
+            };
+            let codec = HuffmanCodec::from_message(&message)
+                .map_err(|err| format!("cannot encode: {}", err))?;
+            let report = match json {
+                true => inspect_to_json(&codec),
+                false => format!(
+                    "{}\n{}",
+                    codec.tree(),
+                    codec.codebook().to_table(SortBy::CodeLength)
+                ),
+            };
+            write_output("-", report.as_bytes())?;
+        }
+        Command::Demo {
+            verbose,
+            explain,
+            steps,
+        } => demo(verbose, explain, steps),
+        Command::Dot { message } => {
+            let codec = HuffmanCodec::from_message(&message)
+                .map_err(|err| format!("cannot encode: {}", err))?;
+            write_output("-", codec.tree().to_dot().as_bytes())?;
         }
     }
+    Ok(ExitCode::SUCCESS)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn frequency_test_numbers() {
-        // given:
-        let numbers = vec![1, 2, 3, 3, 2, 3, 5];
-
-        // when:
-        let actual = frequency(&mut numbers.iter());
-
-        // then:
-        assert_eq!(actual[&1], 1);
-        assert_eq!(actual[&2], 2);
-        assert_eq!(actual[&3], 3);
-        assert_eq!(actual[&5], 1);
+fn demo(verbose: bool, explain: bool, steps: Option<String>) {
+    let examples = match &steps {
+        Some(message) => vec![message.as_str()],
+        None => vec!["BACADAEAFABBAAAGAH", "aardvarks ate apples around aachen"],
+    };
+    for message in examples.iter() {
+        // The codec has the canonical tree with the same code lengths instead.
+        let built = steps.as_ref().and_then(|_| print_steps(message));
+        let codec = match HuffmanCodec::from_message(message) {
+            Ok(codec) => codec,
+            Err(err) => {
+                println!("Encoding failed: {}\n", err);
+                continue;
+            }
+        };
+        if verbose || built.is_some() {
+            let mut rendered = String::new();
+            dump_tree(built.as_ref().unwrap_or(codec.tree()), &mut rendered)
+                .expect("writing to a String cannot fail");
+            print!("{}", rendered);
+        }
+        print!("{}", codec.codebook().to_table(SortBy::CodeLength));
+        let bits = match codec.encode(message) {
+            Ok(bits) => bits,
+            Err(err) => {
+                println!("Encoding failed: {}\n", err);
+                continue;
+            }
+        };
+        println!("String: {}\n", bits);
+        match codec.decode(&bits) {
+            Ok(decoded) => println!("Decoded: {}\n", decoded),
+            Err(err) => println!("Decoding failed: {}\n", err),
+        }
+        let frequency = frequency(&mut message.chars());
+        let stats = CompressionStats::new(&frequency, codec.codebook(), InputEncoding::default());
+        println!("Stats: {}\n", stats);
+        if explain {
+            println!("{}\n", SavingsReport::new(&frequency, codec.codebook()));
+        }
     }
+}
 
-    #[test]
-    fn frequency_test_chars() {
-        // given:
-        let m = "Hello World";
-
-        // when:
-        let actual = frequency(&mut m.chars());
+/// Print each merge of the construction of the tree for the message and
+/// return the tree.
+fn print_steps(message: &str) -> Option<HuffTree> {
+    let label =
+        |tree: &ForestTree<'_, char, u64>| format!("{:?} ({})", tree.chars(), tree.weight());
+    let tree = huffman_with_observer(frequency(&mut message.chars()), |event| {
+        println!(
+            "Step {}: {} + {} -> {}",
+            event.step(),
+            label(&event.left()),
+            label(&event.right()),
+            label(&event.merged())
+        );
+        let forest = event.forest().iter().map(label).collect::<Vec<_>>();
+        println!("  forest: {}", forest.join(", "));
+    });
+    println!();
+    tree
+}
 
-        // then:
-        assert_eq!(actual[&'H'], 1);
-        assert_eq!(actual[&'e'], 1);
-        assert_eq!(actual[&'l'], 3);
-        assert_eq!(actual[&'o'], 2);
+fn main() -> ExitCode {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(command) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
     }
 }
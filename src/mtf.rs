@@ -0,0 +1,228 @@
+//! The move-to-front transform, see CompressOptions::mtf.
+//!
+//! Each symbol is replaced by its index in a list that starts as the sorted
+//! alphabet and the symbol is moved to the front, so symbols used again soon
+//! get small indices.
+
+use alloc::collections::BTreeSet;
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MtfError {
+    /// The index at the position is not less than the `len` symbols of the
+    /// alphabet.
+    IndexOutOfRange {
+        position: usize,
+        index: u32,
+        len: usize,
+    },
+    /// The decoded text would exceed the limit of max_bytes bytes.
+    LimitExceeded { max_bytes: usize },
+}
+
+impl Display for MtfError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MtfError::IndexOutOfRange {
+                position,
+                index,
+                len,
+            } => write!(
+                f,
+                "index {} at position {} is out of range for {} symbols",
+                index, position, len
+            ),
+            MtfError::LimitExceeded { max_bytes } => {
+                write!(f, "the decoded text exceeds {} bytes", max_bytes)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MtfError {}
+
+/// The sorted distinct symbols, the initial list, and the index of each
+/// symbol in the list before it is moved to the front.
+pub fn mtf_encode<T: Ord + Clone>(symbols: &[T]) -> (Vec<T>, Vec<u32>) {
+    let alphabet = symbols
+        .iter()
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let mut list = alphabet.clone();
+    let indices = symbols
+        .iter()
+        .map(|symbol| {
+            let index = list
+                .iter()
+                .position(|other| other == symbol)
+                .expect("the alphabet has every symbol");
+            list[..=index].rotate_right(1);
+            index as u32
+        })
+        .collect();
+    (alphabet, indices)
+}
+
+/// Reverse mtf_encode with the same alphabet.
+pub fn mtf_decode<T: Clone>(alphabet: &[T], indices: &[u32]) -> Result<Vec<T>, MtfError> {
+    let mut list = alphabet.to_vec();
+    indices
+        .iter()
+        .enumerate()
+        .map(|(position, &index)| move_to_front(&mut list, position, index).cloned())
+        .collect()
+}
+
+/// Move the symbol at the index of the list to the front and return it.
+fn move_to_front<T>(list: &mut [T], position: usize, index: u32) -> Result<&T, MtfError> {
+    if index as usize >= list.len() {
+        return Err(MtfError::IndexOutOfRange {
+            position,
+            index,
+            len: list.len(),
+        });
+    }
+    list[..=index as usize].rotate_right(1);
+    Ok(&list[0])
+}
+
+/// Transform the text like mtf_encode, but as a text with each index as a
+/// symbol, so it can be coded like any text.
+pub fn mtf_encode_text(text: &str) -> (Vec<char>, String) {
+    let (alphabet, indices) = mtf_encode(&text.chars().collect::<Vec<_>>());
+    (alphabet, indices.into_iter().map(index_symbol).collect())
+}
+
+/// Reverse mtf_encode_text with the same alphabet.
+pub fn mtf_decode_text(alphabet: &[char], text: &str) -> Result<String, MtfError> {
+    mtf_decode_text_with_limit(alphabet, text, usize::MAX)
+}
+
+/// Reverse mtf_encode_text, but fail instead of producing more than
+/// max_bytes bytes of UTF-8.
+pub fn mtf_decode_text_with_limit(
+    alphabet: &[char],
+    text: &str,
+    max_bytes: usize,
+) -> Result<String, MtfError> {
+    let mut list = alphabet.to_vec();
+    let mut decoded = String::with_capacity(text.len().min(max_bytes));
+    for (position, chr) in text.chars().enumerate() {
+        let &chr = move_to_front(&mut list, position, symbol_index(chr))?;
+        if decoded.len() + chr.len_utf8() > max_bytes {
+            return Err(MtfError::LimitExceeded { max_bytes });
+        }
+        decoded.push(chr);
+    }
+    Ok(decoded)
+}
+
+/// The first surrogate code point and the number of them, which are no chars.
+const SURROGATES: (u32, u32) = (0xd800, 0x800);
+
+/// The char standing for the index, the index-th char. There are as many
+/// indices as there are chars in an alphabet.
+fn index_symbol(index: u32) -> char {
+    let code_point = match index < SURROGATES.0 {
+        true => index,
+        false => index + SURROGATES.1,
+    };
+    char::from_u32(code_point).expect("an alphabet has fewer indices than there are chars")
+}
+
+/// The index the char stands for, the inverse of index_symbol.
+fn symbol_index(chr: char) -> u32 {
+    match chr as u32 {
+        code_point if code_point < SURROGATES.0 => code_point,
+        code_point => code_point - SURROGATES.1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn mtf_indices() {
+        // when:
+        let actual = mtf_encode(&"bananaaa".chars().collect::<Vec<_>>());
+
+        // then:
+        assert_eq!(actual.0, ['a', 'b', 'n']);
+        assert_eq!(actual.1, [1, 1, 2, 1, 1, 1, 0, 0]);
+    }
+
+    #[test]
+    fn mtf_round_trip() {
+        for text in ["", "a", "bananaaa", "Grüße aus München für 5 € 🦀🦀🦀"] {
+            // when:
+            let (alphabet, encoded) = mtf_encode_text(text);
+            let actual = mtf_decode_text(&alphabet, &encoded);
+
+            // then:
+            assert_eq!(actual.as_deref(), Ok(text));
+            assert_eq!(encoded.chars().count(), text.chars().count());
+        }
+    }
+
+    #[test]
+    fn mtf_generic_symbols() {
+        // given:
+        let symbols = vec!["the", "cat", "the", "the", "hat"];
+
+        // when:
+        let (alphabet, indices) = mtf_encode(&symbols);
+        let actual = mtf_decode(&alphabet, &indices);
+
+        // then:
+        assert_eq!(indices, [2, 1, 1, 0, 2]);
+        assert_eq!(actual, Ok(symbols));
+    }
+
+    #[test]
+    fn mtf_index_symbols_skip_surrogates() {
+        for index in [0, 0xd7ff, 0xd800, 0x10ffff - 0x800] {
+            // when:
+            let actual = symbol_index(index_symbol(index));
+
+            // then:
+            assert_eq!(actual, index);
+        }
+        assert_eq!(index_symbol(0xd800), '\u{e000}');
+    }
+
+    #[test]
+    fn mtf_decode_index_out_of_range() {
+        // when:
+        let actual = mtf_decode(&['a', 'b'], &[1, 2]);
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(MtfError::IndexOutOfRange {
+                position: 1,
+                index: 2,
+                len: 2
+            })
+        );
+    }
+
+    #[test]
+    fn mtf_decode_text_with_limit_counts_bytes() {
+        // given: the indices are shorter in UTF-8 than the symbols
+        let (alphabet, encoded) = mtf_encode_text("€€€");
+
+        // when:
+        let limited = mtf_decode_text_with_limit(&alphabet, &encoded, 8);
+        let exact = mtf_decode_text_with_limit(&alphabet, &encoded, 9);
+
+        // then:
+        assert_eq!(limited, Err(MtfError::LimitExceeded { max_bytes: 8 }));
+        assert_eq!(exact.as_deref(), Ok("€€€"));
+    }
+}
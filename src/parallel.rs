@@ -0,0 +1,383 @@
+//! Counting, encoding and decompressing large messages on several threads.
+
+use crate::bits::Bits;
+use crate::code::{encode_with, Codebook};
+use crate::container::{
+    decode_bits, decode_block, decompress, read_block, read_header, read_index, reverse_transforms,
+    ContainerError, Header, Layout,
+};
+use crate::crc::crc32;
+use crate::error::HuffmanError;
+use crate::progress::Reporter;
+use crate::tree::FrequencyTable;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::thread;
+
+/// Count the symbols like frequency, but split the data into up to threads
+/// chunks that are counted concurrently.
+pub fn frequency_parallel(data: &str, threads: usize) -> BTreeMap<char, u64> {
+    let chunks = split_chars(data, threads.max(1));
+    let tables = thread::scope(|scope| {
+        let handles = chunks
+            .iter()
+            .map(|chunk| {
+                scope.spawn(move || {
+                    let mut table = FrequencyTable::new();
+                    table.count_str(chunk);
+                    table
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("counting does not panic"))
+            .collect::<Vec<_>>()
+    });
+    let mut total = FrequencyTable::new();
+    for table in tables {
+        total.merge(table);
+    }
+    total.into_counts()
+}
+
+/// Encode the message with the codebook like encode_with, but split it into
+/// up to num_threads chunks that are encoded concurrently. The result is
+/// identical to encode_with, including the first unknown symbol on error.
+pub fn encode_parallel(
+    codebook: &Codebook,
+    message: &str,
+    num_threads: usize,
+) -> Result<Bits, HuffmanError> {
+    let chunks = split_chars(message, num_threads.max(1));
+    let encoded = thread::scope(|scope| {
+        let handles = chunks
+            .iter()
+            .map(|chunk| scope.spawn(move || encode_with(codebook, chunk)))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("encoding does not panic"))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    let mut bits = Bits::with_capacity(encoded.iter().map(Bits::len).sum());
+    for chunk in &encoded {
+        bits.extend_from_code(chunk);
+    }
+    Ok(bits)
+}
+
+/// Decompress like decompress, but decode the blocks of the output of
+/// compress_blocks or of CompressOptions::block_size on up to threads threads.
+/// The message is identical to decompress, an error in a block is returned as
+/// ContainerError::Block with the index of the first failing block. Other
+/// formats have a single block and are decoded by decompress.
+pub fn decompress_parallel(data: &[u8], threads: usize) -> Result<String, ContainerError> {
+    let Header {
+        codebook,
+        layout,
+        bit_order,
+        rle,
+        alphabet,
+        checksum,
+        data: blocks_data,
+    } = read_header(data)?;
+    let in_block = |index| {
+        move |error| ContainerError::Block {
+            index,
+            error: Box::new(error),
+        }
+    };
+    let decoded = match layout {
+        Layout::Single => return decompress(data),
+        Layout::Blocks => {
+            let (index, blocks_data) = read_index(blocks_data)?;
+            let blocks = index.iter().enumerate().collect::<Vec<_>>();
+            decode_chunks(&blocks, threads, |&(i, block)| {
+                let mut out = String::new();
+                decode_block(
+                    &codebook,
+                    blocks_data,
+                    block,
+                    bit_order,
+                    &mut out,
+                    usize::MAX,
+                    &mut Reporter::new(&mut |_| {}, usize::MAX, None),
+                )
+                .map_err(in_block(i))?;
+                Ok(out)
+            })?
+        }
+        Layout::BlockCodebooks => {
+            if blocks_data.len() < 8 {
+                return Err(ContainerError::TruncatedLength);
+            }
+            let (count, mut rest) = blocks_data.split_at(8);
+            let count = u64::from_be_bytes(count.try_into().unwrap());
+            // Only the blocks before it tell where a block starts, so they are
+            // read here and just decoded concurrently.
+            let mut blocks = Vec::new();
+            for i in 0..count {
+                let (codebook, bits, tail) =
+                    read_block(rest, bit_order).map_err(in_block(i as usize))?;
+                blocks.push((i as usize, codebook, bits));
+                rest = tail;
+            }
+            if !rest.is_empty() {
+                return Err(ContainerError::PayloadLength {
+                    expected: 0,
+                    actual: rest.len(),
+                });
+            }
+            decode_chunks(&blocks, threads, |(i, codebook, bits)| {
+                let mut out = String::new();
+                decode_bits(
+                    codebook,
+                    bits,
+                    None,
+                    &mut out,
+                    usize::MAX,
+                    &mut Reporter::new(&mut |_| {}, usize::MAX, None),
+                )
+                .map_err(|err| in_block(*i)(ContainerError::Decode(err)))?;
+                Ok(out)
+            })?
+        }
+    };
+    let decoded = reverse_transforms(decoded, rle, alphabet.as_deref(), usize::MAX)?;
+    let actual = crc32(decoded.as_bytes());
+    if actual != checksum {
+        return Err(ContainerError::ChecksumMismatch {
+            expected: checksum,
+            actual,
+        });
+    }
+    Ok(decoded)
+}
+
+/// Decode the blocks on up to threads threads, each taking a run of
+/// consecutive blocks, and concatenate the results in the order of the
+/// blocks. Returns the error of the first failing block.
+fn decode_chunks<B: Sync, F>(
+    blocks: &[B],
+    threads: usize,
+    decode: F,
+) -> Result<String, ContainerError>
+where
+    F: Fn(&B) -> Result<String, ContainerError> + Sync,
+{
+    let chunk_len = blocks.len().div_ceil(threads.max(1)).max(1);
+    let decode = &decode;
+    let decoded = thread::scope(|scope| {
+        let handles = blocks
+            .chunks(chunk_len)
+            .map(|chunk| {
+                scope.spawn(move || chunk.iter().map(decode).collect::<Result<Vec<_>, _>>())
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("decoding does not panic"))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+    Ok(decoded.into_iter().flatten().collect())
+}
+
+/// Split s into at most n non-empty chunks of about equal byte length, each
+/// ending on a char boundary.
+fn split_chars(s: &str, n: usize) -> Vec<&str> {
+    let chunk_len = s.len().div_ceil(n).max(1);
+    let mut chunks = Vec::with_capacity(n);
+    let mut rest = s;
+    while !rest.is_empty() {
+        let mut end = chunk_len.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end += 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::encode;
+    use crate::container::{
+        compress, compress_blocks, compress_with_progress, CompressOptions, HeaderMode,
+    };
+    use crate::test_support::{random_numbers, random_text};
+    use crate::tree::frequency;
+
+    #[test]
+    fn encode_parallel_matches_encode_with() {
+        // given: symbols of 1 to 4 UTF-8 bytes and odd code lengths
+        let alphabet = "aaaaabbbcdeäöü€🦀".chars().collect::<Vec<_>>();
+        let message = random_numbers(7)
+            .take(100_000)
+            .map(|x| alphabet[x % alphabet.len()])
+            .collect::<String>();
+        let codebook = encode(&message).codebook;
+        let expected = encode_with(&codebook, &message).unwrap();
+
+        for num_threads in [1, 2, 3, 7, 16].iter() {
+            // when:
+            let actual = encode_parallel(&codebook, &message, *num_threads);
+
+            // then:
+            assert_eq!(actual.as_ref(), Ok(&expected), "{} threads", num_threads);
+        }
+    }
+
+    #[test]
+    fn encode_parallel_more_threads_than_chars() {
+        // given:
+        let codebook = encode("a€🦀").codebook;
+
+        for message in ["", "a", "€", "a€🦀"].iter() {
+            // when:
+            let actual = encode_parallel(&codebook, message, 64);
+
+            // then:
+            assert_eq!(actual, encode_with(&codebook, message));
+        }
+    }
+
+    #[test]
+    fn encode_parallel_reports_first_unknown_symbol() {
+        // given:
+        let codebook = encode("abc").codebook;
+        let message = format!("{}x{}y", "abc".repeat(100), "cba".repeat(100));
+
+        // when:
+        let actual = encode_parallel(&codebook, &message, 4);
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::UnknownSymbol('x')));
+    }
+
+    #[test]
+    fn frequency_parallel_matches_frequency() {
+        // given:
+        let message = random_text(11, 2_000_000);
+        let expected = frequency(&mut message.chars());
+
+        for threads in [1, 2, 5, 8].iter() {
+            // when:
+            let actual = frequency_parallel(&message, *threads);
+
+            // then:
+            assert_eq!(actual, expected, "{} threads", threads);
+        }
+    }
+
+    #[test]
+    fn frequency_parallel_short_inputs() {
+        for message in ["", "a", "ü🦀", "abc"].iter() {
+            for threads in [0, 1, 16].iter() {
+                // when:
+                let actual = frequency_parallel(message, *threads);
+
+                // then:
+                assert_eq!(actual, frequency(&mut message.chars()));
+            }
+        }
+    }
+
+    #[test]
+    fn split_chars_on_boundaries() {
+        // when: 10 bytes in chunks of 3, extended to the next char boundary
+        let actual = split_chars("ä€🦀b", 4);
+
+        // then:
+        assert_eq!(actual, ["ä€", "🦀", "b"]);
+    }
+
+    #[test]
+    fn decompress_parallel_matches_decompress() {
+        // given: 5 blocks, the last one shorter
+        let message = random_text(3, 4_500);
+        let options = CompressOptions {
+            block_size: Some(1_000),
+            ..CompressOptions::default()
+        };
+        for data in [
+            compress(&message),
+            compress_blocks(&message, HeaderMode::Lengths, 1_000),
+            compress_with_progress(&message, options, |_| {}),
+            compress_with_progress(
+                &message,
+                CompressOptions {
+                    rle: true,
+                    ..options
+                },
+                |_| {},
+            ),
+            compress_with_progress(
+                &message,
+                CompressOptions {
+                    rle: true,
+                    mtf: true,
+                    ..options
+                },
+                |_| {},
+            ),
+        ] {
+            for threads in [0, 1, 2, 3, 5, 16] {
+                // when:
+                let actual = decompress_parallel(&data, threads);
+
+                // then:
+                assert_eq!(actual, decompress(&data), "{} threads", threads);
+                assert_eq!(actual.as_deref(), Ok(&message[..]));
+            }
+        }
+    }
+
+    #[test]
+    fn decompress_parallel_reports_corrupted_block() {
+        // given: 5 blocks, the middle one only of 'a's with the code 0
+        let message = [
+            random_text(1, 100),
+            random_text(2, 100),
+            "a".repeat(100),
+            random_text(4, 100),
+            random_text(5, 100),
+        ]
+        .concat();
+        let options = CompressOptions {
+            block_size: Some(100),
+            header_mode: Some(HeaderMode::Codebook),
+            ..CompressOptions::default()
+        };
+        let mut data = compress_with_progress(&message, options, |_| {});
+        let mut payload = 100u64.to_be_bytes().to_vec();
+        payload.extend([0; 13]);
+        let start = data
+            .windows(payload.len())
+            .position(|window| window == &payload[..])
+            .unwrap()
+            + 8;
+        data[start..start + 13].fill(0xff);
+
+        for threads in [1, 2, 3, 5] {
+            // when:
+            let actual = decompress_parallel(&data, threads);
+
+            // then:
+            assert_eq!(
+                actual,
+                Err(ContainerError::Block {
+                    index: 2,
+                    error: Box::new(ContainerError::Decode(HuffmanError::InvalidPrefix {
+                        bit_offset: 0
+                    })),
+                }),
+                "{} threads",
+                threads
+            );
+        }
+    }
+}
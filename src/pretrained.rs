@@ -0,0 +1,117 @@
+//! Codebooks trained once and reused for many messages.
+
+use crate::code::{canonical_codebook, Codebook};
+use crate::tree::{frequency, huffman};
+use alloc::collections::BTreeMap;
+
+/// Relative frequencies of the letters a-z and space in English text, in
+/// occurrences per 10000 letters.
+const ENGLISH_LOWERCASE: [(char, u64); 27] = [
+    (' ', 2000),
+    ('a', 817),
+    ('b', 149),
+    ('c', 278),
+    ('d', 425),
+    ('e', 1270),
+    ('f', 223),
+    ('g', 202),
+    ('h', 609),
+    ('i', 697),
+    ('j', 15),
+    ('k', 77),
+    ('l', 403),
+    ('m', 241),
+    ('n', 675),
+    ('o', 751),
+    ('p', 193),
+    ('q', 10),
+    ('r', 599),
+    ('s', 633),
+    ('t', 906),
+    ('u', 276),
+    ('v', 98),
+    ('w', 236),
+    ('x', 15),
+    ('y', 197),
+    ('z', 7),
+];
+
+/// Constructors for codebooks that do not depend on the message to encode.
+pub trait Pretrained: Sized {
+    /// The canonical codebook for the symbol frequencies of the corpus, None
+    /// for an empty corpus.
+    fn train(corpus: &str) -> Option<Self>;
+    /// A static codebook for lowercase English text: a-z and space.
+    fn english_lowercase() -> Self;
+}
+
+impl Pretrained for Codebook {
+    fn train(corpus: &str) -> Option<Codebook> {
+        Some(canonical_codebook(&huffman(frequency(
+            &mut corpus.chars(),
+        ))?))
+    }
+    fn english_lowercase() -> Codebook {
+        let frequency = ENGLISH_LOWERCASE
+            .iter()
+            .copied()
+            .collect::<BTreeMap<_, _>>();
+        canonical_codebook(&huffman(frequency).expect("the table is not empty"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{decode, encode_with};
+    use crate::error::HuffmanError;
+
+    #[test]
+    fn trained_codebook_round_trip() {
+        // given:
+        let codebook = Codebook::train("the quick brown fox jumps over the lazy dog").unwrap();
+
+        // when:
+        let bits = encode_with(&codebook, "hello world").unwrap();
+        let actual = decode(&codebook, &bits);
+
+        // then:
+        assert_eq!(actual, Ok("hello world".to_string()));
+    }
+
+    #[test]
+    fn english_lowercase_round_trip() {
+        // given:
+        let codebook = Codebook::english_lowercase();
+
+        // when:
+        let bits = encode_with(&codebook, "hello world").unwrap();
+        let actual = decode(&codebook, &bits);
+
+        // then:
+        assert_eq!(actual, Ok("hello world".to_string()));
+        assert!(codebook[&'e'].len() < codebook[&'z'].len());
+        assert!(bits.len() < "hello world".len() * 5);
+    }
+
+    #[test]
+    fn encode_with_unknown_symbol() {
+        // given:
+        let codebook = Codebook::english_lowercase();
+
+        // when:
+        let actual = encode_with(&codebook, "héllo");
+
+        // then:
+        assert_eq!(actual, Err(HuffmanError::UnknownSymbol('é')));
+    }
+
+    #[test]
+    fn train_empty_corpus() {
+        // when:
+        let actual = Codebook::train("");
+
+        // then:
+        assert_eq!(actual, None);
+    }
+}
@@ -0,0 +1,76 @@
+//! Progress reports for compressing and decompressing long messages.
+
+/// How far compress_with_progress or decompress_with_progress got.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    /// The symbols encoded or decoded so far.
+    pub symbols: usize,
+    /// The number of symbols of the whole message, if it is known yet.
+    pub total_symbols: Option<usize>,
+    /// The bytes of output produced so far.
+    pub bytes_written: usize,
+}
+
+impl Progress {
+    /// The share of the symbols processed so far between 0 and 1, if the
+    /// total is known. An empty message is done right away.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total_symbols.map(|total| match total {
+            0 => 1.0,
+            total => self.symbols as f64 / total as f64,
+        })
+    }
+}
+
+/// The default number of symbols between two progress reports, small enough
+/// for regular updates and large enough to not slow down the coding.
+pub const DEFAULT_PROGRESS_INTERVAL: usize = 1 << 16;
+
+/// Counts symbols and reports the progress every interval symbols.
+pub(crate) struct Reporter<'a> {
+    callback: &'a mut dyn FnMut(Progress),
+    interval: usize,
+    symbols: usize,
+    total_symbols: Option<usize>,
+}
+
+impl<'a> Reporter<'a> {
+    /// An interval of 0 is treated as 1.
+    pub(crate) fn new(
+        callback: &'a mut dyn FnMut(Progress),
+        interval: usize,
+        total_symbols: Option<usize>,
+    ) -> Reporter<'a> {
+        Reporter {
+            callback,
+            interval: interval.max(1),
+            symbols: 0,
+            total_symbols,
+        }
+    }
+
+    pub(crate) fn set_total_symbols(&mut self, total_symbols: usize) {
+        self.total_symbols = Some(total_symbols);
+    }
+
+    /// Count a symbol after which bytes_written bytes are produced.
+    pub(crate) fn symbol(&mut self, bytes_written: usize) {
+        self.symbols += 1;
+        if self.symbols.is_multiple_of(self.interval) {
+            (self.callback)(Progress {
+                symbols: self.symbols,
+                total_symbols: self.total_symbols,
+                bytes_written,
+            });
+        }
+    }
+
+    /// Report that all symbols are done, which makes them the total.
+    pub(crate) fn finish(self, bytes_written: usize) {
+        (self.callback)(Progress {
+            symbols: self.symbols,
+            total_symbols: Some(self.symbols),
+            bytes_written,
+        });
+    }
+}
@@ -0,0 +1,252 @@
+//! A run-length transform applied before Huffman coding, see
+//! CompressOptions::rle.
+//!
+//! A run of at least MIN_RUN equal symbols is the symbol followed by RUN and
+//! a length symbol that repeats the previous symbol up to MAX_CHUNK more
+//! times, longer runs by several RUN and length pairs. A RUN in the text is
+//! written as RUN RUN, so runs of RUN itself work the same way.
+
+use alloc::string::String;
+use core::fmt::{self, Display, Formatter};
+
+/// The marker before a length symbol. It is a Unicode noncharacter, so it does
+/// not occur in regular text; if it does, it is doubled.
+pub const RUN: char = '\u{fffe}';
+
+/// The shortest run that is transformed, shorter ones cost no more as they
+/// are.
+pub const MIN_RUN: usize = 4;
+
+/// The most repetitions a single length symbol stands for.
+pub const MAX_CHUNK: usize = 255;
+
+/// The length symbols start in the Supplementary Private Use Area-A. They only
+/// have this meaning after RUN, anywhere else they are regular symbols.
+const LENGTH_BASE: u32 = 0xf0000;
+
+/// The length symbol that repeats the previous symbol count times.
+fn length_symbol(count: usize) -> char {
+    debug_assert!((1..=MAX_CHUNK).contains(&count));
+    char::from_u32(LENGTH_BASE + count as u32).expect("length symbols are chars")
+}
+
+/// The number of repetitions of the length symbol chr, if it is one.
+fn repetitions(chr: char) -> Option<usize> {
+    let count = (chr as u32).checked_sub(LENGTH_BASE)? as usize;
+    match count {
+        1..=MAX_CHUNK => Some(count),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum RleError {
+    /// The text ends with a RUN that has no length symbol.
+    Truncated,
+    /// The RUN at the symbol index is not followed by RUN or a length symbol,
+    /// but by chr.
+    InvalidLength { index: usize, chr: char },
+    /// The RUN at the symbol index starts the text, so there is nothing to
+    /// repeat.
+    NothingToRepeat { index: usize },
+    /// Expanding the runs would exceed the limit of max_bytes bytes.
+    LimitExceeded { max_bytes: usize },
+}
+
+impl Display for RleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RleError::Truncated => write!(f, "the last run has no length"),
+            RleError::InvalidLength { index, chr } => {
+                write!(f, "invalid run length {:?} at symbol {}", chr, index)
+            }
+            RleError::NothingToRepeat { index } => {
+                write!(f, "the run at symbol {} has nothing to repeat", index)
+            }
+            RleError::LimitExceeded { max_bytes } => {
+                write!(f, "the expanded runs exceed {} bytes", max_bytes)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RleError {}
+
+/// Replace the runs of at least MIN_RUN equal symbols in the text by the
+/// symbol and RUN and length pairs.
+pub fn rle_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(chr) = chars.next() {
+        let mut len = 1;
+        while chars.next_if_eq(&chr).is_some() {
+            len += 1;
+        }
+        let literal = |encoded: &mut String| {
+            encoded.push(chr);
+            if chr == RUN {
+                encoded.push(RUN);
+            }
+        };
+        if len < MIN_RUN {
+            (0..len).for_each(|_| literal(&mut encoded));
+            continue;
+        }
+        literal(&mut encoded);
+        let mut rest = len - 1;
+        while rest > 0 {
+            let count = rest.min(MAX_CHUNK);
+            encoded.push(RUN);
+            encoded.push(length_symbol(count));
+            rest -= count;
+        }
+    }
+    encoded
+}
+
+/// Reverse rle_encode.
+pub fn rle_decode(text: &str) -> Result<String, RleError> {
+    rle_decode_with_limit(text, usize::MAX)
+}
+
+/// Reverse rle_encode, but fail instead of producing more than max_bytes
+/// bytes of UTF-8, as a few symbols can expand to a huge text.
+pub fn rle_decode_with_limit(text: &str, max_bytes: usize) -> Result<String, RleError> {
+    let mut decoded = String::with_capacity(text.len().min(max_bytes));
+    let mut previous = None;
+    let mut chars = text.chars().enumerate();
+    while let Some((index, chr)) = chars.next() {
+        let (chr, count) = match chr {
+            RUN => match chars.next() {
+                None => return Err(RleError::Truncated),
+                Some((_, RUN)) => (RUN, 1),
+                Some((_, len)) => match (previous, repetitions(len)) {
+                    (_, None) => return Err(RleError::InvalidLength { index, chr: len }),
+                    (None, _) => return Err(RleError::NothingToRepeat { index }),
+                    (Some(previous), Some(count)) => (previous, count),
+                },
+            },
+            chr => (chr, 1),
+        };
+        if decoded.len() + chr.len_utf8() * count > max_bytes {
+            return Err(RleError::LimitExceeded { max_bytes });
+        }
+        (0..count).for_each(|_| decoded.push(chr));
+        previous = Some(chr);
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::ToString;
+
+    #[test]
+    fn rle_short_runs_are_unchanged() {
+        // given:
+        let text = "aabbbcaab";
+
+        // when:
+        let actual = rle_encode(text);
+
+        // then:
+        assert_eq!(actual, text);
+    }
+
+    #[test]
+    fn rle_run_of_four() {
+        // given:
+        let text = "xaaaay";
+
+        // when:
+        let actual = rle_encode(text);
+
+        // then:
+        assert_eq!(actual, format!("xa{}{}y", RUN, length_symbol(3)));
+        assert_eq!(rle_decode(&actual), Ok(text.into()));
+    }
+
+    #[test]
+    fn rle_run_longer_than_a_chunk() {
+        // given:
+        let text = format!("{}b", "ä".repeat(600));
+
+        // when:
+        let actual = rle_encode(&text);
+
+        // then:
+        let expected = format!(
+            "ä{run}{}{run}{}{run}{}b",
+            length_symbol(255),
+            length_symbol(255),
+            length_symbol(89),
+            run = RUN
+        );
+        assert_eq!(actual, expected);
+        assert_eq!(rle_decode(&actual), Ok(text));
+    }
+
+    #[test]
+    fn rle_runs_of_the_marker() {
+        // given:
+        let marker = RUN.to_string();
+        for text in [
+            marker.clone(),
+            marker.repeat(3),
+            marker.repeat(300),
+            format!("a{}a", marker.repeat(5)),
+        ] {
+            // when:
+            let actual = rle_encode(&text);
+
+            // then:
+            assert_eq!(rle_decode(&actual), Ok(text));
+        }
+    }
+
+    #[test]
+    fn rle_length_symbols_in_text() {
+        // given:
+        let text = format!(
+            "{}{}",
+            length_symbol(7),
+            length_symbol(7).to_string().repeat(9)
+        );
+
+        // when:
+        let actual = rle_decode(&rle_encode(&text));
+
+        // then:
+        assert_eq!(actual, Ok(text));
+    }
+
+    #[test]
+    fn rle_decode_invalid() {
+        assert_eq!(rle_decode("ab\u{fffe}"), Err(RleError::Truncated));
+        assert_eq!(
+            rle_decode("ab\u{fffe}c"),
+            Err(RleError::InvalidLength { index: 2, chr: 'c' })
+        );
+        assert_eq!(
+            rle_decode(&format!("{}{}", RUN, length_symbol(3))),
+            Err(RleError::NothingToRepeat { index: 0 })
+        );
+    }
+
+    #[test]
+    fn rle_decode_with_limit_stops_before_expanding() {
+        // given:
+        let encoded = rle_encode(&"a".repeat(1000));
+
+        // when:
+        let limited = rle_decode_with_limit(&encoded, 999);
+        let exact = rle_decode_with_limit(&encoded, 1000);
+
+        // then:
+        assert_eq!(limited, Err(RleError::LimitExceeded { max_bytes: 999 }));
+        assert_eq!(exact.map(|text| text.len()), Ok(1000));
+    }
+}
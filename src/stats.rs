@@ -0,0 +1,493 @@
+//! Statistics about how well a message was compressed.
+
+use crate::code::Codebook;
+use crate::container::{header_sizes, smallest_header, HeaderMode};
+use crate::table::escape;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use alloc::{format, string::String};
+use core::fmt::{self, Display, Formatter};
+
+/// How the size of the uncompressed input is counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InputEncoding {
+    /// Every char takes as many bytes as in UTF-8.
+    #[default]
+    Utf8,
+    /// Every char takes the same number of bits, e.g. 8 for ASCII.
+    BitsPerChar(u32),
+}
+
+impl InputEncoding {
+    fn bits(self, chr: char) -> usize {
+        match self {
+            InputEncoding::Utf8 => chr.len_utf8() * 8,
+            InputEncoding::BitsPerChar(bits) => bits as usize,
+        }
+    }
+}
+
+/// The Shannon entropy of the frequencies in bits per symbol, 0 for an empty
+/// map. Zero counts are ignored.
+pub fn entropy(frequency: &BTreeMap<char, u64>) -> f64 {
+    let total = frequency.values().map(|&occ| occ as f64).sum::<f64>();
+    let entropy = frequency
+        .values()
+        .filter(|&&occ| occ > 0)
+        .map(|&occ| {
+            let p = occ as f64 / total;
+            -p * p.log2()
+        })
+        .sum::<f64>();
+    // Avoid -0 for a single symbol.
+    entropy.max(0.0)
+}
+
+/// The average code length in bits per symbol for a message with the given
+/// frequencies, 0 for an empty map. Zero counts are ignored.
+///
+/// # Panics
+///
+/// If a symbol with a non-zero count has no code.
+pub fn expected_code_length(frequency: &BTreeMap<char, u64>, codebook: &Codebook) -> f64 {
+    let (symbols, bits) = frequency.iter().filter(|(_, &occ)| occ > 0).fold(
+        (0.0, 0.0),
+        |(symbols, bits), (chr, &occ)| {
+            let occ = occ as f64;
+            (symbols + occ, bits + occ * codebook[chr].len() as f64)
+        },
+    );
+    if symbols == 0.0 {
+        0.0
+    } else {
+        bits / symbols
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressionStats {
+    pub input_symbols: usize,
+    pub input_bits: usize,
+    pub output_bits: usize,
+    /// output_bits / input_bits, 0 for empty input.
+    pub ratio: f64,
+    pub shannon_entropy_bits_per_symbol: f64,
+    pub average_code_length_bits: f64,
+    /// The header mode compress picks, the one with the smallest header.
+    pub header_mode: HeaderMode,
+    /// The size in bytes of the header of each mode.
+    pub header_sizes: Vec<(HeaderMode, usize)>,
+}
+
+impl CompressionStats {
+    /// Compute the statistics for a message with the given symbol frequencies
+    /// encoded with the codebook.
+    ///
+    /// # Panics
+    ///
+    /// If a symbol with a non-zero count has no code.
+    pub fn new(
+        frequency: &BTreeMap<char, u64>,
+        codebook: &Codebook,
+        input_encoding: InputEncoding,
+    ) -> CompressionStats {
+        let mut input_symbols = 0;
+        let mut input_bits = 0;
+        let mut output_bits = 0;
+        for (&chr, &occ) in frequency.iter().filter(|(_, &occ)| occ > 0) {
+            let occ = occ as usize;
+            input_symbols += occ;
+            input_bits += occ * input_encoding.bits(chr);
+            output_bits += occ * codebook[&chr].len();
+        }
+        CompressionStats {
+            input_symbols,
+            input_bits,
+            output_bits,
+            ratio: match input_bits {
+                0 => 0.0,
+                n => output_bits as f64 / n as f64,
+            },
+            shannon_entropy_bits_per_symbol: entropy(frequency),
+            average_code_length_bits: expected_code_length(frequency, codebook),
+            header_mode: smallest_header(frequency, codebook),
+            header_sizes: header_sizes(frequency, codebook),
+        }
+    }
+}
+
+impl Display for CompressionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} symbols, {} -> {} bits ({:.1}%), entropy {:.3} bits/symbol, average code length {:.3} bits, {:?} header",
+            self.input_symbols,
+            self.input_bits,
+            self.output_bits,
+            self.ratio * 100.0,
+            self.shannon_entropy_bits_per_symbol,
+            self.average_code_length_bits,
+            self.header_mode
+        )
+    }
+}
+
+/// The contribution of one symbol to the encoded length.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolSavings {
+    pub symbol: char,
+    pub count: u64,
+    /// count / the total count.
+    pub probability: f64,
+    pub code_length: usize,
+    /// The bits the symbol takes in total.
+    pub bits: u64,
+    /// bits minus the bits of the fixed-length code, negative if the symbol
+    /// saves bits.
+    pub delta: i64,
+}
+
+/// Where the bits of an encoded message come from, per symbol and compared
+/// to a fixed-length code. It displays as an aligned table with a row per
+/// symbol and the totals at the bottom.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavingsReport {
+    rows: Vec<SymbolSavings>,
+    fixed_length: u32,
+}
+
+impl SavingsReport {
+    /// The report compared to the shortest fixed-length code for the symbols,
+    /// ceil(log2(symbols)) bits but at least 1.
+    ///
+    /// # Panics
+    ///
+    /// If a symbol with a non-zero count has no code.
+    pub fn new(frequency: &BTreeMap<char, u64>, codebook: &Codebook) -> SavingsReport {
+        let symbols = frequency.values().filter(|&&occ| occ > 0).count() as u32;
+        let fixed_length = match symbols {
+            0 | 1 => 1,
+            n => u32::BITS - (n - 1).leading_zeros(),
+        };
+        SavingsReport::with_fixed_length(frequency, codebook, fixed_length)
+    }
+
+    /// The report compared to a code with fixed_length bits per symbol, e.g.
+    /// 8 for ASCII.
+    ///
+    /// # Panics
+    ///
+    /// If a symbol with a non-zero count has no code.
+    pub fn with_fixed_length(
+        frequency: &BTreeMap<char, u64>,
+        codebook: &Codebook,
+        fixed_length: u32,
+    ) -> SavingsReport {
+        let total = frequency.values().map(|&occ| occ as f64).sum::<f64>();
+        let mut rows = frequency
+            .iter()
+            .filter(|(_, &occ)| occ > 0)
+            .map(|(&symbol, &count)| {
+                let code_length = codebook[&symbol].len();
+                let bits = count * code_length as u64;
+                let fixed_bits = count * u64::from(fixed_length);
+                SymbolSavings {
+                    symbol,
+                    count,
+                    probability: (count as f64) / total,
+                    code_length,
+                    bits,
+                    delta: bits as i64 - fixed_bits as i64,
+                }
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by_key(|row| (core::cmp::Reverse(row.bits), row.symbol));
+        SavingsReport { rows, fixed_length }
+    }
+
+    /// The rows, most bits first, ties by symbol.
+    pub fn rows(&self) -> &[SymbolSavings] {
+        &self.rows
+    }
+
+    /// The bits per symbol of the fixed-length code.
+    pub fn fixed_length(&self) -> u32 {
+        self.fixed_length
+    }
+
+    pub fn total_count(&self) -> u64 {
+        self.rows.iter().map(|row| row.count).sum()
+    }
+
+    /// The length of the encoded message in bits, as encoded_len_bits.
+    pub fn total_bits(&self) -> u64 {
+        self.rows.iter().map(|row| row.bits).sum()
+    }
+
+    /// The length of the message in bits with the fixed-length code.
+    pub fn fixed_bits(&self) -> u64 {
+        self.total_count() * u64::from(self.fixed_length)
+    }
+
+    /// total_bits minus fixed_bits.
+    pub fn total_delta(&self) -> i64 {
+        self.rows.iter().map(|row| row.delta).sum()
+    }
+}
+
+impl Display for SavingsReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let header = ["symbol", "count", "p", "len", "fixed", "bits", "delta"];
+        let mut rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                [
+                    escape(row.symbol),
+                    row.count.to_string(),
+                    format!("{:.1}%", row.probability * 100.0),
+                    row.code_length.to_string(),
+                    self.fixed_length.to_string(),
+                    row.bits.to_string(),
+                    format!("{:+}", row.delta),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let total = match self.total_count() {
+            0 => String::new(),
+            _ => "100.0%".into(),
+        };
+        rows.push([
+            "total".into(),
+            self.total_count().to_string(),
+            total,
+            String::new(),
+            String::new(),
+            self.total_bits().to_string(),
+            format!("{:+}", self.total_delta()),
+        ]);
+        let mut widths = header.map(|title| title.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let header = header.map(String::from);
+        for row in core::iter::once(&header).chain(&rows) {
+            let mut line = String::new();
+            for (column, (cell, width)) in row.iter().zip(widths).enumerate() {
+                let padding = " ".repeat(width - cell.chars().count());
+                match column {
+                    0 => line.push_str(&format!("{}{}", cell, padding)),
+                    _ => line.push_str(&format!("  {}{}", padding, cell)),
+                }
+            }
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        write!(
+            f,
+            "{} bits instead of {} with {} bits per symbol",
+            self.total_bits(),
+            self.fixed_bits(),
+            self.fixed_length
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{codebook, encode, encoded_len_bits};
+    use crate::test_support::random_numbers;
+    use crate::tree::{frequency, huffman};
+
+    const EPSILON: f64 = 1e-9;
+
+    #[test]
+    fn stats_for_dyadic_distribution() {
+        // given:
+        let message = "aaaabbcd";
+        let codebook = encode(message).codebook;
+
+        // when:
+        let actual = CompressionStats::new(
+            &frequency(&mut message.chars()),
+            &codebook,
+            InputEncoding::default(),
+        );
+
+        // then:
+        assert_eq!(actual.input_symbols, 8);
+        assert_eq!(actual.input_bits, 64);
+        assert_eq!(actual.output_bits, 14);
+        assert!((actual.ratio - 14.0 / 64.0).abs() < EPSILON);
+        assert!((actual.shannon_entropy_bits_per_symbol - 1.75).abs() < EPSILON);
+        assert!((actual.average_code_length_bits - 1.75).abs() < EPSILON);
+    }
+
+    #[test]
+    fn stats_input_encoding() {
+        // given:
+        let message = "äa€";
+        let codebook = encode(message).codebook;
+        let frequency = frequency(&mut message.chars());
+
+        // when:
+        let utf8 = CompressionStats::new(&frequency, &codebook, InputEncoding::Utf8);
+        let fixed = CompressionStats::new(&frequency, &codebook, InputEncoding::BitsPerChar(8));
+
+        // then:
+        assert_eq!(utf8.input_bits, 6 * 8);
+        assert_eq!(fixed.input_bits, 3 * 8);
+    }
+
+    #[test]
+    fn stats_single_symbol() {
+        // given:
+        let codebook = encode("aaaa").codebook;
+
+        // when:
+        let actual = CompressionStats::new(
+            &frequency(&mut "aaaa".chars()),
+            &codebook,
+            InputEncoding::default(),
+        );
+
+        // then:
+        assert_eq!(actual.shannon_entropy_bits_per_symbol, 0.0);
+        assert_eq!(actual.average_code_length_bits, 1.0);
+    }
+
+    #[test]
+    fn stats_empty_input() {
+        // when:
+        let actual =
+            CompressionStats::new(&BTreeMap::new(), &Codebook::new(), InputEncoding::default());
+
+        // then:
+        assert_eq!(actual.ratio, 0.0);
+        assert_eq!(actual.shannon_entropy_bits_per_symbol, 0.0);
+        assert_eq!(actual.average_code_length_bits, 0.0);
+        assert!(!actual.to_string().contains("NaN"));
+    }
+
+    #[test]
+    fn expected_code_length_within_entropy_bound() {
+        for seed in 0..100 {
+            // given:
+            let mut numbers = random_numbers(seed);
+            let symbols = 2 + numbers.next().unwrap() % 40;
+            let len = 1 + numbers.next().unwrap() % 1000;
+            let message = numbers
+                .take(len)
+                // Squaring skews the distribution.
+                .map(|x| (b'a' + ((x % symbols).pow(2) % symbols) as u8) as char)
+                .collect::<String>();
+            let frequency = frequency(&mut message.chars());
+            let codebook = codebook(&huffman(frequency.clone()).unwrap());
+
+            // when:
+            let entropy = entropy(&frequency);
+            let actual = expected_code_length(&frequency, &codebook);
+
+            // then:
+            assert!(actual >= entropy - EPSILON, "{} < {}", actual, entropy);
+            if frequency.len() > 1 {
+                assert!(actual < entropy + 1.0, "{} >= {} + 1", actual, entropy);
+            }
+        }
+    }
+
+    #[test]
+    fn entropy_ignores_zero_counts() {
+        // given:
+        let mut frequency = frequency(&mut "aabb".chars());
+        frequency.insert('z', 0);
+
+        // when:
+        let actual = (entropy(&frequency), entropy(&BTreeMap::new()));
+
+        // then:
+        assert!((actual.0 - 1.0).abs() < EPSILON);
+        assert_eq!(actual.1, 0.0);
+        assert_eq!(
+            expected_code_length(&BTreeMap::new(), &Codebook::new()),
+            0.0
+        );
+    }
+
+    #[test]
+    fn savings_report_of_example() {
+        // given:
+        let message = "BACADAEAFABBAAAGAH";
+        let codec = encode(message);
+        let frequency = frequency(&mut message.chars());
+
+        // when:
+        let actual = SavingsReport::new(&frequency, &codec.codebook);
+
+        // then:
+        assert_eq!(actual.fixed_length(), 3);
+        assert_eq!(
+            actual.rows()[0],
+            SymbolSavings {
+                symbol: 'A',
+                count: 9,
+                probability: 0.5,
+                code_length: 1,
+                bits: 9,
+                delta: -18,
+            }
+        );
+        assert_eq!(actual.rows()[1].symbol, 'B');
+        assert_eq!(actual.rows()[1].count, 3);
+        assert_eq!(actual.rows()[1].code_length, 3);
+        assert_eq!(actual.rows()[1].delta, 0);
+        assert_eq!(actual.total_count(), 18);
+        assert_eq!(actual.fixed_bits(), 54);
+        assert_eq!(actual.total_bits(), codec.bits.len() as u64);
+        assert_eq!(
+            Ok(actual.total_bits()),
+            encoded_len_bits(&frequency, &codec.codebook)
+        );
+        assert_eq!(
+            actual.total_delta(),
+            actual.total_bits() as i64 - actual.fixed_bits() as i64
+        );
+    }
+
+    #[test]
+    fn savings_report_table() {
+        // given:
+        let message = "aaaabbcd";
+        let codebook = encode(message).codebook;
+
+        // when:
+        let actual =
+            SavingsReport::with_fixed_length(&frequency(&mut message.chars()), &codebook, 8)
+                .to_string();
+
+        // then:
+        assert_eq!(
+            actual,
+            "symbol  count       p  len  fixed  bits  delta\n\
+             'a'         4   50.0%    1      8     4    -28\n\
+             'b'         2   25.0%    2      8     4    -12\n\
+             'c'         1   12.5%    3      8     3     -5\n\
+             'd'         1   12.5%    3      8     3     -5\n\
+             total       8  100.0%                14    -50\n\
+             14 bits instead of 64 with 8 bits per symbol"
+        );
+    }
+
+    #[test]
+    fn savings_report_of_nothing() {
+        // when:
+        let actual = SavingsReport::new(&BTreeMap::new(), &Codebook::new());
+
+        // then:
+        assert!(actual.rows().is_empty());
+        assert_eq!((actual.total_bits(), actual.fixed_bits()), (0, 0));
+        assert!(!actual.to_string().contains("NaN"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Iterator adapters that encode and decode without buffering the whole
+//! message.
+
+use crate::bits::Bits;
+use crate::code::Codebook;
+use crate::error::HuffmanError;
+use crate::tree::HuffTree;
+
+/// Encodes a stream of characters into a stream of bits, yielding the bits of
+/// each code as soon as its character has been read.
+///
+/// A character without a code yields an error, after which the iterator ends.
+pub struct HuffmanEncoder<'a, I> {
+    codebook: &'a Codebook,
+    chars: I,
+    code: Option<&'a Bits>,
+    index: usize,
+    failed: bool,
+}
+
+impl<'a, I: Iterator<Item = char>> HuffmanEncoder<'a, I> {
+    pub fn new(codebook: &'a Codebook, chars: I) -> HuffmanEncoder<'a, I> {
+        HuffmanEncoder {
+            codebook,
+            chars,
+            code: None,
+            index: 0,
+            failed: false,
+        }
+    }
+}
+
+impl<I: Iterator<Item = char>> Iterator for HuffmanEncoder<'_, I> {
+    type Item = Result<bool, HuffmanError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(code) = self.code {
+                if self.index < code.len() {
+                    self.index += 1;
+                    return Some(Ok(code.get(self.index - 1)));
+                }
+            }
+            if self.failed {
+                return None;
+            }
+            let chr = self.chars.next()?;
+            match self.codebook.get(&chr) {
+                Some(code) => {
+                    self.code = Some(code);
+                    self.index = 0;
+                }
+                None => {
+                    self.failed = true;
+                    return Some(Err(HuffmanError::UnknownSymbol(chr)));
+                }
+            }
+        }
+    }
+}
+
+/// Decodes a stream of bits into a stream of characters, yielding each
+/// character as soon as its code has been read.
+///
+/// If the bits end in the middle of a code or do not match a code, the last
+/// item is an error.
+pub struct HuffmanDecoder<'a, I> {
+    huff: &'a HuffTree,
+    bits: I,
+    bit_offset: usize,
+    failed: bool,
+}
+
+impl<'a, I: Iterator<Item = bool>> HuffmanDecoder<'a, I> {
+    pub fn new(huff: &'a HuffTree, bits: I) -> HuffmanDecoder<'a, I> {
+        HuffmanDecoder {
+            huff,
+            bits,
+            bit_offset: 0,
+            failed: false,
+        }
+    }
+
+    fn fail(&mut self, err: HuffmanError) -> Option<Result<char, HuffmanError>> {
+        self.failed = true;
+        Some(Err(err))
+    }
+}
+
+impl<I: Iterator<Item = bool>> Iterator for HuffmanDecoder<'_, I> {
+    type Item = Result<char, HuffmanError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let bit_offset = self.bit_offset;
+        let mut node = self.huff;
+        if let HuffTree::Leaf { .. } = node {
+            // A single leaf has the code 0.
+            self.bit_offset += 1;
+            if self.bits.next()? {
+                return self.fail(HuffmanError::InvalidPrefix { bit_offset });
+            }
+        }
+        while let HuffTree::Node { left, right } = node {
+            node = match self.bits.next() {
+                Some(false) => left,
+                Some(true) => right,
+                None if self.bit_offset == bit_offset => return None,
+                None => return self.fail(HuffmanError::TruncatedInput { bit_offset }),
+            };
+            self.bit_offset += 1;
+        }
+        match node {
+            HuffTree::Leaf { chr, .. } => Some(Ok(*chr)),
+            HuffTree::Node { .. } => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::{codebook, encode, Encoded};
+    use crate::tree::{frequency, huffman};
+
+    #[test]
+    fn encoder_streams_long_input() {
+        // given:
+        let unit = "abracadabra, äöü € ";
+        let repetitions = 200_000;
+        let Encoded {
+            codebook,
+            bits: expected,
+            ..
+        } = encode(unit);
+        let chars = std::iter::repeat_n(unit, repetitions).flat_map(str::chars);
+
+        // when:
+        let mut actual = HuffmanEncoder::new(&codebook, chars);
+
+        // then:
+        let mut len = 0;
+        for (actual, expected) in actual.by_ref().zip(expected.iter().cycle()) {
+            assert_eq!(actual, Ok(expected));
+            len += 1;
+        }
+        assert_eq!(actual.next(), None);
+        assert_eq!(len, expected.len() * repetitions);
+    }
+
+    #[test]
+    fn encoder_unknown_symbol() {
+        // given:
+        let Encoded {
+            codebook,
+            bits: expected,
+            ..
+        } = encode("ab");
+
+        // when:
+        let actual = HuffmanEncoder::new(&codebook, "abcab".chars()).collect::<Vec<_>>();
+
+        // then:
+        let mut expected = expected.iter().map(Ok).collect::<Vec<_>>();
+        expected.push(Err(HuffmanError::UnknownSymbol('c')));
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn decoder_round_trip() {
+        // given:
+        let message = "aardvarks ate apples around aachen";
+        let tree = huffman(frequency(&mut message.chars())).unwrap();
+        let codebook = codebook(&tree);
+        let bits = HuffmanEncoder::new(&codebook, message.chars()).map(Result::unwrap);
+
+        // when:
+        let actual = HuffmanDecoder::new(&tree, bits).collect::<Result<String, _>>();
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+    }
+
+    #[test]
+    fn decoder_errors_only_on_mid_code_splits() {
+        // given:
+        let message = "BACADAEAFABBAAAGAH";
+        let tree = huffman(frequency(&mut message.chars())).unwrap();
+        let codebook = codebook(&tree);
+        let bits = HuffmanEncoder::new(&codebook, message.chars())
+            .map(Result::unwrap)
+            .collect::<Vec<_>>();
+        let boundaries = message
+            .chars()
+            .scan(0, |offset, chr| {
+                *offset += codebook[&chr].len();
+                Some(*offset)
+            })
+            .collect::<Vec<_>>();
+
+        for split in 0..=bits.len() {
+            // when:
+            let actual =
+                HuffmanDecoder::new(&tree, bits[..split].iter().copied()).collect::<Vec<_>>();
+
+            // then:
+            let complete = boundaries.iter().take_while(|&&b| b <= split).count();
+            let expected_chars = message.chars().take(complete).map(Ok);
+            let mut expected = expected_chars.collect::<Vec<_>>();
+            if split != 0 && !boundaries.contains(&split) {
+                let bit_offset = boundaries[..complete].last().copied().unwrap_or(0);
+                expected.push(Err(HuffmanError::TruncatedInput { bit_offset }));
+            }
+            assert_eq!(actual, expected, "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn decoder_single_leaf() {
+        // given:
+        let tree = HuffTree::new('a', 2);
+
+        // when:
+        let actual = HuffmanDecoder::new(&tree, [false, false, true, false].iter().copied())
+            .collect::<Vec<_>>();
+
+        // then:
+        assert_eq!(
+            actual,
+            [
+                Ok('a'),
+                Ok('a'),
+                Err(HuffmanError::InvalidPrefix { bit_offset: 2 })
+            ]
+        );
+    }
+}
@@ -0,0 +1,293 @@
+//! Human readable tables of codebooks and symbol frequencies.
+
+use crate::code::Codebook;
+use alloc::collections::BTreeMap;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::{self, Display, Formatter};
+
+/// The order of the rows of a codebook table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy<'a> {
+    Symbol,
+    /// Shortest codes first, ties by symbol.
+    CodeLength,
+    /// Most frequent symbols first, ties by symbol. Symbols missing from the
+    /// map count as 0.
+    Frequency(&'a BTreeMap<char, u64>),
+}
+
+/// Rendering of a codebook as a table with one aligned row per symbol, e.g.
+/// `'e'  1101  (len 4)`.
+pub trait CodebookTable {
+    fn to_table(&self, sort: SortBy) -> String;
+    /// A Display adapter rendering the table sorted by symbol.
+    fn display(&self) -> TableDisplay<'_>;
+}
+
+impl CodebookTable for Codebook {
+    fn to_table(&self, sort: SortBy) -> String {
+        let mut rows = self
+            .iter()
+            .map(|(&chr, code)| (chr, escape(chr), code.to_string()))
+            .collect::<Vec<_>>();
+        match sort {
+            SortBy::Symbol => {}
+            SortBy::CodeLength => rows.sort_by_key(|(chr, _, code)| (code.len(), *chr)),
+            SortBy::Frequency(frequency) => rows.sort_by_key(|(chr, _, _)| {
+                let occ = frequency.get(chr).copied().unwrap_or(0);
+                (core::cmp::Reverse(occ), *chr)
+            }),
+        }
+        let symbol_width = rows
+            .iter()
+            .map(|(_, symbol, _)| symbol.chars().count())
+            .max()
+            .unwrap_or(0);
+        let code_width = rows
+            .iter()
+            .map(|(_, _, code)| code.len())
+            .max()
+            .unwrap_or(0);
+        rows.iter()
+            .map(|(_, symbol, code)| {
+                let symbol_padding = symbol_width - symbol.chars().count();
+                format!(
+                    "{}{}  {:<code_width$}  (len {})\n",
+                    symbol,
+                    " ".repeat(symbol_padding),
+                    code,
+                    code.len(),
+                    code_width = code_width
+                )
+            })
+            .collect()
+    }
+    fn display(&self) -> TableDisplay<'_> {
+        TableDisplay { codebook: self }
+    }
+}
+
+/// Displays a codebook table, created by CodebookTable::display.
+pub struct TableDisplay<'a> {
+    codebook: &'a Codebook,
+}
+
+impl Display for TableDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.codebook.to_table(SortBy::Symbol))
+    }
+}
+
+/// The order of the rows of a frequency histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HistogramOrder {
+    /// Most frequent symbols first, ties by symbol.
+    #[default]
+    Count,
+    Symbol,
+}
+
+/// The width of the bar of the most frequent symbol.
+const BAR_WIDTH: usize = 40;
+
+/// Render the counts as one row per symbol with its count, percentage and a
+/// bar of `#` proportional to the count, e.g. `'e'  17   42.5%  ####`. With
+/// top only the most frequent symbols are shown, followed by a line saying how
+/// many were omitted.
+pub fn frequency_histogram(
+    counts: &BTreeMap<char, u64>,
+    order: HistogramOrder,
+    top: Option<usize>,
+) -> String {
+    let total = counts.values().sum::<u64>();
+    let max = counts.values().copied().max().unwrap_or(0);
+    let mut rows = counts
+        .iter()
+        .map(|(&chr, &occ)| (chr, occ))
+        .collect::<Vec<_>>();
+    rows.sort_by_key(|&(chr, occ)| (core::cmp::Reverse(occ), chr));
+    let omitted = rows.len() - top.unwrap_or(rows.len()).min(rows.len());
+    rows.truncate(rows.len() - omitted);
+    if order == HistogramOrder::Symbol {
+        rows.sort();
+    }
+    let rows = rows
+        .into_iter()
+        .map(|(chr, occ)| (escape(chr), occ))
+        .collect::<Vec<_>>();
+    let symbol_width = rows
+        .iter()
+        .map(|(symbol, _)| symbol.chars().count())
+        .max()
+        .unwrap_or(0);
+    let count_width = rows
+        .iter()
+        .map(|(_, occ)| occ.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (symbol, occ) in rows {
+        let percent = occ as f64 / total as f64 * 100.0;
+        // Rounded half up, as f64::round needs std.
+        let bar = ((occ as f64 / max as f64 * BAR_WIDTH as f64 + 0.5) as usize).max(1);
+        out.push_str(&format!(
+            "{}{}  {:>count_width$}  {:>5.1}%  {}\n",
+            symbol,
+            " ".repeat(symbol_width - symbol.chars().count()),
+            occ,
+            percent,
+            "#".repeat(bar),
+            count_width = count_width
+        ));
+    }
+    if omitted > 0 {
+        out.push_str(&format!("({} more symbols omitted)\n", omitted));
+    }
+    out
+}
+
+/// The quoted symbol with space shown as ␣ and other whitespace and control
+/// characters escaped.
+pub(crate) fn escape(chr: char) -> String {
+    match chr {
+        ' ' => "'␣'".to_string(),
+        c if c.is_whitespace() || c.is_control() => {
+            format!("'{}'", c.escape_default())
+        }
+        c => format!("'{}'", c.escape_debug()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bits::Bits;
+
+    fn codebook() -> Codebook {
+        ["a:0", "b:110", " :10", "\n:1110", "€:1111"]
+            .iter()
+            .map(|entry| {
+                let (chr, code) = entry.split_at(entry.rfind(':').unwrap());
+                let code = code[1..].chars().map(|c| c == '1').collect::<Bits>();
+                (chr.chars().next().unwrap(), code)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn table_sorted_by_symbol() {
+        // when:
+        let actual = codebook().to_table(SortBy::Symbol);
+
+        // then:
+        assert_eq!(
+            actual,
+            "'\\n'  1110  (len 4)\n\
+             '␣'   10    (len 2)\n\
+             'a'   0     (len 1)\n\
+             'b'   110   (len 3)\n\
+             '€'   1111  (len 4)\n"
+        );
+        assert_eq!(codebook().display().to_string(), actual);
+    }
+
+    #[test]
+    fn table_sorted_by_code_length() {
+        // when:
+        let actual = codebook().to_table(SortBy::CodeLength);
+
+        // then:
+        assert_eq!(
+            actual,
+            "'a'   0     (len 1)\n\
+             '␣'   10    (len 2)\n\
+             'b'   110   (len 3)\n\
+             '\\n'  1110  (len 4)\n\
+             '€'   1111  (len 4)\n"
+        );
+    }
+
+    #[test]
+    fn table_sorted_by_frequency() {
+        // given:
+        let frequency = [('a', 5), ('b', 2), (' ', 3), ('€', 2)]
+            .iter()
+            .copied()
+            .collect();
+
+        // when:
+        let actual = codebook().to_table(SortBy::Frequency(&frequency));
+
+        // then:
+        assert_eq!(
+            actual,
+            "'a'   0     (len 1)\n\
+             '␣'   10    (len 2)\n\
+             'b'   110   (len 3)\n\
+             '€'   1111  (len 4)\n\
+             '\\n'  1110  (len 4)\n"
+        );
+    }
+
+    #[test]
+    fn table_of_empty_codebook() {
+        // when:
+        let actual = Codebook::new().to_table(SortBy::Symbol);
+
+        // then:
+        assert_eq!(actual, "");
+    }
+
+    fn counts(text: &str) -> BTreeMap<char, u64> {
+        let mut counts = BTreeMap::new();
+        for chr in text.chars() {
+            *counts.entry(chr).or_default() += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn histogram_sorted_by_count() {
+        // when:
+        let actual = frequency_histogram(&counts("abracadabra\n"), HistogramOrder::Count, None);
+
+        // then:
+        assert_eq!(
+            actual,
+            "'a'   5   41.7%  ########################################\n\
+             'b'   2   16.7%  ################\n\
+             'r'   2   16.7%  ################\n\
+             '\\n'  1    8.3%  ########\n\
+             'c'   1    8.3%  ########\n\
+             'd'   1    8.3%  ########\n"
+        );
+    }
+
+    #[test]
+    fn histogram_top_sorted_by_symbol() {
+        // when:
+        let actual = frequency_histogram(&counts("abracadabra\n"), HistogramOrder::Symbol, Some(3));
+
+        // then:
+        assert_eq!(
+            actual,
+            "'a'  5   41.7%  ########################################\n\
+             'b'  2   16.7%  ################\n\
+             'r'  2   16.7%  ################\n\
+             (3 more symbols omitted)\n"
+        );
+    }
+
+    #[test]
+    fn histogram_of_nothing() {
+        // when:
+        let actual = frequency_histogram(&BTreeMap::new(), HistogramOrder::Count, Some(0));
+
+        // then:
+        assert_eq!(actual, "");
+    }
+}
@@ -0,0 +1,30 @@
+//! Helpers shared by the unit tests. The integration tests and examples have
+//! the same ones in tests/common.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A linear congruential generator, good enough for test messages and
+/// reproducible for each seed.
+pub(crate) fn random_numbers(seed: u64) -> impl Iterator<Item = usize> {
+    core::iter::successors(Some(seed), |x| {
+        Some(
+            x.wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407),
+        )
+    })
+    .skip(1)
+    .map(|x| (x >> 33) as usize)
+}
+
+/// A random text of len symbols of 1 to 4 UTF-8 bytes, with spaces and line
+/// breaks.
+pub(crate) fn random_text(seed: u64, len: usize) -> String {
+    let alphabet = "abcdefghijklmnopqrstuvwxyzäöüß€🦀 \n"
+        .chars()
+        .collect::<Vec<_>>();
+    random_numbers(seed)
+        .take(len)
+        .map(|x| alphabet[x % alphabet.len()])
+        .collect()
+}
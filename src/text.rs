@@ -0,0 +1,203 @@
+//! A line based text format for codebooks: one line per symbol with its code
+//! point and its code, e.g. `U+0061 0101`. Code points keep whitespace and
+//! control characters readable without an escaping scheme.
+
+use crate::bits::Bits;
+use crate::code::{validate_codebook, Codebook, CodebookError};
+use alloc::{format, string::String};
+use core::fmt::{self, Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CodebookParseError {
+    /// The line is not a code point followed by a code.
+    Malformed { line: usize },
+    /// The code on the line contains something other than 0 and 1.
+    InvalidCode { line: usize },
+    /// The symbol on the line already occurred on an earlier line.
+    DuplicateSymbol { line: usize, symbol: char },
+    /// The codes cannot be decoded unambiguously.
+    Invalid(CodebookError),
+}
+
+impl Display for CodebookParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CodebookParseError::Malformed { line } => {
+                write!(f, "line {}: expected a code point and a code", line)
+            }
+            CodebookParseError::InvalidCode { line } => {
+                write!(f, "line {}: the code may only contain 0 and 1", line)
+            }
+            CodebookParseError::DuplicateSymbol { line, symbol } => {
+                write!(
+                    f,
+                    "line {}: symbol {:?} occurs more than once",
+                    line, symbol
+                )
+            }
+            CodebookParseError::Invalid(err) => write!(f, "invalid codebook: {}", err),
+        }
+    }
+}
+
+/// Conversion of a codebook from and to the text format.
+pub trait CodebookText: Sized {
+    fn to_text(&self) -> String;
+    /// Parse the output of to_text. Blank lines are ignored, line numbers in
+    /// errors start at 1.
+    fn from_text(text: &str) -> Result<Self, CodebookParseError>;
+}
+
+impl CodebookText for Codebook {
+    fn to_text(&self) -> String {
+        self.iter()
+            .map(|(&chr, code)| format!("U+{:04X} {}\n", chr as u32, code))
+            .collect()
+    }
+
+    fn from_text(text: &str) -> Result<Codebook, CodebookParseError> {
+        let mut codebook = Codebook::new();
+        for (index, content) in text.lines().enumerate() {
+            let line = index + 1;
+            let mut fields = content.split_whitespace();
+            let symbol = match fields.next() {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+            // A single symbol may have an empty code.
+            let code = fields.next().unwrap_or("");
+            if fields.next().is_some() {
+                return Err(CodebookParseError::Malformed { line });
+            }
+            // from_str_radix would also accept a sign.
+            let symbol = symbol
+                .strip_prefix("U+")
+                .filter(|hex| (1..=6).contains(&hex.len()))
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(core::char::from_u32)
+                .ok_or(CodebookParseError::Malformed { line })?;
+            let code = code
+                .chars()
+                .map(|c| match c {
+                    '0' => Some(false),
+                    '1' => Some(true),
+                    _ => None,
+                })
+                .collect::<Option<Bits>>()
+                .ok_or(CodebookParseError::InvalidCode { line })?;
+            if codebook.insert(symbol, code).is_some() {
+                return Err(CodebookParseError::DuplicateSymbol { line, symbol });
+            }
+        }
+        validate_codebook(&codebook).map_err(CodebookParseError::Invalid)?;
+        Ok(codebook)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::encode;
+
+    #[test]
+    fn text_round_trip() {
+        // given:
+        let codebook = encode("Grüße aus\tMünchen\r\n für 5 € 🦀\u{0}").codebook;
+
+        // when:
+        let text = codebook.to_text();
+        let actual = Codebook::from_text(&text);
+
+        // then:
+        assert_eq!(actual, Ok(codebook));
+        assert_eq!(text.lines().count(), 21);
+    }
+
+    #[test]
+    fn text_is_readable() {
+        // given:
+        let codebook = encode("a b").codebook;
+
+        // when:
+        let actual = codebook.to_text();
+
+        // then:
+        assert_eq!(actual, "U+0020 10\nU+0061 11\nU+0062 0\n");
+    }
+
+    #[test]
+    fn from_text_ignores_blank_lines() {
+        // when:
+        let actual = Codebook::from_text("\nU+0061  0\n\n  U+1F980 1  \n");
+
+        // then:
+        let expected = encode("a🦀").codebook;
+        assert_eq!(actual, Ok(expected));
+    }
+
+    #[test]
+    fn from_text_malformed() {
+        for text in [
+            "U+0061 0\nA 1",
+            "U+0061 0\nU+ZZ 1",
+            "U+0061 0\nU+D800 1",
+            "U+0061 0\nU+0062 1 0",
+            "U+0061 0\nU++62 1",
+            "U+0061 0\nU+ 1",
+            "U+0061 0\nU+0000062 1",
+        ]
+        .iter()
+        {
+            // when:
+            let actual = Codebook::from_text(text);
+
+            // then:
+            assert_eq!(
+                actual,
+                Err(CodebookParseError::Malformed { line: 2 }),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn from_text_invalid_code() {
+        // when:
+        let actual = Codebook::from_text("U+0061 0\n\nU+0062 12");
+
+        // then:
+        assert_eq!(actual, Err(CodebookParseError::InvalidCode { line: 3 }));
+    }
+
+    #[test]
+    fn from_text_duplicate_symbol() {
+        // when:
+        let actual = Codebook::from_text("U+0061 0\nU+0062 10\nU+0061 11");
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(CodebookParseError::DuplicateSymbol {
+                line: 3,
+                symbol: 'a'
+            })
+        );
+    }
+
+    #[test]
+    fn from_text_not_prefix_free() {
+        // when:
+        let actual = Codebook::from_text("U+0061 1\nU+0062 10");
+
+        // then:
+        assert_eq!(
+            actual,
+            Err(CodebookParseError::Invalid(CodebookError::PrefixConflict {
+                prefix: 'a',
+                symbol: 'b'
+            }))
+        );
+    }
+}
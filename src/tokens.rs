@@ -0,0 +1,366 @@
+//! Huffman coding of tokens such as words, grapheme clusters or character
+//! pairs instead of single characters.
+
+use crate::bits::Bits;
+use crate::code::{decode_symbols, encode_symbols, Codebook};
+use crate::error::HuffmanError;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Split text into runs of alphanumeric characters, runs of whitespace and
+/// single other characters, so that concatenating the tokens gives the text
+/// back exactly.
+pub fn split_words(text: &str) -> Vec<String> {
+    #[derive(PartialEq)]
+    enum Class {
+        Word,
+        Space,
+        Other,
+    }
+    let class = |chr: char| {
+        if chr.is_alphanumeric() {
+            Class::Word
+        } else if chr.is_whitespace() {
+            Class::Space
+        } else {
+            Class::Other
+        }
+    };
+    let mut tokens: Vec<String> = Vec::new();
+    let mut previous = None;
+    for chr in text.chars() {
+        let current = class(chr);
+        match tokens.last_mut() {
+            Some(token) if current != Class::Other && previous.as_ref() == Some(&current) => {
+                token.push(chr)
+            }
+            _ => tokens.push(chr.to_string()),
+        }
+        previous = Some(current);
+    }
+    tokens
+}
+
+/// Encode the message token by token, e.g. with split_words as tokenizer.
+pub fn encode_tokens<F: Fn(&str) -> Vec<String>>(
+    message: &str,
+    tokenizer: F,
+) -> (Codebook<String>, Bits) {
+    encode_symbols(&tokenizer(message))
+}
+
+/// Decode the bits into the concatenation of their tokens, which is the
+/// original message for a lossless tokenizer.
+pub fn decode_tokens(codebook: &Codebook<String>, bits: &Bits) -> Result<String, HuffmanError> {
+    Ok(decode_symbols(codebook, bits)?.concat())
+}
+
+/// What encode_with_mode takes as one symbol.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolMode {
+    /// Each Unicode scalar value, like encode.
+    #[default]
+    Chars,
+    /// Each grapheme cluster as split by split_graphemes, so e.g. a letter
+    /// with combining accents or an emoji ZWJ sequence is one symbol.
+    Graphemes,
+}
+
+impl SymbolMode {
+    /// Split text into the symbols of the mode.
+    pub fn split(self, text: &str) -> Vec<String> {
+        match self {
+            SymbolMode::Chars => text.chars().map(String::from).collect(),
+            SymbolMode::Graphemes => split_graphemes(text),
+        }
+    }
+}
+
+/// Encode the message with the symbols of the mode. decode_tokens decodes
+/// the bits back into the exact message.
+pub fn encode_with_mode(message: &str, mode: SymbolMode) -> (Codebook<String>, Bits) {
+    encode_tokens(message, |text| mode.split(text))
+}
+
+/// Split text into grapheme clusters with a simplified version of the
+/// extended grapheme cluster rules of Unicode: a cluster is a character
+/// followed by any combining marks, variation selectors, emoji modifiers and
+/// tags, where a zero width joiner also joins the next character. Two regional
+/// indicators form a flag and CR LF stays together. Other rules, like those
+/// for Hangul syllables and Indic spacing marks, are not applied.
+pub fn split_graphemes(text: &str) -> Vec<String> {
+    let mut clusters: Vec<String> = Vec::new();
+    let mut joined = false;
+    let mut open_flag = false;
+    let mut previous = None;
+    for chr in text.chars() {
+        let extends = joined
+            || is_extending(chr)
+            || (previous == Some('\r') && chr == '\n')
+            || (open_flag && is_regional_indicator(chr));
+        match clusters.last_mut() {
+            Some(cluster) if extends => cluster.push(chr),
+            _ => clusters.push(chr.to_string()),
+        }
+        open_flag = is_regional_indicator(chr) && !(open_flag && extends);
+        joined = chr == ZERO_WIDTH_JOINER;
+        previous = Some(chr);
+    }
+    clusters
+}
+
+const ZERO_WIDTH_JOINER: char = '\u{200d}';
+
+/// Whether the character belongs to the cluster of the character before it.
+fn is_extending(chr: char) -> bool {
+    matches!(chr,
+        '\u{0300}'..='\u{036f}' // combining diacritical marks
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}' // combining marks for symbols, like keycaps
+        | '\u{fe20}'..='\u{fe2f}'
+        | '\u{fe00}'..='\u{fe0f}' // variation selectors
+        | '\u{e0100}'..='\u{e01ef}'
+        | '\u{1f3fb}'..='\u{1f3ff}' // emoji skin tone modifiers
+        | '\u{e0020}'..='\u{e007f}' // tags of subdivision flags
+        | ZERO_WIDTH_JOINER
+    )
+}
+
+fn is_regional_indicator(chr: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&chr)
+}
+
+/// A pair of characters as one symbol. The second character is None only for
+/// the last symbol of a message of odd length.
+pub type Digram = (char, Option<char>);
+
+/// Encode the message as consecutive, non-overlapping pairs of characters.
+pub fn encode_digrams(message: &str) -> (Codebook<Digram>, Bits) {
+    let chars = message.chars().collect::<Vec<_>>();
+    let digrams = chars
+        .chunks(2)
+        .map(|pair| (pair[0], pair.get(1).copied()))
+        .collect::<Vec<_>>();
+    encode_symbols(&digrams)
+}
+
+/// Decode the bits of encode_digrams back into the message.
+pub fn decode_digrams(codebook: &Codebook<Digram>, bits: &Bits) -> Result<String, HuffmanError> {
+    Ok(decode_symbols(codebook, bits)?
+        .into_iter()
+        .flat_map(|(first, second)| Some(first).into_iter().chain(second))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words_separates_words_and_separators() {
+        // when:
+        let actual = split_words("Hello,  world!\tIt's 5€.\n\n");
+
+        // then:
+        assert_eq!(
+            actual,
+            ["Hello", ",", "  ", "world", "!", "\t", "It", "'", "s", " ", "5", "€", ".", "\n\n"]
+        );
+    }
+
+    #[test]
+    fn split_words_is_lossless() {
+        for text in [
+            "",
+            " ",
+            "a",
+            "double  spaces  and\ttabs\t\tand trailing newline\n",
+            "punctuation,glued;to(words)... and ?!",
+            "  leading and trailing  ",
+            "Grüße aus München 🦀🦀",
+        ]
+        .iter()
+        {
+            // when:
+            let actual = split_words(text).concat();
+
+            // then:
+            assert_eq!(&actual, text);
+        }
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        // given:
+        let message = "the cat and the hat,  the bat\tand the  rat.\nthe end\n";
+
+        // when:
+        let (codebook, bits) = encode_tokens(message, split_words);
+        let actual = decode_tokens(&codebook, &bits);
+
+        // then:
+        assert_eq!(actual, Ok(message.to_string()));
+        assert!(codebook[&"the".to_string()].len() <= codebook[&"cat".to_string()].len());
+    }
+
+    #[test]
+    fn tokens_beat_chars_on_repetitive_text() {
+        // given:
+        let message = "to be or not to be that is the question ".repeat(50);
+
+        // when:
+        let (_, word_bits) = encode_tokens(&message, split_words);
+
+        // then:
+        let char_bits = crate::code::encode(&message).bits;
+        assert!(word_bits.len() < char_bits.len() / 2);
+    }
+
+    #[test]
+    fn tokens_with_custom_tokenizer() {
+        // given:
+        let lines = |s: &str| s.split_inclusive('\n').map(str::to_string).collect();
+        let message = "a\nb\na\n";
+
+        // when:
+        let (codebook, bits) = encode_tokens(message, lines);
+
+        // then:
+        assert_eq!(codebook.len(), 2);
+        assert_eq!(decode_tokens(&codebook, &bits), Ok(message.to_string()));
+    }
+
+    #[test]
+    fn tokens_empty_message() {
+        // when:
+        let actual = encode_tokens("", split_words);
+
+        // then:
+        assert_eq!(actual, (Codebook::new(), Bits::new()));
+        assert_eq!(
+            decode_tokens(&Codebook::new(), &Bits::new()),
+            Ok(String::new())
+        );
+    }
+
+    #[test]
+    fn digrams_beat_chars_on_pairs() {
+        // given:
+        let message = "ab".repeat(100) + "cd";
+
+        // when:
+        let (codebook, bits) = encode_digrams(&message);
+
+        // then:
+        let char_bits = crate::code::encode(&message).bits;
+        assert!(bits.len() < char_bits.len(), "{} bits", bits.len());
+        assert_eq!(codebook.len(), 2);
+        assert_eq!(decode_digrams(&codebook, &bits), Ok(message));
+    }
+
+    #[test]
+    fn digrams_round_trip_odd_length() {
+        for message in ["a", "abc", "abababa", "Grüße aus München 🦀"].iter() {
+            // when:
+            let (codebook, bits) = encode_digrams(message);
+            let actual = decode_digrams(&codebook, &bits);
+
+            // then:
+            assert_eq!(actual.as_deref(), Ok(*message));
+        }
+    }
+
+    #[test]
+    fn digrams_mark_odd_trailing_char() {
+        // when:
+        let (codebook, _) = encode_digrams("abababa");
+
+        // then:
+        assert_eq!(
+            codebook.keys().copied().collect::<Vec<_>>(),
+            [('a', None), ('a', Some('b'))]
+        );
+    }
+
+    #[test]
+    fn split_graphemes_keeps_clusters_together() {
+        // given: e with a combining acute accent, the family emoji of three
+        // people joined by ZWJs, the German and Swiss flags, a thumbs up with
+        // skin tone, a keycap and CR LF
+        let text = "e\u{301}t\u{e9} 👨\u{200d}👩\u{200d}👧🇩🇪🇨🇭👍🏽1\u{fe0f}\u{20e3}\r\n\n";
+
+        // when:
+        let actual = split_graphemes(text);
+
+        // then:
+        assert_eq!(
+            actual,
+            [
+                "e\u{301}",
+                "t",
+                "\u{e9}",
+                " ",
+                "👨\u{200d}👩\u{200d}👧",
+                "🇩🇪",
+                "🇨🇭",
+                "👍🏽",
+                "1\u{fe0f}\u{20e3}",
+                "\r\n",
+                "\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn split_graphemes_odd_regional_indicator() {
+        // when:
+        let actual = split_graphemes("🇩🇪🇨a");
+
+        // then:
+        assert_eq!(actual, ["🇩🇪", "🇨", "a"]);
+    }
+
+    #[test]
+    fn graphemes_round_trip() {
+        for message in [
+            "caf\u{65}\u{301} cre\u{300}me bru\u{302}le\u{301}e",
+            "🇩🇪🇫🇷🇩🇪🇮🇹🇩🇪",
+            "👨\u{200d}👩\u{200d}👧 and 👩\u{200d}❤\u{fe0f}\u{200d}👨 and 🏳\u{fe0f}\u{200d}🌈",
+            "\u{200d}leading joiner and trailing joiner\u{200d}",
+            "\u{301} combining mark first",
+        ]
+        .iter()
+        {
+            for &mode in [SymbolMode::Chars, SymbolMode::Graphemes].iter() {
+                // when:
+                let (codebook, bits) = encode_with_mode(message, mode);
+                let actual = decode_tokens(&codebook, &bits).unwrap();
+
+                // then:
+                assert_eq!(actual.as_bytes(), message.as_bytes(), "{:?}", mode);
+            }
+        }
+    }
+
+    #[test]
+    fn graphemes_are_single_symbols() {
+        // given:
+        let message = "👨\u{200d}👩\u{200d}👧👨\u{200d}👩\u{200d}👧e\u{301}";
+
+        // when:
+        let actual = (
+            encode_with_mode(message, SymbolMode::Chars).0,
+            encode_with_mode(message, SymbolMode::Graphemes).0,
+        );
+
+        // then:
+        assert_eq!(actual.0.len(), 6);
+        assert_eq!(
+            actual.1.keys().map(String::as_str).collect::<Vec<_>>(),
+            ["e\u{301}", "👨\u{200d}👩\u{200d}👧"]
+        );
+    }
+}
@@ -0,0 +1,1629 @@
+//! The Huffman tree and its construction from symbol frequencies.
+//!
+//! A tree can be as deep as it has leaves, e.g. one built from a codebook with
+//! long codes. So every walk over a tree, here and in the modules that use it,
+//! keeps an explicit stack instead of recursing, and deep trees do not
+//! overflow the call stack.
+
+use crate::arena::{HuffArena, MergeEvent};
+use alloc::collections::btree_map::{BTreeMap, Entry};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::cmp::Ord;
+use core::fmt::{self, Debug, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::ops::{Add, AddAssign, Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::io::{self, BufRead};
+
+/// A Huffman tree over symbols of type `T`, which are `char`s by default,
+/// with leaf weights of type `W`, which are u64 counts by default.
+///
+/// Trees are equal if they have the same shape, symbols and counts. With the
+/// serde feature they serialize like tree_to_json, nodes as
+/// `{"left": …, "right": …}` and leaves as `{"symbol": "a", "count": 12}`.
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(untagged)
+)]
+pub enum HuffTree<T = char, W = u64> {
+    Leaf {
+        #[cfg_attr(feature = "serde", serde(rename = "count"))]
+        occ: W,
+        #[cfg_attr(feature = "serde", serde(rename = "symbol"))]
+        chr: T,
+    },
+    Node {
+        left: Subtree<T, W>,
+        right: Subtree<T, W>,
+    },
+}
+
+/// A child of a HuffTree::Node, which owns it like a Box, but can be dropped
+/// without dropping its children first.
+pub struct Subtree<T = char, W = u64>(Option<Box<HuffTree<T, W>>>);
+
+impl<T, W> Subtree<T, W> {
+    pub fn new(tree: HuffTree<T, W>) -> Subtree<T, W> {
+        Subtree(Some(Box::new(tree)))
+    }
+    pub fn into_inner(mut self) -> HuffTree<T, W> {
+        *self
+            .0
+            .take()
+            .expect("a subtree is only empty while it is dropped")
+    }
+}
+
+impl<T, W> From<HuffTree<T, W>> for Subtree<T, W> {
+    fn from(tree: HuffTree<T, W>) -> Subtree<T, W> {
+        Subtree::new(tree)
+    }
+}
+
+impl<T, W> Deref for Subtree<T, W> {
+    type Target = HuffTree<T, W>;
+    fn deref(&self) -> &HuffTree<T, W> {
+        self.0
+            .as_deref()
+            .expect("a subtree is only empty while it is dropped")
+    }
+}
+
+impl<T, W> DerefMut for Subtree<T, W> {
+    fn deref_mut(&mut self) -> &mut HuffTree<T, W> {
+        self.0
+            .as_deref_mut()
+            .expect("a subtree is only empty while it is dropped")
+    }
+}
+
+impl<T: Clone, W: Clone> Clone for Subtree<T, W> {
+    fn clone(&self) -> Subtree<T, W> {
+        Subtree::new((**self).clone())
+    }
+}
+
+impl<T: PartialEq, W: PartialEq> PartialEq for Subtree<T, W> {
+    fn eq(&self, other: &Subtree<T, W>) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq, W: Eq> Eq for Subtree<T, W> {}
+
+impl<T: Hash, W: Hash> Hash for Subtree<T, W> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T: Debug, W: Debug> Debug for Subtree<T, W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Clone, W: Clone> Clone for HuffTree<T, W> {
+    fn clone(&self) -> HuffTree<T, W> {
+        self.fold(
+            |chr, occ| HuffTree::Leaf {
+                occ: occ.clone(),
+                chr: chr.clone(),
+            },
+            |_, left, right| HuffTree::Node {
+                left: Subtree::new(left),
+                right: Subtree::new(right),
+            },
+        )
+    }
+}
+
+impl<T: PartialEq, W: PartialEq> PartialEq for HuffTree<T, W> {
+    fn eq(&self, other: &HuffTree<T, W>) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (
+                    HuffTree::Leaf { occ, chr },
+                    HuffTree::Leaf {
+                        occ: other_occ,
+                        chr: other_chr,
+                    },
+                ) if occ == other_occ && chr == other_chr => {}
+                (
+                    HuffTree::Node { left, right },
+                    HuffTree::Node {
+                        left: other_left,
+                        right: other_right,
+                    },
+                ) => {
+                    stack.push((right, other_right));
+                    stack.push((left, other_left));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+impl<T: Eq, W: Eq> Eq for HuffTree<T, W> {}
+
+impl<T: Hash, W: Hash> Hash for HuffTree<T, W> {
+    /// Hash the nodes in pre-order, each marked as leaf or node.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut stack = vec![self];
+        while let Some(tree) = stack.pop() {
+            core::mem::discriminant(tree).hash(state);
+            match tree {
+                HuffTree::Leaf { occ, chr } => {
+                    occ.hash(state);
+                    chr.hash(state);
+                }
+                HuffTree::Node { left, right } => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Debug, W: Debug> Debug for HuffTree<T, W> {
+    /// Write the same as a derived Debug, including the pretty-printed
+    /// `{:#?}` form.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        enum Part<'a, T, W> {
+            Tree(&'a HuffTree<T, W>, usize),
+            Text(String),
+        }
+        let pretty = f.alternate();
+        let indent = |depth: usize| "    ".repeat(depth);
+        // A field value of a leaf, indented like the derived Debug does.
+        let value = |value: &dyn Debug, depth: usize| match pretty {
+            true => format!("{:#?}", value).replace('\n', &format!("\n{}", indent(depth))),
+            false => format!("{:?}", value),
+        };
+        let mut stack = vec![Part::Tree(self, 0)];
+        while let Some(part) = stack.pop() {
+            match part {
+                Part::Text(text) => f.write_str(&text)?,
+                Part::Tree(HuffTree::Leaf { occ, chr }, depth) if pretty => write!(
+                    f,
+                    "Leaf {{\n{1}occ: {2},\n{1}chr: {3},\n{0}}}",
+                    indent(depth),
+                    indent(depth + 1),
+                    value(occ, depth + 1),
+                    value(chr, depth + 1)
+                )?,
+                Part::Tree(HuffTree::Leaf { occ, chr }, _) => write!(
+                    f,
+                    "Leaf {{ occ: {}, chr: {} }}",
+                    value(occ, 0),
+                    value(chr, 0)
+                )?,
+                Part::Tree(HuffTree::Node { left, right }, depth) if pretty => {
+                    f.write_str("Node {\n")?;
+                    f.write_str(&indent(depth + 1))?;
+                    f.write_str("left: ")?;
+                    stack.push(Part::Text(format!(",\n{}}}", indent(depth))));
+                    stack.push(Part::Tree(right, depth + 1));
+                    stack.push(Part::Text(format!(",\n{}right: ", indent(depth + 1))));
+                    stack.push(Part::Tree(left, depth + 1));
+                }
+                Part::Tree(HuffTree::Node { left, right }, depth) => {
+                    f.write_str("Node { left: ")?;
+                    stack.push(Part::Text(" }".to_string()));
+                    stack.push(Part::Tree(right, depth + 1));
+                    stack.push(Part::Text(", right: ".to_string()));
+                    stack.push(Part::Tree(left, depth + 1));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T, W> HuffTree<T, W> {
+    /// Combine the values of the leaves bottom-up, passing each node with the
+    /// values of its left and right subtree.
+    fn fold<'a, R>(
+        &'a self,
+        mut leaf: impl FnMut(&'a T, &'a W) -> R,
+        mut node: impl FnMut(&'a HuffTree<T, W>, R, R) -> R,
+    ) -> R {
+        let mut stack = vec![(self, false)];
+        let mut values = Vec::new();
+        while let Some((tree, visited)) = stack.pop() {
+            match tree {
+                HuffTree::Leaf { occ, chr } => values.push(leaf(chr, occ)),
+                HuffTree::Node { left, right } if !visited => {
+                    stack.push((tree, true));
+                    stack.push((right, false));
+                    stack.push((left, false));
+                }
+                HuffTree::Node { .. } => {
+                    let right = values.pop().expect("the right subtree is folded");
+                    let left = values.pop().expect("the left subtree is folded");
+                    values.push(node(tree, left, right));
+                }
+            }
+        }
+        values.pop().expect("the tree is folded")
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize, W: serde::Serialize> serde::Serialize for Subtree<T, W> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (**self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>, W: serde::Deserialize<'de>> serde::Deserialize<'de>
+    for Subtree<T, W>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HuffTree::deserialize(deserializer).map(Subtree::new)
+    }
+}
+
+impl<T, W> Drop for Subtree<T, W> {
+    /// Detach the children of every node before dropping it, so that dropping
+    /// the node itself does not recurse, however deep the tree is.
+    fn drop(&mut self) {
+        let mut stack = self.0.take().into_iter().collect::<Vec<_>>();
+        while let Some(mut tree) = stack.pop() {
+            if let HuffTree::Node { left, right } = &mut *tree {
+                stack.extend(left.0.take());
+                stack.extend(right.0.take());
+            }
+        }
+    }
+}
+
+impl<T> HuffTree<T> {
+    pub fn new(chr: T, occ: u64) -> HuffTree<T> {
+        HuffTree::Leaf { occ, chr }
+    }
+    pub fn merge(self, other: HuffTree<T>) -> HuffTree<T> {
+        HuffTree::Node {
+            left: Subtree::new(self),
+            right: Subtree::new(other),
+        }
+    }
+    /// The sum of the counts of the leaves, saturating at u64::MAX.
+    ///
+    /// Nodes do not store their count, so this walks the whole subtree. The
+    /// huffman heap keeps the weights in HuffArena instead, and format and
+    /// to_dot count every node in a single walk.
+    pub fn lettercount(&self) -> u64 {
+        self.leaves()
+            .fold(0, |total: u64, (_, occ)| total.saturating_add(occ))
+    }
+    /// The symbols and counts of the leaves from left to right.
+    pub fn leaves(&self) -> Leaves<'_, T> {
+        Leaves { stack: vec![self] }
+    }
+    /// The length of the longest code. A tree that is a single leaf has the
+    /// code length 1 like in its codebook.
+    pub fn depth(&self) -> usize {
+        self.leaf_depths()
+            .map(|(_, depth)| depth)
+            .max()
+            .unwrap_or(0)
+    }
+    /// The sum of count times code length over all leaves, which is the
+    /// number of bits of the encoded message.
+    pub fn weighted_path_length(&self) -> u64 {
+        self.leaf_depths()
+            .map(|(occ, depth)| occ.saturating_mul(depth as u64))
+            .fold(0, u64::saturating_add)
+    }
+    /// The lettercount of every node, keyed by its address, so that labeling
+    /// every node with its count takes a single walk.
+    fn lettercounts(&self) -> BTreeMap<*const HuffTree<T>, u64> {
+        let mut counts = BTreeMap::new();
+        self.fold(
+            |_, &occ| occ,
+            |tree, left, right: u64| {
+                let count = left.saturating_add(right);
+                counts.insert(tree as *const _, count);
+                count
+            },
+        );
+        counts
+    }
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_depths().count()
+    }
+    /// The number of leaves for each code length.
+    pub fn code_length_histogram(&self) -> BTreeMap<usize, usize> {
+        self.leaf_depths()
+            .fold(BTreeMap::new(), |mut histogram, (_, depth)| {
+                *histogram.entry(depth).or_default() += 1;
+                histogram
+            })
+    }
+    /// The count and code length of every leaf.
+    fn leaf_depths(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        let mut stack = vec![(self, 0)];
+        core::iter::from_fn(move || loop {
+            match stack.pop()? {
+                (HuffTree::Leaf { occ, .. }, depth) => return Some((*occ, depth.max(1))),
+                (HuffTree::Node { left, right }, depth) => {
+                    stack.push((right, depth + 1));
+                    stack.push((left, depth + 1));
+                }
+            }
+        })
+    }
+}
+
+impl<T: Ord> HuffTree<T> {
+    /// Replace the occurrence count of every leaf with its count in the
+    /// frequency map, 0 if the symbol is missing.
+    pub fn with_counts(self, frequency: &BTreeMap<T, u64>) -> HuffTree<T> {
+        // None merges the last two rebuilt subtrees.
+        let mut stack = vec![Some(self)];
+        let mut rebuilt = Vec::new();
+        while let Some(task) = stack.pop() {
+            match task {
+                Some(HuffTree::Leaf { chr, .. }) => {
+                    let occ = frequency.get(&chr).copied().unwrap_or(0);
+                    rebuilt.push(HuffTree::Leaf { occ, chr });
+                }
+                Some(HuffTree::Node { left, right }) => {
+                    stack.push(None);
+                    stack.push(Some(right.into_inner()));
+                    stack.push(Some(left.into_inner()));
+                }
+                None => {
+                    let right = rebuilt.pop().expect("the right subtree is rebuilt");
+                    let left = rebuilt.pop().expect("the left subtree is rebuilt");
+                    rebuilt.push(left.merge(right));
+                }
+            }
+        }
+        rebuilt.pop().expect("the tree is rebuilt")
+    }
+}
+
+impl HuffTree {
+    pub fn chars(&self) -> String {
+        self.leaves().map(|(&chr, _)| chr).collect()
+    }
+}
+
+/// Iterator over the leaves of a HuffTree, created by HuffTree::leaves.
+pub struct Leaves<'a, T> {
+    stack: Vec<&'a HuffTree<T>>,
+}
+
+impl<'a, T> Iterator for Leaves<'a, T> {
+    type Item = (&'a T, u64);
+    fn next(&mut self) -> Option<(&'a T, u64)> {
+        loop {
+            match self.stack.pop()? {
+                HuffTree::Leaf { chr, occ } => return Some((chr, *occ)),
+                HuffTree::Node { left, right } => {
+                    self.stack.push(right);
+                    self.stack.push(left);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a HuffTree<T> {
+    type Item = (&'a T, u64);
+    type IntoIter = Leaves<'a, T>;
+    fn into_iter(self) -> Leaves<'a, T> {
+        self.leaves()
+    }
+}
+
+/// Options for HuffTree::format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeFormatOptions {
+    /// Prepended once per level.
+    pub indent: String,
+    /// Show the code of each leaf, e.g. `e: 17 [1101]`.
+    pub show_codes: bool,
+    /// Show the letter count of each subtree after its label, e.g. `left (7):`.
+    pub show_counts: bool,
+    /// Subtrees below this depth are elided as `…`.
+    pub max_depth: Option<usize>,
+}
+
+impl Default for TreeFormatOptions {
+    fn default() -> TreeFormatOptions {
+        TreeFormatOptions {
+            indent: "  ".to_string(),
+            show_codes: false,
+            show_counts: false,
+            max_depth: None,
+        }
+    }
+}
+
+impl<T: Display> HuffTree<T> {
+    /// Render the tree with one line per leaf and per branch label.
+    pub fn format(&self, opts: &TreeFormatOptions) -> String {
+        // Each entry holds a node, the length of its code and its last bit,
+        // which selects the branch label before it.
+        let counts = match opts.show_counts {
+            true => self.lettercounts(),
+            false => BTreeMap::new(),
+        };
+        let mut out = String::new();
+        let mut code = String::new();
+        let mut stack = vec![(self, 0, '0')];
+        while let Some((node, len, bit)) = stack.pop() {
+            if len > 0 {
+                code.truncate(len - 1);
+                code.push(bit);
+                let label = match bit {
+                    '0' => "left",
+                    _ => {
+                        out.push('\n');
+                        "right"
+                    }
+                };
+                out.push_str(&format!("{}{}", opts.indent.repeat(len - 1), label));
+                if opts.show_counts {
+                    let count = match node {
+                        HuffTree::Leaf { occ, .. } => *occ,
+                        HuffTree::Node { .. } => counts[&(node as *const _)],
+                    };
+                    out.push_str(&format!(" ({})", count));
+                }
+                out.push_str(":\n");
+            }
+            let indent = opts.indent.repeat(len);
+            match node {
+                HuffTree::Leaf { chr, occ } => {
+                    out.push_str(&format!("{}{}: {}", indent, chr, occ));
+                    if opts.show_codes {
+                        // A single leaf has the code 0 like in its codebook.
+                        let code = if code.is_empty() { "0" } else { &code };
+                        out.push_str(&format!(" [{}]", code));
+                    }
+                }
+                HuffTree::Node { .. } if opts.max_depth == Some(len) => {
+                    out.push_str(&format!("{}…", indent));
+                }
+                HuffTree::Node { left, right } => {
+                    stack.push((right, len + 1, '1'));
+                    stack.push((left, len + 1, '0'));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl<T: Display> Display for HuffTree<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.format(&TreeFormatOptions::default()))
+    }
+}
+
+impl<T: Display> HuffTree<T> {
+    /// A Graphviz digraph of the tree. Leaves are labeled with their symbol
+    /// and count, internal nodes with their count and edges with the bit.
+    pub fn to_dot(&self) -> String {
+        fn escape(label: &str) -> String {
+            label
+                .chars()
+                .map(|c| match c {
+                    '"' => "\\\"".to_string(),
+                    '\\' => "\\\\".to_string(),
+                    '\n' => "\\n".to_string(),
+                    c => c.to_string(),
+                })
+                .collect()
+        }
+        let counts = self.lettercounts();
+        let mut dot = String::from("digraph HuffTree {\n");
+        // Number the nodes in pre-order. Each entry holds a node and the id of
+        // its parent with the bit of the edge to it.
+        let mut stack = vec![(self, None)];
+        let mut next_id = 0;
+        while let Some((huff, parent)) = stack.pop() {
+            let id = next_id;
+            next_id += 1;
+            if let Some((parent, bit)) = parent {
+                dot.push_str(&format!("  n{} -> n{} [label=\"{}\"];\n", parent, id, bit));
+            }
+            match huff {
+                HuffTree::Leaf { chr, occ } => {
+                    let label = escape(&format!("{}: {}", chr, occ));
+                    dot.push_str(&format!("  n{} [label=\"{}\", shape=box];\n", id, label));
+                }
+                HuffTree::Node { left, right } => {
+                    let label = counts[&(huff as *const _)];
+                    dot.push_str(&format!("  n{} [label=\"{}\"];\n", id, label));
+                    stack.push((right, Some((id, 1))));
+                    stack.push((left, Some((id, 0))));
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Render the tree into any sink, one line per node.
+pub fn dump_tree<T: Display, W: fmt::Write>(huff: &HuffTree<T>, out: &mut W) -> fmt::Result {
+    writeln!(out, "{}", huff)
+}
+
+impl<T: Ord, W> HuffTree<T, W> {
+    /// The smallest symbol in the tree.
+    pub fn min_symbol(&self) -> &T {
+        self.fold(|chr, _| chr, |_, left, right| left.min(right))
+    }
+}
+
+/// A leaf weight for huffman_weighted: u32 or u64 counts or f64 probabilities.
+pub trait HuffWeight: Copy + PartialOrd + Add<Output = Self> {
+    /// Whether the weight can be used, i.e. is not negative or NaN.
+    fn is_valid(self) -> bool;
+    /// The weight of two merged subtrees. Counts saturate instead of
+    /// overflowing.
+    fn combine(self, other: Self) -> Self;
+}
+
+impl HuffWeight for u32 {
+    fn is_valid(self) -> bool {
+        true
+    }
+    fn combine(self, other: u32) -> u32 {
+        self.saturating_add(other)
+    }
+}
+
+impl HuffWeight for u64 {
+    fn is_valid(self) -> bool {
+        true
+    }
+    fn combine(self, other: u64) -> u64 {
+        self.saturating_add(other)
+    }
+}
+
+impl HuffWeight for f64 {
+    fn is_valid(self) -> bool {
+        self >= 0.0
+    }
+    fn combine(self, other: f64) -> f64 {
+        self + other
+    }
+}
+
+impl<T, W: HuffWeight> HuffTree<T, W> {
+    /// The sum of the weights of all leaves.
+    pub fn weight(&self) -> W {
+        self.fold(|_, &occ| occ, |_, left, right| left.combine(right))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum WeightError {
+    /// There are no weights to build a tree from.
+    Empty,
+    /// The weight of the symbol is negative or NaN.
+    Invalid(char),
+}
+
+impl Display for WeightError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightError::Empty => write!(f, "there are no weights"),
+            WeightError::Invalid(chr) => {
+                write!(f, "the weight of {:?} is negative or NaN", chr)
+            }
+        }
+    }
+}
+
+/// Build a Huffmann tree by iteratively combining two minimal elements.
+///
+/// The construction is deterministic: of two trees with the same letter count
+/// the one with the smaller smallest symbol is taken first, and the first tree
+/// taken becomes the left child.
+pub fn huffman<T: Ord>(frequency: BTreeMap<T, u64>) -> Option<HuffTree<T>> {
+    build(frequency)
+}
+
+/// Build the tree like huffman and call the observer after each merge with
+/// the two trees taken, the new one and the remaining forest, e.g. to show
+/// the construction step by step.
+pub fn huffman_with_observer<T: Ord, F>(
+    frequency: BTreeMap<T, u64>,
+    observer: F,
+) -> Option<HuffTree<T>>
+where
+    F: FnMut(MergeEvent<'_, T, u64>),
+{
+    HuffArena::build_with_observer(frequency, observer).map(HuffTree::from)
+}
+
+/// Build a Huffman tree like huffman from weights other than u64 counts, e.g.
+/// u32 counts or probabilities.
+pub fn huffman_weighted<W: HuffWeight>(
+    weights: BTreeMap<char, W>,
+) -> Result<HuffTree<char, W>, WeightError> {
+    if let Some((&chr, _)) = weights.iter().find(|(_, weight)| !weight.is_valid()) {
+        return Err(WeightError::Invalid(chr));
+    }
+    build(weights).ok_or(WeightError::Empty)
+}
+
+fn build<T: Ord, W: HuffWeight>(weights: BTreeMap<T, W>) -> Option<HuffTree<T, W>> {
+    HuffArena::build(weights).map(HuffTree::from)
+}
+
+/// Count the elements. Counting one element at a time cannot realistically
+/// overflow a u64, use try_frequency to add up counts.
+pub fn frequency<T: Ord, I: Iterator<Item = T>>(iter: &mut I) -> BTreeMap<T, u64> {
+    iter.fold(BTreeMap::new(), |mut map, element| {
+        *map.entry(element).or_default() += 1;
+        map
+    })
+}
+
+/// The counts of the symbol add up to more than u64::MAX.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountOverflow<T>(pub T);
+
+impl<T: Debug> Display for CountOverflow<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "the count of {:?} exceeds {}", self.0, u64::MAX)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: Debug> std::error::Error for CountOverflow<T> {}
+
+/// Add up the counts per symbol, e.g. of several frequency maps chained
+/// together, failing instead of overflowing.
+pub fn try_frequency<T: Ord, I: IntoIterator<Item = (T, u64)>>(
+    counts: I,
+) -> Result<BTreeMap<T, u64>, CountOverflow<T>> {
+    let mut map = BTreeMap::new();
+    for (element, occ) in counts {
+        match map.entry(element) {
+            Entry::Vacant(entry) => {
+                entry.insert(occ);
+            }
+            Entry::Occupied(mut entry) => match entry.get().checked_add(occ) {
+                Some(total) => *entry.get_mut() = total,
+                None => return Err(CountOverflow(entry.remove_entry().0)),
+            },
+        }
+    }
+    Ok(map)
+}
+
+/// Count the UTF-8 text of the reader like frequency, one buffer at a time.
+/// Characters split between buffers are counted once.
+#[cfg(feature = "std")]
+pub fn frequency_from_reader<R: BufRead>(reader: R) -> io::Result<BTreeMap<char, u64>> {
+    let mut table = FrequencyTable::new();
+    table.count_reader(reader)?;
+    Ok(table.counts)
+}
+
+/// Count the bytes of the reader one buffer at a time.
+#[cfg(feature = "std")]
+pub fn byte_frequency_from_reader<R: BufRead>(mut reader: R) -> io::Result<BTreeMap<u8, u64>> {
+    let mut counts = [0u64; 256];
+    loop {
+        let buf = match reader.fill_buf() {
+            Ok(buf) => buf,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if buf.is_empty() {
+            break;
+        }
+        for &byte in buf {
+            counts[usize::from(byte)] += 1;
+        }
+        let len = buf.len();
+        reader.consume(len);
+    }
+    Ok((0..=u8::MAX)
+        .zip(counts)
+        .filter(|&(_, occ)| occ > 0)
+        .collect())
+}
+
+/// Symbol counts that can be collected from several sources and merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FrequencyTable {
+    counts: BTreeMap<char, u64>,
+}
+
+impl FrequencyTable {
+    pub fn new() -> FrequencyTable {
+        FrequencyTable::default()
+    }
+    pub fn counts(&self) -> &BTreeMap<char, u64> {
+        &self.counts
+    }
+    pub fn into_counts(self) -> BTreeMap<char, u64> {
+        self.counts
+    }
+    pub fn add_symbol(&mut self, chr: char) {
+        *self.counts.entry(chr).or_default() += 1;
+    }
+    pub fn count_str(&mut self, s: &str) {
+        for chr in s.chars() {
+            self.add_symbol(chr);
+        }
+    }
+    /// Count the UTF-8 text of the reader one buffer at a time. Fails with
+    /// InvalidData if the text is not UTF-8, but keeps what was counted before.
+    #[cfg(feature = "std")]
+    pub fn count_reader<R: BufRead>(&mut self, mut reader: R) -> io::Result<()> {
+        // The bytes of a character that is split between two buffers.
+        let mut pending = Vec::new();
+        loop {
+            let buf = match reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            if buf.is_empty() {
+                if pending.is_empty() {
+                    return Ok(());
+                }
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "the text ends in the middle of a UTF-8 character",
+                ));
+            }
+            pending.extend_from_slice(buf);
+            let len = buf.len();
+            reader.consume(len);
+            let valid = match core::str::from_utf8(&pending) {
+                Ok(text) => text,
+                // An error without length is a character cut off at the end.
+                Err(err) if err.error_len().is_none() => {
+                    core::str::from_utf8(&pending[..err.valid_up_to()]).unwrap()
+                }
+                Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            };
+            let valid_len = valid.len();
+            self.count_str(valid);
+            pending.drain(..valid_len);
+        }
+    }
+    /// Add the counts of the other table, saturating at u64::MAX.
+    pub fn merge(&mut self, other: FrequencyTable) {
+        for (chr, occ) in other.counts {
+            let total = self.counts.entry(chr).or_default();
+            *total = total.saturating_add(occ);
+        }
+    }
+    /// Add the counts of the other table like merge, but fail without
+    /// changing this table if a count would exceed u64::MAX.
+    pub fn try_merge(&mut self, other: FrequencyTable) -> Result<(), CountOverflow<char>> {
+        let counts = self.counts.iter().chain(&other.counts);
+        self.counts = try_frequency(counts.map(|(&chr, &occ)| (chr, occ)))?;
+        Ok(())
+    }
+    /// Build the Huffman tree of the counts.
+    pub fn into_huffman(self) -> Option<HuffTree> {
+        huffman(self.counts)
+    }
+}
+
+impl AddAssign for FrequencyTable {
+    fn add_assign(&mut self, other: FrequencyTable) {
+        self.merge(other);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::random_numbers;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn huff_tree_serde_round_trip() {
+        // given:
+        let tree = huffman(frequency(&mut "say \"hi\" to München €".chars())).unwrap();
+
+        // when:
+        let json = serde_json::to_string(&tree).unwrap();
+        let actual = serde_json::from_str::<HuffTree>(&json).unwrap();
+
+        // then:
+        assert_eq!(actual, tree);
+        assert_eq!(
+            serde_json::to_string(&HuffTree::new('a', 2).merge(HuffTree::new('b', 1))).unwrap(),
+            r#"{"left":{"count":2,"symbol":"a"},"right":{"count":1,"symbol":"b"}}"#
+        );
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
+    fn frequency_test_numbers() {
+        // given:
+        let numbers = vec![1, 2, 3, 3, 2, 3, 5];
+
+        // when:
+        let actual: BTreeMap<_, u64> = frequency(&mut numbers.iter());
+
+        // then:
+        assert_eq!(actual[&1], 1);
+        assert_eq!(actual[&2], 2);
+        assert_eq!(actual[&3], 3);
+        assert_eq!(actual[&5], 1);
+    }
+
+    #[test]
+    fn frequency_test_chars() {
+        // given:
+        let m = "Hello World";
+
+        // when:
+        let actual: BTreeMap<_, u64> = frequency(&mut m.chars());
+
+        // then:
+        assert_eq!(actual[&'H'], 1);
+        assert_eq!(actual[&'e'], 1);
+        assert_eq!(actual[&'l'], 3);
+        assert_eq!(actual[&'o'], 2);
+    }
+
+    #[test]
+    fn frequency_test_merge_beyond_u32() {
+        // given:
+        let mut first = frequency(&mut "aab".chars());
+        first.insert('a', u64::from(u32::MAX));
+        let second = frequency(&mut "abc".chars());
+
+        // when:
+        let actual = try_frequency(first.into_iter().chain(second));
+
+        // then:
+        let expected = [('a', u64::from(u32::MAX) + 1), ('b', 2), ('c', 1)];
+        assert_eq!(actual, Ok(expected.iter().copied().collect()));
+    }
+
+    #[test]
+    fn frequency_test_merge_overflow() {
+        // given:
+        let counts = [('a', 1), ('b', u64::MAX), ('a', 2), ('b', 1)];
+
+        // when:
+        let actual = try_frequency(counts.iter().copied());
+
+        // then:
+        assert_eq!(actual, Err(CountOverflow('b')));
+        assert_eq!(
+            actual.unwrap_err().to_string(),
+            "the count of 'b' exceeds 18446744073709551615"
+        );
+    }
+
+    #[test]
+    fn huffman_with_observer_merges_greedily() {
+        // given:
+        let frequency = frequency(&mut "aaaaabbcd".chars());
+        let mut counts = Vec::new();
+        let mut forests = Vec::new();
+
+        // when:
+        let actual = huffman_with_observer(frequency.clone(), |event| {
+            counts.push((
+                event.left().weight(),
+                event.right().weight(),
+                event.merged().weight(),
+            ));
+            let forest = event
+                .forest()
+                .iter()
+                .map(|tree| tree.chars())
+                .collect::<Vec<_>>();
+            forests.push(format!(
+                "{} + {} -> {}",
+                event.left().chars(),
+                event.right().chars(),
+                forest.join(" ")
+            ));
+        });
+
+        // then:
+        assert_eq!(actual, huffman(frequency));
+        assert_eq!(counts, [(1, 1, 2), (2, 2, 4), (4, 5, 9)]);
+        assert_eq!(
+            forests,
+            ["c + d -> b cd a", "b + cd -> bcd a", "bcd + a -> bcda"]
+        );
+    }
+
+    #[test]
+    fn huffman_counts_beyond_u64_saturate() {
+        // given:
+        let frequency = [('a', u64::MAX), ('b', u64::MAX), ('c', 1)]
+            .iter()
+            .copied()
+            .collect::<BTreeMap<_, _>>();
+
+        // when:
+        let actual = huffman(frequency).unwrap();
+
+        // then:
+        assert_eq!(actual.lettercount(), u64::MAX);
+        assert_eq!(actual.weight(), u64::MAX);
+        assert_eq!(actual.depth(), 2);
+    }
+
+    #[test]
+    fn dump_tree_renders_every_node() {
+        // given:
+        let tree = HuffTree::new('a', 2).merge(HuffTree::new('b', 1).merge(HuffTree::new('c', 1)));
+        let mut actual = String::new();
+
+        // when:
+        dump_tree(&tree, &mut actual).unwrap();
+
+        // then:
+        assert_eq!(
+            actual,
+            "left:\n  a: 2\nright:\n  left:\n    b: 1\n  right:\n    c: 1\n"
+        );
+    }
+
+    #[test]
+    fn tree_equality_is_structural() {
+        // given:
+        let balanced = || {
+            HuffTree::new('a', 1)
+                .merge(HuffTree::new('b', 1))
+                .merge(HuffTree::new('c', 1).merge(HuffTree::new('d', 1)))
+        };
+        let skewed = HuffTree::new('a', 1)
+            .merge(HuffTree::new('b', 1).merge(HuffTree::new('c', 1).merge(HuffTree::new('d', 1))));
+        let renamed = HuffTree::new('a', 1)
+            .merge(HuffTree::new('b', 1))
+            .merge(HuffTree::new('c', 1).merge(HuffTree::new('e', 1)));
+
+        // when:
+        let actual = [
+            balanced() == balanced(),
+            balanced() == skewed,
+            balanced() == renamed,
+        ];
+
+        // then:
+        assert_eq!(skewed.lettercount(), balanced().lettercount());
+        assert_eq!(actual, [true, false, false]);
+    }
+
+    #[test]
+    fn tree_hash_is_structural() {
+        // given:
+        let tree = || HuffTree::new('a', 2).merge(HuffTree::new('b', 1));
+        let mirrored = HuffTree::new('b', 1).merge(HuffTree::new('a', 2));
+
+        // when:
+        let actual = [tree(), tree(), mirrored]
+            .iter()
+            .cloned()
+            .collect::<std::collections::HashSet<_>>();
+
+        // then:
+        assert_eq!(actual.len(), 2);
+    }
+
+    #[test]
+    fn huffman_builds_expected_tree() {
+        // when:
+        let actual = huffman(frequency(&mut "aabbbcd".chars()));
+
+        // then:
+        let expected = HuffTree::new('b', 3)
+            .merge(HuffTree::new('a', 2).merge(HuffTree::new('c', 1).merge(HuffTree::new('d', 1))));
+        assert_eq!(actual, Some(expected));
+    }
+
+    #[test]
+    fn tree_metrics() {
+        // given:
+        let message = "BACADAEAFABBAAAGAH";
+        let tree = huffman(frequency(&mut message.chars())).unwrap();
+
+        // when:
+        let actual = (
+            tree.depth(),
+            tree.weighted_path_length(),
+            tree.leaf_count(),
+            tree.code_length_histogram(),
+        );
+
+        // then:
+        let expected_histogram = [(1, 1), (3, 1), (4, 6)].iter().copied().collect();
+        assert_eq!(actual, (4, 42, 8, expected_histogram));
+        assert_eq!(actual.1 as usize, crate::code::encode(message).bits.len());
+    }
+
+    #[test]
+    fn tree_metrics_single_leaf() {
+        // given:
+        let tree = huffman(frequency(&mut "aaa".chars())).unwrap();
+
+        // when:
+        let actual = (tree.depth(), tree.weighted_path_length(), tree.leaf_count());
+
+        // then:
+        assert_eq!(actual, (1, 3, 1));
+        assert_eq!(crate::code::encode("aaa").bits.len(), 3);
+    }
+
+    #[test]
+    fn tree_metrics_of_deep_tree() {
+        // given: 40 Fibonacci counts give a tree of depth 39
+        let mut fibonacci = (1u64, 1u64);
+        let frequency = (0..40)
+            .map(|i| {
+                let occ = fibonacci.0;
+                fibonacci = (fibonacci.1, fibonacci.0.saturating_add(fibonacci.1));
+                (std::char::from_u32(0x100 + i).unwrap(), occ)
+            })
+            .collect::<BTreeMap<_, _>>();
+        let tree = huffman(frequency.clone()).unwrap();
+
+        // when:
+        let actual = (tree.depth(), tree.leaf_count(), tree.weighted_path_length());
+
+        // then:
+        let codebook = crate::code::codebook(&tree);
+        let expected_length = frequency
+            .iter()
+            .map(|(chr, &occ)| occ * codebook[chr].len() as u64)
+            .sum::<u64>();
+        assert_eq!(actual, (39, 40, expected_length));
+        assert_eq!(tree.code_length_histogram()[&39], 2);
+    }
+
+    #[test]
+    fn leaves_in_display_order() {
+        // given:
+        let tree = huffman(frequency(&mut "BACADAEAFABBAAAGAH".chars())).unwrap();
+        let displayed = tree
+            .to_string()
+            .lines()
+            .filter_map(|line| {
+                let (chr, occ) = line.trim().split_once(": ")?;
+                Some((chr.chars().next()?, occ.parse().ok()?))
+            })
+            .collect::<Vec<(char, u64)>>();
+
+        // when:
+        let actual = tree
+            .leaves()
+            .map(|(&chr, occ)| (chr, occ))
+            .collect::<Vec<_>>();
+
+        // then:
+        assert_eq!(actual, displayed);
+        assert_eq!(actual.len(), 8);
+        assert_eq!(
+            tree.chars(),
+            actual.iter().map(|&(chr, _)| chr).collect::<String>()
+        );
+    }
+
+    #[test]
+    fn leaves_sum_to_lettercount() {
+        // given:
+        let tree = huffman(frequency(&mut "aardvarks ate apples around aachen".chars())).unwrap();
+
+        // when:
+        let actual = (&tree).into_iter().map(|(_, occ)| occ).sum::<u64>();
+
+        // then:
+        assert_eq!(actual, tree.lettercount());
+        assert_eq!(actual, 34);
+    }
+
+    #[test]
+    fn format_with_codes_matches_codebook() {
+        // given:
+        let tree = huffman(frequency(&mut "aardvarks ate apples around aachen".chars())).unwrap();
+        let opts = TreeFormatOptions {
+            show_codes: true,
+            ..TreeFormatOptions::default()
+        };
+
+        // when:
+        let actual = tree
+            .format(&opts)
+            .lines()
+            .filter_map(|line| {
+                let (indent_and_chr, rest) = line.rsplit_once(": ")?;
+                let code = rest.split_once(" [")?.1.strip_suffix(']')?;
+                Some((indent_and_chr.chars().last()?, code.to_string()))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        // then:
+        let expected = crate::code::codebook(&tree)
+            .into_iter()
+            .map(|(chr, code)| (chr, code.to_string()))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(actual, expected);
+        assert_eq!(HuffTree::new('a', 3).format(&opts), "a: 3 [0]");
+    }
+
+    #[test]
+    fn format_with_depth_cutoff_and_counts() {
+        // given:
+        let tree = HuffTree::new('a', 2).merge(HuffTree::new('b', 1).merge(HuffTree::new('c', 1)));
+        let opts = TreeFormatOptions {
+            indent: "| ".to_string(),
+            show_counts: true,
+            max_depth: Some(1),
+            ..TreeFormatOptions::default()
+        };
+
+        // when:
+        let actual = tree.format(&opts);
+
+        // then:
+        assert_eq!(actual, "left (2):\n| a: 2\nright (2):\n| …");
+    }
+
+    #[test]
+    fn deep_tree_drops_without_recursion() {
+        // given: a chain of 300000 nodes, each symbol shared with a counter
+        let counter = alloc::rc::Rc::new(());
+        let tree = (0..300_000).fold(HuffTree::new(counter.clone(), 1), |tree, _| {
+            HuffTree::new(counter.clone(), 1).merge(tree)
+        });
+        assert_eq!(alloc::rc::Rc::strong_count(&counter), 300_002);
+
+        // when:
+        drop(tree);
+
+        // then: every leaf is dropped exactly once
+        assert_eq!(alloc::rc::Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn deep_tree_walks_without_recursion() {
+        // given: a chain of 300000 nodes
+        let tree = (0..300_000).fold(HuffTree::new('b', 1), |tree, _| {
+            HuffTree::new('c', 1).merge(tree)
+        });
+        let hash = |tree: &HuffTree| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            tree.hash(&mut hasher);
+            hasher.finish()
+        };
+        let no_indent = TreeFormatOptions {
+            indent: String::new(),
+            show_counts: true,
+            ..TreeFormatOptions::default()
+        };
+
+        // when:
+        let cloned = tree.clone();
+        let recounted = cloned.clone().with_counts(&frequency(&mut "bcc".chars()));
+
+        // then:
+        assert!(cloned == tree);
+        assert!(recounted != tree);
+        assert_eq!(hash(&cloned), hash(&tree));
+        assert_eq!(recounted.lettercount(), 300_000 * 2 + 1);
+        assert_eq!(*tree.min_symbol(), 'b');
+        assert_eq!(tree.weight(), 300_001);
+        assert!(format!("{:?}", tree).starts_with("Node { left: Leaf { occ: 1, chr: 'c' }"));
+        assert!(tree
+            .format(&no_indent)
+            .starts_with("left (1):\nc: 1\nright (300000):"));
+        assert!(tree.to_dot().contains("  n0 [label=\"300001\"];\n"));
+    }
+
+    #[test]
+    fn debug_matches_derived_format() {
+        // given:
+        let tree = HuffTree::new('a', 2).merge(HuffTree::new('b', 1).merge(HuffTree::new('c', 1)));
+
+        // when:
+        let actual = (format!("{:?}", tree), format!("{:#?}", tree));
+
+        // then:
+        assert_eq!(
+            actual.0,
+            "Node { left: Leaf { occ: 2, chr: 'a' }, right: Node { \
+             left: Leaf { occ: 1, chr: 'b' }, right: Leaf { occ: 1, chr: 'c' } } }"
+        );
+        assert_eq!(
+            actual.1,
+            "Node {
+    left: Leaf {
+        occ: 2,
+        chr: 'a',
+    },
+    right: Node {
+        left: Leaf {
+            occ: 1,
+            chr: 'b',
+        },
+        right: Leaf {
+            occ: 1,
+            chr: 'c',
+        },
+    },
+}"
+        );
+    }
+
+    #[test]
+    fn subtree_into_inner() {
+        // given:
+        let tree = HuffTree::new('a', 2).merge(HuffTree::new('b', 1).merge(HuffTree::new('c', 1)));
+
+        // when:
+        let actual = match tree {
+            HuffTree::Node { left, right } => (left.into_inner(), right.into_inner()),
+            HuffTree::Leaf { .. } => unreachable!(),
+        };
+
+        // then:
+        assert_eq!(actual.0, HuffTree::new('a', 2));
+        assert_eq!(actual.1.chars(), "bc");
+        assert_eq!(format!("{:?}", actual.0), "Leaf { occ: 2, chr: 'a' }");
+    }
+
+    #[test]
+    fn deep_tree_codebook_and_display() {
+        // given: a leaf merged onto the previous tree again and again
+        let depth = 1000;
+        let tree = (1..=depth).fold(
+            HuffTree::new(char::from_u32(0x100).unwrap(), 1),
+            |tree, i| HuffTree::new(char::from_u32(0x100 + i).unwrap(), 1).merge(tree),
+        );
+
+        // when: on a thread whose stack is too small for recursing that deep
+        let (codebook, rendered) = std::thread::scope(|scope| {
+            std::thread::Builder::new()
+                .stack_size(128 * 1024)
+                .spawn_scoped(scope, || (crate::code::codebook(&tree), tree.to_string()))
+                .unwrap()
+                .join()
+                .unwrap()
+        });
+
+        // then:
+        assert_eq!(codebook.len(), depth as usize + 1);
+        assert_eq!(
+            codebook[&char::from_u32(0x100 + depth).unwrap()].to_string(),
+            "0"
+        );
+        assert_eq!(
+            codebook[&char::from_u32(0x100).unwrap()].len(),
+            depth as usize
+        );
+        assert_eq!(rendered.lines().count(), 3 * depth as usize + 1);
+        assert!(rendered.ends_with(&format!("{}\u{100}: 1", "  ".repeat(depth as usize))));
+    }
+
+    #[test]
+    fn format_defaults_match_display() {
+        // given:
+        let tree = HuffTree::new('a', 2).merge(HuffTree::new('b', 1).merge(HuffTree::new('c', 1)));
+
+        // when:
+        let actual = tree.format(&TreeFormatOptions::default());
+
+        // then:
+        assert_eq!(actual, tree.to_string());
+        assert_eq!(
+            actual,
+            "left:\n  a: 2\nright:\n  left:\n    b: 1\n  right:\n    c: 1"
+        );
+    }
+
+    #[test]
+    fn frequency_from_reader_with_tiny_buffer() {
+        // given:
+        let message = "Grüße aus München, Köln und Düsseldorf 🦀🦀 ÄÖÜ €".repeat(7);
+
+        // when:
+        let actual = frequency_from_reader(io::BufReader::with_capacity(3, message.as_bytes()));
+
+        // then:
+        assert_eq!(actual.unwrap(), frequency(&mut message.chars()));
+    }
+
+    #[test]
+    fn frequency_from_reader_rejects_cut_off_char() {
+        // when:
+        let actual = frequency_from_reader(&"ü".as_bytes()[..1]);
+
+        // then:
+        assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn byte_frequency_from_reader_with_tiny_buffer() {
+        // given:
+        let bytes = [0u8, 255, 1, 0, 0xc3, 0xbc, 255, 0];
+
+        // when:
+        let actual = byte_frequency_from_reader(io::BufReader::with_capacity(3, &bytes[..]));
+
+        // then:
+        assert_eq!(actual.unwrap(), frequency(&mut bytes.iter().copied()));
+    }
+
+    #[test]
+    fn huffman_weighted_u64_beyond_u32() {
+        // given:
+        let weights = [
+            ('a', u64::from(u32::MAX)),
+            ('b', u64::from(u32::MAX)),
+            ('c', 1),
+        ]
+        .iter()
+        .copied()
+        .collect();
+
+        // when:
+        let actual = huffman_weighted(weights).unwrap();
+
+        // then:
+        assert_eq!(actual.weight(), 2 * u64::from(u32::MAX) + 1);
+        let lengths = crate::code::codebook(&actual)
+            .into_iter()
+            .map(|(chr, code)| (chr, code.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(lengths, [('a', 2), ('b', 1), ('c', 2)]);
+    }
+
+    #[test]
+    fn huffman_weighted_probabilities() {
+        // given:
+        let weights = [('a', 0.5), ('b', 0.25), ('c', 0.125), ('d', 0.125)]
+            .iter()
+            .copied()
+            .collect();
+
+        // when:
+        let actual = huffman_weighted(weights).unwrap();
+
+        // then:
+        let lengths = crate::code::codebook(&actual)
+            .into_iter()
+            .map(|(chr, code)| (chr, code.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(lengths, [('a', 1), ('b', 2), ('c', 3), ('d', 3)]);
+    }
+
+    #[test]
+    fn huffman_weighted_rejects_invalid_weights() {
+        for &weight in [-0.5, f64::NAN].iter() {
+            // given:
+            let weights = [('a', 0.5), ('b', weight)].iter().copied().collect();
+
+            // when:
+            let actual = huffman_weighted(weights);
+
+            // then:
+            assert_eq!(actual.err(), Some(WeightError::Invalid('b')));
+        }
+        assert_eq!(
+            huffman_weighted::<f64>(BTreeMap::new()).err(),
+            Some(WeightError::Empty)
+        );
+    }
+
+    #[test]
+    fn huffman_weighted_matches_huffman() {
+        // given:
+        let frequency = frequency(&mut "aardvarks ate apples around aachen".chars());
+
+        // when:
+        let actual = huffman_weighted(frequency.clone()).unwrap();
+
+        // then:
+        assert_eq!(Some(actual), huffman(frequency));
+    }
+
+    #[test]
+    fn huffman_breaks_ties_by_smallest_symbol() {
+        // given:
+        let frequency = frequency(&mut "abcde".chars());
+
+        // when:
+        let actual = huffman(frequency).unwrap();
+
+        // then:
+        let actual = crate::code::codebook(&actual)
+            .into_iter()
+            .map(|(chr, code)| (chr, code.to_string()))
+            .collect::<Vec<_>>();
+        let expected = [
+            ('a', "110"),
+            ('b', "111"),
+            ('c', "00"),
+            ('d', "01"),
+            ('e', "10"),
+        ];
+        assert_eq!(
+            actual,
+            expected
+                .iter()
+                .map(|&(chr, code)| (chr, code.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn huffman_is_independent_of_insertion_order() {
+        // given:
+        let mut counts = ('A'..='N')
+            .zip([3, 3, 1, 1, 2, 2, 1, 1, 4, 4, 2, 2, 5, 5].iter().copied())
+            .collect::<Vec<_>>();
+        let expected = crate::code::codebook(&huffman(counts.iter().copied().collect()).unwrap());
+
+        for seed in 0..20u64 {
+            // when:
+            let mut numbers = random_numbers(seed);
+            for i in (1..counts.len()).rev() {
+                counts.swap(i, numbers.next().unwrap() % (i + 1));
+            }
+            let mut frequency = BTreeMap::new();
+            for &(chr, occ) in &counts {
+                frequency.insert(chr, occ);
+            }
+            let actual = crate::code::codebook(&huffman(frequency).unwrap());
+
+            // then:
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn to_dot_two_leaves() {
+        // given:
+        let tree = HuffTree::new('a', 2).merge(HuffTree::new('b', 1));
+
+        // when:
+        let actual = tree.to_dot();
+
+        // then:
+        assert_eq!(
+            actual,
+            "digraph HuffTree {
+  n0 [label=\"3\"];
+  n0 -> n1 [label=\"0\"];
+  n1 [label=\"a: 2\", shape=box];
+  n0 -> n2 [label=\"1\"];
+  n2 [label=\"b: 1\", shape=box];
+}
+"
+        );
+    }
+
+    #[test]
+    fn to_dot_escapes_special_symbols() {
+        // given:
+        let tree = HuffTree::new('"', 1)
+            .merge(HuffTree::new('\\', 1))
+            .merge(HuffTree::new('\n', 1).merge(HuffTree::new(' ', 1)));
+
+        // when:
+        let actual = tree.to_dot();
+
+        // then:
+        assert!(actual.contains(r#"[label="\": 1", shape=box]"#));
+        assert!(actual.contains(r#"[label="\\: 1", shape=box]"#));
+        assert!(actual.contains(r#"[label="\n: 1", shape=box]"#));
+        assert!(actual.contains(r#"[label=" : 1", shape=box]"#));
+    }
+
+    fn table(s: &str) -> FrequencyTable {
+        let mut table = FrequencyTable::new();
+        table.count_str(s);
+        table
+    }
+
+    #[test]
+    fn frequency_table_merge_halves() {
+        // given:
+        let message = "aardvarks ate apples around aachen";
+        let (first, second) = message.split_at(message.len() / 2);
+
+        // when:
+        let mut actual = table(first);
+        actual += table(second);
+
+        // then:
+        assert_eq!(actual, table(message));
+    }
+
+    #[test]
+    fn frequency_table_count_reader_larger_than_buffer() {
+        // given:
+        let message = "Grüße aus München für 5 € 🦀\n".repeat(2000);
+        let path = std::env::temp_dir().join(format!("frequency-table-{}", std::process::id()));
+        std::fs::write(&path, &message).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+
+        // when:
+        let mut actual = FrequencyTable::new();
+        actual.count_reader(io::BufReader::new(file)).unwrap();
+        let mut tiny_buffers = FrequencyTable::new();
+        tiny_buffers
+            .count_reader(io::BufReader::with_capacity(3, message.as_bytes()))
+            .unwrap();
+
+        // then:
+        std::fs::remove_file(&path).unwrap();
+        assert!(message.len() > 8 * 1024);
+        assert_eq!(actual, table(&message));
+        assert_eq!(tiny_buffers, table(&message));
+    }
+
+    #[test]
+    fn frequency_table_count_reader_invalid_utf8() {
+        // when:
+        let actual = FrequencyTable::new().count_reader(&[b'a', 0xff][..]);
+
+        // then:
+        assert_eq!(actual.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn frequency_table_into_huffman_keeps_large_counts() {
+        // given:
+        let mut table = table("ab");
+        table.counts.insert('a', 3 * u64::from(u32::MAX));
+        table.counts.insert('b', u64::from(u32::MAX));
+
+        // when:
+        let actual = table.into_huffman().unwrap();
+
+        // then:
+        assert_eq!(actual.lettercount(), 4 * u64::from(u32::MAX));
+        assert_eq!(actual.chars().len(), 2);
+    }
+
+    #[test]
+    fn frequency_table_try_merge_overflow() {
+        // given:
+        let mut counts = table("ab");
+        counts.counts.insert('b', u64::MAX);
+        let before = counts.clone();
+
+        // when:
+        let actual = counts.try_merge(table("ba"));
+        let mut saturated = before.clone();
+        saturated += table("ba");
+
+        // then:
+        assert_eq!(actual, Err(CountOverflow('b')));
+        assert_eq!(counts, before);
+        assert_eq!(saturated.counts()[&'a'], 2);
+        assert_eq!(saturated.counts()[&'b'], u64::MAX);
+    }
+}
@@ -0,0 +1,53 @@
+//! The browser bindings: thin wasm_bindgen exports over the container and
+//! JSON APIs with errors as readable JsValue messages.
+
+use crate::code::encode;
+use crate::container::{compress, decompress};
+use crate::json::codebook_to_json;
+use alloc::{format, string::String, vec::Vec};
+use wasm_bindgen::prelude::*;
+
+/// The self-contained container of the message.
+#[wasm_bindgen]
+pub fn compress_text(message: &str) -> Vec<u8> {
+    compress(message)
+}
+
+/// Decompress the output of compress_text.
+#[wasm_bindgen]
+pub fn decompress_text(data: &[u8]) -> Result<String, JsValue> {
+    decompress(data).map_err(|err| JsValue::from_str(&format!("cannot decompress: {}", err)))
+}
+
+/// The codebook of the message as JSON for rendering the codes, `{}` for the
+/// empty message.
+#[wasm_bindgen]
+pub fn codebook_json(message: &str) -> String {
+    codebook_to_json(&encode(message).codebook)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trip() {
+        for message in ["", "a", "Grüße aus München 🦀"].iter() {
+            // when:
+            let actual = decompress_text(&compress_text(message));
+
+            // then:
+            assert_eq!(actual.ok().as_deref(), Some(*message));
+        }
+    }
+
+    #[test]
+    fn codebook_json_of_message() {
+        // when:
+        let actual = (codebook_json("aab"), codebook_json(""));
+
+        // then:
+        assert_eq!(actual.0, "{\n  \"a\": \"0\",\n  \"b\": \"1\"\n}");
+        assert_eq!(actual.1, "{}");
+    }
+}
@@ -0,0 +1,637 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn huff(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_huff"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("huff-cli-test-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn compress_decompress_files() {
+    // given:
+    let message = "Grüße aus München, aardvarks ate apples around aachen";
+    let input = temp_path("input.txt");
+    let archive = temp_path("input.huf");
+    let output = temp_path("output.txt");
+    fs::write(&input, message).unwrap();
+
+    // when:
+    let compressed = huff(
+        &[
+            "compress",
+            input.to_str().unwrap(),
+            "-o",
+            archive.to_str().unwrap(),
+        ],
+        b"",
+    );
+    let decompressed = huff(
+        &[
+            "decompress",
+            archive.to_str().unwrap(),
+            "-o",
+            output.to_str().unwrap(),
+        ],
+        b"",
+    );
+
+    // then:
+    assert!(compressed.status.success());
+    assert!(String::from_utf8_lossy(&compressed.stderr).contains("ratio"));
+    assert!(decompressed.status.success());
+    assert_eq!(fs::read_to_string(&output).unwrap(), message);
+    for path in [input, archive, output].iter() {
+        fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn compress_decompress_stdin_stdout() {
+    // given:
+    let message = b"BACADAEAFABBAAAGAH";
+
+    // when:
+    let compressed = huff(&["compress", "-", "-o", "-"], message);
+    let decompressed = huff(&["decompress", "-"], &compressed.stdout);
+
+    // then:
+    assert!(compressed.status.success());
+    assert!(decompressed.status.success());
+    assert_eq!(decompressed.stdout, message);
+}
+
+#[test]
+fn compress_rle() {
+    // given: a bitmap with long runs
+    let message = (0..64)
+        .map(|row| format!("{}{}\n", ".".repeat(row), "#".repeat(300 - row)))
+        .collect::<String>();
+
+    // when:
+    let plain = huff(&["compress"], message.as_bytes());
+    let rle = huff(&["compress", "--rle", "--stats"], message.as_bytes());
+    let decompressed = huff(&["decompress"], &rle.stdout);
+
+    // then:
+    assert!(rle.status.success());
+    assert!(rle.stdout.len() < plain.stdout.len());
+    assert!(String::from_utf8_lossy(&rle.stderr).contains("stats: "));
+    assert!(decompressed.status.success());
+    assert_eq!(String::from_utf8(decompressed.stdout).unwrap(), message);
+}
+
+#[test]
+fn compress_mtf() {
+    // given: clustered symbols
+    let message = ('a'..='z')
+        .map(|letter| format!("{}\n", letter.to_string().repeat(500)))
+        .collect::<String>();
+
+    // when:
+    let plain = huff(&["compress"], message.as_bytes());
+    let mtf = huff(&["compress", "--mtf"], message.as_bytes());
+    let both = huff(
+        &["compress", "--rle", "--mtf", "--stats"],
+        message.as_bytes(),
+    );
+    let decompressed = huff(&["decompress"], &both.stdout);
+
+    // then:
+    assert!(mtf.status.success());
+    assert!(mtf.stdout.len() < plain.stdout.len());
+    assert!(both.stdout.len() < mtf.stdout.len());
+    assert!(String::from_utf8_lossy(&both.stderr).contains("stats: "));
+    assert_eq!(String::from_utf8(decompressed.stdout).unwrap(), message);
+}
+
+#[test]
+fn pipe_without_arguments() {
+    // given:
+    let message = "Grüße aus München, aardvarks ate apples around aachen\n".repeat(20);
+
+    // when:
+    let compressed = huff(&["compress"], message.as_bytes());
+    let decompressed = huff(&["decompress"], &compressed.stdout);
+
+    // then:
+    assert!(compressed.status.success());
+    assert!(compressed.stdout.starts_with(b"HUF1"));
+    assert!(compressed.stdout.len() < message.len());
+    assert!(decompressed.status.success());
+    assert_eq!(String::from_utf8(decompressed.stdout).unwrap(), message);
+    assert!(String::from_utf8_lossy(&decompressed.stderr).contains("ratio"));
+}
+
+#[test]
+fn compress_stats_to_stderr() {
+    // given:
+    let message = b"aaaabbcd";
+
+    // when:
+    let plain = huff(&["compress"], message);
+    let actual = huff(&["compress", "--stats"], message);
+
+    // then:
+    assert!(actual.status.success());
+    assert_eq!(actual.stdout, plain.stdout);
+    let stderr = String::from_utf8(actual.stderr).unwrap();
+    assert!(
+        stderr.contains("stats: 8 symbols, 64 -> 14 bits"),
+        "{}",
+        stderr
+    );
+    assert!(!String::from_utf8(plain.stderr).unwrap().contains("stats"));
+}
+
+#[test]
+fn archive_and_extract() {
+    // given:
+    let dir = temp_path("archive-input");
+    fs::create_dir_all(&dir).unwrap();
+    let files = [
+        ("a.txt", "the quick brown fox jumps over the lazy dog"),
+        ("b.txt", "the lazy dog sleeps"),
+        ("c.txt", "Grüße"),
+    ];
+    let paths = files
+        .iter()
+        .map(|(name, text)| {
+            let path = dir.join(name);
+            fs::write(&path, text).unwrap();
+            path.to_str().unwrap().to_string()
+        })
+        .collect::<Vec<_>>();
+
+    for per_file in [false, true] {
+        let archive = temp_path("archive.hufa");
+        let out = temp_path("archive-output");
+        let one = temp_path("archive-one");
+        let mut args = vec!["archive"];
+        args.extend(paths.iter().map(String::as_str));
+        args.extend(["-o", archive.to_str().unwrap()]);
+        if per_file {
+            args.push("--per-file");
+        }
+
+        // when:
+        let archived = huff(&args, b"");
+        let extracted = huff(
+            &[
+                "extract",
+                archive.to_str().unwrap(),
+                "-d",
+                out.to_str().unwrap(),
+            ],
+            b"",
+        );
+        let extracted_one = huff(
+            &[
+                "extract",
+                archive.to_str().unwrap(),
+                "b.txt",
+                "-d",
+                one.to_str().unwrap(),
+            ],
+            b"",
+        );
+
+        // then:
+        assert!(archived.status.success());
+        assert!(extracted.status.success());
+        assert!(extracted_one.status.success());
+        for (name, text) in files.iter() {
+            assert_eq!(fs::read_to_string(out.join(name)).unwrap(), *text);
+        }
+        assert_eq!(fs::read_dir(&one).unwrap().count(), 1);
+        assert_eq!(fs::read_to_string(one.join("b.txt")).unwrap(), files[1].1);
+        fs::remove_file(&archive).unwrap();
+        fs::remove_dir_all(&out).unwrap();
+        fs::remove_dir_all(&one).unwrap();
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn archive_name_collision() {
+    // given:
+    let first = temp_path("collision-1");
+    let second = temp_path("collision-2");
+    for dir in [&first, &second] {
+        fs::create_dir_all(dir).unwrap();
+        fs::write(dir.join("same.txt"), "text").unwrap();
+    }
+
+    // when:
+    let actual = huff(
+        &[
+            "archive",
+            first.join("same.txt").to_str().unwrap(),
+            second.join("same.txt").to_str().unwrap(),
+        ],
+        b"",
+    );
+
+    // then:
+    fs::remove_dir_all(&first).unwrap();
+    fs::remove_dir_all(&second).unwrap();
+    assert_eq!(actual.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&actual.stderr).contains("duplicate entry name \"same.txt\""));
+}
+
+#[test]
+fn train_and_compress_with_book() {
+    // given: the message has symbols that are not in the corpus
+    let corpus = [temp_path("corpus-1.txt"), temp_path("corpus-2.txt")];
+    let book = temp_path("english.huffbook");
+    fs::write(&corpus[0], "the quick brown fox jumps over the lazy dog\n").unwrap();
+    fs::write(&corpus[1], "pack my box with five dozen liquor jugs\n").unwrap();
+    let message = "The lazy dog packs five boxes. Grüße!\n";
+
+    // when:
+    let trained = huff(
+        &[
+            "train",
+            corpus[0].to_str().unwrap(),
+            corpus[1].to_str().unwrap(),
+            "-o",
+            book.to_str().unwrap(),
+        ],
+        b"",
+    );
+    let compressed = huff(
+        &["compress", "--book", book.to_str().unwrap()],
+        message.as_bytes(),
+    );
+    let decompressed = huff(
+        &["decompress", "--book", book.to_str().unwrap()],
+        &compressed.stdout,
+    );
+    let without_book = huff(&["decompress"], &compressed.stdout);
+    let without_escape = huff(
+        &["compress", "--book", book.to_str().unwrap(), "--no-escape"],
+        message.as_bytes(),
+    );
+
+    // then:
+    for path in corpus.iter().chain([&book]) {
+        fs::remove_file(path).unwrap();
+    }
+    assert!(trained.status.success());
+    assert!(compressed.status.success());
+    assert!(decompressed.status.success());
+    assert_eq!(decompressed.stdout, message.as_bytes());
+    assert_eq!(without_book.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&without_book.stderr).contains("external codebook"));
+    assert_eq!(without_escape.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&without_escape.stderr).contains("'T'"));
+}
+
+fn verify(name: &str, original: &[u8], compressed: &[u8]) -> Output {
+    let original_path = temp_path(&format!("{}.txt", name));
+    let compressed_path = temp_path(&format!("{}.huf", name));
+    fs::write(&original_path, original).unwrap();
+    fs::write(&compressed_path, compressed).unwrap();
+    let output = huff(
+        &[
+            "verify",
+            original_path.to_str().unwrap(),
+            compressed_path.to_str().unwrap(),
+        ],
+        b"",
+    );
+    fs::remove_file(original_path).unwrap();
+    fs::remove_file(compressed_path).unwrap();
+    output
+}
+
+#[test]
+fn verify_identical() {
+    // given:
+    let message = "Grüße aus München, aardvarks ate apples around aachen\n".repeat(10);
+    let compressed = huff(&["compress"], message.as_bytes()).stdout;
+
+    // when:
+    let actual = verify("verify-identical", message.as_bytes(), &compressed);
+
+    // then:
+    assert!(actual.status.success());
+    assert!(String::from_utf8_lossy(&actual.stdout)
+        .starts_with(&format!("OK ({} bytes, ratio ", message.len())));
+}
+
+#[test]
+fn verify_modified_original() {
+    // given: a difference in the second piece the original is read in
+    let mut message = "aardvarks ate apples around aachen\n"
+        .repeat(3000)
+        .into_bytes();
+    let compressed = huff(&["compress"], &message).stdout;
+    message[70_000] = b'X';
+
+    // when:
+    let actual = verify("verify-modified", &message, &compressed);
+
+    // then:
+    assert_eq!(actual.status.code(), Some(3));
+    let stdout = String::from_utf8_lossy(&actual.stdout);
+    assert!(
+        stdout.starts_with("content differs at byte 70000\n"),
+        "{}",
+        stdout
+    );
+    let context = |bytes: &[u8]| {
+        bytes[70_000 - 8..=70_000 + 8]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    assert!(stdout.contains(&format!("original:     {}", context(&message))));
+    message[70_000] = b"aardvarks ate apples around aachen\n"[70_000 % 35];
+    assert!(stdout.contains(&format!("decompressed: {}", context(&message))));
+}
+
+#[test]
+fn verify_length_and_corrupt_archive() {
+    // given:
+    let message = b"BACADAEAFABBAAAGAH";
+    let compressed = huff(&["compress"], message).stdout;
+
+    // when:
+    let shorter = verify("verify-shorter", &message[..10], &compressed);
+    let longer = verify("verify-longer", b"BACADAEAFABBAAAGAHA", &compressed);
+    let corrupt = verify(
+        "verify-corrupt",
+        message,
+        &compressed[..compressed.len() - 1],
+    );
+
+    // then:
+    assert_eq!(shorter.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&shorter.stdout),
+        "length differs: the original has 10 bytes, the decompressed data 18\n"
+    );
+    assert_eq!(longer.status.code(), Some(3));
+    assert!(String::from_utf8_lossy(&longer.stdout).contains("original has 19 bytes"));
+    assert_eq!(corrupt.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&corrupt.stderr).contains("not a valid archive"));
+}
+
+#[test]
+fn decompress_corrupt_archive() {
+    // when:
+    let actual = huff(&["decompress", "-"], b"not an archive");
+
+    // then:
+    assert!(!actual.status.success());
+    assert!(String::from_utf8_lossy(&actual.stderr).contains("not a valid archive"));
+}
+
+#[test]
+fn decompress_without_verification() {
+    // given: a is coded 0 and b 1, so the flipped bit still decodes.
+    let mut archive = huff(&["compress", "-"], b"abab").stdout;
+    *archive.last_mut().unwrap() ^= 0b0010_0000;
+
+    // when:
+    let verified = huff(&["decompress", "-"], &archive);
+    let unverified = huff(&["decompress", "-", "--no-verify"], &archive);
+
+    // then:
+    assert_eq!(verified.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&verified.stderr).contains("checksum mismatch"));
+    assert!(unverified.status.success());
+    assert_eq!(unverified.stdout, b"abbb");
+}
+
+#[test]
+fn compress_missing_file() {
+    // when:
+    let actual = huff(&["compress", "/nonexistent/input.txt"], b"");
+
+    // then:
+    assert!(!actual.status.success());
+    assert!(String::from_utf8_lossy(&actual.stderr).contains("cannot read"));
+}
+
+#[test]
+fn invalid_arguments() {
+    for args in [
+        &[][..],
+        &["unzip", "-"],
+        &["compress", "-", "--fast"],
+        &["decompress", "--stats"],
+        &["archive"],
+        &["archive", "a.txt", "-d", "out"],
+        &["extract"],
+        &["extract", "a.hufa", "a.txt", "b.txt"],
+        &["extract", "a.hufa", "--per-file"],
+        &["compress", "-", "-o"],
+        &["compress", "-", "--no-verify"],
+        &["demo", "extra"],
+        &["demo", "--steps"],
+        &["demo", "--steps", "ab", "--steps", "cd"],
+        &["--dot"],
+        &["freq"],
+        &["freq", "-", "--sort", "size"],
+        &["freq", "-", "--top", "many"],
+        &["freq", "-", "--top"],
+        &["inspect"],
+        &["inspect", "abc", "--yaml"],
+        &["train"],
+        &["train", "a.txt", "--per-file"],
+        &["compress", "--book"],
+        &["compress", "--no-escape"],
+        &["compress", "--book", "a.huffbook", "--stats"],
+        &["compress", "--book", "a.huffbook", "--explain"],
+        &["decompress", "--explain"],
+        &["decompress", "--book", "a.huffbook", "--no-escape"],
+        &["compress", "--rle", "--book", "a.huffbook"],
+        &["decompress", "--rle"],
+        &["compress", "--mtf", "--book", "a.huffbook"],
+        &["decompress", "--mtf"],
+        &["compress", "--verify", "--book", "a.huffbook"],
+        &["decompress", "--verify"],
+        &["verify", "a.txt"],
+        &["verify", "a.txt", "a.huf", "b.huf"],
+        &["verify", "a.txt", "a.huf", "--stats"],
+    ]
+    .iter()
+    {
+        // when:
+        let actual = huff(args, b"");
+
+        // then:
+        assert_eq!(actual.status.code(), Some(2), "args {:?}", args);
+        assert!(String::from_utf8_lossy(&actual.stderr).contains("usage"));
+    }
+}
+
+#[test]
+fn demo_still_works() {
+    // when:
+    let actual = huff(&["demo"], b"");
+
+    // then:
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    assert!(stdout.contains("Decoded: BACADAEAFABBAAAGAH"));
+    assert!(stdout.contains("Decoded: aardvarks ate apples around aachen"));
+    assert!(stdout.contains("Stats: 18 symbols"));
+    assert!(!stdout.contains("left:"));
+}
+
+#[test]
+fn demo_verbose_prints_tree() {
+    // when:
+    let actual = huff(&["demo", "--verbose"], b"");
+
+    // then:
+    assert!(actual.status.success());
+    assert!(String::from_utf8(actual.stdout).unwrap().contains("left:"));
+}
+
+#[test]
+fn demo_steps_prints_merges() {
+    // when:
+    let actual = huff(&["demo", "--steps", "aaaaabbcd"], b"");
+
+    // then:
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    assert!(stdout.starts_with(
+        "Step 1: \"c\" (1) + \"d\" (1) -> \"cd\" (2)\n  \
+         forest: \"b\" (2), \"cd\" (2), \"a\" (5)\n"
+    ));
+    assert!(stdout.contains("Step 3: \"bcd\" (4) + \"a\" (5) -> \"bcda\" (9)\n"));
+    assert!(stdout.contains("left:"));
+    assert!(stdout.contains("String: 000001010110111"));
+    assert!(!stdout.contains("BACADAEAFABBAAAGAH"));
+}
+
+#[test]
+fn demo_explain_prints_savings() {
+    // when:
+    let actual = huff(&["demo", "--explain"], b"");
+
+    // then:
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    assert!(stdout.contains("'A'         9   50.0%    1      3     9    -18"));
+    assert!(stdout.contains("42 bits instead of 54 with 3 bits per symbol"));
+}
+
+#[test]
+fn compress_explain_to_stderr() {
+    // when:
+    let actual = huff(&["compress", "--explain"], b"aaaabbcd");
+
+    // then:
+    assert!(actual.status.success());
+    let stderr = String::from_utf8(actual.stderr).unwrap();
+    assert!(stderr.contains("total       8  100.0%"), "{}", stderr);
+    assert!(stderr.contains("14 bits instead of 16"), "{}", stderr);
+}
+
+#[test]
+fn compress_verify() {
+    // given:
+    let message = "Grüße aus München für 5 € 🦀🦀🦀🦀🦀".repeat(20);
+
+    // when:
+    let verified = huff(&["compress", "--verify", "--rle"], message.as_bytes());
+    let decompressed = huff(&["decompress"], &verified.stdout);
+
+    // then:
+    assert!(verified.status.success());
+    assert_eq!(decompressed.stdout, message.as_bytes());
+}
+
+#[test]
+fn dot_writes_graph() {
+    // when:
+    let actual = huff(&["--dot", "abb"], b"");
+
+    // then:
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    assert!(stdout.starts_with("digraph HuffTree {"));
+    assert!(stdout.contains(r#"[label="b: 2", shape=box]"#));
+}
+
+#[test]
+fn freq_histogram() {
+    // when:
+    let actual = huff(&["freq", "-", "--top", "2"], b"abracadabra");
+
+    // then:
+    assert!(actual.status.success());
+    assert_eq!(
+        String::from_utf8(actual.stdout).unwrap(),
+        "'a'  5   45.5%  ########################################\n\
+         'b'  2   18.2%  ################\n\
+         (3 more symbols omitted)\n"
+    );
+}
+
+#[test]
+fn freq_file_sorted_by_symbol() {
+    // given:
+    let input = temp_path("freq.txt");
+    fs::write(&input, "Grüße 🦀🦀\n").unwrap();
+
+    // when:
+    let actual = huff(&["freq", input.to_str().unwrap(), "--sort", "symbol"], b"");
+
+    // then:
+    fs::remove_file(&input).unwrap();
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    let symbols = stdout
+        .lines()
+        .map(|line| line.split("  ").next().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        symbols,
+        ["'\\n'", "'␣'", "'G'", "'e'", "'r'", "'ß'", "'ü'", "'🦀'"]
+    );
+}
+
+#[test]
+fn inspect_message_as_json() {
+    // when:
+    let actual = huff(&["inspect", "a\"b\"", "--json"], b"");
+
+    // then:
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    assert!(stdout.contains("\"frequencies\": {\n    \"\\\"\": 2,"));
+    assert!(stdout.contains("\"code_lengths\": {\"1\": 1, \"2\": 2}"));
+    assert!(stdout.contains("{\"symbol\": \"\\\"\", \"count\": 2}"));
+}
+
+#[test]
+fn inspect_stdin() {
+    // when:
+    let actual = huff(&["inspect", "-"], b"abb");
+
+    // then:
+    assert!(actual.status.success());
+    let stdout = String::from_utf8(actual.stdout).unwrap();
+    assert!(stdout.contains("left:"));
+    assert!(stdout.contains("'b'  1  (len 1)"));
+}
@@ -0,0 +1,14 @@
+//! Helpers shared by the integration tests and examples, like the ones of the
+//! unit tests in src/test_support.rs.
+
+/// A linear congruential generator, so failures are reproducible.
+pub fn random_numbers(seed: u64) -> impl Iterator<Item = usize> {
+    std::iter::successors(Some(seed), |x| {
+        Some(
+            x.wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407),
+        )
+    })
+    .skip(1)
+    .map(|x| (x >> 33) as usize)
+}
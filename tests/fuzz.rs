@@ -0,0 +1,173 @@
+//! Throws random and mutated valid inputs at the decoders, which must return
+//! an error instead of panicking on any of them.
+
+mod common;
+
+use common::random_numbers;
+use std::io::Read;
+use std::panic::{self, UnwindSafe};
+use std::sync::OnceLock;
+use ue5::header::{deserialize_alphabet, deserialize_frequencies, deserialize_lengths};
+use ue5::io::{
+    decode_packed, decode_resync_bytes, BitOrder, ContainerReader, DecoderBuilder, HuffmanReader,
+    HuffmanWriter,
+};
+use ue5::{
+    compress, compress_blocks, compress_with, compress_with_bit_order, compress_with_codebook,
+    compress_with_progress, decode, decompress, decompress_parallel, decompress_range,
+    decompress_unverified, decompress_with, decompress_with_codebook, decompress_with_progress,
+    Archive, Bits, Codebook, CodebookHeader, CompressOptions, DecompressOptions, HeaderMode,
+};
+
+fn assert_no_panic(data: &[u8], f: impl FnOnce(&[u8]) + UnwindSafe) {
+    let owned = data.to_vec();
+    if panic::catch_unwind(move || f(&owned)).is_err() {
+        panic!("panicked on input {:?}", data);
+    }
+}
+
+/// The message of the samples, whose codebook the codebook decoders use.
+const MESSAGE: &str = "Grüße aus München, aardvarks ate apples around aachen 🦀";
+
+fn codebook() -> &'static Codebook {
+    static CODEBOOK: OnceLock<Codebook> = OnceLock::new();
+    CODEBOOK.get_or_init(|| ue5::encode(MESSAGE).codebook)
+}
+
+/// Call every public decoder of bytes with the data.
+fn decode_all(data: &[u8]) {
+    let codebook = codebook();
+    let limited = DecompressOptions {
+        max_output_bytes: Some(64),
+        ..DecompressOptions::default()
+    };
+    let _ = decompress(data);
+    let _ = decompress_unverified(data);
+    let _ = decompress_with(data, limited);
+    let _ = decompress_with_progress(data, DecompressOptions::default(), |_| {});
+    let _ = decompress_range(data, 1..5);
+    let _ = decompress_parallel(data, 2);
+    let _ = decompress_with_codebook(data, codebook);
+    let _ = ContainerReader::new(data).and_then(|mut reader| reader.read_to_end(&mut Vec::new()));
+    let _ = ContainerReader::with_codebook(data, Some(codebook))
+        .and_then(|mut reader| reader.read_to_end(&mut Vec::new()));
+    if let Ok(archive) = Archive::read(data) {
+        for entry in archive.entries() {
+            let _ = entry.decode();
+        }
+    }
+    let _ = Codebook::deserialize(data);
+    let _ = deserialize_frequencies(data);
+    let _ = deserialize_lengths(data);
+    let _ = deserialize_alphabet(data);
+    let decoder = DecoderBuilder::new().codebook(codebook).build().unwrap();
+    let _ = decoder.decode(data);
+    let _ = HuffmanReader::new(codebook.clone(), data).decode_to_string();
+    let _ = decode_packed(codebook, data, data.len() * 8);
+    let _ = decode_resync_bytes(codebook, data, data.last().copied().unwrap_or(0));
+}
+
+fn samples() -> Vec<Vec<u8>> {
+    let compress_with_options =
+        |options: CompressOptions| compress_with_progress(MESSAGE, options, |_| {});
+    let mut samples = vec![
+        compress(""),
+        compress("a"),
+        compress(MESSAGE),
+        compress_with(MESSAGE, HeaderMode::Frequencies),
+        compress_with(MESSAGE, HeaderMode::Lengths),
+        compress_with_bit_order(MESSAGE, BitOrder::LsbFirst),
+        compress_blocks(MESSAGE, HeaderMode::Codebook, 7),
+        compress_blocks(MESSAGE, HeaderMode::Frequencies, 16),
+        compress_with_options(CompressOptions {
+            rle: true,
+            ..CompressOptions::default()
+        }),
+        compress_with_options(CompressOptions {
+            mtf: true,
+            ..CompressOptions::default()
+        }),
+        compress_with_options(CompressOptions {
+            rle: true,
+            mtf: true,
+            header_mode: Some(HeaderMode::Lengths),
+            ..CompressOptions::default()
+        }),
+        compress_with_options(CompressOptions {
+            block_size: Some(10),
+            ..CompressOptions::default()
+        }),
+        compress_with_options(CompressOptions {
+            block_size: Some(16),
+            bit_order: BitOrder::LsbFirst,
+            ..CompressOptions::default()
+        }),
+        compress_with_codebook(MESSAGE, codebook(), false).unwrap(),
+        Archive::create(vec![
+            ("a.txt".to_string(), MESSAGE.to_string()),
+            ("b.txt".to_string(), "aardvarks".to_string()),
+        ])
+        .unwrap(),
+    ];
+    let mut writer = HuffmanWriter::new(codebook().clone(), Vec::new());
+    writer.write_str(MESSAGE).unwrap();
+    samples.push(writer.finish().unwrap());
+    samples
+}
+
+#[test]
+fn fuzz_random_bytes() {
+    let mut random = random_numbers(1);
+    for _ in 0..100_000 {
+        let len = random.next().unwrap() % 64;
+        let mut data = random
+            .by_ref()
+            .take(len)
+            .map(|x| x as u8)
+            .collect::<Vec<_>>();
+        // Most random data fails at the magic already.
+        if random.next().unwrap().is_multiple_of(2) {
+            data.splice(0..0, b"HUF1".iter().copied());
+        }
+        assert_no_panic(&data, decode_all);
+    }
+}
+
+#[test]
+fn fuzz_mutated_containers() {
+    let samples = samples();
+    let mut random = random_numbers(2);
+    for _ in 0..200_000 {
+        let mut data = samples[random.next().unwrap() % samples.len()].clone();
+        for _ in 0..1 + random.next().unwrap() % 4 {
+            let r = random.next().unwrap();
+            let position = r % (data.len() + 1);
+            match random.next().unwrap() % 4 {
+                0 if position < data.len() => data[position] ^= 1 << (r % 8),
+                1 if position < data.len() => data[position] = (r >> 8) as u8,
+                2 => data.insert(position, (r >> 8) as u8),
+                _ => data.truncate(position),
+            }
+        }
+        assert_no_panic(&data, decode_all);
+    }
+}
+
+#[test]
+fn fuzz_decode_random_bits() {
+    let codebooks = ["a", "ab", "abracadabra", "Grüße aus München"]
+        .iter()
+        .map(|message| ue5::encode(message).codebook)
+        .collect::<Vec<_>>();
+    let mut random = random_numbers(3);
+    for _ in 0..100_000 {
+        let codebook = &codebooks[random.next().unwrap() % codebooks.len()];
+        let bytes = random.by_ref().take(4).map(|x| x as u8).collect::<Vec<_>>();
+        let len = random.next().unwrap() % 33;
+        let bits = Bits::from_bytes(len, &bytes).unwrap();
+        let codebook = codebook.clone();
+        assert_no_panic(&bytes, move |_| {
+            let _ = decode(&codebook, &bits);
+        });
+    }
+}
@@ -0,0 +1,50 @@
+use ue5::{
+    codebook, decode, decode_bytes, encode, encode_bytes, frequency, huffman, Bits, Codebook,
+    Encoded, HuffTree,
+};
+
+#[test]
+fn encode_decode_round_trip() {
+    // given:
+    let message = "aardvarks ate apples around aachen";
+
+    // when:
+    let Encoded { codebook, bits, .. } = encode(message);
+    let actual = decode(&codebook, &bits).unwrap();
+
+    // then:
+    assert_eq!(actual, message);
+}
+
+#[test]
+fn codebook_from_huffman_tree() {
+    // given:
+    let message = "BACADAEAFABBAAAGAH";
+    let tree: HuffTree = huffman(frequency(&mut message.chars())).unwrap();
+
+    // when:
+    let codebook: Codebook = codebook(&tree);
+    let mut bits = Bits::new();
+    for chr in message.chars() {
+        bits.extend_from_code(&codebook[&chr]);
+    }
+
+    // then:
+    assert_eq!(tree.lettercount() as usize, message.len());
+    assert_eq!(bits.to_string().len(), bits.len());
+    assert_eq!(decode(&codebook, &bits).unwrap(), message);
+}
+
+#[test]
+fn encode_decode_bytes_round_trip() {
+    // given:
+    let bytes = include_bytes!("fixtures/pixel.png");
+
+    // when:
+    let (codebook, bits) = encode_bytes(bytes);
+    let actual = decode_bytes(&codebook, &bits).unwrap();
+
+    // then:
+    assert!(bytes.contains(&0));
+    assert_eq!(actual, bytes);
+}
@@ -0,0 +1,39 @@
+//! The wasm bindings in the browser runtime, run with
+//! `cargo test --target wasm32-unknown-unknown --features wasm --test wasm`,
+//! which needs wasm-bindgen-test-runner from wasm-bindgen-cli.
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use ue5::wasm::{codebook_json, compress_text, decompress_text};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn wasm_round_trip() {
+    for message in ["", "a", "Grüße aus München 🦀"].iter() {
+        // when:
+        let actual = decompress_text(&compress_text(message));
+
+        // then:
+        assert_eq!(actual.ok().as_deref(), Some(*message));
+    }
+}
+
+#[wasm_bindgen_test]
+fn wasm_error_is_readable() {
+    // when:
+    let actual = decompress_text(b"HUF0");
+
+    // then:
+    assert_eq!(
+        actual.unwrap_err().as_string().as_deref(),
+        Some("cannot decompress: the data does not start with \"HUF1\"")
+    );
+}
+
+#[wasm_bindgen_test]
+fn wasm_codebook_json() {
+    // when:
+    let actual = codebook_json("aab");
+
+    // then:
+    assert_eq!(actual, "{\n  \"a\": \"0\",\n  \"b\": \"1\"\n}");
+}